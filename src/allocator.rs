@@ -3,6 +3,7 @@
 
 use crate::boot_services::BootServices;
 use crate::ffi::*;
+use crate::runtime::guard::boot_services_available;
 use core::alloc::{GlobalAlloc, Layout};
 use core::ptr::null_mut;
 
@@ -11,74 +12,97 @@ static mut BOOT_SERVICES: Option<&'static BootServices> = None;
 
 /// Initialize the allocator with Boot Services
 ///
+/// Once [`crate::runtime::guard`] records `ExitBootServices()`, allocations
+/// fail and frees are ignored.
+///
 /// # Safety
 /// Must be called once during UEFI application initialization
 pub unsafe fn init_allocator(boot_services: &'static BootServices) {
     BOOT_SERVICES = Some(boot_services);
 }
 
+/// Boot Services registered with [`init_allocator`], while still available
+pub(crate) unsafe fn boot_services() -> Option<&'static BootServices> {
+    if !boot_services_available() {
+        return None;
+    }
+    BOOT_SERVICES
+}
+
+/// Allocate `layout` from pool memory of `memory_type`
+///
+/// AllocatePool only guarantees 8-byte alignment; larger alignments are
+/// over-allocated and the pool pointer is stored just below the result.
+pub(crate) unsafe fn pool_alloc(
+    bs: &BootServices,
+    memory_type: MemoryType,
+    layout: Layout,
+) -> *mut u8 {
+    let mut buffer: *mut core::ffi::c_void = null_mut();
+    let size = layout.size();
+    let align = layout.align();
+
+    if align <= 8 {
+        // Standard allocation
+        let status = (bs.allocate_pool)(memory_type, size, &mut buffer);
+
+        if status == EFI_SUCCESS && !buffer.is_null() {
+            buffer as *mut u8
+        } else {
+            null_mut()
+        }
+    } else {
+        // Over-allocate to handle alignment
+        let total_size = size + align + core::mem::size_of::<usize>();
+
+        let status = (bs.allocate_pool)(memory_type, total_size, &mut buffer);
+
+        if status != EFI_SUCCESS || buffer.is_null() {
+            return null_mut();
+        }
+
+        let base_addr = buffer as usize;
+
+        // Calculate aligned address
+        let offset = core::mem::size_of::<usize>();
+        let data_start = base_addr + offset;
+        let aligned_addr = (data_start + align - 1) & !(align - 1);
+
+        // Store the original pointer before the aligned address
+        let header = (aligned_addr - core::mem::size_of::<usize>()) as *mut usize;
+        *header = base_addr;
+
+        aligned_addr as *mut u8
+    }
+}
+
+/// Free memory returned by [`pool_alloc`]
+pub(crate) unsafe fn pool_free(bs: &BootServices, ptr: *mut u8, layout: Layout) {
+    if layout.align() <= 8 {
+        // Standard deallocation
+        let _ = (bs.free_pool)(ptr as *mut core::ffi::c_void);
+    } else {
+        // Retrieve original pointer from header
+        let header = (ptr as usize - core::mem::size_of::<usize>()) as *const usize;
+        let original_ptr = *header;
+        let _ = (bs.free_pool)(original_ptr as *mut core::ffi::c_void);
+    }
+}
+
 /// UEFI Global Allocator
 pub struct UefiAllocator;
 
 unsafe impl GlobalAlloc for UefiAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        if let Some(bs) = BOOT_SERVICES {
-            let mut buffer: *mut core::ffi::c_void = null_mut();
-            let size = layout.size();
-            let align = layout.align();
-
-            // UEFI AllocatePool returns 8-byte aligned memory
-            // For larger alignments, we need to allocate extra space
-            if align <= 8 {
-                // Standard allocation
-                let status = (bs.allocate_pool)(MemoryType::LoaderData, size, &mut buffer);
-
-                if status == EFI_SUCCESS && !buffer.is_null() {
-                    buffer as *mut u8
-                } else {
-                    null_mut()
-                }
-            } else {
-                // Over-allocate to handle alignment
-                let total_size = size + align + core::mem::size_of::<usize>();
-
-                let status = (bs.allocate_pool)(MemoryType::LoaderData, total_size, &mut buffer);
-
-                if status != EFI_SUCCESS || buffer.is_null() {
-                    return null_mut();
-                }
-
-                let base_addr = buffer as usize;
-
-                // Calculate aligned address
-                let offset = core::mem::size_of::<usize>();
-                let data_start = base_addr + offset;
-                let aligned_addr = (data_start + align - 1) & !(align - 1);
-
-                // Store the original pointer before the aligned address
-                let header = (aligned_addr - core::mem::size_of::<usize>()) as *mut usize;
-                *header = base_addr;
-
-                aligned_addr as *mut u8
-            }
-        } else {
-            null_mut()
+        match boot_services() {
+            Some(bs) => pool_alloc(bs, MemoryType::LoaderData, layout),
+            None => null_mut(),
         }
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        if let Some(bs) = BOOT_SERVICES {
-            let align = layout.align();
-
-            if align <= 8 {
-                // Standard deallocation
-                let _ = (bs.free_pool)(ptr as *mut core::ffi::c_void);
-            } else {
-                // Retrieve original pointer from header
-                let header = (ptr as usize - core::mem::size_of::<usize>()) as *const usize;
-                let original_ptr = *header;
-                let _ = (bs.free_pool)(original_ptr as *mut core::ffi::c_void);
-            }
+        if let Some(bs) = boot_services() {
+            pool_free(bs, ptr, layout);
         }
     }
 }
@@ -92,3 +116,27 @@ static ALLOCATOR: UefiAllocator = UefiAllocator;
 fn alloc_error_handler(layout: Layout) -> ! {
     panic!("Allocation error: {:?}", layout);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boot_services::mock as bs_mock;
+    use crate::runtime::guard;
+    use std::boxed::Box;
+
+    #[test]
+    fn test_allocator_stops_after_exit() {
+        guard::reset();
+        bs_mock::reset();
+        unsafe { init_allocator(Box::leak(Box::new(bs_mock::boot_services()))) };
+        let layout = Layout::from_size_align(48, 64).unwrap();
+        let ptr = unsafe { UefiAllocator.alloc(layout) };
+        assert!(!ptr.is_null());
+        assert_eq!(ptr as usize % 64, 0);
+        unsafe { UefiAllocator.dealloc(ptr, layout) };
+
+        guard::exit_boot_services();
+        assert!(unsafe { UefiAllocator.alloc(layout) }.is_null());
+        guard::reset();
+    }
+}
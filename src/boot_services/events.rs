@@ -2,6 +2,7 @@
 //! UEFI Event and Timer Services
 
 use crate::boot_services::BootServices;
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
 
 // Event types
@@ -38,24 +39,20 @@ impl<'a> EventWrapper<'a> {
         notify_tpl: Tpl,
         notify_function: Option<EventNotifyFn>,
         notify_context: *mut core::ffi::c_void,
-    ) -> Result<Self, Status> {
+    ) -> Result<Self> {
         let mut event = core::ptr::null_mut();
         let notify_fn =
             notify_function.map_or(core::ptr::null_mut(), |f| f as *mut core::ffi::c_void);
 
-        let status = (bs.create_event)(
+        (bs.create_event)(
             event_type,
             notify_tpl,
             notify_fn,
             notify_context,
             &mut event,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(EventWrapper { bs, event })
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(EventWrapper { bs, event })
     }
 
     /// Create a new event (ex version with event group)
@@ -66,53 +63,50 @@ impl<'a> EventWrapper<'a> {
         notify_function: Option<EventNotifyFn>,
         notify_context: *mut core::ffi::c_void,
         event_group: Option<&Guid>,
-    ) -> Result<Self, Status> {
+    ) -> Result<Self> {
         let mut event = core::ptr::null_mut();
         let notify_fn =
             notify_function.map_or(core::ptr::null_mut(), |f| f as *mut core::ffi::c_void);
         let group_ptr = event_group.map_or(core::ptr::null(), |g| g as *const _);
 
-        let status = (bs.create_event_ex)(
+        (bs.create_event_ex)(
             event_type,
             notify_tpl,
             notify_fn,
             notify_context,
             group_ptr,
             &mut event,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(EventWrapper { bs, event })
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(EventWrapper { bs, event })
     }
 
     /// Set timer
-    pub unsafe fn set_timer(&self, timer_type: TimerDelay, trigger_time: u64) -> Status {
-        (self.bs.set_timer)(self.event, timer_type as u32, trigger_time)
+    pub unsafe fn set_timer(&self, timer_type: TimerDelay, trigger_time: u64) -> Result<()> {
+        (self.bs.set_timer)(self.event, timer_type as u32, trigger_time).to_result()
     }
 
     /// Signal the event
-    pub unsafe fn signal(&self) -> Status {
-        (self.bs.signal_event)(self.event)
+    pub unsafe fn signal(&self) -> Result<()> {
+        (self.bs.signal_event)(self.event).to_result()
     }
 
-    /// Check the event status
-    pub unsafe fn check(&self) -> Status {
-        (self.bs.check_event)(self.event)
+    /// Check whether the event is signaled
+    ///
+    /// Returns `Ok(false)` when the event is not yet signaled.
+    pub unsafe fn check(&self) -> Result<bool> {
+        match (self.bs.check_event)(self.event) {
+            EFI_NOT_READY => Ok(false),
+            status => status.to_result().map(|_| true),
+        }
     }
 
     /// Wait for event
-    pub unsafe fn wait(&self) -> Result<usize, Status> {
+    pub unsafe fn wait(&self) -> Result<usize> {
         let mut index = 0;
-        let status = (self.bs.wait_for_event)(1, &self.event as *const _ as *mut Event, &mut index);
-
-        if status == EFI_SUCCESS {
-            Ok(index)
-        } else {
-            Err(status)
-        }
+        (self.bs.wait_for_event)(1, &self.event as *const _ as *mut Event, &mut index)
+            .to_result()?;
+        Ok(index)
     }
 
     /// Get the raw event handle
@@ -121,7 +115,7 @@ impl<'a> EventWrapper<'a> {
     }
 }
 
-impl<'a> Drop for EventWrapper<'a> {
+impl Drop for EventWrapper<'_> {
     fn drop(&mut self) {
         unsafe {
             let _ = (self.bs.close_event)(self.event);
@@ -136,36 +130,36 @@ pub struct Timer<'a> {
 
 impl<'a> Timer<'a> {
     /// Create a new timer
-    pub unsafe fn create(bs: &'a BootServices, tpl: Tpl) -> Result<Self, Status> {
+    pub unsafe fn create(bs: &'a BootServices, tpl: Tpl) -> Result<Self> {
         let event = EventWrapper::create(bs, EVT_TIMER, tpl, None, core::ptr::null_mut())?;
 
         Ok(Timer { event })
     }
 
     /// Set timer to fire after delay (in 100ns units)
-    pub unsafe fn set_relative(&self, delay_100ns: u64) -> Status {
+    pub unsafe fn set_relative(&self, delay_100ns: u64) -> Result<()> {
         self.event.set_timer(TimerDelay::TimerRelative, delay_100ns)
     }
 
     /// Set timer to fire periodically (in 100ns units)
-    pub unsafe fn set_periodic(&self, period_100ns: u64) -> Status {
+    pub unsafe fn set_periodic(&self, period_100ns: u64) -> Result<()> {
         self.event
             .set_timer(TimerDelay::TimerPeriodic, period_100ns)
     }
 
     /// Cancel the timer
-    pub unsafe fn cancel(&self) -> Status {
+    pub unsafe fn cancel(&self) -> Result<()> {
         self.event.set_timer(TimerDelay::TimerCancel, 0)
     }
 
     /// Wait for timer to fire
-    pub unsafe fn wait(&self) -> Result<(), Status> {
+    pub unsafe fn wait(&self) -> Result<()> {
         self.event.wait().map(|_| ())
     }
 
     /// Check if timer has fired
     pub unsafe fn check(&self) -> bool {
-        self.event.check() == EFI_SUCCESS
+        self.event.check().unwrap_or(false)
     }
 }
 
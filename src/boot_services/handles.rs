@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Handle database enumeration helpers
+
+use crate::boot_services::BootServices;
+use crate::ffi::*;
+use core::ops::Deref;
+
+/// EFI_LOCATE_SEARCH_TYPE
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LocateSearchType {
+    AllHandles = 0,
+    ByRegisterNotify = 1,
+    ByProtocol = 2,
+}
+
+/// Pool-allocated array of handles returned by `LocateHandleBuffer`
+///
+/// Derefs to a slice of handles and frees the pool buffer on drop.
+pub struct HandleBuffer<'a> {
+    bs: &'a BootServices,
+    buffer: *mut *mut Handle,
+    count: usize,
+}
+
+impl<'a> HandleBuffer<'a> {
+    /// Take ownership of a pool buffer returned by the firmware
+    ///
+    /// # Safety
+    /// `buffer` must be a pool allocation holding `count` handles, or null
+    /// with `count == 0`.
+    pub unsafe fn new(bs: &'a BootServices, buffer: *mut *mut Handle, count: usize) -> Self {
+        HandleBuffer { bs, buffer, count }
+    }
+
+    /// Get the handles as a slice
+    pub fn handles(&self) -> &[*mut Handle] {
+        if self.buffer.is_null() {
+            &[]
+        } else {
+            unsafe { core::slice::from_raw_parts(self.buffer, self.count) }
+        }
+    }
+}
+
+impl Deref for HandleBuffer<'_> {
+    type Target = [*mut Handle];
+
+    fn deref(&self) -> &[*mut Handle] {
+        self.handles()
+    }
+}
+
+impl Drop for HandleBuffer<'_> {
+    fn drop(&mut self) {
+        if !self.buffer.is_null() {
+            unsafe {
+                let _ = (self.bs.free_pool)(self.buffer as *mut core::ffi::c_void);
+            }
+        }
+    }
+}
@@ -0,0 +1,394 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Owned UEFI Memory Map
+//!
+//! The firmware reports the memory map as an array of `EFI_MEMORY_DESCRIPTOR`
+//! entries whose stride is `descriptor_size`, which may be larger than
+//! `size_of::<MemoryDescriptor>()`. [`MemoryMap`] owns the raw buffer and
+//! always walks it using the firmware-provided stride.
+
+use crate::boot_services::BootServices;
+use crate::error::{Error, Result, StatusExt};
+use crate::ffi::*;
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// Extra descriptors to reserve when allocating the map buffer
+///
+/// Allocating the buffer itself can split a free region, so the map may grow
+/// between the size query and the actual call.
+const EXTRA_DESCRIPTORS: usize = 8;
+
+/// Maximum number of `EFI_BUFFER_TOO_SMALL` retries before giving up
+const MAX_RETRIES: usize = 4;
+
+/// Size of a UEFI page in bytes
+pub const EFI_PAGE_SIZE: u64 = 4096;
+
+/// Owned copy of the UEFI memory map
+pub struct MemoryMap {
+    /// Backing storage, kept as `u64` so descriptors are 8-byte aligned
+    buffer: Vec<u64>,
+    /// Number of valid bytes in `buffer`
+    map_size: usize,
+    map_key: usize,
+    descriptor_size: usize,
+    descriptor_version: u32,
+}
+
+impl MemoryMap {
+    /// Retrieve the current memory map from Boot Services
+    ///
+    /// The buffer is sized from an initial query, padded for the allocation
+    /// itself, and the call is retried if the map grew in between.
+    pub fn get(bs: &BootServices) -> Result<Self> {
+        let mut map_size = 0;
+        let mut map_key = 0;
+        let mut descriptor_size = 0;
+        let mut descriptor_version = 0;
+
+        let status = unsafe {
+            (bs.get_memory_map)(
+                &mut map_size,
+                core::ptr::null_mut(),
+                &mut map_key,
+                &mut descriptor_size,
+                &mut descriptor_version,
+            )
+        };
+        if status != EFI_BUFFER_TOO_SMALL {
+            status.to_result()?;
+        }
+
+        for _ in 0..MAX_RETRIES {
+            let capacity = map_size + EXTRA_DESCRIPTORS * descriptor_size.max(1);
+            let mut buffer: Vec<u64> = vec![0; capacity.div_ceil(8)];
+
+            map_size = buffer.len() * 8;
+            let status = unsafe {
+                (bs.get_memory_map)(
+                    &mut map_size,
+                    buffer.as_mut_ptr() as *mut MemoryDescriptor,
+                    &mut map_key,
+                    &mut descriptor_size,
+                    &mut descriptor_version,
+                )
+            };
+
+            match status {
+                EFI_SUCCESS => {
+                    return Self::from_raw(
+                        buffer,
+                        map_size,
+                        map_key,
+                        descriptor_size,
+                        descriptor_version,
+                    )
+                }
+                EFI_BUFFER_TOO_SMALL => continue,
+                _ => return Err(Error::new(status)),
+            }
+        }
+
+        Err(Error::buffer_too_small(map_size))
+    }
+
+    /// Re-read the memory map into the existing buffer
+    ///
+    /// Does not allocate, so it is safe to call between a failed
+    /// `ExitBootServices` and the retry.
+    pub fn refresh(&mut self, bs: &BootServices) -> Result<()> {
+        let mut map_size = self.buffer.len() * 8;
+        let mut descriptor_size = 0;
+        unsafe {
+            (bs.get_memory_map)(
+                &mut map_size,
+                self.buffer.as_mut_ptr() as *mut MemoryDescriptor,
+                &mut self.map_key,
+                &mut descriptor_size,
+                &mut self.descriptor_version,
+            )
+        }
+        .to_result_with_size(map_size)?;
+        Self::check_layout(self.buffer.len(), map_size, descriptor_size)?;
+        self.map_size = map_size;
+        self.descriptor_size = descriptor_size;
+        Ok(())
+    }
+
+    fn check_layout(buffer_len: usize, map_size: usize, descriptor_size: usize) -> Result<()> {
+        if descriptor_size < core::mem::size_of::<MemoryDescriptor>() || map_size > buffer_len * 8 {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        Ok(())
+    }
+
+    /// Build a memory map from an already-populated buffer
+    ///
+    /// `map_size` is the number of valid bytes and `descriptor_size` the
+    /// stride reported by the firmware. Fails with `EFI_INVALID_PARAMETER`
+    /// if the stride is smaller than a descriptor or `map_size` overruns
+    /// the buffer.
+    pub fn from_raw(
+        buffer: Vec<u64>,
+        map_size: usize,
+        map_key: usize,
+        descriptor_size: usize,
+        descriptor_version: u32,
+    ) -> Result<Self> {
+        Self::check_layout(buffer.len(), map_size, descriptor_size)?;
+
+        Ok(Self {
+            buffer,
+            map_size,
+            map_key,
+            descriptor_size,
+            descriptor_version,
+        })
+    }
+
+    /// Key identifying this snapshot of the map (needed by `ExitBootServices`)
+    pub fn map_key(&self) -> usize {
+        self.map_key
+    }
+
+    /// Size in bytes of each descriptor as reported by the firmware
+    pub fn descriptor_size(&self) -> usize {
+        self.descriptor_size
+    }
+
+    /// Descriptor version as reported by the firmware
+    pub fn descriptor_version(&self) -> u32 {
+        self.descriptor_version
+    }
+
+    /// Number of descriptors in the map
+    pub fn len(&self) -> usize {
+        self.map_size / self.descriptor_size
+    }
+
+    /// Check if the map holds no descriptors
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the descriptor at `index`
+    pub fn get_descriptor(&self, index: usize) -> Option<MemoryDescriptor> {
+        if index >= self.len() {
+            return None;
+        }
+        unsafe { Some(core::ptr::read_unaligned(self.descriptor_ptr(index))) }
+    }
+
+    /// Iterate over all descriptors
+    pub fn entries(&self) -> MemoryMapIter<'_> {
+        MemoryMapIter {
+            map: self,
+            index: 0,
+        }
+    }
+
+    /// Sort descriptors by physical start address
+    pub fn sort(&mut self) {
+        // Insertion sort in place; maps are small and mostly ordered already
+        for i in 1..self.len() {
+            let mut j = i;
+            while j > 0 {
+                let prev = self.read(j - 1);
+                let cur = self.read(j);
+                if prev.physical_start <= cur.physical_start {
+                    break;
+                }
+                self.write(j - 1, cur);
+                self.write(j, prev);
+                j -= 1;
+            }
+        }
+    }
+
+    /// Merge physically adjacent regions with the same type and attributes
+    ///
+    /// The map should be sorted first; only neighbouring entries are merged.
+    pub fn coalesce(&mut self) {
+        let count = self.len();
+        if count == 0 {
+            return;
+        }
+
+        let mut out = 0;
+        let mut current = self.read(0);
+
+        for i in 1..count {
+            let next = self.read(i);
+            let current_end = current.physical_start + current.number_of_pages * EFI_PAGE_SIZE;
+
+            if next.memory_type == current.memory_type
+                && next.attribute == current.attribute
+                && next.physical_start == current_end
+            {
+                current.number_of_pages += next.number_of_pages;
+            } else {
+                self.write(out, current);
+                out += 1;
+                current = next;
+            }
+        }
+
+        self.write(out, current);
+        self.map_size = (out + 1) * self.descriptor_size;
+    }
+
+    /// Total number of pages of the given memory type
+    pub fn total_pages(&self, memory_type: MemoryType) -> u64 {
+        self.entries()
+            .filter(|d| d.memory_type == memory_type as u32)
+            .map(|d| d.number_of_pages)
+            .sum()
+    }
+
+    /// Raw pointer to the first descriptor, for passing back to the firmware
+    pub fn as_ptr(&self) -> *const MemoryDescriptor {
+        self.buffer.as_ptr() as *const MemoryDescriptor
+    }
+
+    /// Size in bytes of the valid portion of the map
+    pub fn map_size(&self) -> usize {
+        self.map_size
+    }
+
+    fn descriptor_ptr(&self, index: usize) -> *const MemoryDescriptor {
+        unsafe {
+            (self.buffer.as_ptr() as *const u8).add(index * self.descriptor_size)
+                as *const MemoryDescriptor
+        }
+    }
+
+    fn read(&self, index: usize) -> MemoryDescriptor {
+        unsafe { core::ptr::read_unaligned(self.descriptor_ptr(index)) }
+    }
+
+    fn write(&mut self, index: usize, descriptor: MemoryDescriptor) {
+        unsafe {
+            let ptr = (self.buffer.as_mut_ptr() as *mut u8).add(index * self.descriptor_size);
+            core::ptr::write_unaligned(ptr as *mut MemoryDescriptor, descriptor);
+        }
+    }
+}
+
+/// Iterator over the descriptors of a [`MemoryMap`]
+pub struct MemoryMapIter<'a> {
+    map: &'a MemoryMap,
+    index: usize,
+}
+
+impl Iterator for MemoryMapIter<'_> {
+    type Item = MemoryDescriptor;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let descriptor = self.map.get_descriptor(self.index)?;
+        self.index += 1;
+        Some(descriptor)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.map.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for MemoryMapIter<'_> {}
+
+impl<'a> IntoIterator for &'a MemoryMap {
+    type Item = MemoryDescriptor;
+    type IntoIter = MemoryMapIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Firmware commonly reports a stride larger than the struct
+    const STRIDE: usize = 48;
+
+    fn descriptor(memory_type: MemoryType, start: u64, pages: u64) -> MemoryDescriptor {
+        MemoryDescriptor {
+            memory_type: memory_type as u32,
+            physical_start: start,
+            virtual_start: 0,
+            number_of_pages: pages,
+            attribute: EFI_MEMORY_WB,
+        }
+    }
+
+    fn build(descriptors: &[MemoryDescriptor]) -> MemoryMap {
+        let map_size = descriptors.len() * STRIDE;
+        let buffer = vec![0u64; map_size / 8];
+        let mut map = MemoryMap::from_raw(buffer, map_size, 0x42, STRIDE, 1).unwrap();
+        for (i, d) in descriptors.iter().enumerate() {
+            map.write(i, *d);
+        }
+        map
+    }
+
+    #[test]
+    fn test_iterate_with_stride() {
+        let map = build(&[
+            descriptor(MemoryType::ConventionalMemory, 0x1000, 4),
+            descriptor(MemoryType::LoaderData, 0x5000, 2),
+        ]);
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.map_key(), 0x42);
+        let starts: Vec<u64> = map.entries().map(|d| d.physical_start).collect();
+        assert_eq!(starts, [0x1000, 0x5000]);
+        assert!(map.get_descriptor(2).is_none());
+    }
+
+    #[test]
+    fn test_sort_and_coalesce() {
+        let mut map = build(&[
+            descriptor(MemoryType::ConventionalMemory, 0x3000, 1),
+            descriptor(MemoryType::ConventionalMemory, 0x1000, 2),
+            descriptor(MemoryType::LoaderData, 0x4000, 1),
+            descriptor(MemoryType::LoaderData, 0x5000, 3),
+        ]);
+
+        map.sort();
+        let starts: Vec<u64> = map.entries().map(|d| d.physical_start).collect();
+        assert_eq!(starts, [0x1000, 0x3000, 0x4000, 0x5000]);
+
+        map.coalesce();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get_descriptor(0).unwrap().number_of_pages, 3);
+        assert_eq!(map.get_descriptor(1).unwrap().number_of_pages, 4);
+    }
+
+    #[test]
+    fn test_total_pages() {
+        let map = build(&[
+            descriptor(MemoryType::ConventionalMemory, 0x1000, 4),
+            descriptor(MemoryType::LoaderData, 0x5000, 2),
+            descriptor(MemoryType::ConventionalMemory, 0x9000, 6),
+        ]);
+
+        assert_eq!(map.total_pages(MemoryType::ConventionalMemory), 10);
+        assert_eq!(map.total_pages(MemoryType::LoaderData), 2);
+        assert_eq!(map.total_pages(MemoryType::ACPIMemoryNVS), 0);
+    }
+
+    #[test]
+    fn test_from_raw_rejects_bad_layout() {
+        let short = MemoryMap::from_raw(vec![0u64; 12], 48, 0, 8, 1);
+        assert_eq!(short.err().unwrap().status(), EFI_INVALID_PARAMETER);
+
+        let overrun = MemoryMap::from_raw(vec![0u64; 6], 96, 0, STRIDE, 1);
+        assert_eq!(overrun.err().unwrap().status(), EFI_INVALID_PARAMETER);
+    }
+}
@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! In-memory Boot Services for unit tests
+//!
+//! Only the memory allocation services are backed by real memory; every
+//! other entry point returns `EFI_UNSUPPORTED`. Allocations are tracked per
+//! thread, so tests can check for leaks with [`outstanding_pages`].
+
+use crate::boot_services::{BootServices, EFI_BOOT_SERVICES_SIGNATURE};
+use crate::ffi::*;
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::cell::RefCell;
+use std::vec::Vec;
+
+const PAGE_SIZE: usize = 4096;
+
+std::thread_local! {
+    static PAGES: RefCell<Vec<(PhysicalAddress, usize)>> = const { RefCell::new(Vec::new()) };
+    static POOL: RefCell<Vec<(usize, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn page_layout(pages: usize) -> Layout {
+    Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap()
+}
+
+/// Release every allocation made on the current thread
+pub(crate) fn reset() {
+    for (addr, pages) in PAGES.with(|p| core::mem::take(&mut *p.borrow_mut())) {
+        unsafe { dealloc(addr as *mut u8, page_layout(pages)) };
+    }
+    for (addr, size) in POOL.with(|p| core::mem::take(&mut *p.borrow_mut())) {
+        unsafe { dealloc(addr as *mut u8, Layout::from_size_align(size, 8).unwrap()) };
+    }
+}
+
+/// Number of pages currently allocated on this thread
+pub(crate) fn outstanding_pages() -> usize {
+    PAGES.with(|p| p.borrow().iter().map(|&(_, pages)| pages).sum())
+}
+
+unsafe extern "efiapi" fn allocate_pages(
+    alloc_type: AllocateType,
+    _memory_type: MemoryType,
+    pages: Uintn,
+    memory: *mut PhysicalAddress,
+) -> Status {
+    if pages == 0 || memory.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+    if alloc_type != AllocateType::AllocateAnyPages {
+        return EFI_UNSUPPORTED;
+    }
+    let addr = alloc_zeroed(page_layout(pages)) as PhysicalAddress;
+    if addr == 0 {
+        return EFI_OUT_OF_RESOURCES;
+    }
+    PAGES.with(|p| p.borrow_mut().push((addr, pages)));
+    *memory = addr;
+    EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn free_pages(memory: PhysicalAddress, pages: Uintn) -> Status {
+    PAGES.with(|p| {
+        let mut p = p.borrow_mut();
+        match p.iter().position(|&entry| entry == (memory, pages)) {
+            Some(i) => {
+                p.remove(i);
+                dealloc(memory as *mut u8, page_layout(pages));
+                EFI_SUCCESS
+            }
+            None => EFI_NOT_FOUND,
+        }
+    })
+}
+
+unsafe extern "efiapi" fn allocate_pool(
+    _pool_type: MemoryType,
+    size: Uintn,
+    buffer: *mut *mut core::ffi::c_void,
+) -> Status {
+    if size == 0 || buffer.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+    let ptr = alloc_zeroed(Layout::from_size_align(size, 8).unwrap());
+    if ptr.is_null() {
+        return EFI_OUT_OF_RESOURCES;
+    }
+    POOL.with(|p| p.borrow_mut().push((ptr as usize, size)));
+    *buffer = ptr as *mut core::ffi::c_void;
+    EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn free_pool(buffer: *mut core::ffi::c_void) -> Status {
+    POOL.with(|p| {
+        let mut p = p.borrow_mut();
+        match p.iter().position(|&(addr, _)| addr == buffer as usize) {
+            Some(i) => {
+                let (_, size) = p.remove(i);
+                dealloc(buffer as *mut u8, Layout::from_size_align(size, 8).unwrap());
+                EFI_SUCCESS
+            }
+            None => EFI_INVALID_PARAMETER,
+        }
+    })
+}
+
+unsafe extern "efiapi" fn unsupported() -> Status {
+    EFI_UNSUPPORTED
+}
+
+// The remaining services are never called by the unit tests; they all share
+// one stub that reports EFI_UNSUPPORTED.
+macro_rules! unsupported {
+    () => {
+        unsafe { core::mem::transmute::<*const (), _>(unsupported as *const ()) }
+    };
+}
+
+/// Boot Services table backed by the thread's allocation tracker
+pub(crate) fn boot_services() -> BootServices {
+    BootServices {
+        hdr: TableHeader {
+            signature: EFI_BOOT_SERVICES_SIGNATURE,
+            revision: 0,
+            header_size: core::mem::size_of::<BootServices>() as u32,
+            crc32: 0,
+            reserved: 0,
+        },
+        raise_tpl: unsupported!(),
+        restore_tpl: unsupported!(),
+        allocate_pages,
+        free_pages,
+        get_memory_map: unsupported!(),
+        allocate_pool,
+        free_pool,
+        create_event: unsupported!(),
+        set_timer: unsupported!(),
+        wait_for_event: unsupported!(),
+        signal_event: unsupported!(),
+        close_event: unsupported!(),
+        check_event: unsupported!(),
+        install_protocol_interface: unsupported!(),
+        reinstall_protocol_interface: unsupported!(),
+        uninstall_protocol_interface: unsupported!(),
+        handle_protocol: unsupported!(),
+        reserved: core::ptr::null_mut(),
+        register_protocol_notify: unsupported!(),
+        locate_handle: unsupported!(),
+        locate_device_path: unsupported!(),
+        install_configuration_table: unsupported!(),
+        load_image: unsupported!(),
+        start_image: unsupported!(),
+        exit: unsupported!(),
+        unload_image: unsupported!(),
+        exit_boot_services: unsupported!(),
+        get_next_monotonic_count: unsupported!(),
+        stall: unsupported!(),
+        set_watchdog_timer: unsupported!(),
+        connect_controller: unsupported!(),
+        disconnect_controller: unsupported!(),
+        open_protocol: unsupported!(),
+        close_protocol: unsupported!(),
+        open_protocol_information: unsupported!(),
+        protocols_per_handle: unsupported!(),
+        locate_handle_buffer: unsupported!(),
+        locate_protocol: unsupported!(),
+        install_multiple_protocol_interfaces: core::ptr::null_mut(),
+        uninstall_multiple_protocol_interfaces: core::ptr::null_mut(),
+        calculate_crc32: unsupported!(),
+        copy_mem: unsupported!(),
+        set_mem: unsupported!(),
+        create_event_ex: unsupported!(),
+    }
+}
@@ -4,11 +4,19 @@
 use crate::ffi::*;
 
 pub mod events;
+pub mod handles;
+pub mod memory_map;
+#[cfg(test)]
+pub(crate) mod mock;
 pub mod safe_wrappers;
+pub mod scoped_protocol;
 pub mod tpl;
 
 pub use events::*;
+pub use handles::{HandleBuffer, LocateSearchType};
+pub use memory_map::{MemoryMap, MemoryMapIter};
 pub use safe_wrappers::BootServicesWrapper;
+pub use scoped_protocol::*;
 pub use tpl::*;
 
 /// EFI_BOOT_SERVICES Table
@@ -1,12 +1,23 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! Safe wrappers around Boot Services
 
-use crate::boot_services::BootServices;
+use crate::boot_services::{
+    BootServices, HandleBuffer, LocateSearchType, MemoryMap, OpenProtocolAttributes, ScopedProtocol,
+};
+use crate::error::{Error, StatusExt};
 use crate::ffi::*;
+use crate::protocols::{DevicePathProtocol, Protocol};
 use core::ptr::null_mut;
+use core::ptr::NonNull;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
 
 /// Result type for UEFI operations
-pub type Result<T> = core::result::Result<T, Status>;
+pub use crate::error::Result;
 
 /// Safe wrapper for Boot Services
 pub struct BootServicesWrapper<'a> {
@@ -27,87 +38,146 @@ impl<'a> BootServicesWrapper<'a> {
         pages: usize,
     ) -> Result<PhysicalAddress> {
         let mut addr: PhysicalAddress = 0;
-        let status = unsafe { (self.bs.allocate_pages)(alloc_type, memory_type, pages, &mut addr) };
-
-        if status == EFI_SUCCESS {
-            Ok(addr)
-        } else {
-            Err(status)
-        }
+        unsafe { (self.bs.allocate_pages)(alloc_type, memory_type, pages, &mut addr) }
+            .to_result()?;
+        Ok(addr)
     }
 
     /// Free memory pages
     pub fn free_pages(&self, memory: PhysicalAddress, pages: usize) -> Result<()> {
-        let status = unsafe { (self.bs.free_pages)(memory, pages) };
+        unsafe { (self.bs.free_pages)(memory, pages) }.to_result()
+    }
 
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+    /// Get a snapshot of the current memory map
+    pub fn memory_map(&self) -> Result<MemoryMap> {
+        MemoryMap::get(self.bs)
     }
 
     /// Allocate memory pool
     pub fn allocate_pool(&self, pool_type: MemoryType, size: usize) -> Result<*mut u8> {
         let mut buffer: *mut core::ffi::c_void = null_mut();
-        let status = unsafe { (self.bs.allocate_pool)(pool_type, size, &mut buffer) };
-
-        if status == EFI_SUCCESS {
-            Ok(buffer as *mut u8)
-        } else {
-            Err(status)
-        }
+        unsafe { (self.bs.allocate_pool)(pool_type, size, &mut buffer) }.to_result()?;
+        Ok(buffer as *mut u8)
     }
 
     /// Free memory pool
     pub fn free_pool(&self, buffer: *mut u8) -> Result<()> {
-        let status = unsafe { (self.bs.free_pool)(buffer as *mut core::ffi::c_void) };
-
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+        unsafe { (self.bs.free_pool)(buffer as *mut core::ffi::c_void) }.to_result()
     }
 
     /// Stall execution for a number of microseconds
     pub fn stall(&self, microseconds: usize) -> Result<()> {
-        let status = unsafe { (self.bs.stall)(microseconds) };
-
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+        unsafe { (self.bs.stall)(microseconds) }.to_result()
     }
 
     /// Locate a protocol interface
     pub fn locate_protocol(&self, protocol: &Guid) -> Result<*mut core::ffi::c_void> {
         let mut interface: *mut core::ffi::c_void = null_mut();
-        let status = unsafe { (self.bs.locate_protocol)(protocol, null_mut(), &mut interface) };
+        unsafe { (self.bs.locate_protocol)(protocol, null_mut(), &mut interface) }.to_result()?;
+        Ok(interface)
+    }
 
-        if status == EFI_SUCCESS {
-            Ok(interface)
-        } else {
-            Err(status)
+    /// Open protocol `P` on a handle
+    ///
+    /// The interface is closed again when the returned [`ScopedProtocol`]
+    /// is dropped. Use `ByDriver` or `Exclusive` attributes to claim the
+    /// protocol for a driver.
+    ///
+    /// # Safety
+    /// The handles must be valid handles obtained from the UEFI firmware
+    pub unsafe fn open_protocol<P: Protocol>(
+        &self,
+        handle: *mut Handle,
+        agent: *mut Handle,
+        controller: *mut Handle,
+        attributes: OpenProtocolAttributes,
+    ) -> Result<ScopedProtocol<'a, P>> {
+        let mut interface: *mut core::ffi::c_void = null_mut();
+        (self.bs.open_protocol)(
+            handle,
+            &P::GUID,
+            &mut interface,
+            agent,
+            controller,
+            attributes.as_raw(),
+        )
+        .to_result()?;
+
+        let interface = NonNull::new(interface as *mut P).ok_or(Error::new(EFI_UNSUPPORTED))?;
+        Ok(ScopedProtocol::new(
+            self.bs, interface, handle, agent, controller,
+        ))
+    }
+
+    /// Find all handles that support protocol `P`
+    pub fn find_handles<P: Protocol>(&self) -> Result<HandleBuffer<'a>> {
+        self.locate_handle_buffer(LocateSearchType::ByProtocol, &P::GUID)
+    }
+
+    /// Get every handle in the handle database
+    pub fn all_handles(&self) -> Result<HandleBuffer<'a>> {
+        self.locate_handle_buffer(LocateSearchType::AllHandles, core::ptr::null())
+    }
+
+    fn locate_handle_buffer(
+        &self,
+        search_type: LocateSearchType,
+        protocol: *const Guid,
+    ) -> Result<HandleBuffer<'a>> {
+        let mut count = 0;
+        let mut buffer: *mut *mut Handle = null_mut();
+        unsafe {
+            (self.bs.locate_handle_buffer)(
+                search_type as u32,
+                protocol,
+                null_mut(),
+                &mut count,
+                &mut buffer,
+            )
+            .to_result()?;
+            Ok(HandleBuffer::new(self.bs, buffer, count))
         }
     }
 
-    /// Exit boot services
+    /// Get the GUIDs of all protocols installed on a handle
     ///
     /// # Safety
-    /// The image_handle must be a valid handle obtained from the UEFI firmware
-    pub unsafe fn exit_boot_services(
-        &self,
-        image_handle: *mut Handle,
-        map_key: usize,
-    ) -> Result<()> {
-        let status = (self.bs.exit_boot_services)(image_handle, map_key);
-
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
+    /// The handle must be a valid handle obtained from the UEFI firmware
+    pub unsafe fn protocols_on(&self, handle: *mut Handle) -> Result<Vec<Guid>> {
+        let mut buffer: *mut *const Guid = null_mut();
+        let mut count = 0;
+        (self.bs.protocols_per_handle)(handle, &mut buffer, &mut count).to_result()?;
+
+        if buffer.is_null() {
+            return Ok(Vec::new());
         }
+
+        let guids = core::slice::from_raw_parts(buffer, count)
+            .iter()
+            .map(|guid| **guid)
+            .collect();
+        let _ = (self.bs.free_pool)(buffer as *mut core::ffi::c_void);
+        Ok(guids)
+    }
+
+    /// Find the handle closest to `device_path` that supports protocol `P`
+    ///
+    /// On success `device_path` is advanced past the matched part, leaving
+    /// the remaining path.
+    ///
+    /// # Safety
+    /// `device_path` must point to a valid, end-terminated device path
+    pub unsafe fn locate_device_path<P: Protocol>(
+        &self,
+        device_path: &mut *mut DevicePathProtocol,
+    ) -> Result<*mut Handle> {
+        let mut handle: *mut Handle = null_mut();
+        (self.bs.locate_device_path)(
+            &P::GUID,
+            device_path as *mut *mut DevicePathProtocol as *mut *mut core::ffi::c_void,
+            &mut handle,
+        )
+        .to_result()?;
+        Ok(handle)
     }
 }
@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! RAII protocol interfaces opened through `OpenProtocol`
+
+use crate::boot_services::BootServices;
+use crate::ffi::*;
+use crate::protocols::Protocol;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+// OpenProtocol attribute bits
+pub const EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL: u32 = 0x00000001;
+pub const EFI_OPEN_PROTOCOL_GET_PROTOCOL: u32 = 0x00000002;
+pub const EFI_OPEN_PROTOCOL_TEST_PROTOCOL: u32 = 0x00000004;
+pub const EFI_OPEN_PROTOCOL_BY_CHILD_CONTROLLER: u32 = 0x00000008;
+pub const EFI_OPEN_PROTOCOL_BY_DRIVER: u32 = 0x00000010;
+pub const EFI_OPEN_PROTOCOL_EXCLUSIVE: u32 = 0x00000020;
+
+/// How a protocol interface is opened
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OpenProtocolAttributes {
+    /// Plain access without taking ownership (`GET_PROTOCOL`)
+    GetProtocol,
+    /// Record a child controller's use of a parent's protocol
+    ByChildController,
+    /// Driver-model access; fails if another driver already has it
+    ByDriver,
+    /// Exclusive access; disconnects other drivers using the protocol
+    Exclusive,
+    /// Driver-model access that also disconnects other drivers
+    ByDriverExclusive,
+}
+
+impl OpenProtocolAttributes {
+    /// Get the raw `Attributes` value for `OpenProtocol`
+    pub const fn as_raw(self) -> u32 {
+        match self {
+            Self::GetProtocol => EFI_OPEN_PROTOCOL_GET_PROTOCOL,
+            Self::ByChildController => EFI_OPEN_PROTOCOL_BY_CHILD_CONTROLLER,
+            Self::ByDriver => EFI_OPEN_PROTOCOL_BY_DRIVER,
+            Self::Exclusive => EFI_OPEN_PROTOCOL_EXCLUSIVE,
+            Self::ByDriverExclusive => EFI_OPEN_PROTOCOL_BY_DRIVER | EFI_OPEN_PROTOCOL_EXCLUSIVE,
+        }
+    }
+}
+
+/// A protocol interface that is closed again when dropped
+///
+/// Returned by [`BootServicesWrapper::open_protocol`](crate::boot_services::BootServicesWrapper::open_protocol).
+/// Derefs to the protocol struct, and calls `CloseProtocol` with the same
+/// handle, agent and controller on drop.
+pub struct ScopedProtocol<'a, P: Protocol> {
+    bs: &'a BootServices,
+    interface: NonNull<P>,
+    handle: *mut Handle,
+    agent: *mut Handle,
+    controller: *mut Handle,
+    _marker: PhantomData<&'a mut P>,
+}
+
+impl<'a, P: Protocol> ScopedProtocol<'a, P> {
+    /// Wrap an interface that was opened with `OpenProtocol`
+    ///
+    /// # Safety
+    /// `interface` must have been returned by `OpenProtocol` for `P::GUID`
+    /// on `handle` with the given agent and controller.
+    pub unsafe fn new(
+        bs: &'a BootServices,
+        interface: NonNull<P>,
+        handle: *mut Handle,
+        agent: *mut Handle,
+        controller: *mut Handle,
+    ) -> Self {
+        ScopedProtocol {
+            bs,
+            interface,
+            handle,
+            agent,
+            controller,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the raw interface pointer
+    pub fn as_ptr(&self) -> *mut P {
+        self.interface.as_ptr()
+    }
+
+    /// Get the handle the protocol was opened on
+    pub fn handle(&self) -> *mut Handle {
+        self.handle
+    }
+}
+
+impl<P: Protocol> Deref for ScopedProtocol<'_, P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        unsafe { self.interface.as_ref() }
+    }
+}
+
+impl<P: Protocol> DerefMut for ScopedProtocol<'_, P> {
+    fn deref_mut(&mut self) -> &mut P {
+        unsafe { self.interface.as_mut() }
+    }
+}
+
+impl<P: Protocol> Drop for ScopedProtocol<'_, P> {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = (self.bs.close_protocol)(self.handle, &P::GUID, self.agent, self.controller);
+        }
+    }
+}
@@ -46,7 +46,7 @@ impl TplGuard {
     /// A TplGuard that will restore the old TPL when dropped
     pub unsafe fn raise(new_tpl: Tpl) -> Self {
         let boot_services = get_boot_services();
-        let old_tpl = ((*boot_services).raise_tpl)(new_tpl);
+        let old_tpl = (boot_services.raise_tpl)(new_tpl);
         TplGuard { old_tpl }
     }
 
@@ -69,7 +69,7 @@ impl Drop for TplGuard {
     fn drop(&mut self) {
         unsafe {
             let boot_services = get_boot_services();
-            ((*boot_services).restore_tpl)(self.old_tpl);
+            (boot_services.restore_tpl)(self.old_tpl);
         }
     }
 }
@@ -84,8 +84,8 @@ pub mod tpl_utils {
     /// Requires valid Boot Services table
     pub unsafe fn get_current_tpl() -> Tpl {
         let boot_services = get_boot_services();
-        let current = ((*boot_services).raise_tpl)(TPL_HIGH_LEVEL);
-        ((*boot_services).restore_tpl)(current);
+        let current = (boot_services.raise_tpl)(TPL_HIGH_LEVEL);
+        (boot_services.restore_tpl)(current);
         current
     }
 
@@ -183,19 +183,15 @@ impl CallbackGuard {
     }
 }
 
-/// Get the global Boot Services pointer
+/// Get the global Boot Services table
+///
+/// This is the table registered by `efi_entry!` through
+/// [`crate::allocator::init_allocator`].
 ///
 /// # Safety
 /// This assumes Boot Services have been properly initialized
-unsafe fn get_boot_services() -> *mut BootServices {
-    // In a real implementation, this would get the boot services from
-    // the system table stored globally during initialization.
-    // For now, we'll use a placeholder that should be replaced with
-    // actual global state management.
-    extern "C" {
-        static mut BOOT_SERVICES: *mut BootServices;
-    }
-    BOOT_SERVICES
+unsafe fn get_boot_services() -> &'static BootServices {
+    crate::allocator::boot_services().expect("boot services not available")
 }
 
 /// Scoped TPL elevation macro
@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Firmware Management Protocol Capsules
+//!
+//! Encoder and decoder for the `EFI_FIRMWARE_MANAGEMENT_CAPSULE_HEADER`
+//! body carried by FMP capsules: embedded drivers followed by payload items
+//! aimed at an FMP instance by image type GUID. Also handles the
+//! `FMP_PAYLOAD_HEADER` that FmpDevicePkg prepends to each image.
+
+use crate::capsule::Capsule;
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::guid;
+use crate::runtime_services::VariableCodec;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// EFI_FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID
+pub const FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID: Guid = guid!("6dcbd5ed-e82d-4c44-bda1-7194199ad92a");
+
+/// EFI_FIRMWARE_MANAGEMENT_CAPSULE_HEADER_INIT_VERSION
+pub const FMP_CAPSULE_HEADER_INIT_VERSION: u32 = 0x00000001;
+/// EFI_FIRMWARE_MANAGEMENT_CAPSULE_IMAGE_HEADER_INIT_VERSION
+pub const FMP_CAPSULE_IMAGE_HEADER_INIT_VERSION: u32 = 0x00000003;
+
+/// Size of EFI_FIRMWARE_MANAGEMENT_CAPSULE_HEADER without the offset list
+pub const FMP_CAPSULE_HEADER_SIZE: usize = 8;
+
+/// Image capsule support flags (image header version 3)
+pub const CAPSULE_SUPPORT_AUTHENTICATION: u64 = 0x0000000000000001;
+pub const CAPSULE_SUPPORT_DEPENDENCY: u64 = 0x0000000000000002;
+
+/// FMP_PAYLOAD_HEADER signature ("MSS1")
+pub const FMP_PAYLOAD_HEADER_SIGNATURE: u32 = u32::from_le_bytes(*b"MSS1");
+/// Size of FMP_PAYLOAD_HEADER
+pub const FMP_PAYLOAD_HEADER_SIZE: usize = 16;
+
+fn u16_at(data: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([data[off], data[off + 1]])
+}
+
+fn u32_at(data: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(data[off..off + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(data[off..off + 8].try_into().unwrap())
+}
+
+/// Size of EFI_FIRMWARE_MANAGEMENT_CAPSULE_IMAGE_HEADER for a given version
+///
+/// Version 2 added `UpdateHardwareInstance`, version 3 `ImageCapsuleSupport`.
+pub fn image_header_size(version: u32) -> usize {
+    match version {
+        1 => 32,
+        2 => 40,
+        _ => 48,
+    }
+}
+
+/// One payload item: EFI_FIRMWARE_MANAGEMENT_CAPSULE_IMAGE_HEADER plus data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FmpPayload {
+    /// Image type GUID of the target FMP instance (the ESRT firmware class)
+    pub image_type_id: Guid,
+    /// 1-based index of the image within the FMP instance
+    pub image_index: u8,
+    /// Hardware instance to update, 0 for all
+    pub hardware_instance: u64,
+    pub image_capsule_support: u64,
+    pub image: Vec<u8>,
+    pub vendor_code: Vec<u8>,
+}
+
+impl FmpPayload {
+    /// Create a payload for image `image_index` of the FMP instance `image_type_id`
+    pub fn new(image_type_id: Guid, image_index: u8, image: &[u8]) -> Self {
+        FmpPayload {
+            image_type_id,
+            image_index,
+            hardware_instance: 0,
+            image_capsule_support: 0,
+            image: image.to_vec(),
+            vendor_code: Vec::new(),
+        }
+    }
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let version = u32_at(data, 0);
+        if version == 0 {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        let header_size = image_header_size(version);
+        if data.len() < header_size {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let image_size = u32_at(data, 24) as usize;
+        let vendor_code_size = u32_at(data, 28) as usize;
+        let image_end = header_size
+            .checked_add(image_size)
+            .ok_or(Error::new(EFI_BAD_BUFFER_SIZE))?;
+        let end = image_end
+            .checked_add(vendor_code_size)
+            .ok_or(Error::new(EFI_BAD_BUFFER_SIZE))?;
+        if end > data.len() {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        Ok(FmpPayload {
+            image_type_id: Guid::decode(&data[4..20])?,
+            image_index: data[20],
+            hardware_instance: if version >= 2 { u64_at(data, 32) } else { 0 },
+            image_capsule_support: if version >= 3 { u64_at(data, 40) } else { 0 },
+            image: data[header_size..image_end].to_vec(),
+            vendor_code: data[image_end..end].to_vec(),
+        })
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) -> Result<()> {
+        let image_size =
+            u32::try_from(self.image.len()).map_err(|_| Error::new(EFI_BAD_BUFFER_SIZE))?;
+        let vendor_code_size =
+            u32::try_from(self.vendor_code.len()).map_err(|_| Error::new(EFI_BAD_BUFFER_SIZE))?;
+        out.extend_from_slice(&FMP_CAPSULE_IMAGE_HEADER_INIT_VERSION.to_le_bytes());
+        self.image_type_id.encode(out);
+        out.extend_from_slice(&[self.image_index, 0, 0, 0]);
+        out.extend_from_slice(&image_size.to_le_bytes());
+        out.extend_from_slice(&vendor_code_size.to_le_bytes());
+        out.extend_from_slice(&self.hardware_instance.to_le_bytes());
+        out.extend_from_slice(&self.image_capsule_support.to_le_bytes());
+        out.extend_from_slice(&self.image);
+        out.extend_from_slice(&self.vendor_code);
+        Ok(())
+    }
+}
+
+/// EFI_FIRMWARE_MANAGEMENT_CAPSULE_HEADER body with its items
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FmpCapsule {
+    /// Embedded driver images, loaded before the payloads are applied
+    pub drivers: Vec<Vec<u8>>,
+    pub payloads: Vec<FmpPayload>,
+}
+
+impl FmpCapsule {
+    /// Create an empty FMP capsule
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the body that follows EFI_CAPSULE_HEADER
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < FMP_CAPSULE_HEADER_SIZE {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        if u32_at(data, 0) != FMP_CAPSULE_HEADER_INIT_VERSION {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        let driver_count = u16_at(data, 4) as usize;
+        let item_count = driver_count + u16_at(data, 6) as usize;
+        let list_end = FMP_CAPSULE_HEADER_SIZE + item_count * 8;
+        if data.len() < list_end {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+
+        let mut offsets = Vec::with_capacity(item_count + 1);
+        for i in 0..item_count {
+            let offset = u64_at(data, FMP_CAPSULE_HEADER_SIZE + i * 8);
+            let offset = usize::try_from(offset).map_err(|_| Error::new(EFI_BAD_BUFFER_SIZE))?;
+            let previous = offsets.last().copied().unwrap_or(list_end);
+            if offset < previous || offset > data.len() {
+                return Err(Error::new(EFI_INVALID_PARAMETER));
+            }
+            offsets.push(offset);
+        }
+        offsets.push(data.len());
+
+        let mut capsule = FmpCapsule::new();
+        for (i, item) in offsets.windows(2).enumerate() {
+            let item = &data[item[0]..item[1]];
+            if i < driver_count {
+                capsule.drivers.push(item.to_vec());
+            } else {
+                capsule.payloads.push(FmpPayload::parse(item)?);
+            }
+        }
+        Ok(capsule)
+    }
+
+    /// Serialize to the body that follows EFI_CAPSULE_HEADER
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let driver_count =
+            u16::try_from(self.drivers.len()).map_err(|_| Error::new(EFI_INVALID_PARAMETER))?;
+        let payload_count =
+            u16::try_from(self.payloads.len()).map_err(|_| Error::new(EFI_INVALID_PARAMETER))?;
+        let item_count = self.drivers.len() + self.payloads.len();
+
+        let mut items = Vec::new();
+        let mut offsets = Vec::with_capacity(item_count);
+        let base = FMP_CAPSULE_HEADER_SIZE + item_count * 8;
+        for driver in &self.drivers {
+            offsets.push((base + items.len()) as u64);
+            items.extend_from_slice(driver);
+        }
+        for payload in &self.payloads {
+            offsets.push((base + items.len()) as u64);
+            payload.encode(&mut items)?;
+        }
+
+        let mut out = Vec::with_capacity(base + items.len());
+        out.extend_from_slice(&FMP_CAPSULE_HEADER_INIT_VERSION.to_le_bytes());
+        out.extend_from_slice(&driver_count.to_le_bytes());
+        out.extend_from_slice(&payload_count.to_le_bytes());
+        for offset in offsets {
+            out.extend_from_slice(&offset.to_le_bytes());
+        }
+        out.extend_from_slice(&items);
+        Ok(out)
+    }
+
+    /// Decode the body of a capsule addressed to the FMP capsule GUID
+    pub fn from_capsule(capsule: &Capsule) -> Result<Self> {
+        if capsule.guid != FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        Self::parse(&capsule.payload)
+    }
+
+    /// Wrap this body in a capsule addressed to the FMP capsule GUID
+    pub fn to_capsule(&self, flags: u32) -> Result<Capsule> {
+        Ok(Capsule {
+            guid: FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID,
+            flags,
+            payload: self.to_bytes()?,
+        })
+    }
+
+    /// Payloads aimed at the firmware class `image_type_id`
+    pub fn payloads_for<'a>(
+        &'a self,
+        image_type_id: &'a Guid,
+    ) -> impl Iterator<Item = &'a FmpPayload> + 'a {
+        self.payloads
+            .iter()
+            .filter(move |p| p.image_type_id == *image_type_id)
+    }
+}
+
+/// FMP_PAYLOAD_HEADER
+///
+/// Version header FmpDevicePkg places in front of the firmware image inside
+/// a payload item.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FmpPayloadHeader {
+    pub fw_version: u32,
+    pub lowest_supported_version: u32,
+}
+
+impl FmpPayloadHeader {
+    /// Create a header for `fw_version`
+    pub fn new(fw_version: u32, lowest_supported_version: u32) -> Self {
+        FmpPayloadHeader {
+            fw_version,
+            lowest_supported_version,
+        }
+    }
+
+    /// Parse the header, returning it with the firmware image that follows
+    pub fn parse(data: &[u8]) -> Result<(Self, &[u8])> {
+        if data.len() < FMP_PAYLOAD_HEADER_SIZE {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        if u32_at(data, 0) != FMP_PAYLOAD_HEADER_SIGNATURE {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        let header_size = u32_at(data, 4) as usize;
+        if header_size < FMP_PAYLOAD_HEADER_SIZE {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        if header_size > data.len() {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let header = FmpPayloadHeader {
+            fw_version: u32_at(data, 8),
+            lowest_supported_version: u32_at(data, 12),
+        };
+        Ok((header, &data[header_size..]))
+    }
+
+    /// Prepend this header to `image`
+    pub fn wrap(&self, image: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(FMP_PAYLOAD_HEADER_SIZE + image.len());
+        out.extend_from_slice(&FMP_PAYLOAD_HEADER_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&(FMP_PAYLOAD_HEADER_SIZE as u32).to_le_bytes());
+        out.extend_from_slice(&self.fw_version.to_le_bytes());
+        out.extend_from_slice(&self.lowest_supported_version.to_le_bytes());
+        out.extend_from_slice(image);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capsule::CAPSULE_FLAGS_PERSIST_ACROSS_RESET;
+
+    const DEVICE: Guid = guid!("e0b2a1f5-4c3d-4e6f-8a9b-1c2d3e4f5a6b");
+    const OTHER: Guid = guid!("0f1e2d3c-4b5a-4697-8877-665544332211");
+
+    #[test]
+    fn test_fmp_capsule_round_trip() {
+        let image = FmpPayloadHeader::new(0x0102, 0x0100).wrap(b"firmware");
+        let mut payload = FmpPayload::new(DEVICE, 1, &image);
+        payload.hardware_instance = 2;
+        payload.vendor_code = b"vc".to_vec();
+        let capsule = FmpCapsule {
+            drivers: vec![b"driver-efi".to_vec()],
+            payloads: vec![payload, FmpPayload::new(OTHER, 2, b"other")],
+        };
+
+        let wrapped = capsule
+            .to_capsule(CAPSULE_FLAGS_PERSIST_ACROSS_RESET)
+            .unwrap();
+        let image_bytes = wrapped.to_bytes().unwrap();
+        let decoded = FmpCapsule::from_capsule(&Capsule::parse(&image_bytes).unwrap()).unwrap();
+        assert_eq!(decoded, capsule);
+
+        let targeted: Vec<_> = decoded.payloads_for(&DEVICE).collect();
+        assert_eq!(targeted.len(), 1);
+        assert_eq!(targeted[0].vendor_code, b"vc");
+        let (header, fw) = FmpPayloadHeader::parse(&targeted[0].image).unwrap();
+        assert_eq!(header, FmpPayloadHeader::new(0x0102, 0x0100));
+        assert_eq!(fw, b"firmware");
+
+        let foreign = Capsule::new(OTHER, 0, &capsule.to_bytes().unwrap());
+        assert_eq!(
+            FmpCapsule::from_capsule(&foreign).unwrap_err().status(),
+            EFI_UNSUPPORTED
+        );
+    }
+
+    #[test]
+    fn test_fmp_capsule_older_image_header_and_bad_offsets() {
+        // Version 1 image header: no hardware instance or capsule support
+        let mut body = Vec::new();
+        body.extend_from_slice(&1u32.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&16u64.to_le_bytes());
+        body.extend_from_slice(&1u32.to_le_bytes());
+        DEVICE.encode(&mut body);
+        body.extend_from_slice(&[3, 0, 0, 0]);
+        body.extend_from_slice(&4u32.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(b"abcd");
+
+        let capsule = FmpCapsule::parse(&body).unwrap();
+        assert_eq!(capsule.payloads.len(), 1);
+        assert_eq!(capsule.payloads[0].image_index, 3);
+        assert_eq!(capsule.payloads[0].hardware_instance, 0);
+        assert_eq!(capsule.payloads[0].image, b"abcd");
+
+        assert_eq!(
+            FmpCapsule::parse(&body[..body.len() - 1])
+                .unwrap_err()
+                .status(),
+            EFI_BAD_BUFFER_SIZE
+        );
+        body[8..16].copy_from_slice(&4u64.to_le_bytes());
+        assert_eq!(
+            FmpCapsule::parse(&body).unwrap_err().status(),
+            EFI_INVALID_PARAMETER
+        );
+    }
+}
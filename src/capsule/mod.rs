@@ -0,0 +1,592 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Capsule Update
+//!
+//! Builds `EFI_CAPSULE_HEADER` images, lays them out in page memory behind
+//! the `EFI_CAPSULE_BLOCK_DESCRIPTOR` chain that `UpdateCapsule()` consumes,
+//! and drives `QueryCapsuleCapabilities()`, `UpdateCapsule()` and the reset
+//! the firmware asks for afterwards. [`fmp`] handles the FMP capsule body.
+
+pub mod fmp;
+
+pub use fmp::*;
+
+use crate::boot_services::memory_map::EFI_PAGE_SIZE;
+use crate::boot_services::{BootServices, BootServicesWrapper};
+use crate::error::{Error, ErrorData, Result, StatusExt};
+use crate::ffi::*;
+use crate::runtime_services::{ResetType, RuntimeServices, RuntimeServicesWrapper, VariableCodec};
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The capsule is held in memory across a warm reset and processed then
+pub const CAPSULE_FLAGS_PERSIST_ACROSS_RESET: u32 = 0x0001_0000;
+/// After the reset the capsule is published in the configuration table
+pub const CAPSULE_FLAGS_POPULATE_SYSTEM_TABLE: u32 = 0x0002_0000;
+/// `UpdateCapsule()` resets the system itself instead of returning
+pub const CAPSULE_FLAGS_INITIATE_RESET: u32 = 0x0004_0000;
+
+/// Size of EFI_CAPSULE_HEADER
+pub const CAPSULE_HEADER_SIZE: usize = core::mem::size_of::<CapsuleHeader>();
+
+const DESCRIPTOR_SIZE: usize = core::mem::size_of::<CapsuleBlockDescriptor>();
+
+/// EFI_CAPSULE_HEADER
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CapsuleHeader {
+    pub capsule_guid: Guid,
+    pub header_size: u32,
+    pub flags: u32,
+    pub capsule_image_size: u32,
+}
+
+impl CapsuleHeader {
+    /// Parse and bounds-check the header at the start of a capsule image
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < CAPSULE_HEADER_SIZE {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let u32_at = |off: usize| u32::from_le_bytes(data[off..off + 4].try_into().unwrap());
+        let header = CapsuleHeader {
+            capsule_guid: Guid::decode(&data[..16])?,
+            header_size: u32_at(16),
+            flags: u32_at(20),
+            capsule_image_size: u32_at(24),
+        };
+        if (header.header_size as usize) < CAPSULE_HEADER_SIZE
+            || header.header_size > header.capsule_image_size
+        {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        if header.capsule_image_size as usize > data.len() {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        Ok(header)
+    }
+}
+
+/// EFI_CAPSULE_BLOCK_DESCRIPTOR
+///
+/// A zero `length` turns the entry into a continuation pointer to the next
+/// descriptor block, or terminates the list when `address` is zero too.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CapsuleBlockDescriptor {
+    pub length: u64,
+    pub address: PhysicalAddress,
+}
+
+impl CapsuleBlockDescriptor {
+    /// Check whether this entry ends the list
+    pub fn is_terminator(&self) -> bool {
+        self.length == 0 && self.address == 0
+    }
+
+    /// Check whether this entry points at the next descriptor block
+    pub fn is_continuation(&self) -> bool {
+        self.length == 0 && self.address != 0
+    }
+}
+
+/// Check a flag combination against the rules `UpdateCapsule()` enforces
+pub fn validate_flags(flags: u32) -> Result<()> {
+    let needs_persist = CAPSULE_FLAGS_POPULATE_SYSTEM_TABLE | CAPSULE_FLAGS_INITIATE_RESET;
+    if flags & needs_persist != 0 && flags & CAPSULE_FLAGS_PERSIST_ACROSS_RESET == 0 {
+        return Err(Error::new(EFI_INVALID_PARAMETER));
+    }
+    Ok(())
+}
+
+/// A capsule image: header GUID, flags and payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capsule {
+    pub guid: Guid,
+    pub flags: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Capsule {
+    /// Create a capsule for `guid` carrying `payload`
+    pub fn new(guid: Guid, flags: u32, payload: &[u8]) -> Self {
+        Capsule {
+            guid,
+            flags,
+            payload: payload.to_vec(),
+        }
+    }
+
+    /// Parse a capsule image, skipping any header bytes past EFI_CAPSULE_HEADER
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let header = CapsuleHeader::parse(data)?;
+        Ok(Capsule {
+            guid: header.capsule_guid,
+            flags: header.flags,
+            payload: data[header.header_size as usize..header.capsule_image_size as usize].to_vec(),
+        })
+    }
+
+    /// Size of the serialized image
+    pub fn image_size(&self) -> usize {
+        CAPSULE_HEADER_SIZE + self.payload.len()
+    }
+
+    /// Serialize to the image `UpdateCapsule()` expects
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        validate_flags(self.flags)?;
+        let image_size =
+            u32::try_from(self.image_size()).map_err(|_| Error::new(EFI_BAD_BUFFER_SIZE))?;
+        let mut out = Vec::with_capacity(image_size as usize);
+        self.guid.encode(&mut out);
+        out.extend_from_slice(&(CAPSULE_HEADER_SIZE as u32).to_le_bytes());
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&image_size.to_le_bytes());
+        out.extend_from_slice(&self.payload);
+        Ok(out)
+    }
+}
+
+// ============================================================================
+// Scatter-gather list
+// ============================================================================
+
+struct Block {
+    base: PhysicalAddress,
+    pages: usize,
+    descriptors: PhysicalAddress,
+}
+
+/// Capsule images copied into page memory behind a descriptor chain
+///
+/// Each capsule gets one page allocation holding the image followed by a
+/// two-entry descriptor block: the image itself, then a continuation to the
+/// next capsule's block or the terminator. The pages are freed on drop.
+pub struct ScatterGatherList<'a> {
+    bs: BootServicesWrapper<'a>,
+    blocks: Vec<Block>,
+    flags: u32,
+    total_size: u64,
+}
+
+impl<'a> ScatterGatherList<'a> {
+    /// Copy `images` (complete capsules, header included) into boot-services pages
+    pub fn new(bs: &'a BootServices, images: &[&[u8]]) -> Result<Self> {
+        if images.is_empty() {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        let mut list = ScatterGatherList {
+            bs: BootServicesWrapper::new(bs),
+            blocks: Vec::with_capacity(images.len()),
+            flags: 0,
+            total_size: 0,
+        };
+        for image in images {
+            let header = CapsuleHeader::parse(image)?;
+            if header.capsule_image_size as usize != image.len() {
+                return Err(Error::new(EFI_INVALID_PARAMETER));
+            }
+            validate_flags(header.flags)?;
+
+            let table_offset = image.len().next_multiple_of(8);
+            let size = table_offset + 2 * DESCRIPTOR_SIZE;
+            let pages = size.div_ceil(EFI_PAGE_SIZE as usize);
+            let base = list.bs.allocate_pages(
+                AllocateType::AllocateAnyPages,
+                MemoryType::BootServicesData,
+                pages,
+            )?;
+            unsafe {
+                core::ptr::copy_nonoverlapping(image.as_ptr(), base as *mut u8, image.len());
+            }
+            list.blocks.push(Block {
+                base,
+                pages,
+                descriptors: base + table_offset as u64,
+            });
+            list.flags |= header.flags;
+            list.total_size += image.len() as u64;
+        }
+
+        for (i, block) in list.blocks.iter().enumerate() {
+            let next = list.blocks.get(i + 1).map_or(0, |b| b.descriptors);
+            let table = block.descriptors as *mut CapsuleBlockDescriptor;
+            unsafe {
+                table.write(CapsuleBlockDescriptor {
+                    length: images[i].len() as u64,
+                    address: block.base,
+                });
+                table.add(1).write(CapsuleBlockDescriptor {
+                    length: 0,
+                    address: next,
+                });
+            }
+        }
+        Ok(list)
+    }
+
+    /// Physical address of the first descriptor block
+    pub fn address(&self) -> PhysicalAddress {
+        self.blocks[0].descriptors
+    }
+
+    /// Number of capsules in the list
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Always false; a list holds at least one capsule
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Union of the capsule flags
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Combined size of the capsule images
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    /// Walk the chain and return its data descriptors
+    pub fn descriptors(&self) -> Vec<CapsuleBlockDescriptor> {
+        let mut out = Vec::new();
+        let mut entry = self.address() as *const CapsuleBlockDescriptor;
+        loop {
+            let desc = unsafe { entry.read() };
+            if desc.is_terminator() {
+                return out;
+            }
+            if desc.is_continuation() {
+                entry = desc.address as *const CapsuleBlockDescriptor;
+            } else {
+                out.push(desc);
+                entry = unsafe { entry.add(1) };
+            }
+        }
+    }
+
+    /// Give up ownership of the pages, returning the chain address
+    ///
+    /// Needed when the capsules must survive past this list, e.g. across the
+    /// reset that processes a persistent capsule.
+    pub fn into_raw(self) -> PhysicalAddress {
+        let address = self.address();
+        core::mem::forget(self);
+        address
+    }
+
+    fn header_pointers(&self) -> Vec<*mut core::ffi::c_void> {
+        self.blocks
+            .iter()
+            .map(|b| b.base as *mut core::ffi::c_void)
+            .collect()
+    }
+}
+
+impl Drop for ScatterGatherList<'_> {
+    fn drop(&mut self) {
+        for block in &self.blocks {
+            let _ = self.bs.free_pages(block.base, block.pages);
+        }
+    }
+}
+
+// ============================================================================
+// Capsule Services
+// ============================================================================
+
+/// Result of `QueryCapsuleCapabilities()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CapsuleCapabilities {
+    pub maximum_capsule_size: u64,
+    pub reset_type: ResetType,
+}
+
+/// Safe wrapper for the capsule runtime services
+pub struct CapsuleService<'a> {
+    rt: &'a RuntimeServices,
+}
+
+impl<'a> CapsuleService<'a> {
+    /// Create a new capsule service wrapper
+    pub fn new(rt: &'a RuntimeServices) -> Self {
+        CapsuleService { rt }
+    }
+
+    /// Ask the firmware whether it can take these capsules and how it must reset
+    ///
+    /// A reset type outside EFI_RESET_TYPE fails with `EFI_DEVICE_ERROR`.
+    pub unsafe fn query_capabilities(
+        &self,
+        list: &ScatterGatherList,
+    ) -> Result<CapsuleCapabilities> {
+        let mut headers = list.header_pointers();
+        let mut maximum_capsule_size = 0u64;
+        // Read as the raw EFI_RESET_TYPE; firmware may report any value
+        let mut reset_type = 0u32;
+        (self.rt.query_capsule_capabilities)(
+            headers.as_mut_ptr(),
+            headers.len(),
+            &mut maximum_capsule_size,
+            &mut reset_type,
+        )
+        .to_result()?;
+        Ok(CapsuleCapabilities {
+            maximum_capsule_size,
+            reset_type: ResetType::try_from(reset_type)
+                .map_err(|_| Error::new(EFI_DEVICE_ERROR))?,
+        })
+    }
+
+    /// Pass the capsules to the firmware
+    pub unsafe fn update_capsule(&self, list: &ScatterGatherList) -> Result<()> {
+        let mut headers = list.header_pointers();
+        (self.rt.update_capsule)(headers.as_mut_ptr(), headers.len(), list.address()).to_result()
+    }
+
+    /// Check the capsules fit, then submit them
+    ///
+    /// Returns the reset type the firmware asked for when the capsules only
+    /// take effect across a reset, `None` when they were processed in place.
+    pub unsafe fn submit(&self, list: &ScatterGatherList) -> Result<Option<ResetType>> {
+        let caps = self.query_capabilities(list)?;
+        if list.total_size() > caps.maximum_capsule_size {
+            return Err(Error::with_data(
+                EFI_BAD_BUFFER_SIZE,
+                ErrorData::RequiredSize(list.total_size() as usize),
+            ));
+        }
+        self.update_capsule(list)?;
+        Ok((list.flags() & CAPSULE_FLAGS_PERSIST_ACROSS_RESET != 0).then_some(caps.reset_type))
+    }
+
+    /// Submit the capsules and perform the reset that applies them
+    ///
+    /// Only returns on error, or when the firmware processed the capsules
+    /// without needing a reset.
+    pub unsafe fn submit_and_reset(&self, list: ScatterGatherList) -> Result<()> {
+        if let Some(reset_type) = self.submit(&list)? {
+            list.into_raw();
+            RuntimeServicesWrapper::new(self.rt).reset_system(reset_type, EFI_SUCCESS, &[]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boot_services::mock as bs_mock;
+    use crate::runtime_services::mock;
+    use std::cell::{Cell, RefCell};
+    use std::sync::mpsc;
+
+    const FW_GUID: Guid = FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID;
+
+    std::thread_local! {
+        static MAX_SIZE: RefCell<u64> = const { RefCell::new(0) };
+        static REPORTED_RESET: Cell<u32> = const { Cell::new(ResetType::EfiResetWarm as u32) };
+        static RESETS: RefCell<Option<mpsc::Sender<(ResetType, Status, usize)>>> =
+            const { RefCell::new(None) };
+        static SUBMITTED: RefCell<Vec<(usize, PhysicalAddress)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    unsafe extern "efiapi" fn update_capsule(
+        _headers: *mut *mut core::ffi::c_void,
+        count: Uintn,
+        scatter_gather_list: PhysicalAddress,
+    ) -> Status {
+        SUBMITTED.with(|s| s.borrow_mut().push((count, scatter_gather_list)));
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn query_capsule_capabilities(
+        headers: *mut *mut core::ffi::c_void,
+        count: Uintn,
+        maximum_capsule_size: *mut Uint64,
+        reset_type: *mut Uint32,
+    ) -> Status {
+        let headers = core::slice::from_raw_parts(headers, count);
+        if headers
+            .iter()
+            .any(|&h| (*(h as *const CapsuleHeader)).capsule_guid != FW_GUID)
+        {
+            return EFI_UNSUPPORTED;
+        }
+        *maximum_capsule_size = MAX_SIZE.with(|m| *m.borrow());
+        *reset_type = REPORTED_RESET.with(Cell::get);
+        EFI_SUCCESS
+    }
+
+    /// Report the reset, with the pages still allocated, then never return
+    unsafe extern "efiapi" fn reset_system(
+        reset_type: ResetType,
+        reset_status: Status,
+        _data_size: Uintn,
+        _reset_data: *const core::ffi::c_void,
+    ) -> ! {
+        let pages = bs_mock::outstanding_pages();
+        RESETS.with(|r| {
+            let _ = r
+                .borrow()
+                .as_ref()
+                .unwrap()
+                .send((reset_type, reset_status, pages));
+        });
+        loop {
+            std::thread::park();
+        }
+    }
+
+    fn capsule_services(max_size: u64) -> RuntimeServices {
+        mock::reset();
+        MAX_SIZE.with(|m| *m.borrow_mut() = max_size);
+        SUBMITTED.with(|s| s.borrow_mut().clear());
+        REPORTED_RESET.with(|r| r.set(ResetType::EfiResetWarm as u32));
+        RuntimeServices {
+            update_capsule,
+            query_capsule_capabilities,
+            reset_system,
+            ..mock::runtime_services()
+        }
+    }
+
+    #[test]
+    fn test_capsule_image_round_trip() {
+        let capsule = Capsule::new(FW_GUID, CAPSULE_FLAGS_PERSIST_ACROSS_RESET, b"payload");
+        let image = capsule.to_bytes().unwrap();
+        assert_eq!(image.len(), CAPSULE_HEADER_SIZE + 7);
+        assert_eq!(Capsule::parse(&image).unwrap(), capsule);
+
+        let header = CapsuleHeader::parse(&image).unwrap();
+        assert_eq!(header.header_size as usize, CAPSULE_HEADER_SIZE);
+        assert_eq!(header.capsule_image_size as usize, image.len());
+        assert_eq!(
+            CapsuleHeader::parse(&image[..image.len() - 1])
+                .unwrap_err()
+                .status(),
+            EFI_BAD_BUFFER_SIZE
+        );
+
+        let orphan = Capsule::new(FW_GUID, CAPSULE_FLAGS_INITIATE_RESET, b"x");
+        assert_eq!(
+            orphan.to_bytes().unwrap_err().status(),
+            EFI_INVALID_PARAMETER
+        );
+        let populate = CAPSULE_FLAGS_PERSIST_ACROSS_RESET | CAPSULE_FLAGS_POPULATE_SYSTEM_TABLE;
+        assert!(Capsule::new(FW_GUID, populate, b"x").to_bytes().is_ok());
+    }
+
+    #[test]
+    fn test_scatter_gather_chain() {
+        bs_mock::reset();
+        let bs = bs_mock::boot_services();
+        let first = Capsule::new(FW_GUID, 0, &[0xA5; 5000]).to_bytes().unwrap();
+        let second = Capsule::new(FW_GUID, 0, b"second").to_bytes().unwrap();
+
+        let list = ScatterGatherList::new(&bs, &[&first, &second]).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.total_size(), (first.len() + second.len()) as u64);
+        assert_eq!(bs_mock::outstanding_pages(), 3);
+
+        let descriptors = list.descriptors();
+        assert_eq!(descriptors.len(), 2);
+        for (desc, image) in descriptors.iter().zip([&first, &second]) {
+            assert_eq!(desc.length as usize, image.len());
+            let copied =
+                unsafe { core::slice::from_raw_parts(desc.address as *const u8, image.len()) };
+            assert_eq!(copied, image.as_slice());
+        }
+
+        drop(list);
+        assert_eq!(bs_mock::outstanding_pages(), 0);
+
+        let mut truncated = second.clone();
+        truncated.push(0);
+        assert_eq!(
+            ScatterGatherList::new(&bs, &[&truncated])
+                .err()
+                .unwrap()
+                .status(),
+            EFI_INVALID_PARAMETER
+        );
+    }
+
+    #[test]
+    fn test_submit_returns_requested_reset() {
+        bs_mock::reset();
+        let bs = bs_mock::boot_services();
+        let image = Capsule::new(FW_GUID, CAPSULE_FLAGS_PERSIST_ACROSS_RESET, &[1; 64])
+            .to_bytes()
+            .unwrap();
+        let list = ScatterGatherList::new(&bs, &[&image]).unwrap();
+
+        let rt = capsule_services(0x1000);
+        let service = CapsuleService::new(&rt);
+        let reset = unsafe { service.submit(&list) }.unwrap();
+        assert_eq!(reset, Some(ResetType::EfiResetWarm));
+        assert_eq!(
+            SUBMITTED.with(|s| s.borrow().clone()),
+            [(1, list.address())]
+        );
+
+        let rt = capsule_services(32);
+        let err = unsafe { CapsuleService::new(&rt).submit(&list) }.unwrap_err();
+        assert_eq!(err.status(), EFI_BAD_BUFFER_SIZE);
+        assert_eq!(err.required_size(), Some(image.len()));
+        assert!(SUBMITTED.with(|s| s.borrow().is_empty()));
+
+        let in_place = Capsule::new(FW_GUID, 0, b"now").to_bytes().unwrap();
+        let list = ScatterGatherList::new(&bs, &[&in_place]).unwrap();
+        let rt = capsule_services(0x1000);
+        assert_eq!(unsafe { CapsuleService::new(&rt).submit(&list) }, Ok(None));
+
+        // A reset type outside EFI_RESET_TYPE is a firmware error
+        REPORTED_RESET.with(|r| r.set(7));
+        assert_eq!(
+            unsafe { CapsuleService::new(&rt).query_capabilities(&list) }.unwrap_err(),
+            Error::new(EFI_DEVICE_ERROR)
+        );
+    }
+
+    #[test]
+    fn test_submit_and_reset() {
+        bs_mock::reset();
+        let bs = bs_mock::boot_services();
+        let rt = capsule_services(0x1000);
+        let in_place = Capsule::new(FW_GUID, 0, b"now").to_bytes().unwrap();
+        let list = ScatterGatherList::new(&bs, &[&in_place]).unwrap();
+        assert_eq!(
+            unsafe { CapsuleService::new(&rt).submit_and_reset(list) },
+            Ok(())
+        );
+        assert_eq!(bs_mock::outstanding_pages(), 0);
+
+        let image = Capsule::new(FW_GUID, CAPSULE_FLAGS_PERSIST_ACROSS_RESET, &[1; 64])
+            .to_bytes()
+            .unwrap();
+        let list = ScatterGatherList::new(&bs, &[&image]).unwrap();
+        let rt = capsule_services(32);
+        let err = unsafe { CapsuleService::new(&rt).submit_and_reset(list) }.unwrap_err();
+        assert_eq!(err.status(), EFI_BAD_BUFFER_SIZE);
+        assert_eq!(bs_mock::outstanding_pages(), 0);
+
+        // The reset never returns, so it runs on a thread left parked there
+        let (sender, resets) = mpsc::channel();
+        std::thread::spawn(move || {
+            bs_mock::reset();
+            let bs = bs_mock::boot_services();
+            let rt = capsule_services(0x1000);
+            RESETS.with(|r| *r.borrow_mut() = Some(sender));
+            let list = ScatterGatherList::new(&bs, &[&image]).unwrap();
+            let _ = unsafe { CapsuleService::new(&rt).submit_and_reset(list) };
+        });
+        let (reset_type, reset_status, pages) = resets.recv().unwrap();
+        assert_eq!(reset_type, ResetType::EfiResetWarm);
+        assert_eq!(reset_status, EFI_SUCCESS);
+        // The capsule pages must survive into the reset
+        assert_eq!(pages, 1);
+    }
+}
@@ -0,0 +1,994 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! SHA-1, SHA-2 and HMAC
+//!
+//! Streaming implementations of SHA-1, SHA-256, SHA-384 and SHA-512
+//! (FIPS 180-4) and HMAC (RFC 2104). [`Hasher`] uses the firmware Hash
+//! Protocol when one is available and falls back to these otherwise.
+
+use crate::boot_services::safe_wrappers::BootServicesWrapper;
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::protocols::security::{
+    HashProtocol, HASH_ALGORITHM_SHA1_GUID, HASH_ALGORITHM_SHA256_GUID, HASH_ALGORITHM_SHA384_GUID,
+    HASH_ALGORITHM_SHA512_GUID, HASH_PROTOCOL_GUID,
+};
+
+/// Largest digest produced by any supported algorithm
+pub const MAX_DIGEST_SIZE: usize = 64;
+
+/// Largest block size of any supported algorithm
+const MAX_BLOCK_SIZE: usize = 128;
+
+/// A streaming hash function
+pub trait Digest: Clone {
+    /// Digest size in bytes
+    const OUTPUT_SIZE: usize;
+    /// Block size in bytes
+    const BLOCK_SIZE: usize;
+    /// Digest type
+    type Output: AsRef<[u8]> + Copy;
+
+    /// Start a new hash
+    fn new() -> Self;
+
+    /// Add data to the hash
+    fn update(&mut self, data: &[u8]);
+
+    /// Finish the hash and return the digest
+    fn finalize(self) -> Self::Output;
+
+    /// Hash `data` in one go
+    fn digest(data: &[u8]) -> Self::Output {
+        let mut hasher = Self::new();
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+/// Partial block and message length shared by the Merkle-Damgard hashes
+#[derive(Clone)]
+struct BlockBuffer<const N: usize> {
+    data: [u8; N],
+    len: usize,
+    total: u64,
+}
+
+impl<const N: usize> BlockBuffer<N> {
+    const fn new() -> Self {
+        BlockBuffer {
+            data: [0; N],
+            len: 0,
+            total: 0,
+        }
+    }
+
+    fn input(&mut self, mut data: &[u8], mut compress: impl FnMut(&[u8; N])) {
+        self.total = self.total.wrapping_add(data.len() as u64);
+
+        if self.len > 0 {
+            let take = core::cmp::min(N - self.len, data.len());
+            self.data[self.len..self.len + take].copy_from_slice(&data[..take]);
+            self.len += take;
+            data = &data[take..];
+            if self.len < N {
+                return;
+            }
+            compress(&self.data);
+            self.len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(N);
+        for block in &mut blocks {
+            compress(block.try_into().unwrap());
+        }
+        let rest = blocks.remainder();
+        self.data[..rest.len()].copy_from_slice(rest);
+        self.len = rest.len();
+    }
+
+    /// Append the padding and big-endian bit length, `length_size` bytes wide
+    fn finish(&mut self, length_size: usize, mut compress: impl FnMut(&[u8; N])) {
+        let bits_low = self.total << 3;
+        let bits_high = self.total >> 61;
+
+        self.data[self.len] = 0x80;
+        self.len += 1;
+        if self.len > N - length_size {
+            self.data[self.len..].fill(0);
+            compress(&self.data);
+            self.len = 0;
+        }
+        self.data[self.len..N - 8].fill(0);
+        self.data[N - 8..].copy_from_slice(&bits_low.to_be_bytes());
+        if length_size == 16 {
+            self.data[N - 16..N - 8].copy_from_slice(&bits_high.to_be_bytes());
+        }
+        compress(&self.data);
+        self.len = 0;
+    }
+}
+
+// ============================================================================
+// SHA-1
+// ============================================================================
+
+const SHA1_IV: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+fn sha1_compress(state: &mut [u32; 5], block: &[u8; 64]) {
+    let mut w = [0u32; 80];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes(word.try_into().unwrap());
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for (i, &wi) in w.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5a827999),
+            20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let t = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(wi);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = t;
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// SHA-1
+///
+/// Only for compatibility with existing signatures and measurements.
+#[derive(Clone)]
+pub struct Sha1 {
+    state: [u32; 5],
+    buffer: BlockBuffer<64>,
+}
+
+impl Digest for Sha1 {
+    const OUTPUT_SIZE: usize = 20;
+    const BLOCK_SIZE: usize = 64;
+    type Output = [u8; 20];
+
+    fn new() -> Self {
+        Sha1 {
+            state: SHA1_IV,
+            buffer: BlockBuffer::new(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.buffer.input(data, |block| sha1_compress(state, block));
+    }
+
+    fn finalize(mut self) -> [u8; 20] {
+        let state = &mut self.state;
+        self.buffer.finish(8, |block| sha1_compress(state, block));
+        let mut out = [0u8; 20];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+}
+
+// ============================================================================
+// SHA-256
+// ============================================================================
+
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+fn sha256_compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes(word.try_into().unwrap());
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (&k, &wi) in SHA256_K.iter().zip(w.iter()) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(k)
+            .wrapping_add(wi);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// SHA-256
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: BlockBuffer<64>,
+}
+
+impl Digest for Sha256 {
+    const OUTPUT_SIZE: usize = 32;
+    const BLOCK_SIZE: usize = 64;
+    type Output = [u8; 32];
+
+    fn new() -> Self {
+        Sha256 {
+            state: SHA256_IV,
+            buffer: BlockBuffer::new(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.buffer
+            .input(data, |block| sha256_compress(state, block));
+    }
+
+    fn finalize(mut self) -> [u8; 32] {
+        let state = &mut self.state;
+        self.buffer.finish(8, |block| sha256_compress(state, block));
+        let mut out = [0u8; 32];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+}
+
+// ============================================================================
+// SHA-384 and SHA-512
+// ============================================================================
+
+const SHA384_IV: [u64; 8] = [
+    0xcbbb9d5dc1059ed8,
+    0x629a292a367cd507,
+    0x9159015a3070dd17,
+    0x152fecd8f70e5939,
+    0x67332667ffc00b31,
+    0x8eb44a8768581511,
+    0xdb0c2e0d64f98fa7,
+    0x47b5481dbefa4fa4,
+];
+
+const SHA512_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+#[rustfmt::skip]
+const SHA512_K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+fn sha512_compress(state: &mut [u64; 8], block: &[u8; 128]) {
+    let mut w = [0u64; 80];
+    for (i, word) in block.chunks_exact(8).enumerate() {
+        w[i] = u64::from_be_bytes(word.try_into().unwrap());
+    }
+    for i in 16..80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (&k, &wi) in SHA512_K.iter().zip(w.iter()) {
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(k)
+            .wrapping_add(wi);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// Shared state of SHA-384 and SHA-512
+#[derive(Clone)]
+struct Sha512Core {
+    state: [u64; 8],
+    buffer: BlockBuffer<128>,
+}
+
+impl Sha512Core {
+    const fn new(iv: [u64; 8]) -> Self {
+        Sha512Core {
+            state: iv,
+            buffer: BlockBuffer::new(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.buffer
+            .input(data, |block| sha512_compress(state, block));
+    }
+
+    fn finish(mut self) -> [u8; 64] {
+        let state = &mut self.state;
+        self.buffer
+            .finish(16, |block| sha512_compress(state, block));
+        let mut out = [0u8; 64];
+        for (chunk, word) in out.chunks_exact_mut(8).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+}
+
+/// SHA-384
+#[derive(Clone)]
+pub struct Sha384(Sha512Core);
+
+impl Digest for Sha384 {
+    const OUTPUT_SIZE: usize = 48;
+    const BLOCK_SIZE: usize = 128;
+    type Output = [u8; 48];
+
+    fn new() -> Self {
+        Sha384(Sha512Core::new(SHA384_IV))
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self) -> [u8; 48] {
+        let mut out = [0u8; 48];
+        out.copy_from_slice(&self.0.finish()[..48]);
+        out
+    }
+}
+
+/// SHA-512
+#[derive(Clone)]
+pub struct Sha512(Sha512Core);
+
+impl Digest for Sha512 {
+    const OUTPUT_SIZE: usize = 64;
+    const BLOCK_SIZE: usize = 128;
+    type Output = [u8; 64];
+
+    fn new() -> Self {
+        Sha512(Sha512Core::new(SHA512_IV))
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self) -> [u8; 64] {
+        self.0.finish()
+    }
+}
+
+// ============================================================================
+// HMAC
+// ============================================================================
+
+/// HMAC over digest `D`
+#[derive(Clone)]
+pub struct Hmac<D: Digest> {
+    inner: D,
+    outer: D,
+}
+
+impl<D: Digest> Hmac<D> {
+    /// Start a MAC with `key`
+    ///
+    /// Keys longer than the block size are hashed first, as RFC 2104
+    /// requires.
+    pub fn new(key: &[u8]) -> Self {
+        let mut block = [0u8; MAX_BLOCK_SIZE];
+        if key.len() > D::BLOCK_SIZE {
+            block[..D::OUTPUT_SIZE].copy_from_slice(D::digest(key).as_ref());
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+        let block = &mut block[..D::BLOCK_SIZE];
+
+        let mut inner = D::new();
+        block.iter_mut().for_each(|b| *b ^= 0x36);
+        inner.update(block);
+
+        let mut outer = D::new();
+        block.iter_mut().for_each(|b| *b ^= 0x36 ^ 0x5c);
+        outer.update(block);
+
+        block.fill(0);
+        Hmac { inner, outer }
+    }
+
+    /// Add data to the MAC
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Finish the MAC and return the tag
+    pub fn finalize(self) -> D::Output {
+        let inner = self.inner.finalize();
+        let mut outer = self.outer;
+        outer.update(inner.as_ref());
+        outer.finalize()
+    }
+
+    /// Finish the MAC and compare it against `tag` in constant time
+    pub fn verify(self, tag: &[u8]) -> bool {
+        let computed = self.finalize();
+        let computed = computed.as_ref();
+        if computed.len() != tag.len() {
+            return false;
+        }
+        computed
+            .iter()
+            .zip(tag)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+
+    /// Compute the MAC of `data` in one go
+    pub fn mac(key: &[u8], data: &[u8]) -> D::Output {
+        let mut hmac = Self::new(key);
+        hmac.update(data);
+        hmac.finalize()
+    }
+}
+
+// ============================================================================
+// Algorithm selection
+// ============================================================================
+
+/// Hash algorithms supported by this module
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Map an `EFI_HASH_ALGORITHM_*_GUID` to an algorithm
+    pub fn from_guid(guid: &Guid) -> Option<Self> {
+        match *guid {
+            HASH_ALGORITHM_SHA1_GUID => Some(HashAlgorithm::Sha1),
+            HASH_ALGORITHM_SHA256_GUID => Some(HashAlgorithm::Sha256),
+            HASH_ALGORITHM_SHA384_GUID => Some(HashAlgorithm::Sha384),
+            HASH_ALGORITHM_SHA512_GUID => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// `EFI_HASH_ALGORITHM_*_GUID` of the algorithm
+    pub fn guid(self) -> Guid {
+        match self {
+            HashAlgorithm::Sha1 => HASH_ALGORITHM_SHA1_GUID,
+            HashAlgorithm::Sha256 => HASH_ALGORITHM_SHA256_GUID,
+            HashAlgorithm::Sha384 => HASH_ALGORITHM_SHA384_GUID,
+            HashAlgorithm::Sha512 => HASH_ALGORITHM_SHA512_GUID,
+        }
+    }
+
+    /// Digest size in bytes
+    pub fn digest_size(self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => Sha1::OUTPUT_SIZE,
+            HashAlgorithm::Sha256 => Sha256::OUTPUT_SIZE,
+            HashAlgorithm::Sha384 => Sha384::OUTPUT_SIZE,
+            HashAlgorithm::Sha512 => Sha512::OUTPUT_SIZE,
+        }
+    }
+
+    /// Hash `data` in software
+    pub fn digest(self, data: &[u8]) -> HashValue {
+        let mut context = HashContext::new(self);
+        context.update(data);
+        context.finalize()
+    }
+
+    /// Compute an HMAC of `data` in software
+    pub fn hmac(self, key: &[u8], data: &[u8]) -> HashValue {
+        match self {
+            HashAlgorithm::Sha1 => HashValue::new(self, &Hmac::<Sha1>::mac(key, data)),
+            HashAlgorithm::Sha256 => HashValue::new(self, &Hmac::<Sha256>::mac(key, data)),
+            HashAlgorithm::Sha384 => HashValue::new(self, &Hmac::<Sha384>::mac(key, data)),
+            HashAlgorithm::Sha512 => HashValue::new(self, &Hmac::<Sha512>::mac(key, data)),
+        }
+    }
+}
+
+/// A digest tagged with its algorithm
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct HashValue {
+    algorithm: HashAlgorithm,
+    bytes: [u8; MAX_DIGEST_SIZE],
+}
+
+impl HashValue {
+    pub(crate) fn new(algorithm: HashAlgorithm, digest: &[u8]) -> Self {
+        let mut bytes = [0u8; MAX_DIGEST_SIZE];
+        bytes[..digest.len()].copy_from_slice(digest);
+        HashValue { algorithm, bytes }
+    }
+
+    /// Algorithm that produced the digest
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Digest bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.algorithm.digest_size()]
+    }
+}
+
+impl AsRef<[u8]> for HashValue {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl core::fmt::Debug for HashValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}(", self.algorithm)?;
+        for b in self.as_bytes() {
+            write!(f, "{:02x}", b)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Streaming software hash with the algorithm picked at runtime
+#[derive(Clone)]
+pub enum HashContext {
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl HashContext {
+    /// Start a new hash
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha1 => HashContext::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => HashContext::Sha256(Sha256::new()),
+            HashAlgorithm::Sha384 => HashContext::Sha384(Sha384::new()),
+            HashAlgorithm::Sha512 => HashContext::Sha512(Sha512::new()),
+        }
+    }
+
+    /// Algorithm of this context
+    pub fn algorithm(&self) -> HashAlgorithm {
+        match self {
+            HashContext::Sha1(_) => HashAlgorithm::Sha1,
+            HashContext::Sha256(_) => HashAlgorithm::Sha256,
+            HashContext::Sha384(_) => HashAlgorithm::Sha384,
+            HashContext::Sha512(_) => HashAlgorithm::Sha512,
+        }
+    }
+
+    /// Add data to the hash
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            HashContext::Sha1(h) => h.update(data),
+            HashContext::Sha256(h) => h.update(data),
+            HashContext::Sha384(h) => h.update(data),
+            HashContext::Sha512(h) => h.update(data),
+        }
+    }
+
+    /// Finish the hash and return the digest
+    pub fn finalize(self) -> HashValue {
+        let algorithm = self.algorithm();
+        match self {
+            HashContext::Sha1(h) => HashValue::new(algorithm, &h.finalize()),
+            HashContext::Sha256(h) => HashValue::new(algorithm, &h.finalize()),
+            HashContext::Sha384(h) => HashValue::new(algorithm, &h.finalize()),
+            HashContext::Sha512(h) => HashValue::new(algorithm, &h.finalize()),
+        }
+    }
+}
+
+// ============================================================================
+// Firmware / software facade
+// ============================================================================
+
+/// Hashing through the firmware Hash Protocol with a software fallback
+///
+/// Algorithms the firmware reports as `EFI_UNSUPPORTED` are computed in
+/// software; other firmware errors are returned.
+pub struct Hasher<'a> {
+    protocol: Option<&'a mut HashProtocol>,
+}
+
+impl<'a> Hasher<'a> {
+    /// Create a hasher using `protocol` when given
+    pub fn new(protocol: Option<&'a mut HashProtocol>) -> Self {
+        Self { protocol }
+    }
+
+    /// Create a hasher that only uses the software implementation
+    pub fn software() -> Self {
+        Self { protocol: None }
+    }
+
+    /// Create a hasher using the first Hash Protocol instance, if any
+    ///
+    /// # Safety
+    /// The located interface must stay valid for `'a`
+    pub unsafe fn locate(bs: &BootServicesWrapper<'_>) -> Self {
+        let protocol = bs
+            .locate_protocol(&HASH_PROTOCOL_GUID)
+            .ok()
+            .and_then(|interface| (interface as *mut HashProtocol).as_mut());
+        Self { protocol }
+    }
+
+    /// Check whether a firmware Hash Protocol is in use
+    pub fn has_firmware_protocol(&self) -> bool {
+        self.protocol.is_some()
+    }
+
+    /// Hash `data` with `algorithm`
+    pub fn hash(&mut self, algorithm: HashAlgorithm, data: &[u8]) -> Result<HashValue> {
+        if let Some(protocol) = self.protocol.as_deref_mut() {
+            let mut digest = [0u8; MAX_DIGEST_SIZE];
+            match unsafe { protocol.hash(&algorithm.guid(), data, false, &mut digest) } {
+                Ok(()) => {
+                    return Ok(HashValue::new(
+                        algorithm,
+                        &digest[..algorithm.digest_size()],
+                    ))
+                }
+                Err(err) if err.status() == EFI_UNSUPPORTED => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(algorithm.digest(data))
+    }
+
+    /// Hash `data` with the algorithm named by an `EFI_HASH_ALGORITHM_*_GUID`
+    pub fn hash_by_guid(&mut self, algorithm: &Guid, data: &[u8]) -> Result<HashValue> {
+        let algorithm = HashAlgorithm::from_guid(algorithm).ok_or(Error::new(EFI_UNSUPPORTED))?;
+        self.hash(algorithm, data)
+    }
+
+    /// Compute SHA-256
+    pub fn sha256(&mut self, data: &[u8]) -> Result<[u8; 32]> {
+        let value = self.hash(HashAlgorithm::Sha256, data)?;
+        Ok(value.as_bytes().try_into().unwrap())
+    }
+
+    /// Compute SHA-384
+    pub fn sha384(&mut self, data: &[u8]) -> Result<[u8; 48]> {
+        let value = self.hash(HashAlgorithm::Sha384, data)?;
+        Ok(value.as_bytes().try_into().unwrap())
+    }
+
+    /// Compute SHA-512
+    pub fn sha512(&mut self, data: &[u8]) -> Result<[u8; 64]> {
+        let value = self.hash(HashAlgorithm::Sha512, data)?;
+        Ok(value.as_bytes().try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::security::HashOutput;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    const MSG_448: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    const MSG_896: &[u8] = b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+
+    fn check(algorithm: HashAlgorithm, vectors: &[(&[u8], &str)]) {
+        for (msg, expected) in vectors {
+            assert_eq!(
+                algorithm.digest(msg).as_bytes(),
+                hex(expected),
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn test_sha1_nist_vectors() {
+        check(
+            HashAlgorithm::Sha1,
+            &[
+                (b"", "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+                (b"abc", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+                (MSG_448, "84983e441c3bd26ebaae4aa1f95129e5e54670f1"),
+                (MSG_896, "a49b2446a02c645bf419f995b67091253a04a259"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_sha256_nist_vectors() {
+        check(
+            HashAlgorithm::Sha256,
+            &[
+                (
+                    b"",
+                    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                ),
+                (
+                    b"abc",
+                    "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+                ),
+                (
+                    MSG_448,
+                    "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+                ),
+                (
+                    MSG_896,
+                    "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_sha384_nist_vectors() {
+        check(
+            HashAlgorithm::Sha384,
+            &[
+                (b"", "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b"),
+                (b"abc", "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"),
+                (MSG_448, "3391fdddfc8dc7393707a65b1b4709397cf8b1d162af05abfe8f450de5f36bc6b0455a8520bc4e6f5fe95b1fe3c8452b"),
+                (MSG_896, "09330c33f71147e83d192fc782cd1b4753111b173b3b05d22fa08086e3b0f712fcc7c71a557e2db966c3e9fa91746039"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_sha512_nist_vectors() {
+        check(
+            HashAlgorithm::Sha512,
+            &[
+                (b"", "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"),
+                (b"abc", "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"),
+                (MSG_448, "204a8fc6dda82f0a0ced7beb8e08a41657c16ef468b228a8279be331a703c33596fd15c13b1b07f9aa1d3bea57789ca031ad85c7a71dd70354ec631238ca3445"),
+                (MSG_896, "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_streaming_million_a() {
+        // One million 'a' fed in uneven chunks to exercise the block buffer
+        let chunk = [b'a'; 997];
+        let expected = [
+            (HashAlgorithm::Sha1, "34aa973cd4c4daa4f61eeb2bdbad27316534016f"),
+            (HashAlgorithm::Sha256, "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"),
+            (HashAlgorithm::Sha384, "9d0e1809716474cb086e834e310a4a1ced149e9c00f248527972cec5704c2a5b07b8b3dc38ecc4ebae97ddd87f3d8985"),
+            (HashAlgorithm::Sha512, "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973ebde0ff244877ea60a4cb0432ce577c31beb009c5c2c49aa2e4eadb217ad8cc09b"),
+        ];
+        for (algorithm, digest) in expected {
+            let mut context = HashContext::new(algorithm);
+            let mut remaining = 1_000_000;
+            while remaining > 0 {
+                let n = core::cmp::min(remaining, chunk.len());
+                context.update(&chunk[..n]);
+                remaining -= n;
+            }
+            assert_eq!(
+                context.finalize().as_bytes(),
+                hex(digest),
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn test_hmac_rfc_vectors() {
+        // RFC 2202 / RFC 4231 test cases 1, 2 and the oversized key case
+        let cases: [(&[u8], &[u8]); 3] = [
+            (&[0x0b; 20], b"Hi There"),
+            (b"Jefe", b"what do ya want for nothing?"),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+            ),
+        ];
+        let expected = [
+            (HashAlgorithm::Sha1, [
+                "b617318655057264e28bc0b6fb378c8ef146be00",
+                "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79",
+                "90d0dace1c1bdc957339307803160335bde6df2b",
+            ]),
+            (HashAlgorithm::Sha256, [
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ]),
+            (HashAlgorithm::Sha384, [
+                "afd03944d84895626b0825f4ab46907f15f9dadbe4101ec682aa034c7cebc59cfaea9ea9076ede7f4af152e8b2fa9cb6",
+                "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47e42ec3736322445e8e2240ca5e69e2c78b3239ecfab21649",
+                "4ece084485813e9088d2c63a041bc5b44f9ef1012a2b588f3cd11f05033ac4c60c2ef6ab4030fe8296248df163f44952",
+            ]),
+            (HashAlgorithm::Sha512, [
+                "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+                "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+            ]),
+        ];
+        for (algorithm, tags) in expected {
+            for ((key, data), tag) in cases.iter().zip(tags) {
+                assert_eq!(
+                    algorithm.hmac(key, data).as_bytes(),
+                    hex(tag),
+                    "{:?}",
+                    algorithm
+                );
+            }
+        }
+
+        let mut hmac = Hmac::<Sha256>::new(b"Jefe");
+        hmac.update(b"what do ya want ");
+        hmac.update(b"for nothing?");
+        assert!(hmac.clone().verify(&hex(expected[1].1[1])));
+        assert!(!hmac.verify(&[0u8; 32]));
+    }
+
+    unsafe extern "efiapi" fn fw_get_hash_size(
+        _this: *mut HashProtocol,
+        _algorithm: *const Guid,
+        _size: *mut Uintn,
+    ) -> Status {
+        EFI_UNSUPPORTED
+    }
+
+    // Supports SHA-256 only and returns a marker digest so the path taken is visible
+    unsafe extern "efiapi" fn fw_hash(
+        _this: *mut HashProtocol,
+        algorithm: *const Guid,
+        _extend: Boolean,
+        _message: *const Uint8,
+        _message_size: Uint64,
+        hash: *mut HashOutput,
+    ) -> Status {
+        if *algorithm == HASH_ALGORITHM_SHA256_GUID {
+            *(*hash).sha256_hash = [0x5a; 32];
+            EFI_SUCCESS
+        } else if *algorithm == HASH_ALGORITHM_SHA1_GUID {
+            EFI_DEVICE_ERROR
+        } else {
+            EFI_UNSUPPORTED
+        }
+    }
+
+    #[test]
+    fn test_hasher_fallback() {
+        let mut protocol = HashProtocol {
+            get_hash_size: fw_get_hash_size,
+            hash: fw_hash,
+        };
+        let mut hasher = Hasher::new(Some(&mut protocol));
+        assert!(hasher.has_firmware_protocol());
+        assert_eq!(hasher.sha256(b"abc").unwrap(), [0x5a; 32]);
+        assert_eq!(
+            hasher.sha512(b"abc").unwrap(),
+            Sha512::digest(b"abc"),
+            "unsupported algorithms fall back to software"
+        );
+        assert_eq!(
+            hasher
+                .hash(HashAlgorithm::Sha1, b"abc")
+                .unwrap_err()
+                .status(),
+            EFI_DEVICE_ERROR
+        );
+
+        let mut software = Hasher::software();
+        assert_eq!(software.sha256(b"abc").unwrap(), Sha256::digest(b"abc"));
+        assert_eq!(
+            software
+                .hash_by_guid(&HASH_ALGORITHM_SHA384_GUID, b"abc")
+                .unwrap()
+                .algorithm(),
+            HashAlgorithm::Sha384
+        );
+        assert!(software.hash_by_guid(&Guid::null(), b"").is_err());
+    }
+}
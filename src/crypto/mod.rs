@@ -0,0 +1,7 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Software Cryptography
+//!
+//! no_std implementations for firmware that lacks the corresponding
+//! protocols.
+
+pub mod hash;
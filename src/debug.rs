@@ -0,0 +1,355 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Low-level debugging helpers for QEMU/GDB sessions
+//!
+//! Output goes straight to I/O ports, so it works before the logger is set
+//! up and after `ExitBootServices`. Port output is only available on x86;
+//! on other architectures the writers discard their input.
+
+use crate::boot_services::{BootServicesWrapper, OpenProtocolAttributes};
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::protocols::LoadedImageProtocol;
+use core::fmt::{self, Write};
+
+/// QEMU debug console port (`-debugcon file:debug.log -global isa-debugcon.iobase=0x402`)
+pub const QEMU_DEBUGCON_PORT: u16 = 0x402;
+
+/// Legacy 16550 UART base ports
+pub const COM1_PORT: u16 = 0x3F8;
+pub const COM2_PORT: u16 = 0x2F8;
+pub const COM3_PORT: u16 = 0x3E8;
+pub const COM4_PORT: u16 = 0x2E8;
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+unsafe fn outb(port: u16, value: u8) {
+    core::arch::asm!(
+        "out dx, al",
+        in("dx") port,
+        in("al") value,
+        options(nomem, nostack, preserves_flags)
+    );
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    core::arch::asm!(
+        "in al, dx",
+        out("al") value,
+        in("dx") port,
+        options(nomem, nostack, preserves_flags)
+    );
+    value
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+unsafe fn outb(_port: u16, _value: u8) {}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+unsafe fn inb(_port: u16) -> u8 {
+    0
+}
+
+/// Writer for the QEMU debugcon device
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DebugCon;
+
+impl DebugCon {
+    /// Write raw bytes to the debug console
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            unsafe { outb(QEMU_DEBUGCON_PORT, byte) };
+        }
+    }
+}
+
+impl Write for DebugCon {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+// 16550 register offsets
+const UART_DATA: u16 = 0;
+const UART_IER: u16 = 1;
+const UART_FCR: u16 = 2;
+const UART_LCR: u16 = 3;
+const UART_MCR: u16 = 4;
+const UART_LSR: u16 = 5;
+
+/// Baud rate with a divisor of 1 (1.8432 MHz UART clock / 16)
+const UART_MAX_BAUD: u32 = 115_200;
+
+const UART_LCR_DLAB: u8 = 0x80;
+const UART_LCR_8N1: u8 = 0x03;
+const UART_LSR_THRE: u8 = 0x20;
+
+/// Polled 16550 UART writer
+///
+/// Newlines are expanded to `\r\n` for terminal emulators.
+#[derive(Debug, Copy, Clone)]
+pub struct SerialPort {
+    base: u16,
+}
+
+impl SerialPort {
+    /// Create a writer for the UART at `base` without reprogramming it
+    pub const fn new(base: u16) -> Self {
+        SerialPort { base }
+    }
+
+    /// Writer for COM1, the port QEMU connects to `-serial stdio`
+    pub const fn com1() -> Self {
+        Self::new(COM1_PORT)
+    }
+
+    /// Get the base I/O port
+    pub fn base(&self) -> u16 {
+        self.base
+    }
+
+    /// Program the UART for `baud` 8N1 with FIFOs enabled and interrupts off
+    ///
+    /// Firmware usually configures COM1 already, so this is only needed for
+    /// other ports or a different baud rate. Fails with
+    /// `EFI_INVALID_PARAMETER` if `baud` has no 16-bit divisor.
+    pub fn init(&mut self, baud: u32) -> Result<()> {
+        let divisor = baud_divisor(baud)?;
+        unsafe {
+            outb(self.base + UART_IER, 0x00);
+            outb(self.base + UART_LCR, UART_LCR_DLAB);
+            outb(self.base + UART_DATA, divisor as u8);
+            outb(self.base + UART_IER, (divisor >> 8) as u8);
+            outb(self.base + UART_LCR, UART_LCR_8N1);
+            outb(self.base + UART_FCR, 0xC7);
+            outb(self.base + UART_MCR, 0x03);
+        }
+        Ok(())
+    }
+
+    /// Write one byte, waiting for the transmit holding register to empty
+    pub fn write_byte(&mut self, byte: u8) {
+        unsafe {
+            // Bounded so a missing UART cannot hang the caller
+            for _ in 0..100_000 {
+                if inb(self.base + UART_LSR) & UART_LSR_THRE != 0 {
+                    break;
+                }
+                core::hint::spin_loop();
+            }
+            outb(self.base + UART_DATA, byte);
+        }
+    }
+
+    /// Write raw bytes, expanding `\n` to `\r\n`
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+    }
+}
+
+/// Divisor latch value for `baud`, which must be between 2 and 115200
+fn baud_divisor(baud: u32) -> Result<u16> {
+    if baud == 0 || baud > UART_MAX_BAUD {
+        return Err(Error::new(EFI_INVALID_PARAMETER));
+    }
+    u16::try_from(UART_MAX_BAUD / baud).map_err(|_| Error::new(EFI_INVALID_PARAMETER))
+}
+
+impl Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Print to the QEMU debug console
+#[macro_export]
+macro_rules! debug_print {
+    ($($arg:tt)*) => {{
+        use core::fmt::Write as _;
+        let _ = write!($crate::debug::DebugCon, $($arg)*);
+    }};
+}
+
+/// Print a line to the QEMU debug console
+#[macro_export]
+macro_rules! debug_println {
+    () => {
+        $crate::debug_print!("\n")
+    };
+    ($($arg:tt)*) => {{
+        $crate::debug_print!($($arg)*);
+        $crate::debug_print!("\n");
+    }};
+}
+
+/// `hexdump -C` style formatter for a byte slice
+///
+/// ```ignore
+/// debug_print!("{}", hexdump(&buffer).with_base(buffer.as_ptr() as usize));
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct HexDump<'a> {
+    data: &'a [u8],
+    base: usize,
+}
+
+/// Format `data` as offset, hex bytes and an ASCII column, 16 bytes per line
+pub fn hexdump(data: &[u8]) -> HexDump<'_> {
+    HexDump { data, base: 0 }
+}
+
+impl HexDump<'_> {
+    /// Start the offset column at `base` instead of zero
+    pub fn with_base(self, base: usize) -> Self {
+        HexDump { base, ..self }
+    }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.data.chunks(16).enumerate() {
+            write!(f, "{:08x} ", self.base + i * 16)?;
+            for col in 0..16 {
+                if col == 8 {
+                    f.write_char(' ')?;
+                }
+                match line.get(col) {
+                    Some(byte) => write!(f, " {:02x}", byte)?,
+                    None => f.write_str("   ")?,
+                }
+            }
+            f.write_str("  |")?;
+            for &byte in line {
+                let c = if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                };
+                f.write_char(c)?;
+            }
+            f.write_str("|\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Cleared by the debugger to release [`wait_for_debugger`]
+#[no_mangle]
+#[used]
+pub static mut UEFI_DEBUGGER_WAIT: u8 = 1;
+
+/// Spin until a debugger clears [`UEFI_DEBUGGER_WAIT`]
+///
+/// After attaching, release the loop from GDB with
+/// `set var UEFI_DEBUGGER_WAIT = 0` and `continue`.
+pub fn wait_for_debugger() {
+    debug_println!("Waiting for debugger: set var UEFI_DEBUGGER_WAIT = 0");
+    unsafe {
+        core::ptr::write_volatile(&raw mut UEFI_DEBUGGER_WAIT, 1);
+        while core::ptr::read_volatile(&raw const UEFI_DEBUGGER_WAIT) != 0 {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Load address and size of a loaded image
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub base: u64,
+    pub size: u64,
+}
+
+/// Get the load address and size of `image` from its `LoadedImageProtocol`
+///
+/// # Safety
+/// `image` must be a valid image handle
+pub unsafe fn image_info(bs: &BootServicesWrapper, image: *mut Handle) -> Result<ImageInfo> {
+    let loaded_image = bs.open_protocol::<LoadedImageProtocol>(
+        image,
+        image,
+        core::ptr::null_mut(),
+        OpenProtocolAttributes::GetProtocol,
+    )?;
+    Ok(ImageInfo {
+        base: loaded_image.image_base as u64,
+        size: loaded_image.image_size,
+    })
+}
+
+/// Print the load address of `image` to the debug console and the log
+///
+/// The base is what GDB needs to relocate symbols, e.g.
+/// `add-symbol-file app.debug -o <base>`.
+///
+/// # Safety
+/// `image` must be a valid image handle
+pub unsafe fn print_image_base(bs: &BootServicesWrapper, image: *mut Handle) -> Result<ImageInfo> {
+    let info = image_info(bs, image)?;
+    debug_println!("Image base: {:#x} size: {:#x}", info.base, info.size);
+    crate::log_info!("Image base: {:#x} size: {:#x}", info.base, info.size);
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, string::String, vec::Vec};
+
+    #[test]
+    fn test_hexdump_full_line() {
+        let data: [u8; 16] = *b"0123456789ABCDEF";
+        let out = format!("{}", hexdump(&data));
+        assert_eq!(
+            out,
+            "00000000  30 31 32 33 34 35 36 37  38 39 41 42 43 44 45 46  |0123456789ABCDEF|\n"
+        );
+    }
+
+    #[test]
+    fn test_hexdump_partial_line_and_base() {
+        let data = [0x00, 0x41, 0x7f, 0x20];
+        let out = format!("{}", hexdump(&data).with_base(0x1000));
+        assert_eq!(
+            out,
+            "00001000  00 41 7f 20                                       |.A. |\n"
+        );
+    }
+
+    #[test]
+    fn test_hexdump_multiple_lines() {
+        let data = [0xffu8; 20];
+        let out: String = format!("{}", hexdump(&data));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("00000010  ff ff ff ff "));
+        assert!(lines[1].ends_with("|....|"));
+        assert_eq!(lines[0].len(), lines[1].len() + 12);
+    }
+
+    #[test]
+    fn test_hexdump_empty() {
+        assert_eq!(format!("{}", hexdump(&[])), "");
+    }
+
+    #[test]
+    fn test_baud_divisor() {
+        assert_eq!(baud_divisor(115_200), Ok(1));
+        assert_eq!(baud_divisor(9600), Ok(12));
+        assert_eq!(baud_divisor(2), Ok(57_600));
+        for baud in [0, 1, 230_400] {
+            assert_eq!(
+                baud_divisor(baud).unwrap_err().status(),
+                EFI_INVALID_PARAMETER
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Application entry point support
+//!
+//! [`efi_entry!`](crate::efi_entry) generates the `efi_main` symbol and runs
+//! the common bootstrap before handing control to a typed `main`.
+
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::logger::{LogLevel, Logger};
+use crate::protocols::SimpleTextOutputProtocol;
+use crate::system_table::{Boot, RawSystemTable, SystemTable, EFI_SYSTEM_TABLE_SIGNATURE};
+
+/// Validate the system table and initialize the crate's global services
+///
+/// Checks the table header signature and CRC32, then sets up the global
+/// allocator, the console logger and the panic handler.
+///
+/// # Safety
+/// `system_table` must be the pointer passed to the image entry point, and
+/// this must only be called once.
+pub unsafe fn bootstrap(system_table: *mut RawSystemTable) -> Result<SystemTable<Boot>> {
+    let st =
+        SystemTable::<Boot>::from_ptr(system_table).ok_or(Error::new(EFI_INVALID_PARAMETER))?;
+
+    let hdr = st.header();
+    if !hdr.verify_signature(EFI_SYSTEM_TABLE_SIGNATURE) {
+        return Err(Error::new(EFI_INCOMPATIBLE_VERSION));
+    }
+    if !hdr.verify_crc32() {
+        return Err(Error::new(EFI_CRC_ERROR));
+    }
+
+    let raw = &*system_table;
+    crate::allocator::init_allocator(&*raw.boot_services);
+
+    if !raw.con_out.is_null() {
+        let console = raw.con_out as *mut SimpleTextOutputProtocol;
+        Logger::init(console, LogLevel::Info);
+        crate::panic_handler::init_panic_handler(console);
+    }
+
+    Ok(st)
+}
+
+/// Convert the result of an entry point into the status returned to firmware
+pub fn exit_status(result: Result) -> Status {
+    match result {
+        Ok(()) => EFI_SUCCESS,
+        Err(err) => err.status(),
+    }
+}
+
+/// Generate the UEFI `efi_main` entry point
+///
+/// The named function must have the signature
+/// `fn(image: *mut Handle, st: SystemTable<Boot>) -> Result`. Before it runs,
+/// the system table header is validated and the allocator, logger and panic
+/// handler are initialized; its `Result` is mapped back to a `Status`.
+///
+/// # Example
+/// ```ignore
+/// use uefi_rust_intergration::*;
+///
+/// efi_entry!(main);
+///
+/// fn main(_image: *mut Handle, st: SystemTable<Boot>) -> Result {
+///     log_info!("firmware revision {:#x}", st.firmware_revision());
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! efi_entry {
+    ($main:path) => {
+        // The firmware calls this with raw pointers; it cannot be `unsafe`
+        #[allow(clippy::not_unsafe_ptr_arg_deref)]
+        #[no_mangle]
+        pub extern "efiapi" fn efi_main(
+            image: *mut $crate::ffi::Handle,
+            system_table: *mut $crate::system_table::RawSystemTable,
+        ) -> $crate::ffi::Status {
+            let st = match unsafe { $crate::entry::bootstrap(system_table) } {
+                Ok(st) => st,
+                Err(err) => return err.status(),
+            };
+            $crate::entry::exit_status($main(image, st))
+        }
+    };
+}
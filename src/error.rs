@@ -0,0 +1,554 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Typed UEFI Error Handling
+//!
+//! Wraps raw `EFI_STATUS` values in an [`Error`] that knows the spec name of
+//! the status, whether it is a warning or an error, and optionally carries
+//! extra data reported by the firmware (such as the buffer size required
+//! after `EFI_BUFFER_TOO_SMALL`).
+
+use crate::ffi::*;
+use core::fmt;
+
+/// Result type returned by the safe wrappers
+pub type Result<T = (), E = Error> = core::result::Result<T, E>;
+
+/// Every non-success EFI_STATUS defined by the specification
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    // Warnings
+    WarnUnknownGlyph,
+    WarnDeleteFailure,
+    WarnWriteFailure,
+    WarnBufferTooSmall,
+    WarnStaleData,
+    WarnFileSystem,
+    WarnResetRequired,
+
+    // Errors
+    LoadError,
+    InvalidParameter,
+    Unsupported,
+    BadBufferSize,
+    BufferTooSmall,
+    NotReady,
+    DeviceError,
+    WriteProtected,
+    OutOfResources,
+    VolumeCorrupted,
+    VolumeFull,
+    NoMedia,
+    MediaChanged,
+    NotFound,
+    AccessDenied,
+    NoResponse,
+    NoMapping,
+    Timeout,
+    NotStarted,
+    AlreadyStarted,
+    Aborted,
+    IcmpError,
+    TftpError,
+    ProtocolError,
+    IncompatibleVersion,
+    SecurityViolation,
+    CrcError,
+    EndOfMedia,
+    EndOfFile,
+    InvalidLanguage,
+    CompromisedData,
+    IpAddressConflict,
+    HttpError,
+    NetworkUnreachable,
+    HostUnreachable,
+    ProtocolUnreachable,
+    PortUnreachable,
+    ConnectionFin,
+    ConnectionReset,
+    ConnectionRefused,
+
+    /// A status value the specification does not define
+    Unknown(Status),
+}
+
+/// Table of (status, kind, spec name) used for both directions of the mapping
+const STATUS_TABLE: &[(Status, ErrorKind, &str)] = &[
+    (
+        EFI_WARN_UNKNOWN_GLYPH,
+        ErrorKind::WarnUnknownGlyph,
+        "EFI_WARN_UNKNOWN_GLYPH",
+    ),
+    (
+        EFI_WARN_DELETE_FAILURE,
+        ErrorKind::WarnDeleteFailure,
+        "EFI_WARN_DELETE_FAILURE",
+    ),
+    (
+        EFI_WARN_WRITE_FAILURE,
+        ErrorKind::WarnWriteFailure,
+        "EFI_WARN_WRITE_FAILURE",
+    ),
+    (
+        EFI_WARN_BUFFER_TOO_SMALL,
+        ErrorKind::WarnBufferTooSmall,
+        "EFI_WARN_BUFFER_TOO_SMALL",
+    ),
+    (
+        EFI_WARN_STALE_DATA,
+        ErrorKind::WarnStaleData,
+        "EFI_WARN_STALE_DATA",
+    ),
+    (
+        EFI_WARN_FILE_SYSTEM,
+        ErrorKind::WarnFileSystem,
+        "EFI_WARN_FILE_SYSTEM",
+    ),
+    (
+        EFI_WARN_RESET_REQUIRED,
+        ErrorKind::WarnResetRequired,
+        "EFI_WARN_RESET_REQUIRED",
+    ),
+    (EFI_LOAD_ERROR, ErrorKind::LoadError, "EFI_LOAD_ERROR"),
+    (
+        EFI_INVALID_PARAMETER,
+        ErrorKind::InvalidParameter,
+        "EFI_INVALID_PARAMETER",
+    ),
+    (EFI_UNSUPPORTED, ErrorKind::Unsupported, "EFI_UNSUPPORTED"),
+    (
+        EFI_BAD_BUFFER_SIZE,
+        ErrorKind::BadBufferSize,
+        "EFI_BAD_BUFFER_SIZE",
+    ),
+    (
+        EFI_BUFFER_TOO_SMALL,
+        ErrorKind::BufferTooSmall,
+        "EFI_BUFFER_TOO_SMALL",
+    ),
+    (EFI_NOT_READY, ErrorKind::NotReady, "EFI_NOT_READY"),
+    (EFI_DEVICE_ERROR, ErrorKind::DeviceError, "EFI_DEVICE_ERROR"),
+    (
+        EFI_WRITE_PROTECTED,
+        ErrorKind::WriteProtected,
+        "EFI_WRITE_PROTECTED",
+    ),
+    (
+        EFI_OUT_OF_RESOURCES,
+        ErrorKind::OutOfResources,
+        "EFI_OUT_OF_RESOURCES",
+    ),
+    (
+        EFI_VOLUME_CORRUPTED,
+        ErrorKind::VolumeCorrupted,
+        "EFI_VOLUME_CORRUPTED",
+    ),
+    (EFI_VOLUME_FULL, ErrorKind::VolumeFull, "EFI_VOLUME_FULL"),
+    (EFI_NO_MEDIA, ErrorKind::NoMedia, "EFI_NO_MEDIA"),
+    (
+        EFI_MEDIA_CHANGED,
+        ErrorKind::MediaChanged,
+        "EFI_MEDIA_CHANGED",
+    ),
+    (EFI_NOT_FOUND, ErrorKind::NotFound, "EFI_NOT_FOUND"),
+    (
+        EFI_ACCESS_DENIED,
+        ErrorKind::AccessDenied,
+        "EFI_ACCESS_DENIED",
+    ),
+    (EFI_NO_RESPONSE, ErrorKind::NoResponse, "EFI_NO_RESPONSE"),
+    (EFI_NO_MAPPING, ErrorKind::NoMapping, "EFI_NO_MAPPING"),
+    (EFI_TIMEOUT, ErrorKind::Timeout, "EFI_TIMEOUT"),
+    (EFI_NOT_STARTED, ErrorKind::NotStarted, "EFI_NOT_STARTED"),
+    (
+        EFI_ALREADY_STARTED,
+        ErrorKind::AlreadyStarted,
+        "EFI_ALREADY_STARTED",
+    ),
+    (EFI_ABORTED, ErrorKind::Aborted, "EFI_ABORTED"),
+    (EFI_ICMP_ERROR, ErrorKind::IcmpError, "EFI_ICMP_ERROR"),
+    (EFI_TFTP_ERROR, ErrorKind::TftpError, "EFI_TFTP_ERROR"),
+    (
+        EFI_PROTOCOL_ERROR,
+        ErrorKind::ProtocolError,
+        "EFI_PROTOCOL_ERROR",
+    ),
+    (
+        EFI_INCOMPATIBLE_VERSION,
+        ErrorKind::IncompatibleVersion,
+        "EFI_INCOMPATIBLE_VERSION",
+    ),
+    (
+        EFI_SECURITY_VIOLATION,
+        ErrorKind::SecurityViolation,
+        "EFI_SECURITY_VIOLATION",
+    ),
+    (EFI_CRC_ERROR, ErrorKind::CrcError, "EFI_CRC_ERROR"),
+    (EFI_END_OF_MEDIA, ErrorKind::EndOfMedia, "EFI_END_OF_MEDIA"),
+    (EFI_END_OF_FILE, ErrorKind::EndOfFile, "EFI_END_OF_FILE"),
+    (
+        EFI_INVALID_LANGUAGE,
+        ErrorKind::InvalidLanguage,
+        "EFI_INVALID_LANGUAGE",
+    ),
+    (
+        EFI_COMPROMISED_DATA,
+        ErrorKind::CompromisedData,
+        "EFI_COMPROMISED_DATA",
+    ),
+    (
+        EFI_IP_ADDRESS_CONFLICT,
+        ErrorKind::IpAddressConflict,
+        "EFI_IP_ADDRESS_CONFLICT",
+    ),
+    (EFI_HTTP_ERROR, ErrorKind::HttpError, "EFI_HTTP_ERROR"),
+    (
+        EFI_NETWORK_UNREACHABLE,
+        ErrorKind::NetworkUnreachable,
+        "EFI_NETWORK_UNREACHABLE",
+    ),
+    (
+        EFI_HOST_UNREACHABLE,
+        ErrorKind::HostUnreachable,
+        "EFI_HOST_UNREACHABLE",
+    ),
+    (
+        EFI_PROTOCOL_UNREACHABLE,
+        ErrorKind::ProtocolUnreachable,
+        "EFI_PROTOCOL_UNREACHABLE",
+    ),
+    (
+        EFI_PORT_UNREACHABLE,
+        ErrorKind::PortUnreachable,
+        "EFI_PORT_UNREACHABLE",
+    ),
+    (
+        EFI_CONNECTION_FIN,
+        ErrorKind::ConnectionFin,
+        "EFI_CONNECTION_FIN",
+    ),
+    (
+        EFI_CONNECTION_RESET,
+        ErrorKind::ConnectionReset,
+        "EFI_CONNECTION_RESET",
+    ),
+    (
+        EFI_CONNECTION_REFUSED,
+        ErrorKind::ConnectionRefused,
+        "EFI_CONNECTION_REFUSED",
+    ),
+];
+
+impl ErrorKind {
+    /// Map a raw status to its named kind
+    pub fn from_status(status: Status) -> Self {
+        STATUS_TABLE
+            .iter()
+            .find(|(code, _, _)| *code == status)
+            .map_or(ErrorKind::Unknown(status), |(_, kind, _)| *kind)
+    }
+
+    /// Get the raw status value for this kind
+    pub fn status(self) -> Status {
+        if let ErrorKind::Unknown(status) = self {
+            return status;
+        }
+        STATUS_TABLE
+            .iter()
+            .find(|(_, kind, _)| *kind == self)
+            .map(|(code, _, _)| *code)
+            .expect("every named ErrorKind is in STATUS_TABLE")
+    }
+
+    /// Get the spec name (e.g. `EFI_NOT_FOUND`), if the status is defined
+    pub fn name(self) -> Option<&'static str> {
+        STATUS_TABLE
+            .iter()
+            .find(|(_, kind, _)| *kind == self)
+            .map(|(_, _, name)| *name)
+    }
+
+    /// Check if this kind is a warning rather than an error
+    pub fn is_warning(self) -> bool {
+        is_warning(self.status())
+    }
+}
+
+/// Additional data reported alongside a failing status
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorData {
+    /// Buffer size (in bytes) the firmware needs to complete the call
+    RequiredSize(usize),
+    /// TPM_RC returned by a TPM 2.0 command
+    TpmResponseCode(u32),
+}
+
+/// A non-success EFI_STATUS with optional payload
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Error {
+    status: Status,
+    data: Option<ErrorData>,
+}
+
+impl Error {
+    /// Create an error from a raw status
+    pub const fn new(status: Status) -> Self {
+        Error { status, data: None }
+    }
+
+    /// Create an error carrying additional data
+    pub const fn with_data(status: Status, data: ErrorData) -> Self {
+        Error {
+            status,
+            data: Some(data),
+        }
+    }
+
+    /// Create an `EFI_BUFFER_TOO_SMALL` error recording the required size
+    pub const fn buffer_too_small(required_size: usize) -> Self {
+        Self::with_data(EFI_BUFFER_TOO_SMALL, ErrorData::RequiredSize(required_size))
+    }
+
+    /// Get the raw status value
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Get the named kind of this error
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::from_status(self.status)
+    }
+
+    /// Get the additional data, if any
+    pub fn data(&self) -> Option<ErrorData> {
+        self.data
+    }
+
+    /// Get the buffer size the firmware asked for, if reported
+    pub fn required_size(&self) -> Option<usize> {
+        match self.data {
+            Some(ErrorData::RequiredSize(size)) => Some(size),
+            _ => None,
+        }
+    }
+
+    /// Get the TPM_RC behind this error, if it came from a TPM command
+    pub fn tpm_response_code(&self) -> Option<u32> {
+        match self.data {
+            Some(ErrorData::TpmResponseCode(code)) => Some(code),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a warning status
+    pub fn is_warning(&self) -> bool {
+        is_warning(self.status)
+    }
+
+    /// Check if this is an error status
+    pub fn is_error(&self) -> bool {
+        is_error(self.status)
+    }
+}
+
+impl From<Error> for Status {
+    fn from(error: Error) -> Status {
+        error.status
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error::new(kind.status())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind().name() {
+            Some(name) => f.write_str(name)?,
+            None => write!(f, "EFI_STATUS({:#x})", self.status)?,
+        }
+        match self.data {
+            Some(ErrorData::RequiredSize(size)) => write!(f, " (required size: {} bytes)", size),
+            Some(ErrorData::TpmResponseCode(code)) => write!(f, " (TPM_RC {:#x})", code),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error({})", self)
+    }
+}
+
+/// Conversion of raw EFI_STATUS values into [`Result`]s
+///
+/// Any status other than `EFI_SUCCESS` becomes an `Err`; use
+/// [`Error::is_warning`] to tell warnings apart from hard failures.
+pub trait StatusExt {
+    /// Convert to `Ok(())` on success
+    fn to_result(self) -> Result<()>;
+
+    /// Convert to `Ok(f())` on success, evaluating `f` only on success
+    fn to_result_with_val<T>(self, f: impl FnOnce() -> T) -> Result<T>;
+
+    /// Convert to a result, recording `required_size` on `EFI_BUFFER_TOO_SMALL`
+    fn to_result_with_size(self, required_size: usize) -> Result<()>;
+}
+
+impl StatusExt for Status {
+    fn to_result(self) -> Result<()> {
+        if self == EFI_SUCCESS {
+            Ok(())
+        } else {
+            Err(Error::new(self))
+        }
+    }
+
+    fn to_result_with_val<T>(self, f: impl FnOnce() -> T) -> Result<T> {
+        self.to_result().map(|()| f())
+    }
+
+    fn to_result_with_size(self, required_size: usize) -> Result<()> {
+        if self == EFI_BUFFER_TOO_SMALL {
+            Err(Error::buffer_too_small(required_size))
+        } else {
+            self.to_result()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_round_trip() {
+        for (status, kind, _) in STATUS_TABLE {
+            assert_eq!(ErrorKind::from_status(*status), *kind);
+            assert_eq!(kind.status(), *status);
+        }
+    }
+
+    #[test]
+    fn test_every_kind_round_trips() {
+        // The exhaustive match fails to compile when a variant is missing
+        macro_rules! named_kinds {
+            ($($kind:ident),* $(,)?) => {{
+                fn covered(kind: ErrorKind) {
+                    match kind {
+                        $(ErrorKind::$kind)|* | ErrorKind::Unknown(_) => {}
+                    }
+                }
+                covered(ErrorKind::Unknown(0));
+                [$(ErrorKind::$kind),*]
+            }};
+        }
+        let kinds = named_kinds!(
+            WarnUnknownGlyph,
+            WarnDeleteFailure,
+            WarnWriteFailure,
+            WarnBufferTooSmall,
+            WarnStaleData,
+            WarnFileSystem,
+            WarnResetRequired,
+            LoadError,
+            InvalidParameter,
+            Unsupported,
+            BadBufferSize,
+            BufferTooSmall,
+            NotReady,
+            DeviceError,
+            WriteProtected,
+            OutOfResources,
+            VolumeCorrupted,
+            VolumeFull,
+            NoMedia,
+            MediaChanged,
+            NotFound,
+            AccessDenied,
+            NoResponse,
+            NoMapping,
+            Timeout,
+            NotStarted,
+            AlreadyStarted,
+            Aborted,
+            IcmpError,
+            TftpError,
+            ProtocolError,
+            IncompatibleVersion,
+            SecurityViolation,
+            CrcError,
+            EndOfMedia,
+            EndOfFile,
+            InvalidLanguage,
+            CompromisedData,
+            IpAddressConflict,
+            HttpError,
+            NetworkUnreachable,
+            HostUnreachable,
+            ProtocolUnreachable,
+            PortUnreachable,
+            ConnectionFin,
+            ConnectionReset,
+            ConnectionRefused,
+        );
+        assert_eq!(kinds.len(), STATUS_TABLE.len());
+        for kind in kinds {
+            let status = kind.status();
+            assert_ne!(status, EFI_SUCCESS);
+            assert_eq!(ErrorKind::from_status(status), kind);
+            assert!(kind.name().is_some());
+        }
+
+        let unknown = ErrorKind::Unknown(EFI_LOAD_ERROR | 0x1000);
+        assert_eq!(ErrorKind::from_status(unknown.status()), unknown);
+    }
+
+    #[test]
+    fn test_warning_vs_error() {
+        let warn = Error::new(EFI_WARN_STALE_DATA);
+        assert!(warn.is_warning());
+        assert!(!warn.is_error());
+
+        let err = Error::new(EFI_DEVICE_ERROR);
+        assert!(err.is_error());
+        assert!(!err.is_warning());
+    }
+
+    #[test]
+    fn test_to_result() {
+        assert_eq!(EFI_SUCCESS.to_result(), Ok(()));
+        assert_eq!(EFI_NOT_FOUND.to_result(), Err(Error::new(EFI_NOT_FOUND)));
+        assert_eq!(EFI_SUCCESS.to_result_with_val(|| 7), Ok(7));
+    }
+
+    #[test]
+    fn test_required_size() {
+        let err = EFI_BUFFER_TOO_SMALL.to_result_with_size(64).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+        assert_eq!(err.required_size(), Some(64));
+        assert_eq!(
+            EFI_NOT_FOUND
+                .to_result_with_size(64)
+                .unwrap_err()
+                .required_size(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(format!("{}", Error::new(EFI_NOT_FOUND)), "EFI_NOT_FOUND");
+        assert_eq!(
+            format!("{}", Error::buffer_too_small(16)),
+            "EFI_BUFFER_TOO_SMALL (required size: 16 bytes)"
+        );
+        assert_eq!(
+            format!("{:?}", Error::new(EFI_ACCESS_DENIED)),
+            "Error(EFI_ACCESS_DENIED)"
+        );
+        assert_eq!(ErrorKind::from_status(0x1234), ErrorKind::Unknown(0x1234));
+        assert_eq!(format!("{}", Error::new(0x1234)), "EFI_STATUS(0x1234)");
+    }
+}
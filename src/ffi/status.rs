@@ -41,6 +41,17 @@ pub const EFI_END_OF_MEDIA: Status = ERROR_BIT | 28;
 pub const EFI_END_OF_FILE: Status = ERROR_BIT | 31;
 pub const EFI_INVALID_LANGUAGE: Status = ERROR_BIT | 32;
 pub const EFI_COMPROMISED_DATA: Status = ERROR_BIT | 33;
+pub const EFI_IP_ADDRESS_CONFLICT: Status = ERROR_BIT | 34;
+pub const EFI_HTTP_ERROR: Status = ERROR_BIT | 35;
+
+// Network error codes (UEFI Specification Appendix D)
+pub const EFI_NETWORK_UNREACHABLE: Status = ERROR_BIT | 100;
+pub const EFI_HOST_UNREACHABLE: Status = ERROR_BIT | 101;
+pub const EFI_PROTOCOL_UNREACHABLE: Status = ERROR_BIT | 102;
+pub const EFI_PORT_UNREACHABLE: Status = ERROR_BIT | 103;
+pub const EFI_CONNECTION_FIN: Status = ERROR_BIT | 104;
+pub const EFI_CONNECTION_RESET: Status = ERROR_BIT | 105;
+pub const EFI_CONNECTION_REFUSED: Status = ERROR_BIT | 106;
 
 // Warning codes
 pub const EFI_WARN_UNKNOWN_GLYPH: Status = 1;
@@ -48,12 +59,19 @@ pub const EFI_WARN_DELETE_FAILURE: Status = 2;
 pub const EFI_WARN_WRITE_FAILURE: Status = 3;
 pub const EFI_WARN_BUFFER_TOO_SMALL: Status = 4;
 pub const EFI_WARN_STALE_DATA: Status = 5;
+pub const EFI_WARN_FILE_SYSTEM: Status = 6;
+pub const EFI_WARN_RESET_REQUIRED: Status = 7;
 
 /// Check if status is an error
 pub fn is_error(status: Status) -> bool {
     (status & ERROR_BIT) != 0
 }
 
+/// Check if status is a warning (non-zero without the error bit)
+pub fn is_warning(status: Status) -> bool {
+    status != EFI_SUCCESS && !is_error(status)
+}
+
 /// Check if status is success
 pub fn is_success(status: Status) -> bool {
     status == EFI_SUCCESS
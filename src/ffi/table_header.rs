@@ -14,9 +14,54 @@ pub struct TableHeader {
     pub reserved: Uint32,
 }
 
+/// Byte offset of the `crc32` field within the header
+const CRC32_OFFSET: usize = 16;
+
 impl TableHeader {
     /// Verify the table header signature
     pub fn verify_signature(&self, expected: u64) -> bool {
         self.signature == expected
     }
+
+    /// Verify the header CRC32
+    ///
+    /// The CRC covers `header_size` bytes starting at the header, computed
+    /// with the `crc32` field itself treated as zero.
+    ///
+    /// # Safety
+    /// `self` must be the start of a table at least `header_size` bytes long
+    pub unsafe fn verify_crc32(&self) -> bool {
+        let size = self.header_size as usize;
+        if size < core::mem::size_of::<TableHeader>() {
+            return false;
+        }
+
+        let bytes = core::slice::from_raw_parts(self as *const _ as *const u8, size);
+        let crc = bytes.iter().enumerate().fold(!0u32, |crc, (i, &byte)| {
+            let byte = if (CRC32_OFFSET..CRC32_OFFSET + 4).contains(&i) {
+                0
+            } else {
+                byte
+            };
+            crc32_update(crc, byte)
+        });
+
+        !crc == self.crc32
+    }
+}
+
+/// Compute the CRC32 used for UEFI tables (IEEE 802.3, reflected)
+pub fn crc32(data: &[u8]) -> u32 {
+    !data
+        .iter()
+        .fold(!0u32, |crc, &byte| crc32_update(crc, byte))
+}
+
+fn crc32_update(mut crc: u32, byte: u8) -> u32 {
+    crc ^= byte as u32;
+    for _ in 0..8 {
+        let mask = (crc & 1).wrapping_neg();
+        crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+    }
+    crc
 }
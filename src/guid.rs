@@ -1,13 +1,34 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! GUID Management Utilities
 
+use crate::capsule::FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID;
 use crate::ffi::Guid;
-#[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use crate::protocols::gpt_partition_types::*;
+use crate::protocols::shell::{SHELL_PARAMETERS_PROTOCOL_GUID, SHELL_PROTOCOL_GUID};
+use crate::protocols::*;
+use crate::runtime_services::variables::{
+    EFI_GLOBAL_VARIABLE_GUID, EFI_IMAGE_SECURITY_DATABASE_GUID,
+};
+use crate::tables::acpi::{ACPI_20_TABLE_GUID, ACPI_TABLE_GUID};
+use crate::tables::esrt::SYSTEM_RESOURCE_TABLE_GUID;
+use crate::tables::smbios::{SMBIOS3_TABLE_GUID, SMBIOS_TABLE_GUID};
+use core::str::FromStr;
 
-/// Helper macro to define a GUID constant
+/// Create a GUID constant
+///
+/// `guid!("8be4df61-93ca-11d2-aa0d-00e098032b8c")` parses the string at
+/// compile time, so a malformed literal is a build error. The older
+/// `guid!(NAME = d1, d2, d3, [d4...])` form defines a named constant from
+/// its fields.
 #[macro_export]
 macro_rules! guid {
+    ($s:literal) => {{
+        const GUID: $crate::ffi::Guid = match $crate::ffi::Guid::parse($s) {
+            Some(guid) => guid,
+            None => panic!("invalid GUID literal"),
+        };
+        GUID
+    }};
     ($name:ident = $d1:expr, $d2:expr, $d3:expr, [$d4_0:expr, $d4_1:expr, $d4_2:expr, $d4_3:expr, $d4_4:expr, $d4_5:expr, $d4_6:expr, $d4_7:expr]) => {
         pub const $name: $crate::ffi::Guid = $crate::ffi::Guid::new(
             $d1,
@@ -18,8 +39,278 @@ macro_rules! guid {
     };
 }
 
+/// Spec names for every GUID the crate defines
+pub static KNOWN_GUIDS: &[(Guid, &str)] = &[
+    // Console and image
+    (
+        SIMPLE_TEXT_INPUT_PROTOCOL_GUID,
+        "EFI_SIMPLE_TEXT_INPUT_PROTOCOL_GUID",
+    ),
+    (
+        SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID,
+        "EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID",
+    ),
+    (SERIAL_IO_PROTOCOL_GUID, "EFI_SERIAL_IO_PROTOCOL_GUID"),
+    (
+        GRAPHICS_OUTPUT_PROTOCOL_GUID,
+        "EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID",
+    ),
+    (LOADED_IMAGE_PROTOCOL_GUID, "EFI_LOADED_IMAGE_PROTOCOL_GUID"),
+    (DEVICE_PATH_PROTOCOL_GUID, "EFI_DEVICE_PATH_PROTOCOL_GUID"),
+    // Driver model
+    (
+        DRIVER_BINDING_PROTOCOL_GUID,
+        "EFI_DRIVER_BINDING_PROTOCOL_GUID",
+    ),
+    (
+        COMPONENT_NAME_PROTOCOL_GUID,
+        "EFI_COMPONENT_NAME_PROTOCOL_GUID",
+    ),
+    (
+        COMPONENT_NAME2_PROTOCOL_GUID,
+        "EFI_COMPONENT_NAME2_PROTOCOL_GUID",
+    ),
+    (
+        DRIVER_DIAGNOSTICS_PROTOCOL_GUID,
+        "EFI_DRIVER_DIAGNOSTICS_PROTOCOL_GUID",
+    ),
+    (
+        DRIVER_DIAGNOSTICS2_PROTOCOL_GUID,
+        "EFI_DRIVER_DIAGNOSTICS2_PROTOCOL_GUID",
+    ),
+    (
+        DRIVER_CONFIGURATION_PROTOCOL_GUID,
+        "EFI_DRIVER_CONFIGURATION_PROTOCOL_GUID",
+    ),
+    (
+        DRIVER_CONFIGURATION2_PROTOCOL_GUID,
+        "EFI_DRIVER_CONFIGURATION2_PROTOCOL_GUID",
+    ),
+    // Storage and file systems
+    (BLOCK_IO_PROTOCOL_GUID, "EFI_BLOCK_IO_PROTOCOL_GUID"),
+    (DISK_IO_PROTOCOL_GUID, "EFI_DISK_IO_PROTOCOL_GUID"),
+    (DISK_IO2_PROTOCOL_GUID, "EFI_DISK_IO2_PROTOCOL_GUID"),
+    (
+        PARTITION_INFO_PROTOCOL_GUID,
+        "EFI_PARTITION_INFO_PROTOCOL_GUID",
+    ),
+    (
+        SCSI_PASS_THRU_PROTOCOL_GUID,
+        "EFI_SCSI_PASS_THRU_PROTOCOL_GUID",
+    ),
+    (
+        EXT_SCSI_PASS_THRU_PROTOCOL_GUID,
+        "EFI_EXT_SCSI_PASS_THRU_PROTOCOL_GUID",
+    ),
+    (
+        NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID,
+        "EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID",
+    ),
+    (
+        SIMPLE_FILE_SYSTEM_PROTOCOL_GUID,
+        "EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID",
+    ),
+    (FILE_INFO_GUID, "EFI_FILE_INFO_ID"),
+    (FILE_PROTOCOL_GUID, "EFI_FILE_SYSTEM_INFO_ID"),
+    (
+        EFI_SYSTEM_PARTITION_GUID,
+        "EFI_PART_TYPE_EFI_SYSTEM_PART_GUID",
+    ),
+    (MICROSOFT_BASIC_DATA_GUID, "MICROSOFT_BASIC_DATA_GUID"),
+    (LINUX_FILESYSTEM_DATA_GUID, "LINUX_FILESYSTEM_DATA_GUID"),
+    // Buses and platform
+    (PCI_IO_PROTOCOL_GUID, "EFI_PCI_IO_PROTOCOL_GUID"),
+    (USB_IO_PROTOCOL_GUID, "EFI_USB_IO_PROTOCOL_GUID"),
+    (MP_SERVICES_PROTOCOL_GUID, "EFI_MP_SERVICES_PROTOCOL_GUID"),
+    (TIMESTAMP_PROTOCOL_GUID, "EFI_TIMESTAMP_PROTOCOL_GUID"),
+    (RNG_PROTOCOL_GUID, "EFI_RNG_PROTOCOL_GUID"),
+    (
+        RNG_ALGORITHM_SP800_90_HASH_256_GUID,
+        "EFI_RNG_ALGORITHM_SP800_90_HASH_256_GUID",
+    ),
+    (
+        RNG_ALGORITHM_SP800_90_HMAC_256_GUID,
+        "EFI_RNG_ALGORITHM_SP800_90_HMAC_256_GUID",
+    ),
+    (
+        RNG_ALGORITHM_SP800_90_CTR_256_GUID,
+        "EFI_RNG_ALGORITHM_SP800_90_CTR_256_GUID",
+    ),
+    (
+        RNG_ALGORITHM_X9_31_3DES_GUID,
+        "EFI_RNG_ALGORITHM_X9_31_3DES_GUID",
+    ),
+    (
+        RNG_ALGORITHM_X9_31_AES_GUID,
+        "EFI_RNG_ALGORITHM_X9_31_AES_GUID",
+    ),
+    (RNG_ALGORITHM_RAW, "EFI_RNG_ALGORITHM_RAW"),
+    (
+        FIRMWARE_MANAGEMENT_PROTOCOL_GUID,
+        "EFI_FIRMWARE_MANAGEMENT_PROTOCOL_GUID",
+    ),
+    // HII
+    (HII_DATABASE_PROTOCOL_GUID, "EFI_HII_DATABASE_PROTOCOL_GUID"),
+    (HII_STRING_PROTOCOL_GUID, "EFI_HII_STRING_PROTOCOL_GUID"),
+    (HII_FONT_PROTOCOL_GUID, "EFI_HII_FONT_PROTOCOL_GUID"),
+    (HII_IMAGE_PROTOCOL_GUID, "EFI_HII_IMAGE_PROTOCOL_GUID"),
+    (
+        HII_CONFIG_ACCESS_PROTOCOL_GUID,
+        "EFI_HII_CONFIG_ACCESS_PROTOCOL_GUID",
+    ),
+    (
+        HII_CONFIG_ROUTING_PROTOCOL_GUID,
+        "EFI_HII_CONFIG_ROUTING_PROTOCOL_GUID",
+    ),
+    // Shell
+    (SHELL_PROTOCOL_GUID, "EFI_SHELL_PROTOCOL_GUID"),
+    (
+        SHELL_PARAMETERS_PROTOCOL_GUID,
+        "EFI_SHELL_PARAMETERS_PROTOCOL_GUID",
+    ),
+    // Network
+    (
+        SIMPLE_NETWORK_PROTOCOL_GUID,
+        "EFI_SIMPLE_NETWORK_PROTOCOL_GUID",
+    ),
+    (
+        PXE_BASE_CODE_PROTOCOL_GUID,
+        "EFI_PXE_BASE_CODE_PROTOCOL_GUID",
+    ),
+    (
+        PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID,
+        "EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID",
+    ),
+    (
+        ARP_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_ARP_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (ARP_PROTOCOL_GUID, "EFI_ARP_PROTOCOL_GUID"),
+    (
+        IP4_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_IP4_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (IP4_PROTOCOL_GUID, "EFI_IP4_PROTOCOL_GUID"),
+    (
+        IP6_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_IP6_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (IP6_PROTOCOL_GUID, "EFI_IP6_PROTOCOL_GUID"),
+    (
+        DHCP4_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_DHCP4_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (DHCP4_PROTOCOL_GUID, "EFI_DHCP4_PROTOCOL_GUID"),
+    (
+        DHCP6_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_DHCP6_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (DHCP6_PROTOCOL_GUID, "EFI_DHCP6_PROTOCOL_GUID"),
+    (
+        DNS4_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_DNS4_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (DNS4_PROTOCOL_GUID, "EFI_DNS4_PROTOCOL_GUID"),
+    (
+        DNS6_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_DNS6_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (DNS6_PROTOCOL_GUID, "EFI_DNS6_PROTOCOL_GUID"),
+    (
+        TCP4_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_TCP4_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (TCP4_PROTOCOL_GUID, "EFI_TCP4_PROTOCOL_GUID"),
+    (
+        TCP6_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_TCP6_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (TCP6_PROTOCOL_GUID, "EFI_TCP6_PROTOCOL_GUID"),
+    (
+        UDP4_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_UDP4_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (UDP4_PROTOCOL_GUID, "EFI_UDP4_PROTOCOL_GUID"),
+    (
+        UDP6_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_UDP6_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (UDP6_PROTOCOL_GUID, "EFI_UDP6_PROTOCOL_GUID"),
+    (
+        HTTP_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_HTTP_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (HTTP_PROTOCOL_GUID, "EFI_HTTP_PROTOCOL_GUID"),
+    // Security
+    (
+        SECURITY_ARCH_PROTOCOL_GUID,
+        "EFI_SECURITY_ARCH_PROTOCOL_GUID",
+    ),
+    (
+        SECURITY2_ARCH_PROTOCOL_GUID,
+        "EFI_SECURITY2_ARCH_PROTOCOL_GUID",
+    ),
+    (HASH_PROTOCOL_GUID, "EFI_HASH_PROTOCOL_GUID"),
+    (HASH2_PROTOCOL_GUID, "EFI_HASH2_PROTOCOL_GUID"),
+    (
+        HASH2_SERVICE_BINDING_PROTOCOL_GUID,
+        "EFI_HASH2_SERVICE_BINDING_PROTOCOL_GUID",
+    ),
+    (HASH_ALGORITHM_SHA1_GUID, "EFI_HASH_ALGORITHM_SHA1_GUID"),
+    (HASH_ALGORITHM_SHA256_GUID, "EFI_HASH_ALGORITHM_SHA256_GUID"),
+    (HASH_ALGORITHM_SHA384_GUID, "EFI_HASH_ALGORITHM_SHA384_GUID"),
+    (HASH_ALGORITHM_SHA512_GUID, "EFI_HASH_ALGORITHM_SHA512_GUID"),
+    (PKCS7_VERIFY_PROTOCOL_GUID, "EFI_PKCS7_VERIFY_PROTOCOL_GUID"),
+    (CERT_SHA256_GUID, "EFI_CERT_SHA256_GUID"),
+    (CERT_RSA2048_GUID, "EFI_CERT_RSA2048_GUID"),
+    (CERT_X509_GUID, "EFI_CERT_X509_GUID"),
+    (CERT_SHA1_GUID, "EFI_CERT_SHA1_GUID"),
+    (CERT_SHA224_GUID, "EFI_CERT_SHA224_GUID"),
+    (CERT_SHA384_GUID, "EFI_CERT_SHA384_GUID"),
+    (CERT_SHA512_GUID, "EFI_CERT_SHA512_GUID"),
+    (CERT_RSA2048_SHA1_GUID, "EFI_CERT_RSA2048_SHA1_GUID"),
+    (CERT_RSA2048_SHA256_GUID, "EFI_CERT_RSA2048_SHA256_GUID"),
+    (CERT_X509_SHA256_GUID, "EFI_CERT_X509_SHA256_GUID"),
+    (CERT_X509_SHA384_GUID, "EFI_CERT_X509_SHA384_GUID"),
+    (CERT_X509_SHA512_GUID, "EFI_CERT_X509_SHA512_GUID"),
+    (CERT_TYPE_PKCS7_GUID, "EFI_CERT_TYPE_PKCS7_GUID"),
+    (TPM2_PROTOCOL_GUID, "EFI_TCG2_PROTOCOL_GUID"),
+    (
+        FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID,
+        "EFI_FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID",
+    ),
+    // Variables and configuration tables
+    (EFI_GLOBAL_VARIABLE_GUID, "EFI_GLOBAL_VARIABLE"),
+    (
+        EFI_IMAGE_SECURITY_DATABASE_GUID,
+        "EFI_IMAGE_SECURITY_DATABASE_GUID",
+    ),
+    (ACPI_TABLE_GUID, "ACPI_TABLE_GUID"),
+    (ACPI_20_TABLE_GUID, "EFI_ACPI_TABLE_GUID"),
+    (SMBIOS_TABLE_GUID, "SMBIOS_TABLE_GUID"),
+    (SMBIOS3_TABLE_GUID, "SMBIOS3_TABLE_GUID"),
+    (SYSTEM_RESOURCE_TABLE_GUID, "EFI_SYSTEM_RESOURCE_TABLE_GUID"),
+];
+
+/// Look up the spec name of a GUID defined by this crate
+pub fn guid_name(guid: &Guid) -> Option<&'static str> {
+    KNOWN_GUIDS
+        .iter()
+        .find(|(known, _)| known == guid)
+        .map(|(_, name)| *name)
+}
+
+/// Formats as `XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX`
+///
+/// The alternate form (`{:#}`) prints the spec name instead when the GUID
+/// is in [`KNOWN_GUIDS`].
 impl core::fmt::Display for Guid {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if f.alternate() {
+            if let Some(name) = self.name() {
+                return f.write_str(name);
+            }
+        }
         write!(
             f,
             "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
@@ -41,39 +332,47 @@ impl core::fmt::Display for Guid {
 impl Guid {
     /// Parse a GUID from a string
     /// Format: "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"
-    pub fn parse(s: &str) -> Option<Self> {
-        if s.len() != 36 {
-            return None;
-        }
-
-        let parts: Vec<&str> = s.split('-').collect();
-        if parts.len() != 5 {
+    ///
+    /// Usable in `const` context; see [`guid!`](crate::guid).
+    pub const fn parse(s: &str) -> Option<Self> {
+        let b = s.as_bytes();
+        if b.len() != 36 || b[8] != b'-' || b[13] != b'-' || b[18] != b'-' || b[23] != b'-' {
             return None;
         }
 
-        let data1 = u32::from_str_radix(parts[0], 16).ok()?;
-        let data2 = u16::from_str_radix(parts[1], 16).ok()?;
-        let data3 = u16::from_str_radix(parts[2], 16).ok()?;
+        let data1 = match parse_hex(b, 0, 8) {
+            Some(v) => v as u32,
+            None => return None,
+        };
+        let data2 = match parse_hex(b, 9, 4) {
+            Some(v) => v as u16,
+            None => return None,
+        };
+        let data3 = match parse_hex(b, 14, 4) {
+            Some(v) => v as u16,
+            None => return None,
+        };
 
+        // Byte offsets of data4[0..8] in the string
+        const DATA4_OFFSETS: [usize; 8] = [19, 21, 24, 26, 28, 30, 32, 34];
         let mut data4 = [0u8; 8];
-        let part3 = parts[3];
-        if part3.len() != 4 {
-            return None;
-        }
-        data4[0] = u8::from_str_radix(&part3[0..2], 16).ok()?;
-        data4[1] = u8::from_str_radix(&part3[2..4], 16).ok()?;
-
-        let part4 = parts[4];
-        if part4.len() != 12 {
-            return None;
-        }
-        for i in 0..6 {
-            data4[i + 2] = u8::from_str_radix(&part4[i * 2..i * 2 + 2], 16).ok()?;
+        let mut i = 0;
+        while i < 8 {
+            data4[i] = match parse_hex(b, DATA4_OFFSETS[i], 2) {
+                Some(v) => v as u8,
+                None => return None,
+            };
+            i += 1;
         }
 
         Some(Guid::new(data1, data2, data3, data4))
     }
 
+    /// Get the spec name if this is a GUID the crate defines
+    pub fn name(&self) -> Option<&'static str> {
+        guid_name(self)
+    }
+
     /// Check if this GUID is null (all zeros)
     pub fn is_null(&self) -> bool {
         self.data1 == 0 && self.data2 == 0 && self.data3 == 0 && self.data4 == [0; 8]
@@ -85,6 +384,31 @@ impl Guid {
     }
 }
 
+/// Parse `len` hex digits starting at `start`
+const fn parse_hex(b: &[u8], start: usize, len: usize) -> Option<u64> {
+    let mut value = 0u64;
+    let mut i = start;
+    while i < start + len {
+        let digit = match b[i] {
+            b'0'..=b'9' => b[i] - b'0',
+            b'a'..=b'f' => b[i] - b'a' + 10,
+            b'A'..=b'F' => b[i] - b'A' + 10,
+            _ => return None,
+        };
+        value = (value << 4) | digit as u64;
+        i += 1;
+    }
+    Some(value)
+}
+
+impl FromStr for Guid {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Guid::parse(s).ok_or(crate::error::Error::new(crate::ffi::EFI_INVALID_PARAMETER))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,4 +443,50 @@ mod tests {
         let guid2 = Guid::new(1, 0, 0, [0; 8]);
         assert!(!guid2.is_null());
     }
+
+    #[test]
+    fn test_guid_macro_matches_fields() {
+        const GLOBAL: Guid = crate::guid!("8be4df61-93ca-11d2-aa0d-00e098032b8c");
+        assert_eq!(
+            GLOBAL,
+            Guid::new(
+                0x8BE4DF61,
+                0x93CA,
+                0x11D2,
+                [0xAA, 0x0D, 0x00, 0xE0, 0x98, 0x03, 0x2B, 0x8C]
+            )
+        );
+        assert_eq!(GLOBAL, EFI_GLOBAL_VARIABLE_GUID);
+    }
+
+    #[test]
+    fn test_guid_parse_rejects_malformed() {
+        assert!(Guid::parse("8be4df61-93ca-11d2-aa0d-00e098032b8").is_none());
+        assert!(Guid::parse("8be4df61_93ca-11d2-aa0d-00e098032b8c").is_none());
+        assert!(Guid::parse("8be4df61-93ca-11d2-aa0d-00e098032b8g").is_none());
+        assert!("not a guid".parse::<Guid>().is_err());
+    }
+
+    #[test]
+    fn test_guid_alternate_display_uses_name() {
+        assert_eq!(
+            format!("{:#}", LOADED_IMAGE_PROTOCOL_GUID),
+            "EFI_LOADED_IMAGE_PROTOCOL_GUID"
+        );
+        assert_eq!(
+            format!("{}", LOADED_IMAGE_PROTOCOL_GUID),
+            "5B1B31A1-9562-11D2-8E3F-00A0C969723B"
+        );
+        let unknown = Guid::new(1, 2, 3, [4; 8]);
+        assert_eq!(format!("{:#}", unknown), format!("{}", unknown));
+    }
+
+    #[test]
+    fn test_known_guids_are_unique() {
+        for (i, (guid, name)) in KNOWN_GUIDS.iter().enumerate() {
+            for (other, other_name) in &KNOWN_GUIDS[i + 1..] {
+                assert_ne!(guid, other, "{} and {} share a GUID", name, other_name);
+            }
+        }
+    }
 }
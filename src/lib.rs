@@ -7,6 +7,7 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(not(feature = "std"), feature(alloc_error_handler))]
 #![cfg_attr(not(feature = "std"), feature(lang_items))]
+#![cfg_attr(not(feature = "std"), allow(internal_features))]
 #![allow(clippy::missing_safety_doc)]
 #![allow(improper_ctypes_definitions)]
 #![allow(clippy::mut_from_ref)]
@@ -16,6 +17,7 @@
 #![cfg_attr(test, allow(unused_imports))]
 #![cfg_attr(test, allow(unused_variables))]
 #![cfg_attr(test, allow(clippy::useless_transmute))]
+#![cfg_attr(test, allow(clippy::missing_transmute_annotations))]
 #![cfg_attr(test, allow(clippy::field_reassign_with_default))]
 #![cfg_attr(test, allow(clippy::unnecessary_unwrap))]
 #![cfg_attr(test, allow(clippy::needless_parens))]
@@ -25,21 +27,31 @@ extern crate alloc;
 
 pub mod allocator;
 pub mod boot_services;
+pub mod capsule;
+pub mod crypto;
 pub mod debug;
+pub mod entry;
+pub mod error;
 pub mod ffi;
 pub mod graphics;
 pub mod guid;
 pub mod intrinsics;
 pub mod logger;
 pub mod panic_handler;
+pub mod pe;
 pub mod protocols;
+pub mod runtime;
 pub mod runtime_services;
+pub mod signature_database;
 pub mod string;
 pub mod system_table;
 pub mod tables;
+pub mod tpm2;
 
+pub use error::{Error, ErrorKind, Result, StatusExt};
 pub use ffi::*;
-pub use system_table::SystemTable;
+pub use string::{CStr16, CString16};
+pub use system_table::{Boot, RawSystemTable, Runtime, SystemTable};
 
 #[cfg(not(feature = "std"))]
 use core::panic::PanicInfo;
@@ -1,10 +1,28 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Logging Framework
+//!
+//! Records fan out to any number of sinks (console, serial port, in-memory
+//! ring buffer, file), each with its own level. Module filters cap the
+//! level for records whose module path matches a prefix.
 
+use crate::boot_services::BootServices;
+use crate::error::Result;
 use crate::ffi::*;
-use crate::protocols::SimpleTextOutputProtocol;
+use crate::protocols::{
+    FileProtocol, SerialIoProtocol, SerialWriter, SimpleFileSystemProtocol,
+    SimpleTextOutputProtocol, TimestampProtocol, EFI_FILE_MODE_CREATE, EFI_FILE_MODE_READ,
+    EFI_FILE_MODE_WRITE,
+};
+use crate::runtime::guard::boot_services_available;
+use crate::string::CString16;
 use core::fmt::{self, Write};
 
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+#[cfg(feature = "std")]
+use std::{vec, vec::Vec};
+
 /// Log level
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
@@ -18,10 +36,197 @@ pub enum LogLevel {
 
 static mut LOGGER: Option<Logger> = None;
 
+/// A single log record as passed to the sinks
+#[derive(Debug, Copy, Clone)]
+pub struct Record<'a> {
+    pub level: LogLevel,
+    /// Module path of the call site, empty if unknown
+    pub module: &'a str,
+    /// See [`TimestampSource`] for the unit
+    pub timestamp: Option<u64>,
+    pub args: fmt::Arguments<'a>,
+}
+
+/// Where log output goes
+pub enum Sink {
+    Console(*mut SimpleTextOutputProtocol),
+    Serial(*mut SerialIoProtocol),
+    Memory(RingBuffer),
+    File(*mut FileProtocol),
+}
+
+impl Sink {
+    /// Whether the sink depends on boot services
+    fn needs_boot_services(&self) -> bool {
+        !matches!(self, Sink::Memory(_))
+    }
+}
+
+struct SinkEntry {
+    sink: Sink,
+    level: LogLevel,
+}
+
+struct ModuleFilter {
+    prefix: &'static str,
+    level: LogLevel,
+}
+
+impl ModuleFilter {
+    fn matches(&self, module: &str) -> bool {
+        match module.strip_prefix(self.prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with("::"),
+            None => false,
+        }
+    }
+}
+
+/// Source of record timestamps
+#[derive(Debug, Copy, Clone)]
+pub enum TimestampSource {
+    None,
+    /// `EFI_TIMESTAMP_PROTOCOL`; records carry microseconds
+    Timestamp {
+        protocol: *mut TimestampProtocol,
+        frequency: u64,
+    },
+    /// `GetNextMonotonicCount`; records carry the raw count
+    MonotonicCount(*const BootServices),
+}
+
+impl TimestampSource {
+    /// Use the timestamp protocol, reading its frequency once
+    ///
+    /// # Safety
+    /// The protocol pointer must stay valid while it is in use
+    pub unsafe fn timestamp(protocol: *mut TimestampProtocol) -> Result<Self> {
+        let props = (*protocol).get_properties()?;
+        Ok(TimestampSource::Timestamp {
+            protocol,
+            frequency: props.frequency,
+        })
+    }
+
+    fn now(&self) -> Option<u64> {
+        unsafe {
+            match *self {
+                TimestampSource::None => None,
+                TimestampSource::Timestamp {
+                    protocol,
+                    frequency,
+                } => {
+                    let ticks = (*protocol).get_timestamp();
+                    if frequency == 0 {
+                        Some(ticks)
+                    } else {
+                        // Split into whole seconds and remainder so the
+                        // product stays in u64 without 128-bit division
+                        Some(
+                            ticks / frequency * 1_000_000
+                                + ticks % frequency * 1_000_000 / frequency,
+                        )
+                    }
+                }
+                TimestampSource::MonotonicCount(bs) => {
+                    let mut count = 0;
+                    ((*bs).get_next_monotonic_count)(&mut count);
+                    Some(count)
+                }
+            }
+        }
+    }
+}
+
+/// Fixed-size byte buffer that overwrites the oldest data when full
+pub struct RingBuffer {
+    buf: Vec<u8>,
+    head: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    /// Allocate a ring buffer holding up to `capacity` bytes
+    pub fn new(capacity: usize) -> Self {
+        RingBuffer {
+            buf: vec![0; capacity],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Maximum number of bytes retained
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Number of bytes currently held
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Discard all contents
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Append bytes, dropping the oldest ones if needed
+    pub fn push(&mut self, data: &[u8]) {
+        let cap = self.capacity();
+        if cap == 0 {
+            return;
+        }
+        // Only the tail end of an oversized write can be kept
+        let data = &data[data.len().saturating_sub(cap)..];
+        for &byte in data {
+            let tail = (self.head + self.len) % cap;
+            self.buf[tail] = byte;
+            if self.len == cap {
+                self.head = (self.head + 1) % cap;
+            } else {
+                self.len += 1;
+            }
+        }
+    }
+
+    /// Get the contents, oldest first, as two slices
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let cap = self.capacity();
+        if self.head + self.len <= cap {
+            (&self.buf[self.head..self.head + self.len], &[])
+        } else {
+            let wrapped = self.head + self.len - cap;
+            (&self.buf[self.head..], &self.buf[..wrapped])
+        }
+    }
+
+    /// Copy the contents, oldest first
+    pub fn to_vec(&self) -> Vec<u8> {
+        let (a, b) = self.as_slices();
+        let mut out = Vec::with_capacity(self.len);
+        out.extend_from_slice(a);
+        out.extend_from_slice(b);
+        out
+    }
+}
+
+impl Write for RingBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
 /// UEFI Logger
 pub struct Logger {
-    console: Option<*mut SimpleTextOutputProtocol>,
-    level: LogLevel,
+    sinks: Vec<SinkEntry>,
+    filters: Vec<ModuleFilter>,
+    timestamp: TimestampSource,
 }
 
 impl Default for Logger {
@@ -34,52 +239,212 @@ impl Logger {
     /// Create a new logger
     pub const fn new() -> Self {
         Logger {
-            console: None,
-            level: LogLevel::Info,
+            sinks: Vec::new(),
+            filters: Vec::new(),
+            timestamp: TimestampSource::None,
         }
     }
 
     /// Initialize the logger with a console output protocol
     ///
+    /// Replaces any existing logger configuration.
+    ///
     /// # Safety
     /// The console pointer must be valid for the lifetime of the logger
     pub unsafe fn init(console: *mut SimpleTextOutputProtocol, level: LogLevel) {
-        LOGGER = Some(Logger {
-            console: Some(console),
+        let mut logger = Logger::new();
+        logger.sinks.push(SinkEntry {
+            sink: Sink::Console(console),
             level,
         });
+        LOGGER = Some(logger);
+    }
+
+    /// Add a sink that receives records up to `level`
+    ///
+    /// Creates the global logger if it does not exist yet.
+    ///
+    /// # Safety
+    /// Any protocol pointer in `sink` must be valid for the lifetime of the logger
+    pub unsafe fn add_sink(sink: Sink, level: LogLevel) {
+        Self::get_or_init().sinks.push(SinkEntry { sink, level });
+    }
+
+    /// Also send log records to a serial port at `Info` level
+    ///
+    /// # Safety
+    /// The serial pointer must be valid for the lifetime of the logger
+    pub unsafe fn set_serial(serial: *mut SerialIoProtocol) {
+        Self::add_sink(Sink::Serial(serial), LogLevel::Info);
+    }
+
+    /// Capture records up to `level` in an in-memory ring buffer
+    ///
+    /// The buffer keeps working after `ExitBootServices`.
+    pub fn add_memory_sink(capacity: usize, level: LogLevel) {
+        unsafe { Self::add_sink(Sink::Memory(RingBuffer::new(capacity)), level) }
+    }
+
+    /// Append records up to `level` to `path` on a file system
+    ///
+    /// The file is created if needed, and closed at `ExitBootServices`.
+    ///
+    /// # Safety
+    /// `fs` must be a valid simple file system protocol instance
+    pub unsafe fn add_file_sink(
+        fs: *mut SimpleFileSystemProtocol,
+        path: &str,
+        level: LogLevel,
+    ) -> Result<()> {
+        let name = CString16::try_from(path)?;
+        let root = (*fs).open_volume()?;
+        let file = (*root).open(
+            &name,
+            EFI_FILE_MODE_READ | EFI_FILE_MODE_WRITE | EFI_FILE_MODE_CREATE,
+            0,
+        );
+        let _ = (*root).close();
+        let file = file?;
+        // Position 0xFFFFFFFFFFFFFFFF seeks to end of file
+        if let Err(err) = (*file).set_position(u64::MAX) {
+            let _ = (*file).close();
+            return Err(err);
+        }
+        Self::add_sink(Sink::File(file), level);
+        Ok(())
     }
 
-    /// Set log level
+    /// Get a copy of the first memory sink's contents
+    pub fn captured() -> Option<Vec<u8>> {
+        Self::get()?
+            .sinks
+            .iter()
+            .find_map(|entry| match &entry.sink {
+                Sink::Memory(ring) => Some(ring.to_vec()),
+                _ => None,
+            })
+    }
+
+    /// Set the level of every sink
     pub fn set_level(level: LogLevel) {
-        unsafe {
-            if let Some(ref mut logger) = LOGGER {
-                logger.level = level;
+        if let Some(logger) = Self::get() {
+            for entry in logger.sinks.iter_mut() {
+                entry.level = level;
             }
         }
     }
 
+    /// Cap the level for modules whose path starts with `prefix`
+    ///
+    /// `prefix` matches whole path segments, and the longest matching
+    /// prefix wins. Sinks still apply their own level.
+    pub fn set_module_level(prefix: &'static str, level: LogLevel) {
+        let logger = Self::get_or_init();
+        match logger.filters.iter_mut().find(|f| f.prefix == prefix) {
+            Some(filter) => filter.level = level,
+            None => logger.filters.push(ModuleFilter { prefix, level }),
+        }
+    }
+
+    /// Remove all module filters
+    pub fn clear_module_filters() {
+        if let Some(logger) = Self::get() {
+            logger.filters.clear();
+        }
+    }
+
+    /// Set where record timestamps come from
+    pub fn set_timestamp_source(source: TimestampSource) {
+        Self::get_or_init().timestamp = source;
+    }
+
     /// Get the global logger
     fn get() -> Option<&'static mut Logger> {
         unsafe { (&raw mut LOGGER).as_mut().and_then(|x| x.as_mut()) }
     }
 
-    /// Log a message
+    fn get_or_init() -> &'static mut Logger {
+        unsafe {
+            let slot = &raw mut LOGGER;
+            (*slot).get_or_insert_with(Logger::new)
+        }
+    }
+
+    /// Log a message without module information
     pub fn log(level: LogLevel, args: fmt::Arguments) {
+        Self::log_from(level, "", args);
+    }
+
+    /// Log a message from `module`
+    pub fn log_from(level: LogLevel, module: &str, args: fmt::Arguments) {
         if let Some(logger) = Self::get() {
-            if level <= logger.level {
-                logger.write_log(level, args);
-            }
+            logger.dispatch(level, module, args);
         }
     }
 
-    fn write_log(&mut self, level: LogLevel, args: fmt::Arguments) {
-        if let Some(console) = self.console {
-            let _ = write!(ConsoleWriter { console }, "[{}] ", level);
-            let _ = ConsoleWriter { console }.write_fmt(args);
-            let _ = write!(ConsoleWriter { console }, "\r\n");
+    /// Maximum level allowed for `module` by the filters
+    fn module_level(&self, module: &str) -> LogLevel {
+        self.filters
+            .iter()
+            .filter(|f| f.matches(module))
+            .max_by_key(|f| f.prefix.len())
+            .map_or(LogLevel::Trace, |f| f.level)
+    }
+
+    fn dispatch(&mut self, level: LogLevel, module: &str, args: fmt::Arguments) {
+        if level > self.module_level(module) {
+            return;
         }
+        let boot_services = boot_services_available();
+        let enabled = |entry: &SinkEntry| {
+            level <= entry.level && (boot_services || !entry.sink.needs_boot_services())
+        };
+        if !self.sinks.iter().any(enabled) {
+            return;
+        }
+
+        let record = Record {
+            level,
+            module,
+            // The timestamp protocol goes away with boot services
+            timestamp: if boot_services {
+                self.timestamp.now()
+            } else {
+                None
+            },
+            args,
+        };
+
+        // Each sink is written independently so a failing one does not
+        // lose records on the others
+        for entry in self.sinks.iter_mut() {
+            if !enabled(entry) {
+                continue;
+            }
+            let _ = match &mut entry.sink {
+                Sink::Console(console) => {
+                    write_record(&mut ConsoleWriter { console: *console }, &record)
+                }
+                Sink::Serial(serial) => {
+                    write_record(&mut unsafe { SerialWriter::new(*serial) }, &record)
+                }
+                Sink::Memory(ring) => write_record(ring, &record),
+                Sink::File(file) => write_record(&mut FileWriter { file: *file }, &record),
+            };
+        }
+    }
+}
+
+fn write_record<W: Write>(w: &mut W, record: &Record) -> fmt::Result {
+    if let Some(ts) = record.timestamp {
+        write!(w, "[{:>12}] ", ts)?;
+    }
+    write!(w, "[{}] ", record.level)?;
+    if !record.module.is_empty() {
+        write!(w, "{}: ", record.module)?;
     }
+    w.write_fmt(record.args)?;
+    w.write_str("\r\n")
 }
 
 impl fmt::Display for LogLevel {
@@ -100,9 +465,6 @@ struct ConsoleWriter {
 
 impl Write for ConsoleWriter {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        #[cfg(not(feature = "std"))]
-        use alloc::vec::Vec;
-
         let ucs2: Vec<u16> = s.encode_utf16().chain(core::iter::once(0)).collect();
         unsafe {
             let status = ((*self.console).output_string)(self.console, ucs2.as_ptr());
@@ -115,38 +477,324 @@ impl Write for ConsoleWriter {
     }
 }
 
+struct FileWriter {
+    file: *mut FileProtocol,
+}
+
+impl Write for FileWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut data = s.as_bytes();
+        while !data.is_empty() {
+            match unsafe { (*self.file).write(data) } {
+                Ok(0) | Err(_) => return Err(fmt::Error),
+                Ok(written) => data = &data[written..],
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Flush and close file sinks before exiting boot services
+///
+/// Once [`crate::runtime::guard`] records the exit, records only reach the
+/// sinks that do not need boot services, so a memory sink can still capture
+/// runtime messages.
+///
+/// # Safety
+/// Must not race with other logger calls
+pub unsafe fn close_files() {
+    if let Some(logger) = Logger::get() {
+        for entry in logger.sinks.iter() {
+            if let Sink::File(file) = entry.sink {
+                let _ = (*file).flush();
+                let _ = (*file).close();
+            }
+        }
+        logger
+            .sinks
+            .retain(|entry| !matches!(entry.sink, Sink::File(_)));
+    }
+}
+
 /// Log macros
 #[macro_export]
 macro_rules! log_error {
     ($($arg:tt)*) => {
-        $crate::logger::Logger::log($crate::logger::LogLevel::Error, format_args!($($arg)*))
+        $crate::logger::Logger::log_from(
+            $crate::logger::LogLevel::Error,
+            module_path!(),
+            format_args!($($arg)*),
+        )
     };
 }
 
 #[macro_export]
 macro_rules! log_warn {
     ($($arg:tt)*) => {
-        $crate::logger::Logger::log($crate::logger::LogLevel::Warn, format_args!($($arg)*))
+        $crate::logger::Logger::log_from(
+            $crate::logger::LogLevel::Warn,
+            module_path!(),
+            format_args!($($arg)*),
+        )
     };
 }
 
 #[macro_export]
 macro_rules! log_info {
     ($($arg:tt)*) => {
-        $crate::logger::Logger::log($crate::logger::LogLevel::Info, format_args!($($arg)*))
+        $crate::logger::Logger::log_from(
+            $crate::logger::LogLevel::Info,
+            module_path!(),
+            format_args!($($arg)*),
+        )
     };
 }
 
 #[macro_export]
 macro_rules! log_debug {
     ($($arg:tt)*) => {
-        $crate::logger::Logger::log($crate::logger::LogLevel::Debug, format_args!($($arg)*))
+        $crate::logger::Logger::log_from(
+            $crate::logger::LogLevel::Debug,
+            module_path!(),
+            format_args!($($arg)*),
+        )
     };
 }
 
 #[macro_export]
 macro_rules! log_trace {
     ($($arg:tt)*) => {
-        $crate::logger::Logger::log($crate::logger::LogLevel::Trace, format_args!($($arg)*))
+        $crate::logger::Logger::log_from(
+            $crate::logger::LogLevel::Trace,
+            module_path!(),
+            format_args!($($arg)*),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::protocols::{
+        TimestampProperties, EFI_FILE_PROTOCOL_REVISION, EFI_SERIAL_IO_PROTOCOL_REVISION,
     };
+    use crate::runtime::guard;
+    use core::cell::{Cell, RefCell};
+
+    #[cfg(not(feature = "std"))]
+    use alloc::string::String;
+
+    fn memory_logger(level: LogLevel) -> Logger {
+        let mut logger = Logger::new();
+        logger.sinks.push(SinkEntry {
+            sink: Sink::Memory(RingBuffer::new(256)),
+            level,
+        });
+        logger
+    }
+
+    fn contents(logger: &Logger) -> String {
+        match &logger.sinks[0].sink {
+            Sink::Memory(ring) => String::from_utf8(ring.to_vec()).unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_wraps() {
+        let mut ring = RingBuffer::new(4);
+        ring.push(b"abc");
+        assert_eq!(ring.to_vec(), b"abc");
+        ring.push(b"def");
+        assert_eq!(ring.len(), 4);
+        assert_eq!(ring.to_vec(), b"cdef");
+        ring.push(b"0123456789");
+        assert_eq!(ring.to_vec(), b"6789");
+        ring.clear();
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn test_sink_level_and_format() {
+        let mut logger = memory_logger(LogLevel::Warn);
+        logger.dispatch(LogLevel::Info, "app", format_args!("hidden"));
+        logger.dispatch(LogLevel::Warn, "app::net", format_args!("x={}", 1));
+        assert_eq!(contents(&logger), "[WARN ] app::net: x=1\r\n");
+    }
+
+    #[test]
+    fn test_module_filters() {
+        let mut logger = memory_logger(LogLevel::Trace);
+        logger.filters.push(ModuleFilter {
+            prefix: "app::net",
+            level: LogLevel::Error,
+        });
+        logger.filters.push(ModuleFilter {
+            prefix: "app::net::tcp",
+            level: LogLevel::Debug,
+        });
+
+        assert_eq!(logger.module_level("app::net"), LogLevel::Error);
+        assert_eq!(logger.module_level("app::net::udp"), LogLevel::Error);
+        assert_eq!(logger.module_level("app::net::tcp"), LogLevel::Debug);
+        assert_eq!(logger.module_level("app::network"), LogLevel::Trace);
+
+        logger.dispatch(LogLevel::Info, "app::net::udp", format_args!("dropped"));
+        logger.dispatch(LogLevel::Debug, "app::net::tcp", format_args!("kept"));
+        assert_eq!(contents(&logger), "[DEBUG] app::net::tcp: kept\r\n");
+    }
+    std::thread_local! {
+        static TICKS: Cell<u64> = const { Cell::new(0) };
+        static OUTPUT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+
+    unsafe extern "efiapi" fn unsupported() -> Status {
+        EFI_UNSUPPORTED
+    }
+
+    macro_rules! unsupported {
+        () => {
+            unsafe { core::mem::transmute::<*const (), _>(unsupported as *const ()) }
+        };
+    }
+
+    unsafe extern "efiapi" fn get_timestamp(_this: *mut TimestampProtocol) -> Uint64 {
+        TICKS.with(Cell::get)
+    }
+
+    unsafe extern "efiapi" fn get_properties(
+        _this: *mut TimestampProtocol,
+        properties: *mut TimestampProperties,
+    ) -> Status {
+        *properties = TimestampProperties {
+            frequency: 3_000_000,
+            end_value: u64::MAX,
+        };
+        EFI_SUCCESS
+    }
+
+    // Both sinks accept at most 5 bytes per call to exercise partial writes
+    fn capture(buffer_size: *mut Uintn, buffer: *const core::ffi::c_void) -> Status {
+        unsafe {
+            let n = (*buffer_size).min(5);
+            let data = core::slice::from_raw_parts(buffer as *const u8, n);
+            OUTPUT.with(|o| o.borrow_mut().extend_from_slice(data));
+            *buffer_size = n;
+        }
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn serial_write(
+        _this: *mut SerialIoProtocol,
+        buffer_size: *mut Uintn,
+        buffer: *const core::ffi::c_void,
+    ) -> Status {
+        capture(buffer_size, buffer)
+    }
+
+    unsafe extern "efiapi" fn file_write(
+        _this: *mut FileProtocol,
+        buffer_size: *mut Uintn,
+        buffer: *const core::ffi::c_void,
+    ) -> Status {
+        capture(buffer_size, buffer)
+    }
+
+    fn single_sink_logger(sink: Sink) -> Logger {
+        OUTPUT.with(|o| o.borrow_mut().clear());
+        let mut logger = Logger::new();
+        logger.sinks.push(SinkEntry {
+            sink,
+            level: LogLevel::Info,
+        });
+        logger
+    }
+
+    fn output() -> String {
+        OUTPUT.with(|o| String::from_utf8(o.borrow().clone()).unwrap())
+    }
+
+    #[test]
+    fn test_timestamp_source_microseconds() {
+        let mut protocol = TimestampProtocol {
+            get_timestamp,
+            get_properties,
+        };
+        let source = unsafe { TimestampSource::timestamp(&mut protocol) }.unwrap();
+
+        TICKS.with(|t| t.set(10_500_000));
+        assert_eq!(source.now(), Some(3_500_000));
+        // Would overflow a u64 product of ticks and 1_000_000
+        TICKS.with(|t| t.set(u64::MAX));
+        assert_eq!(source.now(), Some(6_148_914_691_236_517_205));
+        assert_eq!(TimestampSource::None.now(), None);
+
+        TICKS.with(|t| t.set(3_000_042));
+        let mut logger = memory_logger(LogLevel::Info);
+        logger.timestamp = source;
+        logger.dispatch(LogLevel::Info, "", format_args!("tick"));
+        assert_eq!(contents(&logger), "[     1000014] [INFO ] tick\r\n");
+    }
+
+    #[test]
+    fn test_serial_sink() {
+        let mut serial = SerialIoProtocol {
+            revision: EFI_SERIAL_IO_PROTOCOL_REVISION,
+            reset: unsupported!(),
+            set_attributes: unsupported!(),
+            set_control: unsupported!(),
+            get_control: unsupported!(),
+            write: serial_write,
+            read: unsupported!(),
+            mode: core::ptr::null_mut(),
+            device_type_guid: core::ptr::null(),
+        };
+        let mut logger = single_sink_logger(Sink::Serial(&mut serial));
+        logger.dispatch(LogLevel::Debug, "app", format_args!("hidden"));
+        logger.dispatch(LogLevel::Error, "app::uart", format_args!("code {}", 7));
+        assert_eq!(output(), "[ERROR] app::uart: code 7\r\n");
+
+        // After ExitBootServices() only the memory sink is written
+        logger.sinks.insert(
+            0,
+            SinkEntry {
+                sink: Sink::Memory(RingBuffer::new(256)),
+                level: LogLevel::Info,
+            },
+        );
+        guard::exit_boot_services();
+        logger.dispatch(LogLevel::Error, "app", format_args!("runtime"));
+        guard::reset();
+        assert_eq!(output(), "[ERROR] app::uart: code 7\r\n");
+        assert_eq!(contents(&logger), "[ERROR] app: runtime\r\n");
+    }
+
+    #[test]
+    fn test_file_sink() {
+        let mut file = FileProtocol {
+            revision: EFI_FILE_PROTOCOL_REVISION,
+            open: unsupported!(),
+            close: unsupported!(),
+            delete: unsupported!(),
+            read: unsupported!(),
+            write: file_write,
+            get_position: unsupported!(),
+            set_position: unsupported!(),
+            get_info: unsupported!(),
+            set_info: unsupported!(),
+            flush: unsupported!(),
+        };
+        let mut logger = single_sink_logger(Sink::File(&mut file));
+        logger.dispatch(
+            LogLevel::Info,
+            "app::fs",
+            format_args!("saved {} bytes", 512),
+        );
+        logger.dispatch(LogLevel::Warn, "", format_args!("done"));
+        assert_eq!(
+            output(),
+            "[INFO ] app::fs: saved 512 bytes\r\n[WARN ] done\r\n"
+        );
+    }
 }
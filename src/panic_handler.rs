@@ -6,6 +6,8 @@ use crate::ffi::*;
 #[cfg(not(test))]
 use crate::protocols::SimpleTextOutputProtocol;
 #[cfg(all(not(test), not(feature = "std")))]
+use core::fmt::Write;
+#[cfg(all(not(test), not(feature = "std")))]
 use core::panic::PanicInfo;
 
 #[cfg(test)]
@@ -55,7 +57,9 @@ impl core::fmt::Write for PanicWriter {
 #[cfg(not(feature = "std"))]
 pub fn panic_handler(info: &PanicInfo) -> ! {
     unsafe {
-        if let Some(console) = CONSOLE_OUT {
+        // The console is a boot services protocol
+        let console = CONSOLE_OUT.filter(|_| crate::runtime::guard::boot_services_available());
+        if let Some(console) = console {
             let mut writer = PanicWriter { console };
 
             // Set red text on black background
@@ -0,0 +1,319 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Authenticode Image Digests
+//!
+//! Computes the digest that Authenticode signatures and `db`/`dbx` hash
+//! entries cover, following the "Windows Authenticode Portable Executable
+//! Signature Format" specification, and extracts the embedded certificate
+//! table.
+
+use crate::crypto::hash::{HashAlgorithm, HashContext, HashValue};
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::pe::{PeImage, SectionHeader, IMAGE_DIRECTORY_ENTRY_SECURITY};
+use crate::protocols::security::{WinCertificate, WIN_CERT_TYPE_PKCS_SIGNED_DATA};
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const WIN_CERTIFICATE_HEADER_SIZE: usize = core::mem::size_of::<WinCertificate>();
+
+fn range(data: &[u8], start: usize, end: usize) -> Result<&[u8]> {
+    data.get(start..end).ok_or(Error::new(EFI_BAD_BUFFER_SIZE))
+}
+
+/// Compute the Authenticode digest of a PE/COFF image
+///
+/// The CheckSum field, the security data directory entry and the
+/// certificate table are left out, and sections are hashed in file order.
+/// Fails with `EFI_BAD_BUFFER_SIZE` if a section or the certificate table
+/// lies outside `image`.
+pub fn digest(image: &[u8], algorithm: HashAlgorithm) -> Result<HashValue> {
+    digest_image(&PeImage::parse(image)?, algorithm)
+}
+
+/// Compute the Authenticode digest of an already parsed image
+pub fn digest_image(image: &PeImage<'_>, algorithm: HashAlgorithm) -> Result<HashValue> {
+    let data = image.data();
+    let size_of_headers = image.size_of_headers();
+    let checksum_end = image.checksum_offset() + 4;
+    let mut context = HashContext::new(algorithm);
+
+    context.update(range(data, 0, image.checksum_offset())?);
+    match image.data_directory_offset(IMAGE_DIRECTORY_ENTRY_SECURITY) {
+        Some(entry) => {
+            context.update(range(data, checksum_end, entry)?);
+            context.update(range(data, entry + 8, size_of_headers)?);
+        }
+        None => context.update(range(data, checksum_end, size_of_headers)?),
+    }
+
+    let mut sections: Vec<SectionHeader> = image
+        .sections()
+        .filter(|section| section.size_of_raw_data != 0)
+        .collect();
+    sections.sort_by_key(|section| section.pointer_to_raw_data);
+
+    let mut hashed = size_of_headers;
+    for section in &sections {
+        let start = section.pointer_to_raw_data as usize;
+        let end = start
+            .checked_add(section.size_of_raw_data as usize)
+            .ok_or(Error::new(EFI_BAD_BUFFER_SIZE))?;
+        context.update(range(data, start, end)?);
+        hashed += section.size_of_raw_data as usize;
+    }
+
+    // Data past the last section but before the certificate table
+    if data.len() > hashed {
+        let certificate_size = image
+            .data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY)
+            .map_or(0, |dir| dir.size as usize);
+        let end = data
+            .len()
+            .checked_sub(certificate_size)
+            .filter(|&end| end >= hashed)
+            .ok_or(Error::new(EFI_BAD_BUFFER_SIZE))?;
+        context.update(range(data, hashed, end)?);
+    }
+
+    Ok(context.finalize())
+}
+
+/// An entry of the attribute certificate table
+#[derive(Debug, Copy, Clone)]
+pub struct AuthenticodeCertificate<'a> {
+    pub header: WinCertificate,
+    /// bCertificate contents, without the WIN_CERTIFICATE header
+    pub data: &'a [u8],
+}
+
+impl AuthenticodeCertificate<'_> {
+    /// Check for a PKCS#7 SignedData blob, as taken by `Pkcs7VerifyProtocol`
+    pub fn is_pkcs_signed_data(&self) -> bool {
+        self.header.certificate_type == WIN_CERT_TYPE_PKCS_SIGNED_DATA
+    }
+}
+
+/// Extract the WIN_CERTIFICATE entries of a PE/COFF image
+///
+/// Returns an empty list for unsigned images. Fails with
+/// `EFI_BAD_BUFFER_SIZE` if the table lies outside `image` or an entry
+/// length is inconsistent.
+pub fn certificates(image: &[u8]) -> Result<Vec<AuthenticodeCertificate<'_>>> {
+    image_certificates(&PeImage::parse(image)?)
+}
+
+/// Extract the WIN_CERTIFICATE entries of an already parsed image
+pub fn image_certificates<'a>(image: &PeImage<'a>) -> Result<Vec<AuthenticodeCertificate<'a>>> {
+    let bad = || Error::new(EFI_BAD_BUFFER_SIZE);
+    let mut certificates = Vec::new();
+    let Some(dir) = image.data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY) else {
+        return Ok(certificates);
+    };
+    if dir.virtual_address == 0 || dir.size == 0 {
+        return Ok(certificates);
+    }
+
+    let start = dir.virtual_address as usize;
+    let end = start.checked_add(dir.size as usize).ok_or_else(bad)?;
+    let table = range(image.data(), start, end)?;
+
+    let mut offset = 0;
+    while offset < table.len() {
+        let header = range(table, offset, offset + WIN_CERTIFICATE_HEADER_SIZE)?;
+        let header = WinCertificate {
+            length: u32::from_le_bytes(header[0..4].try_into().unwrap()),
+            revision: u16::from_le_bytes(header[4..6].try_into().unwrap()),
+            certificate_type: u16::from_le_bytes(header[6..8].try_into().unwrap()),
+        };
+        let length = header.length as usize;
+        if length <= WIN_CERTIFICATE_HEADER_SIZE {
+            return Err(bad());
+        }
+        let data_end = offset.checked_add(length).ok_or_else(bad)?;
+        certificates.push(AuthenticodeCertificate {
+            header,
+            data: range(table, offset + WIN_CERTIFICATE_HEADER_SIZE, data_end)?,
+        });
+        // Entries are quadword aligned
+        offset = data_end.checked_next_multiple_of(8).ok_or_else(bad)?;
+    }
+    Ok(certificates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::IMAGE_NT_OPTIONAL_HDR64_MAGIC;
+    use crate::protocols::security::WIN_CERT_REVISION_2_0;
+    use crate::signature_database::SignatureDatabase;
+
+    const OPTIONAL_HEADER: usize = 0x98;
+    const CHECKSUM: usize = OPTIONAL_HEADER + 64;
+    const SECURITY_ENTRY: usize = OPTIONAL_HEADER + 112 + 4 * 8;
+    const HEADERS_SIZE: usize = 0x200;
+
+    fn put_u16(image: &mut [u8], offset: usize, value: u16) {
+        image[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(image: &mut [u8], offset: usize, value: u32) {
+        image[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn certificate(data: &[u8]) -> Vec<u8> {
+        let mut entry = Vec::new();
+        entry.extend_from_slice(&(8 + data.len() as u32).to_le_bytes());
+        entry.extend_from_slice(&WIN_CERT_REVISION_2_0.to_le_bytes());
+        entry.extend_from_slice(&WIN_CERT_TYPE_PKCS_SIGNED_DATA.to_le_bytes());
+        entry.extend_from_slice(data);
+        entry.resize(entry.len().next_multiple_of(8), 0);
+        entry
+    }
+
+    /// PE32+ image with `.text` at 0x400 listed before `.data` at 0x200,
+    /// `trailing` extra bytes and then the certificate table
+    fn build_image(trailing: usize, cert_table: &[u8]) -> Vec<u8> {
+        let mut image = vec![0u8; 0x600 + trailing];
+        put_u16(&mut image, 0, 0x5A4D);
+        put_u32(&mut image, 0x3C, 0x80);
+        put_u32(&mut image, 0x80, 0x4550);
+        put_u16(&mut image, 0x84, 0x8664);
+        put_u16(&mut image, 0x86, 2);
+        put_u16(&mut image, 0x84 + 16, 240);
+        put_u16(&mut image, OPTIONAL_HEADER, IMAGE_NT_OPTIONAL_HDR64_MAGIC);
+        put_u32(&mut image, OPTIONAL_HEADER + 60, HEADERS_SIZE as u32);
+        put_u32(&mut image, CHECKSUM, 0x1234_5678);
+        put_u32(&mut image, OPTIONAL_HEADER + 108, 16);
+
+        let sections = [(b".text\0\0\0", 0x400u32), (b".data\0\0\0", 0x200u32)];
+        for (i, (name, pointer)) in sections.iter().enumerate() {
+            let entry = OPTIONAL_HEADER + 240 + i * 40;
+            image[entry..entry + 8].copy_from_slice(&name[..]);
+            put_u32(&mut image, entry + 16, 0x200);
+            put_u32(&mut image, entry + 20, *pointer);
+        }
+        for (i, byte) in image[HEADERS_SIZE..].iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+
+        if !cert_table.is_empty() {
+            let table = image.len() as u32;
+            put_u32(&mut image, SECURITY_ENTRY, table);
+            put_u32(&mut image, SECURITY_ENTRY + 4, cert_table.len() as u32);
+            image.extend_from_slice(cert_table);
+        }
+        image
+    }
+
+    fn expected_digest(image: &[u8], trailing: usize) -> HashValue {
+        let mut covered = Vec::new();
+        covered.extend_from_slice(&image[..CHECKSUM]);
+        covered.extend_from_slice(&image[CHECKSUM + 4..SECURITY_ENTRY]);
+        covered.extend_from_slice(&image[SECURITY_ENTRY + 8..HEADERS_SIZE]);
+        covered.extend_from_slice(&image[0x200..0x400]);
+        covered.extend_from_slice(&image[0x400..0x600]);
+        covered.extend_from_slice(&image[0x600..0x600 + trailing]);
+        HashAlgorithm::Sha256.digest(&covered)
+    }
+
+    #[test]
+    fn test_digest_excluded_fields() {
+        let certs = [certificate(b"first"), certificate(&[0x30; 17])].concat();
+        let image = build_image(0, &certs);
+        let hash = digest(&image, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(hash, expected_digest(&image, 0));
+
+        // Checksum and certificate contents are not covered
+        let mut resigned = image.clone();
+        put_u32(&mut resigned, CHECKSUM, 0);
+        let last = resigned.len() - 1;
+        resigned[last] ^= 0xFF;
+        assert_eq!(digest(&resigned, HashAlgorithm::Sha256).unwrap(), hash);
+
+        // An unsigned image hashes the same as the signed one
+        let unsigned = build_image(0, &[]);
+        assert_eq!(digest(&unsigned, HashAlgorithm::Sha256).unwrap(), hash);
+
+        let mut patched = image.clone();
+        patched[0x300] ^= 1;
+        assert_ne!(digest(&patched, HashAlgorithm::Sha256).unwrap(), hash);
+
+        for algorithm in [HashAlgorithm::Sha384, HashAlgorithm::Sha512] {
+            let value = digest(&image, algorithm).unwrap();
+            assert_eq!(value.as_bytes().len(), algorithm.digest_size());
+        }
+
+        // The digest is what dbx hash entries hold
+        let mut dbx = SignatureDatabase::new();
+        dbx.add_hash(Guid::null(), hash.as_bytes()).unwrap();
+        assert!(dbx.contains_hash(hash.as_bytes()));
+    }
+
+    #[test]
+    fn test_digest_trailing_data() {
+        let image = build_image(0x10, &certificate(b"sig"));
+        assert_eq!(
+            digest(&image, HashAlgorithm::Sha256).unwrap(),
+            expected_digest(&image, 0x10)
+        );
+
+        // Certificate table larger than the data after the sections
+        let mut broken = image.clone();
+        put_u32(&mut broken, SECURITY_ENTRY + 4, 0x400);
+        assert_eq!(
+            digest(&broken, HashAlgorithm::Sha256).unwrap_err().status(),
+            EFI_BAD_BUFFER_SIZE
+        );
+    }
+
+    #[test]
+    fn test_certificates() {
+        let certs = [certificate(b"first"), certificate(&[0x30; 17])].concat();
+        let image = build_image(0, &certs);
+        let entries = certificates(&image).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].data, b"first");
+        assert_eq!(entries[1].data, [0x30; 17]);
+        assert_eq!(entries[1].header.length, 8 + 17);
+        assert!(entries.iter().all(|cert| cert.is_pkcs_signed_data()));
+
+        assert!(certificates(&build_image(0, &[])).unwrap().is_empty());
+
+        let mut overlong = image.clone();
+        let table = overlong.len() - certs.len();
+        put_u32(&mut overlong, table, 0x1000);
+        assert_eq!(
+            certificates(&overlong).unwrap_err().status(),
+            EFI_BAD_BUFFER_SIZE
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_images() {
+        let image = build_image(0, &[]);
+        let status = |data: &[u8]| digest(data, HashAlgorithm::Sha256).unwrap_err().status();
+
+        let mut not_mz = image.clone();
+        not_mz[0] = b'X';
+        assert_eq!(status(&not_mz), EFI_UNSUPPORTED);
+
+        let mut bad_magic = image.clone();
+        put_u16(&mut bad_magic, OPTIONAL_HEADER, 0x0107);
+        assert_eq!(status(&bad_magic), EFI_UNSUPPORTED);
+
+        assert_eq!(status(&image[..0x100]), EFI_BAD_BUFFER_SIZE);
+
+        let mut outside = image.clone();
+        put_u32(&mut outside, OPTIONAL_HEADER + 240 + 20, 0x10_0000);
+        assert_eq!(status(&outside), EFI_BAD_BUFFER_SIZE);
+
+        let parsed = PeImage::parse(&image).unwrap();
+        assert!(parsed.is_pe32_plus());
+        assert_eq!(parsed.machine(), 0x8664);
+        assert_eq!(parsed.checksum(), 0x1234_5678);
+        let sections: Vec<SectionHeader> = parsed.sections().collect();
+        assert_eq!(sections[0].name(), b".text");
+        assert_eq!(sections[1].name(), b".data");
+    }
+}
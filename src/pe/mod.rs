@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! PE/COFF Images
+//!
+//! Read-only access to the headers of PE32 and PE32+ images as loaded by
+//! UEFI firmware.
+
+pub mod authenticode;
+
+use crate::error::{Error, Result};
+use crate::ffi::*;
+
+/// "MZ"
+pub const IMAGE_DOS_SIGNATURE: u16 = 0x5A4D;
+/// "PE\0\0"
+pub const IMAGE_NT_SIGNATURE: u32 = 0x0000_4550;
+pub const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x010B;
+pub const IMAGE_NT_OPTIONAL_HDR64_MAGIC: u16 = 0x020B;
+
+pub const IMAGE_DIRECTORY_ENTRY_EXPORT: usize = 0;
+pub const IMAGE_DIRECTORY_ENTRY_IMPORT: usize = 1;
+pub const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+pub const IMAGE_DIRECTORY_ENTRY_EXCEPTION: usize = 3;
+pub const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
+pub const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+pub const IMAGE_DIRECTORY_ENTRY_DEBUG: usize = 6;
+
+const DOS_LFANEW_OFFSET: usize = 0x3C;
+const COFF_HEADER_SIZE: usize = 20;
+const SECTION_HEADER_SIZE: usize = 40;
+const DATA_DIRECTORY_SIZE: usize = 8;
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    offset
+        .checked_add(N)
+        .and_then(|end| data.get(offset..end))
+        .map(|bytes| bytes.try_into().unwrap())
+        .ok_or(Error::new(EFI_BAD_BUFFER_SIZE))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+/// IMAGE_DATA_DIRECTORY
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DataDirectory {
+    /// RVA of the table, or a file offset for the security directory
+    pub virtual_address: u32,
+    pub size: u32,
+}
+
+/// IMAGE_SECTION_HEADER
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SectionHeader {
+    pub name: [u8; 8],
+    pub virtual_size: u32,
+    pub virtual_address: u32,
+    pub size_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+    pub characteristics: u32,
+}
+
+impl SectionHeader {
+    fn parse(data: &[u8], offset: usize) -> Result<Self> {
+        Ok(SectionHeader {
+            name: read_bytes(data, offset)?,
+            virtual_size: read_u32(data, offset + 8)?,
+            virtual_address: read_u32(data, offset + 12)?,
+            size_of_raw_data: read_u32(data, offset + 16)?,
+            pointer_to_raw_data: read_u32(data, offset + 20)?,
+            characteristics: read_u32(data, offset + 36)?,
+        })
+    }
+
+    /// Section name with trailing NULs removed
+    pub fn name(&self) -> &[u8] {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(8);
+        &self.name[..len]
+    }
+}
+
+/// A PE32 or PE32+ image in memory, in its on-disk layout
+#[derive(Debug, Copy, Clone)]
+pub struct PeImage<'a> {
+    data: &'a [u8],
+    coff_header_offset: usize,
+    optional_header_offset: usize,
+    magic: u16,
+    size_of_headers: usize,
+}
+
+impl<'a> PeImage<'a> {
+    /// Parse the headers of `data`
+    ///
+    /// Fails with `EFI_UNSUPPORTED` if the DOS, PE or optional header magic
+    /// is wrong and with `EFI_BAD_BUFFER_SIZE` if the headers or section
+    /// table do not fit.
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if read_u16(data, 0)? != IMAGE_DOS_SIGNATURE {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        let pe_offset = read_u32(data, DOS_LFANEW_OFFSET)? as usize;
+        if read_u32(data, pe_offset)? != IMAGE_NT_SIGNATURE {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+
+        let coff_header_offset = pe_offset + 4;
+        let optional_header_offset = coff_header_offset + COFF_HEADER_SIZE;
+        let magic = read_u16(data, optional_header_offset)?;
+        if magic != IMAGE_NT_OPTIONAL_HDR32_MAGIC && magic != IMAGE_NT_OPTIONAL_HDR64_MAGIC {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+
+        let image = PeImage {
+            data,
+            coff_header_offset,
+            optional_header_offset,
+            magic,
+            size_of_headers: read_u32(data, optional_header_offset + 60)? as usize,
+        };
+
+        let section_table_end = image.section_table_offset()?
+            + image.number_of_sections() as usize * SECTION_HEADER_SIZE;
+        let directories_end = image.data_directories_offset()
+            + image.number_of_rva_and_sizes()? as usize * DATA_DIRECTORY_SIZE;
+        if image.size_of_headers > data.len()
+            || section_table_end > image.size_of_headers
+            || directories_end > image.size_of_headers
+        {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        Ok(image)
+    }
+
+    /// The whole image
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Check for a PE32+ (64-bit) optional header
+    pub fn is_pe32_plus(&self) -> bool {
+        self.magic == IMAGE_NT_OPTIONAL_HDR64_MAGIC
+    }
+
+    /// IMAGE_FILE_MACHINE_* value of the COFF header
+    pub fn machine(&self) -> u16 {
+        read_u16(self.data, self.coff_header_offset).unwrap()
+    }
+
+    /// Number of entries in the section table
+    pub fn number_of_sections(&self) -> u16 {
+        read_u16(self.data, self.coff_header_offset + 2).unwrap()
+    }
+
+    /// Combined size of all headers and the section table
+    pub fn size_of_headers(&self) -> usize {
+        self.size_of_headers
+    }
+
+    /// File offset of the CheckSum field of the optional header
+    pub fn checksum_offset(&self) -> usize {
+        self.optional_header_offset + 64
+    }
+
+    /// CheckSum field of the optional header
+    pub fn checksum(&self) -> u32 {
+        read_u32(self.data, self.checksum_offset()).unwrap()
+    }
+
+    fn number_of_rva_and_sizes(&self) -> Result<u32> {
+        let offset = if self.is_pe32_plus() { 108 } else { 92 };
+        read_u32(self.data, self.optional_header_offset + offset)
+    }
+
+    fn data_directories_offset(&self) -> usize {
+        self.optional_header_offset + if self.is_pe32_plus() { 112 } else { 96 }
+    }
+
+    fn section_table_offset(&self) -> Result<usize> {
+        let size_of_optional_header = read_u16(self.data, self.coff_header_offset + 16)?;
+        Ok(self.optional_header_offset + size_of_optional_header as usize)
+    }
+
+    /// Number of data directory entries present
+    pub fn number_of_data_directories(&self) -> usize {
+        self.number_of_rva_and_sizes().unwrap() as usize
+    }
+
+    /// File offset of data directory entry `index`, if present
+    pub fn data_directory_offset(&self, index: usize) -> Option<usize> {
+        (index < self.number_of_data_directories())
+            .then(|| self.data_directories_offset() + index * DATA_DIRECTORY_SIZE)
+    }
+
+    /// Data directory entry `index`, if present
+    pub fn data_directory(&self, index: usize) -> Option<DataDirectory> {
+        let offset = self.data_directory_offset(index)?;
+        Some(DataDirectory {
+            virtual_address: read_u32(self.data, offset).unwrap(),
+            size: read_u32(self.data, offset + 4).unwrap(),
+        })
+    }
+
+    /// Section headers in section table order
+    pub fn sections(&self) -> impl Iterator<Item = SectionHeader> + 'a {
+        let data = self.data;
+        let table = self.section_table_offset().unwrap();
+        (0..self.number_of_sections() as usize)
+            .map(move |i| SectionHeader::parse(data, table + i * SECTION_HEADER_SIZE).unwrap())
+    }
+}
@@ -1,15 +1,13 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Block I/O Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_BLOCK_IO_PROTOCOL_GUID
-pub const BLOCK_IO_PROTOCOL_GUID: Guid = Guid::new(
-    0x964e5b21,
-    0x6459,
-    0x11d2,
-    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const BLOCK_IO_PROTOCOL_GUID: Guid = guid!("964e5b21-6459-11d2-8e39-00a0c969723b");
 
 /// EFI_BLOCK_IO_PROTOCOL_REVISION
 pub const EFI_BLOCK_IO_PROTOCOL_REVISION: Uint64 = 0x00010000;
@@ -62,10 +60,14 @@ pub struct BlockIoProtocol {
     pub flush_blocks: unsafe extern "efiapi" fn(this: *mut BlockIoProtocol) -> Status,
 }
 
+unsafe impl Protocol for BlockIoProtocol {
+    const GUID: Guid = BLOCK_IO_PROTOCOL_GUID;
+}
+
 impl BlockIoProtocol {
     /// Reset the block device
-    pub unsafe fn reset(&mut self, extended_verification: bool) -> Status {
-        (self.reset)(self, extended_verification as Boolean)
+    pub unsafe fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        (self.reset)(self, extended_verification as Boolean).to_result()
     }
 
     /// Read blocks
@@ -75,8 +77,8 @@ impl BlockIoProtocol {
         lba: u64,
         buffer_size: usize,
         buffer: *mut core::ffi::c_void,
-    ) -> Status {
-        (self.read_blocks)(self, media_id, lba, buffer_size, buffer)
+    ) -> Result<()> {
+        (self.read_blocks)(self, media_id, lba, buffer_size, buffer).to_result()
     }
 
     /// Write blocks
@@ -86,13 +88,13 @@ impl BlockIoProtocol {
         lba: u64,
         buffer_size: usize,
         buffer: *const core::ffi::c_void,
-    ) -> Status {
-        (self.write_blocks)(self, media_id, lba, buffer_size, buffer)
+    ) -> Result<()> {
+        (self.write_blocks)(self, media_id, lba, buffer_size, buffer).to_result()
     }
 
     /// Flush blocks
-    pub unsafe fn flush_blocks(&mut self) -> Status {
-        (self.flush_blocks)(self)
+    pub unsafe fn flush_blocks(&mut self) -> Result<()> {
+        (self.flush_blocks)(self).to_result()
     }
 
     /// Get media information
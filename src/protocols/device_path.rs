@@ -1,15 +1,17 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Device Path Protocol
 
+use crate::error::{Error, Result};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
+use crate::string::CStr16;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
 
 /// EFI_DEVICE_PATH_PROTOCOL_GUID
-pub const DEVICE_PATH_PROTOCOL_GUID: Guid = Guid::new(
-    0x09576e91,
-    0x6d3f,
-    0x11d2,
-    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const DEVICE_PATH_PROTOCOL_GUID: Guid = guid!("09576e91-6d3f-11d2-8e39-00a0c969723b");
 
 /// EFI_DEVICE_PATH_PROTOCOL
 #[repr(C)]
@@ -20,6 +22,10 @@ pub struct DevicePathProtocol {
     pub length: [Uint8; 2],
 }
 
+unsafe impl Protocol for DevicePathProtocol {
+    const GUID: Guid = DEVICE_PATH_PROTOCOL_GUID;
+}
+
 // Device path types
 pub const HARDWARE_DEVICE_PATH: u8 = 0x01;
 pub const ACPI_DEVICE_PATH: u8 = 0x02;
@@ -125,3 +131,96 @@ pub struct HardDriveDevicePath {
     pub mbr_type: Uint8,
     pub signature_type: Uint8,
 }
+
+/// Owned copy of a single device path node
+///
+/// `data` holds the bytes after the 4-byte node header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevicePathNode {
+    pub node_type: u8,
+    pub sub_type: u8,
+    pub data: Vec<u8>,
+}
+
+impl DevicePathNode {
+    const HEADER_SIZE: usize = core::mem::size_of::<DevicePathProtocol>();
+
+    /// End of the entire device path
+    pub fn end_entire() -> Self {
+        DevicePathNode {
+            node_type: END_DEVICE_PATH_TYPE,
+            sub_type: END_ENTIRE_DEVICE_PATH_SUBTYPE,
+            data: Vec::new(),
+        }
+    }
+
+    /// Media file path node, e.g. `\EFI\BOOT\BOOTX64.EFI`
+    pub fn file_path(path: &CStr16) -> Self {
+        let data = path
+            .as_slice_with_nul()
+            .iter()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        DevicePathNode {
+            node_type: MEDIA_DEVICE_PATH,
+            sub_type: MEDIA_FILEPATH_DP,
+            data,
+        }
+    }
+
+    /// Check if this is an end node
+    pub fn is_end(&self) -> bool {
+        self.node_type == END_DEVICE_PATH_TYPE
+    }
+
+    /// Check if this is the end of the entire device path
+    pub fn is_end_entire(&self) -> bool {
+        self.is_end() && self.sub_type == END_ENTIRE_DEVICE_PATH_SUBTYPE
+    }
+
+    /// Length of the node including its header
+    pub fn length(&self) -> usize {
+        Self::HEADER_SIZE + self.data.len()
+    }
+
+    /// Append the node in its on-disk form
+    ///
+    /// Fails with `EFI_BAD_BUFFER_SIZE` if the node is longer than 64 KiB.
+    pub fn write_to(&self, out: &mut Vec<u8>) -> Result<()> {
+        let length = u16::try_from(self.length()).map_err(|_| Error::new(EFI_BAD_BUFFER_SIZE))?;
+        out.push(self.node_type);
+        out.push(self.sub_type);
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&self.data);
+        Ok(())
+    }
+
+    /// Parse a packed list of device path nodes
+    ///
+    /// The nodes must exactly fill `bytes` and the last one must end the
+    /// entire path; anything else fails with `EFI_BAD_BUFFER_SIZE`. End
+    /// nodes are kept, so multi-instance paths survive a round trip.
+    pub fn parse_list(bytes: &[u8]) -> Result<Vec<DevicePathNode>> {
+        let mut nodes = Vec::new();
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            if rest.len() < Self::HEADER_SIZE {
+                return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+            }
+            let length = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+            if length < Self::HEADER_SIZE || length > rest.len() {
+                return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+            }
+            nodes.push(DevicePathNode {
+                node_type: rest[0],
+                sub_type: rest[1],
+                data: rest[Self::HEADER_SIZE..length].to_vec(),
+            });
+            rest = &rest[length..];
+        }
+        match nodes.last() {
+            Some(node) if node.is_end_entire() => Ok(nodes),
+            _ => Err(Error::new(EFI_BAD_BUFFER_SIZE)),
+        }
+    }
+}
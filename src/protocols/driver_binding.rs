@@ -1,7 +1,10 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Driver Binding Protocol - Driver Model Support
 
+use crate::error::{Error, Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
@@ -10,12 +13,7 @@ use alloc::vec::Vec;
 use std::vec::Vec;
 
 /// EFI_DRIVER_BINDING_PROTOCOL_GUID
-pub const DRIVER_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0x18A031AB,
-    0xB443,
-    0x4D1A,
-    [0xA5, 0xC0, 0x0C, 0x09, 0x26, 0x1E, 0x9F, 0x71],
-);
+pub const DRIVER_BINDING_PROTOCOL_GUID: Guid = guid!("18a031ab-b443-4d1a-a5c0-0c09261e9f71");
 
 /// EFI_DRIVER_BINDING_PROTOCOL
 #[repr(C)]
@@ -41,14 +39,18 @@ pub struct DriverBindingProtocol {
     pub driver_binding_handle: *mut Handle,
 }
 
+unsafe impl Protocol for DriverBindingProtocol {
+    const GUID: Guid = DRIVER_BINDING_PROTOCOL_GUID;
+}
+
 impl DriverBindingProtocol {
     /// Test if this driver supports the given controller
     pub unsafe fn supported(
         &mut self,
         controller_handle: *mut Handle,
         remaining_device_path: *mut core::ffi::c_void,
-    ) -> Status {
-        (self.supported)(self, controller_handle, remaining_device_path)
+    ) -> Result<()> {
+        (self.supported)(self, controller_handle, remaining_device_path).to_result()
     }
 
     /// Start managing a controller
@@ -56,8 +58,8 @@ impl DriverBindingProtocol {
         &mut self,
         controller_handle: *mut Handle,
         remaining_device_path: *mut core::ffi::c_void,
-    ) -> Status {
-        (self.start)(self, controller_handle, remaining_device_path)
+    ) -> Result<()> {
+        (self.start)(self, controller_handle, remaining_device_path).to_result()
     }
 
     /// Stop managing a controller
@@ -65,31 +67,22 @@ impl DriverBindingProtocol {
         &mut self,
         controller_handle: *mut Handle,
         child_handles: &[*mut Handle],
-    ) -> Status {
+    ) -> Result<()> {
         (self.stop)(
             self,
             controller_handle,
             child_handles.len(),
             child_handles.as_ptr() as *mut *mut Handle,
         )
+        .to_result()
     }
 }
 
 /// EFI_COMPONENT_NAME_PROTOCOL_GUID
-pub const COMPONENT_NAME_PROTOCOL_GUID: Guid = Guid::new(
-    0x107A772C,
-    0xD5E1,
-    0x11D4,
-    [0x9A, 0x46, 0x00, 0x90, 0x27, 0x3F, 0xC1, 0x4D],
-);
+pub const COMPONENT_NAME_PROTOCOL_GUID: Guid = guid!("107a772c-d5e1-11d4-9a46-0090273fc14d");
 
 /// EFI_COMPONENT_NAME2_PROTOCOL_GUID
-pub const COMPONENT_NAME2_PROTOCOL_GUID: Guid = Guid::new(
-    0x6A7A5CFF,
-    0xE8D9,
-    0x4F70,
-    [0xBA, 0xDA, 0x75, 0xAB, 0x30, 0x25, 0xCE, 0x14],
-);
+pub const COMPONENT_NAME2_PROTOCOL_GUID: Guid = guid!("6a7a5cff-e8d9-4f70-bada-75ab3025ce14");
 
 /// EFI_COMPONENT_NAME2_PROTOCOL
 #[repr(C)]
@@ -109,17 +102,16 @@ pub struct ComponentName2Protocol {
     pub supported_languages: *const Char8,
 }
 
+unsafe impl Protocol for ComponentName2Protocol {
+    const GUID: Guid = COMPONENT_NAME2_PROTOCOL_GUID;
+}
+
 impl ComponentName2Protocol {
     /// Get the driver name
-    pub unsafe fn get_driver_name(&mut self, language: &[u8]) -> Result<*mut Char16, Status> {
+    pub unsafe fn get_driver_name(&mut self, language: &[u8]) -> Result<*mut Char16> {
         let mut driver_name = core::ptr::null_mut();
-        let status = (self.get_driver_name)(self, language.as_ptr(), &mut driver_name);
-
-        if status == EFI_SUCCESS {
-            Ok(driver_name)
-        } else {
-            Err(status)
-        }
+        (self.get_driver_name)(self, language.as_ptr(), &mut driver_name).to_result()?;
+        Ok(driver_name)
     }
 
     /// Get the controller name
@@ -128,39 +120,25 @@ impl ComponentName2Protocol {
         controller_handle: *mut Handle,
         child_handle: *mut Handle,
         language: &[u8],
-    ) -> Result<*mut Char16, Status> {
+    ) -> Result<*mut Char16> {
         let mut controller_name = core::ptr::null_mut();
-        let status = (self.get_controller_name)(
+        (self.get_controller_name)(
             self,
             controller_handle,
             child_handle,
             language.as_ptr(),
             &mut controller_name,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(controller_name)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(controller_name)
     }
 }
 
 /// EFI_DRIVER_DIAGNOSTICS_PROTOCOL_GUID
-pub const DRIVER_DIAGNOSTICS_PROTOCOL_GUID: Guid = Guid::new(
-    0x0784924F,
-    0xE296,
-    0x11D4,
-    [0x9A, 0x49, 0x00, 0x90, 0x27, 0x3F, 0xC1, 0x4D],
-);
+pub const DRIVER_DIAGNOSTICS_PROTOCOL_GUID: Guid = guid!("0784924f-e296-11d4-9a49-0090273fc14d");
 
 /// EFI_DRIVER_DIAGNOSTICS2_PROTOCOL_GUID
-pub const DRIVER_DIAGNOSTICS2_PROTOCOL_GUID: Guid = Guid::new(
-    0x4D330321,
-    0x025F,
-    0x4AAC,
-    [0x90, 0xD8, 0x5E, 0xD9, 0x00, 0x17, 0x3B, 0x63],
-);
+pub const DRIVER_DIAGNOSTICS2_PROTOCOL_GUID: Guid = guid!("4d330321-025f-4aac-90d8-5ed900173b63");
 
 /// Diagnostics Type
 #[repr(u32)]
@@ -187,6 +165,10 @@ pub struct DriverDiagnostics2Protocol {
     pub supported_languages: *const Char8,
 }
 
+unsafe impl Protocol for DriverDiagnostics2Protocol {
+    const GUID: Guid = DRIVER_DIAGNOSTICS2_PROTOCOL_GUID;
+}
+
 impl DriverDiagnostics2Protocol {
     /// Run diagnostics on a controller
     pub unsafe fn run_diagnostics(
@@ -195,15 +177,12 @@ impl DriverDiagnostics2Protocol {
         child_handle: *mut Handle,
         diagnostic_type: DiagnosticsType,
         language: &[u8],
-    ) -> Result<(Guid, Vec<u16>), Status> {
-        #[cfg(not(feature = "std"))]
-        use alloc::vec::Vec;
-
+    ) -> Result<(Guid, Vec<u16>)> {
         let mut error_type = core::ptr::null_mut();
         let mut buffer_size = 0;
         let mut buffer = core::ptr::null_mut();
 
-        let status = (self.run_diagnostics)(
+        (self.run_diagnostics)(
             self,
             controller_handle,
             child_handle,
@@ -212,25 +191,24 @@ impl DriverDiagnostics2Protocol {
             &mut error_type,
             &mut buffer_size,
             &mut buffer,
-        );
-
-        if status == EFI_SUCCESS && !error_type.is_null() && !buffer.is_null() {
-            let guid = *error_type;
-            let result_buffer = core::slice::from_raw_parts(buffer, buffer_size).to_vec();
-            Ok((guid, result_buffer))
-        } else {
-            Err(status)
+        )
+        .to_result()?;
+
+        if error_type.is_null() || buffer.is_null() {
+            return Err(Error::new(EFI_DEVICE_ERROR));
         }
+
+        let guid = *error_type;
+        let result_buffer = core::slice::from_raw_parts(buffer, buffer_size).to_vec();
+        Ok((guid, result_buffer))
     }
 }
 
 /// EFI_DRIVER_CONFIGURATION_PROTOCOL_GUID
-pub const DRIVER_CONFIGURATION_PROTOCOL_GUID: Guid = Guid::new(
-    0x107A772B,
-    0xD5E1,
-    0x11D4,
-    [0x9A, 0x46, 0x00, 0x90, 0x27, 0x3F, 0xC1, 0x4D],
-);
+pub const DRIVER_CONFIGURATION_PROTOCOL_GUID: Guid = guid!("107a772b-d5e1-11d4-9a46-0090273fc14d");
+
+/// EFI_DRIVER_CONFIGURATION2_PROTOCOL_GUID
+pub const DRIVER_CONFIGURATION2_PROTOCOL_GUID: Guid = guid!("bfd7dc1d-24f1-40d9-82e7-2e09bb6b4ebe");
 
 /// Configuration Action
 #[repr(u32)]
@@ -268,6 +246,10 @@ pub struct DriverConfiguration2Protocol {
     pub supported_languages: *const Char8,
 }
 
+unsafe impl Protocol for DriverConfiguration2Protocol {
+    const GUID: Guid = DRIVER_CONFIGURATION2_PROTOCOL_GUID;
+}
+
 impl DriverConfiguration2Protocol {
     /// Set driver configuration options
     pub unsafe fn set_options(
@@ -275,22 +257,18 @@ impl DriverConfiguration2Protocol {
         controller_handle: *mut Handle,
         child_handle: *mut Handle,
         language: &[u8],
-    ) -> Result<ConfigurationAction, Status> {
+    ) -> Result<ConfigurationAction> {
         let mut action_required = ConfigurationAction::ConfigurationActionNone;
 
-        let status = (self.set_options)(
+        (self.set_options)(
             self,
             controller_handle,
             child_handle,
             language.as_ptr(),
             &mut action_required,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(action_required)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(action_required)
     }
 
     /// Check if options are valid
@@ -298,8 +276,8 @@ impl DriverConfiguration2Protocol {
         &mut self,
         controller_handle: *mut Handle,
         child_handle: *mut Handle,
-    ) -> Status {
-        (self.options_valid)(self, controller_handle, child_handle)
+    ) -> Result<()> {
+        (self.options_valid)(self, controller_handle, child_handle).to_result()
     }
 
     /// Force default configuration
@@ -308,21 +286,17 @@ impl DriverConfiguration2Protocol {
         controller_handle: *mut Handle,
         child_handle: *mut Handle,
         default_type: u32,
-    ) -> Result<ConfigurationAction, Status> {
+    ) -> Result<ConfigurationAction> {
         let mut action_required = ConfigurationAction::ConfigurationActionNone;
 
-        let status = (self.force_defaults)(
+        (self.force_defaults)(
             self,
             controller_handle,
             child_handle,
             default_type,
             &mut action_required,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(action_required)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(action_required)
     }
 }
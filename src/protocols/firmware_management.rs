@@ -1,21 +1,20 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Firmware Management Protocol
 
+use crate::error::{Error, Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
+use crate::string::{CStr16, CString16};
 
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 
 #[cfg(feature = "std")]
-use std::vec::Vec;
+use std::{vec, vec::Vec};
 
 /// EFI_FIRMWARE_MANAGEMENT_PROTOCOL_GUID
-pub const FIRMWARE_MANAGEMENT_PROTOCOL_GUID: Guid = Guid::new(
-    0x86c77a67,
-    0x0b97,
-    0x4633,
-    [0xa1, 0x87, 0x49, 0x10, 0x4d, 0x06, 0x85, 0xc7],
-);
+pub const FIRMWARE_MANAGEMENT_PROTOCOL_GUID: Guid = guid!("86c77a67-0b97-4633-a187-49104d0685c7");
 
 /// Image Attribute Definitions
 pub const IMAGE_ATTRIBUTE_IMAGE_UPDATABLE: u64 = 0x0000000000000001;
@@ -40,6 +39,87 @@ pub const PACKAGE_ATTRIBUTE_VERSION_UPDATABLE: u32 = 0x00000001;
 pub const PACKAGE_ATTRIBUTE_RESET_REQUIRED: u32 = 0x00000002;
 pub const PACKAGE_ATTRIBUTE_AUTHENTICATION_REQUIRED: u32 = 0x00000004;
 
+/// Highest EFI_FIRMWARE_IMAGE_DESCRIPTOR version this crate understands
+pub const EFI_FIRMWARE_IMAGE_DESCRIPTOR_VERSION: u32 = 4;
+
+/// Dependency Expression Opcodes
+pub const EFI_FMP_DEP_PUSH_GUID: u8 = 0x00;
+pub const EFI_FMP_DEP_PUSH_VERSION: u8 = 0x01;
+pub const EFI_FMP_DEP_VERSION_STR: u8 = 0x02;
+pub const EFI_FMP_DEP_AND: u8 = 0x03;
+pub const EFI_FMP_DEP_OR: u8 = 0x04;
+pub const EFI_FMP_DEP_NOT: u8 = 0x05;
+pub const EFI_FMP_DEP_TRUE: u8 = 0x06;
+pub const EFI_FMP_DEP_FALSE: u8 = 0x07;
+pub const EFI_FMP_DEP_EQ: u8 = 0x08;
+pub const EFI_FMP_DEP_NE: u8 = 0x09;
+pub const EFI_FMP_DEP_GT: u8 = 0x0A;
+pub const EFI_FMP_DEP_GTE: u8 = 0x0B;
+pub const EFI_FMP_DEP_LT: u8 = 0x0C;
+pub const EFI_FMP_DEP_LTE: u8 = 0x0D;
+pub const EFI_FMP_DEP_END: u8 = 0x0E;
+
+/// Last Attempt Status Values
+pub const LAST_ATTEMPT_STATUS_SUCCESS: u32 = 0x00000000;
+pub const LAST_ATTEMPT_STATUS_ERROR_UNSUCCESSFUL: u32 = 0x00000001;
+pub const LAST_ATTEMPT_STATUS_ERROR_INSUFFICIENT_RESOURCES: u32 = 0x00000002;
+pub const LAST_ATTEMPT_STATUS_ERROR_INCORRECT_VERSION: u32 = 0x00000003;
+pub const LAST_ATTEMPT_STATUS_ERROR_INVALID_FORMAT: u32 = 0x00000004;
+pub const LAST_ATTEMPT_STATUS_ERROR_AUTH_ERROR: u32 = 0x00000005;
+pub const LAST_ATTEMPT_STATUS_ERROR_PWR_EVT_AC: u32 = 0x00000006;
+pub const LAST_ATTEMPT_STATUS_ERROR_PWR_EVT_BATT: u32 = 0x00000007;
+pub const LAST_ATTEMPT_STATUS_ERROR_UNSATISFIED_DEPENDENCIES: u32 = 0x00000008;
+pub const LAST_ATTEMPT_STATUS_ERROR_UNSUCCESSFUL_VENDOR_RANGE_MIN: u32 = 0x00001000;
+pub const LAST_ATTEMPT_STATUS_ERROR_UNSUCCESSFUL_VENDOR_RANGE_MAX: u32 = 0x00004000;
+
+/// Outcome of the last firmware update attempt
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LastAttemptStatus {
+    Success,
+    Unsuccessful,
+    InsufficientResources,
+    IncorrectVersion,
+    InvalidFormat,
+    AuthError,
+    PowerEventAc,
+    PowerEventBattery,
+    UnsatisfiedDependencies,
+    /// Vendor-defined failure in the reserved vendor range
+    Vendor(u32),
+    Unknown(u32),
+}
+
+impl LastAttemptStatus {
+    /// Decode a raw LAST_ATTEMPT_STATUS value
+    pub fn from_raw(raw: u32) -> Self {
+        match raw {
+            LAST_ATTEMPT_STATUS_SUCCESS => LastAttemptStatus::Success,
+            LAST_ATTEMPT_STATUS_ERROR_UNSUCCESSFUL => LastAttemptStatus::Unsuccessful,
+            LAST_ATTEMPT_STATUS_ERROR_INSUFFICIENT_RESOURCES => {
+                LastAttemptStatus::InsufficientResources
+            }
+            LAST_ATTEMPT_STATUS_ERROR_INCORRECT_VERSION => LastAttemptStatus::IncorrectVersion,
+            LAST_ATTEMPT_STATUS_ERROR_INVALID_FORMAT => LastAttemptStatus::InvalidFormat,
+            LAST_ATTEMPT_STATUS_ERROR_AUTH_ERROR => LastAttemptStatus::AuthError,
+            LAST_ATTEMPT_STATUS_ERROR_PWR_EVT_AC => LastAttemptStatus::PowerEventAc,
+            LAST_ATTEMPT_STATUS_ERROR_PWR_EVT_BATT => LastAttemptStatus::PowerEventBattery,
+            LAST_ATTEMPT_STATUS_ERROR_UNSATISFIED_DEPENDENCIES => {
+                LastAttemptStatus::UnsatisfiedDependencies
+            }
+            LAST_ATTEMPT_STATUS_ERROR_UNSUCCESSFUL_VENDOR_RANGE_MIN
+                ..=LAST_ATTEMPT_STATUS_ERROR_UNSUCCESSFUL_VENDOR_RANGE_MAX => {
+                LastAttemptStatus::Vendor(raw)
+            }
+            _ => LastAttemptStatus::Unknown(raw),
+        }
+    }
+
+    /// Check whether the last attempt succeeded
+    pub fn is_success(self) -> bool {
+        self == LastAttemptStatus::Success
+    }
+}
+
 /// EFI_FIRMWARE_IMAGE_DESCRIPTOR
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -58,8 +138,33 @@ pub struct FirmwareImageDescriptor {
     pub last_attempt_version: Uint32,
     pub last_attempt_status: Uint32,
     pub hardware_instance: Uint64,
+    /// EFI_FIRMWARE_IMAGE_DEP (version 4)
+    pub dependencies: *const Uint8,
 }
 
+// Descriptor sizes up to the first field each version added
+const DESCRIPTOR_V1_SIZE: usize =
+    core::mem::offset_of!(FirmwareImageDescriptor, lowest_supported_image_version);
+const DESCRIPTOR_V2_SIZE: usize =
+    core::mem::offset_of!(FirmwareImageDescriptor, last_attempt_version);
+const DESCRIPTOR_V3_SIZE: usize = core::mem::offset_of!(FirmwareImageDescriptor, dependencies);
+const DESCRIPTOR_V4_SIZE: usize = core::mem::size_of::<FirmwareImageDescriptor>();
+
+// Bytes of a descriptor defined by `version`
+fn descriptor_size_for(version: u32) -> Option<usize> {
+    match version {
+        0 => None,
+        1 => Some(DESCRIPTOR_V1_SIZE),
+        2 => Some(DESCRIPTOR_V2_SIZE),
+        3 => Some(DESCRIPTOR_V3_SIZE),
+        _ => Some(DESCRIPTOR_V4_SIZE),
+    }
+}
+
+// Longest dependency expression walked before giving up on a missing
+// EFI_FMP_DEP_END or VERSION_STR terminator
+const MAX_DEPENDENCIES_SIZE: usize = 0x1000;
+
 /// EFI_FIRMWARE_MANAGEMENT_PROTOCOL
 #[repr(C)]
 pub struct FirmwareManagementProtocol {
@@ -113,80 +218,107 @@ pub struct FirmwareManagementProtocol {
     ) -> Status,
 }
 
-impl FirmwareManagementProtocol {
-    /// Get firmware image information
-    pub unsafe fn get_image_info(
-        &mut self,
-    ) -> Result<(Vec<FirmwareImageDescriptor>, u32, u32), Status> {
-        #[cfg(not(feature = "std"))]
-        use alloc::vec::Vec;
+// The progress callback of SetImage() takes no context argument, so the
+// closure of the set_image_with_progress() call in flight is parked here.
+static mut PROGRESS: Option<*mut dyn FnMut(usize)> = None;
+
+unsafe extern "efiapi" fn progress_trampoline(completion: Uintn) -> Status {
+    if let Some(progress) = *core::ptr::addr_of!(PROGRESS) {
+        (*progress)(completion);
+    }
+    EFI_SUCCESS
+}
+
+unsafe impl Protocol for FirmwareManagementProtocol {
+    const GUID: Guid = FIRMWARE_MANAGEMENT_PROTOCOL_GUID;
+}
 
+impl FirmwareManagementProtocol {
+    // Call GetImageInfo() into a buffer of the size it asks for
+    unsafe fn image_info_buffer(&mut self) -> Result<ImageInfoBuffer> {
         let mut image_info_size = 0;
-        let mut descriptor_version = 0u32;
-        let mut descriptor_count = 0u8;
-        let mut descriptor_size = 0;
-        let mut package_version = 0u32;
-        let mut package_version_name = core::ptr::null_mut();
+        let mut info = ImageInfoBuffer {
+            buffer: Vec::new(),
+            descriptor_version: 0,
+            descriptor_count: 0,
+            descriptor_size: 0,
+            package_version: 0,
+            package_version_name: core::ptr::null_mut(),
+        };
 
-        // First call to get size
         let status = (self.get_image_info)(
             self,
             &mut image_info_size,
             core::ptr::null_mut(),
-            &mut descriptor_version,
-            &mut descriptor_count,
-            &mut descriptor_size,
-            &mut package_version,
-            &mut package_version_name,
+            &mut info.descriptor_version,
+            &mut info.descriptor_count,
+            &mut info.descriptor_size,
+            &mut info.package_version,
+            &mut info.package_version_name,
         );
-
-        if status != EFI_BUFFER_TOO_SMALL && status != EFI_SUCCESS {
-            return Err(status);
+        if status == EFI_SUCCESS {
+            info.descriptor_count = 0;
+            return Ok(info);
+        }
+        if status != EFI_BUFFER_TOO_SMALL {
+            return Err(Error::new(status));
         }
 
-        // Allocate buffer
-        let mut descriptors = Vec::with_capacity(descriptor_count as usize);
-        descriptors.resize(descriptor_count as usize, core::mem::zeroed());
-
-        // Second call to get data
-        let status = (self.get_image_info)(
+        // Descriptors hold u64 fields, so the buffer must be 8-byte aligned
+        info.buffer = vec![0u64; image_info_size.div_ceil(8)];
+        (self.get_image_info)(
             self,
             &mut image_info_size,
-            descriptors.as_mut_ptr(),
-            &mut descriptor_version,
-            &mut descriptor_count,
-            &mut descriptor_size,
-            &mut package_version,
-            &mut package_version_name,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok((descriptors, descriptor_version, package_version))
-        } else {
-            Err(status)
+            info.buffer.as_mut_ptr() as *mut FirmwareImageDescriptor,
+            &mut info.descriptor_version,
+            &mut info.descriptor_count,
+            &mut info.descriptor_size,
+            &mut info.package_version,
+            &mut info.package_version_name,
+        )
+        .to_result()?;
+
+        let minimum =
+            descriptor_size_for(info.descriptor_version).ok_or(Error::new(EFI_UNSUPPORTED))?;
+        let available = image_info_size.min(info.buffer.len() * 8);
+        if info.descriptor_size < minimum
+            || info.descriptor_count as usize * info.descriptor_size > available
+        {
+            return Err(Error::new(EFI_DEVICE_ERROR));
         }
+        Ok(info)
+    }
+
+    /// Get firmware image information
+    ///
+    /// Descriptors are copied out at the firmware's `descriptor_size` stride.
+    /// Only the fields the reported descriptor version defines are copied;
+    /// later fields are zeroed, whatever padding the driver left there.
+    pub unsafe fn get_image_info(&mut self) -> Result<(Vec<FirmwareImageDescriptor>, u32, u32)> {
+        let info = self.image_info_buffer()?;
+        let descriptors = (0..info.descriptor_count as usize)
+            .map(|i| info.descriptor(i))
+            .collect();
+        Ok((descriptors, info.descriptor_version, info.package_version))
+    }
+
+    /// Get firmware image information as owned, version-independent entries
+    pub unsafe fn image_descriptors(&mut self) -> Result<FirmwareImageDescriptors> {
+        FirmwareImageDescriptors::new(&self.image_info_buffer()?)
     }
 
     /// Get a firmware image
-    pub unsafe fn get_image(
-        &mut self,
-        image_index: u8,
-        buffer: &mut [u8],
-    ) -> Result<usize, Status> {
+    pub unsafe fn get_image(&mut self, image_index: u8, buffer: &mut [u8]) -> Result<usize> {
         let mut image_size = buffer.len();
 
-        let status = (self.get_image)(
+        (self.get_image)(
             self,
             image_index,
             buffer.as_mut_ptr() as *mut core::ffi::c_void,
             &mut image_size,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(image_size)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(image_size)
     }
 
     /// Set (update) a firmware image
@@ -196,12 +328,12 @@ impl FirmwareManagementProtocol {
         image: &[u8],
         vendor_code: Option<&[u8]>,
         progress_callback: Option<unsafe extern "efiapi" fn(completion: Uintn) -> Status>,
-    ) -> Result<(), Status> {
+    ) -> Result<()> {
         let mut abort_reason = core::ptr::null_mut();
 
         let vendor_ptr = vendor_code.map_or(core::ptr::null(), |v| v.as_ptr() as *const _);
 
-        let status = (self.set_image)(
+        (self.set_image)(
             self,
             image_index,
             image.as_ptr() as *const core::ffi::c_void,
@@ -209,56 +341,67 @@ impl FirmwareManagementProtocol {
             vendor_ptr,
             progress_callback,
             &mut abort_reason,
-        );
+        )
+        .to_result()
+    }
 
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+    /// Set (update) a firmware image, reporting progress to a closure
+    ///
+    /// `progress` receives the completion percentage (1-100) each time the
+    /// driver reports progress.
+    pub unsafe fn set_image_with_progress<F: FnMut(usize)>(
+        &mut self,
+        image_index: u8,
+        image: &[u8],
+        vendor_code: Option<&[u8]>,
+        mut progress: F,
+    ) -> Result<()> {
+        let callback: &mut (dyn FnMut(usize) + '_) = &mut progress;
+        // Erase the lifetime for the slot; it is cleared again before
+        // `progress` goes out of scope.
+        let callback = core::mem::transmute::<
+            *mut (dyn FnMut(usize) + '_),
+            *mut (dyn FnMut(usize) + 'static),
+        >(callback);
+        let previous = core::ptr::replace(core::ptr::addr_of_mut!(PROGRESS), Some(callback));
+        let result = self.set_image(image_index, image, vendor_code, Some(progress_trampoline));
+        core::ptr::write(core::ptr::addr_of_mut!(PROGRESS), previous);
+        result
     }
 
     /// Check if an image is valid for update
-    pub unsafe fn check_image(&mut self, image_index: u8, image: &[u8]) -> Result<u32, Status> {
+    pub unsafe fn check_image(&mut self, image_index: u8, image: &[u8]) -> Result<u32> {
         let mut image_updatable = 0u32;
 
-        let status = (self.check_image)(
+        (self.check_image)(
             self,
             image_index,
             image.as_ptr() as *const core::ffi::c_void,
             image.len(),
             &mut image_updatable,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(image_updatable)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(image_updatable)
     }
 
     /// Get package information
-    pub unsafe fn get_package_info(&mut self) -> Result<(u32, u64, u64), Status> {
+    pub unsafe fn get_package_info(&mut self) -> Result<(u32, u64, u64)> {
         let mut package_version = 0u32;
         let mut package_version_name = core::ptr::null_mut();
         let mut package_version_name_max_len = 0u32;
         let mut attributes_supported = 0u64;
         let mut attributes_setting = 0u64;
 
-        let status = (self.get_package_info)(
+        (self.get_package_info)(
             self,
             &mut package_version,
             &mut package_version_name,
             &mut package_version_name_max_len,
             &mut attributes_supported,
             &mut attributes_setting,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok((package_version, attributes_supported, attributes_setting))
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok((package_version, attributes_supported, attributes_setting))
     }
 }
 
@@ -287,4 +430,392 @@ impl FirmwareImageDescriptor {
     pub fn is_uefi_image(&self) -> bool {
         (self.attributes_setting & IMAGE_ATTRIBUTE_UEFI_IMAGE) != 0
     }
+
+    /// Decode the status of the last update attempt
+    pub fn last_attempt_status(&self) -> LastAttemptStatus {
+        LastAttemptStatus::from_raw(self.last_attempt_status)
+    }
+}
+
+struct ImageInfoBuffer {
+    buffer: Vec<u64>,
+    descriptor_version: u32,
+    descriptor_count: u8,
+    descriptor_size: usize,
+    package_version: u32,
+    package_version_name: *mut Char16,
+}
+
+impl ImageInfoBuffer {
+    // Descriptor `index`, with fields its version does not define zeroed
+    //
+    // `image_info_buffer` checked the version and that every descriptor
+    // lies within the buffer.
+    fn descriptor(&self, index: usize) -> FirmwareImageDescriptor {
+        let start = index * self.descriptor_size;
+        let len = descriptor_size_for(self.descriptor_version)
+            .unwrap_or(0)
+            .min(self.descriptor_size);
+        assert!(start + len <= self.buffer.len() * 8);
+        unsafe {
+            let mut descriptor: FirmwareImageDescriptor = core::mem::zeroed();
+            core::ptr::copy_nonoverlapping(
+                (self.buffer.as_ptr() as *const u8).add(start),
+                &mut descriptor as *mut FirmwareImageDescriptor as *mut u8,
+                len,
+            );
+            descriptor
+        }
+    }
+}
+
+unsafe fn owned_string(ptr: *const Char16) -> Option<CString16> {
+    CStr16::from_ptr(ptr).ok().map(CString16::from)
+}
+
+// Copy a dependency expression, up to and including EFI_FMP_DEP_END
+//
+// The expression carries no length, so the walk stops with
+// `EFI_INVALID_PARAMETER` at MAX_DEPENDENCIES_SIZE bytes.
+unsafe fn copy_dependencies(expression: *const u8) -> Result<Vec<u8>> {
+    let byte = |offset: usize| {
+        if offset < MAX_DEPENDENCIES_SIZE {
+            Ok(*expression.add(offset))
+        } else {
+            Err(Error::new(EFI_INVALID_PARAMETER))
+        }
+    };
+    let mut len = 0;
+    loop {
+        let opcode = byte(len)?;
+        len += 1;
+        match opcode {
+            EFI_FMP_DEP_PUSH_GUID => len += 16,
+            EFI_FMP_DEP_PUSH_VERSION => len += 4,
+            EFI_FMP_DEP_VERSION_STR => {
+                while byte(len)? != 0 {
+                    len += 1;
+                }
+                len += 1;
+            }
+            EFI_FMP_DEP_AND..=EFI_FMP_DEP_LTE => {}
+            EFI_FMP_DEP_END => break,
+            _ => return Err(Error::new(EFI_INVALID_PARAMETER)),
+        }
+    }
+    Ok(core::slice::from_raw_parts(expression, len).to_vec())
+}
+
+/// One firmware image, decoded from any EFI_FIRMWARE_IMAGE_DESCRIPTOR version
+///
+/// Fields introduced after the descriptor version the driver reported are
+/// `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareImageInfo {
+    pub image_index: u8,
+    pub image_type_id: Guid,
+    pub image_id: u64,
+    pub image_id_name: Option<CString16>,
+    pub version: u32,
+    pub version_name: Option<CString16>,
+    pub size: usize,
+    pub attributes_supported: u64,
+    pub attributes_setting: u64,
+    pub compatibilities: u64,
+    /// Version 2
+    pub lowest_supported_image_version: Option<u32>,
+    /// Version 3
+    pub last_attempt_version: Option<u32>,
+    /// Version 3
+    pub last_attempt_status: Option<LastAttemptStatus>,
+    /// Version 3
+    pub hardware_instance: Option<u64>,
+    /// Version 4; the raw dependency expression including EFI_FMP_DEP_END
+    pub dependencies: Option<Vec<u8>>,
+}
+
+impl FirmwareImageInfo {
+    unsafe fn decode(descriptor: &FirmwareImageDescriptor, version: u32) -> Result<Self> {
+        let dependencies = if version >= 4 && !descriptor.dependencies.is_null() {
+            Some(copy_dependencies(descriptor.dependencies)?)
+        } else {
+            None
+        };
+        Ok(FirmwareImageInfo {
+            image_index: descriptor.image_index,
+            image_type_id: descriptor.image_type_id,
+            image_id: descriptor.image_id,
+            image_id_name: owned_string(descriptor.image_id_name),
+            version: descriptor.version,
+            version_name: owned_string(descriptor.version_name),
+            size: descriptor.size,
+            attributes_supported: descriptor.attributes_supported,
+            attributes_setting: descriptor.attributes_setting,
+            compatibilities: descriptor.compatibilities,
+            lowest_supported_image_version: (version >= 2)
+                .then_some(descriptor.lowest_supported_image_version),
+            last_attempt_version: (version >= 3).then_some(descriptor.last_attempt_version),
+            last_attempt_status: (version >= 3)
+                .then(|| LastAttemptStatus::from_raw(descriptor.last_attempt_status)),
+            hardware_instance: (version >= 3).then_some(descriptor.hardware_instance),
+            dependencies,
+        })
+    }
+
+    /// Check if image is updatable
+    pub fn is_updatable(&self) -> bool {
+        (self.attributes_supported & IMAGE_ATTRIBUTE_IMAGE_UPDATABLE) != 0
+    }
+
+    /// Check if reset is required after update
+    pub fn requires_reset(&self) -> bool {
+        (self.attributes_setting & IMAGE_ATTRIBUTE_RESET_REQUIRED) != 0
+    }
+}
+
+/// Iterator over the images reported by GetImageInfo()
+///
+/// Entries and strings are copied out of firmware memory up front, so the
+/// iterator stays valid after the protocol is closed.
+pub struct FirmwareImageDescriptors {
+    descriptor_version: u32,
+    package_version: u32,
+    package_version_name: Option<CString16>,
+    images: vec::IntoIter<FirmwareImageInfo>,
+}
+
+impl FirmwareImageDescriptors {
+    unsafe fn new(info: &ImageInfoBuffer) -> Result<Self> {
+        let images = (0..info.descriptor_count as usize)
+            .map(|i| FirmwareImageInfo::decode(&info.descriptor(i), info.descriptor_version))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(FirmwareImageDescriptors {
+            descriptor_version: info.descriptor_version,
+            package_version: info.package_version,
+            package_version_name: owned_string(info.package_version_name),
+            images: images.into_iter(),
+        })
+    }
+
+    /// Descriptor version the driver reported
+    pub fn descriptor_version(&self) -> u32 {
+        self.descriptor_version
+    }
+
+    /// Package version
+    pub fn package_version(&self) -> u32 {
+        self.package_version
+    }
+
+    /// Package version name, if the driver provides one
+    pub fn package_version_name(&self) -> Option<&CStr16> {
+        self.package_version_name.as_deref()
+    }
+}
+
+impl Iterator for FirmwareImageDescriptors {
+    type Item = FirmwareImageInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.images.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.images.size_hint()
+    }
+}
+
+impl ExactSizeIterator for FirmwareImageDescriptors {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const IMAGE_TYPE: Guid = guid!("e0b2a1f5-4c3d-4e6f-8a9b-1c2d3e4f5a6b");
+
+    static IMAGE_NAME: [Char16; 5] = [b'B' as u16, b'I' as u16, b'O' as u16, b'S' as u16, 0];
+    static DEPENDENCIES: [u8; 7] = [
+        EFI_FMP_DEP_PUSH_VERSION,
+        5,
+        0,
+        0,
+        0,
+        EFI_FMP_DEP_TRUE,
+        EFI_FMP_DEP_END,
+    ];
+
+    std::thread_local! {
+        // (descriptor version, descriptor size)
+        static LAYOUT: Cell<(u32, usize)> = const { Cell::new((4, DESCRIPTOR_V4_SIZE)) };
+    }
+
+    unsafe extern "efiapi" fn get_image_info(
+        _this: *mut FirmwareManagementProtocol,
+        image_info_size: *mut Uintn,
+        image_info: *mut FirmwareImageDescriptor,
+        descriptor_version: *mut Uint32,
+        descriptor_count: *mut Uint8,
+        descriptor_size: *mut Uintn,
+        package_version: *mut Uint32,
+        package_version_name: *mut *mut Char16,
+    ) -> Status {
+        let (version, size) = LAYOUT.with(Cell::get);
+        *descriptor_version = version;
+        *descriptor_count = 2;
+        *descriptor_size = size;
+        *package_version = 0x10;
+        *package_version_name = core::ptr::null_mut();
+        if *image_info_size < 2 * size {
+            *image_info_size = 2 * size;
+            return EFI_BUFFER_TOO_SMALL;
+        }
+        for i in 0..2u8 {
+            let descriptor = FirmwareImageDescriptor {
+                image_index: i + 1,
+                image_type_id: IMAGE_TYPE,
+                image_id: i as u64,
+                image_id_name: IMAGE_NAME.as_ptr(),
+                version: 7,
+                version_name: core::ptr::null(),
+                size: 0x1000,
+                attributes_supported: IMAGE_ATTRIBUTE_IMAGE_UPDATABLE,
+                attributes_setting: 0,
+                compatibilities: 0,
+                lowest_supported_image_version: 5,
+                last_attempt_version: 6,
+                last_attempt_status: LAST_ATTEMPT_STATUS_ERROR_PWR_EVT_AC,
+                hardware_instance: 3,
+                dependencies: DEPENDENCIES.as_ptr(),
+            };
+            core::ptr::copy_nonoverlapping(
+                &descriptor as *const FirmwareImageDescriptor as *const u8,
+                (image_info as *mut u8).add(i as usize * size),
+                size.min(DESCRIPTOR_V4_SIZE),
+            );
+        }
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn set_image(
+        _this: *mut FirmwareManagementProtocol,
+        _image_index: Uint8,
+        _image: *const core::ffi::c_void,
+        _image_size: Uintn,
+        _vendor_code: *const core::ffi::c_void,
+        progress: Option<unsafe extern "efiapi" fn(completion: Uintn) -> Status>,
+        _abort_reason: *mut *mut Char16,
+    ) -> Status {
+        if let Some(progress) = progress {
+            for completion in [10, 50, 100] {
+                progress(completion);
+            }
+        }
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn unsupported() -> Status {
+        EFI_UNSUPPORTED
+    }
+
+    fn fmp() -> FirmwareManagementProtocol {
+        unsafe {
+            FirmwareManagementProtocol {
+                get_image_info,
+                get_image: core::mem::transmute::<*const (), _>(unsupported as *const ()),
+                set_image,
+                check_image: core::mem::transmute::<*const (), _>(unsupported as *const ()),
+                get_package_info: core::mem::transmute::<*const (), _>(unsupported as *const ()),
+                set_package_info: core::mem::transmute::<*const (), _>(unsupported as *const ()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_image_descriptors_by_version() {
+        let mut fmp = fmp();
+
+        // Version 4 with the driver padding each descriptor
+        LAYOUT.with(|l| l.set((4, DESCRIPTOR_V4_SIZE + 16)));
+        let images = unsafe { fmp.image_descriptors() }.unwrap();
+        assert_eq!(images.descriptor_version(), 4);
+        assert_eq!(images.package_version(), 0x10);
+        assert!(images.package_version_name().is_none());
+        let images: Vec<_> = images.collect();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[1].image_index, 2);
+        assert_eq!(images[1].image_id_name.as_deref().unwrap(), "BIOS");
+        assert_eq!(images[1].hardware_instance, Some(3));
+        assert_eq!(
+            images[1].last_attempt_status,
+            Some(LastAttemptStatus::PowerEventAc)
+        );
+        assert_eq!(images[1].dependencies.as_deref(), Some(&DEPENDENCIES[..]));
+
+        // Version 1 stops after the compatibilities field
+        LAYOUT.with(|l| l.set((1, DESCRIPTOR_V1_SIZE)));
+        let images: Vec<_> = unsafe { fmp.image_descriptors() }.unwrap().collect();
+        assert_eq!(images[1].image_id, 1);
+        assert!(images[1].is_updatable());
+        assert_eq!(images[1].lowest_supported_image_version, None);
+        assert_eq!(images[1].last_attempt_version, None);
+        assert_eq!(images[1].dependencies, None);
+        let (raw, version, _) = unsafe { fmp.get_image_info() }.unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(raw[1].image_index, 2);
+        assert_eq!(raw[1].hardware_instance, 0);
+
+        // Padding after a version 1 descriptor is not read as newer fields
+        LAYOUT.with(|l| l.set((1, DESCRIPTOR_V1_SIZE + 24)));
+        let (raw, version, _) = unsafe { fmp.get_image_info() }.unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(raw[1].image_index, 2);
+        assert_eq!(raw[1].lowest_supported_image_version, 0);
+        assert_eq!(raw[1].last_attempt_version, 0);
+        assert_eq!(raw[1].last_attempt_status, 0);
+        let images: Vec<_> = unsafe { fmp.image_descriptors() }.unwrap().collect();
+        assert_eq!(images[1].lowest_supported_image_version, None);
+
+        // A descriptor too small for its claimed version
+        LAYOUT.with(|l| l.set((3, DESCRIPTOR_V2_SIZE)));
+        assert_eq!(
+            unsafe { fmp.image_descriptors() }.err().unwrap().status(),
+            EFI_DEVICE_ERROR
+        );
+    }
+
+    #[test]
+    fn test_copy_dependencies_bounded() {
+        let mut expression = vec![EFI_FMP_DEP_VERSION_STR];
+        expression.extend_from_slice(b"1.2\0");
+        expression.extend_from_slice(&[EFI_FMP_DEP_PUSH_VERSION, 1, 0, 0, 0]);
+        expression.extend_from_slice(&[EFI_FMP_DEP_GTE, EFI_FMP_DEP_END]);
+        assert_eq!(
+            unsafe { copy_dependencies(expression.as_ptr()) }.unwrap(),
+            expression
+        );
+
+        // No EFI_FMP_DEP_END within the bound
+        let endless = vec![EFI_FMP_DEP_TRUE; MAX_DEPENDENCIES_SIZE + 8];
+        assert_eq!(
+            unsafe { copy_dependencies(endless.as_ptr()) }.unwrap_err(),
+            Error::new(EFI_INVALID_PARAMETER)
+        );
+        // A VERSION_STR without its NUL
+        let mut unterminated = vec![b'9'; MAX_DEPENDENCIES_SIZE + 8];
+        unterminated[0] = EFI_FMP_DEP_VERSION_STR;
+        assert_eq!(
+            unsafe { copy_dependencies(unterminated.as_ptr()) }.unwrap_err(),
+            Error::new(EFI_INVALID_PARAMETER)
+        );
+    }
+
+    #[test]
+    fn test_set_image_with_progress_closure() {
+        let mut fmp = fmp();
+        let mut seen = Vec::new();
+        unsafe { fmp.set_image_with_progress(1, b"image", None, |c| seen.push(c)) }.unwrap();
+        assert_eq!(seen, [10, 50, 100]);
+        assert!(unsafe { (*core::ptr::addr_of!(PROGRESS)).is_none() });
+    }
 }
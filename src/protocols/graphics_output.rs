@@ -1,15 +1,13 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Graphics Output Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID
-pub const GRAPHICS_OUTPUT_PROTOCOL_GUID: Guid = Guid::new(
-    0x9042a9de,
-    0x23dc,
-    0x4a38,
-    [0x96, 0xfb, 0x7a, 0xde, 0xd0, 0x80, 0x51, 0x6a],
-);
+pub const GRAPHICS_OUTPUT_PROTOCOL_GUID: Guid = guid!("9042a9de-23dc-4a38-96fb-7aded080516a");
 
 /// EFI_GRAPHICS_PIXEL_FORMAT
 #[repr(u32)]
@@ -102,25 +100,25 @@ pub struct GraphicsOutputProtocol {
     pub mode: *mut GraphicsOutputProtocolMode,
 }
 
+unsafe impl Protocol for GraphicsOutputProtocol {
+    const GUID: Guid = GRAPHICS_OUTPUT_PROTOCOL_GUID;
+}
+
 impl GraphicsOutputProtocol {
     /// Query mode information
     pub unsafe fn query_mode(
         &mut self,
         mode_number: u32,
-    ) -> Result<(*mut GraphicsOutputModeInformation, usize), Status> {
+    ) -> Result<(*mut GraphicsOutputModeInformation, usize)> {
         let mut size_of_info = 0;
         let mut info = core::ptr::null_mut();
-        let status = (self.query_mode)(self, mode_number, &mut size_of_info, &mut info);
-        if status == EFI_SUCCESS {
-            Ok((info, size_of_info))
-        } else {
-            Err(status)
-        }
+        (self.query_mode)(self, mode_number, &mut size_of_info, &mut info).to_result()?;
+        Ok((info, size_of_info))
     }
 
     /// Set mode
-    pub unsafe fn set_mode(&mut self, mode_number: u32) -> Status {
-        (self.set_mode)(self, mode_number)
+    pub unsafe fn set_mode(&mut self, mode_number: u32) -> Result<()> {
+        (self.set_mode)(self, mode_number).to_result()
     }
 
     /// Block transfer
@@ -135,7 +133,7 @@ impl GraphicsOutputProtocol {
         width: usize,
         height: usize,
         delta: usize,
-    ) -> Status {
+    ) -> Result<()> {
         (self.blt)(
             self,
             blt_buffer,
@@ -148,6 +146,7 @@ impl GraphicsOutputProtocol {
             height,
             delta,
         )
+        .to_result()
     }
 
     /// Get current mode information
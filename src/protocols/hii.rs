@@ -3,56 +3,29 @@
 //!
 //! The HII Database is used for managing strings, fonts, forms, keyboards, and images.
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 use crate::runtime_services::Time;
 
 /// EFI_HII_DATABASE_PROTOCOL_GUID
-pub const HII_DATABASE_PROTOCOL_GUID: Guid = Guid::new(
-    0xef9fc172,
-    0xa1b2,
-    0x4693,
-    [0xb3, 0x27, 0x6d, 0x32, 0xfc, 0x41, 0x60, 0x42],
-);
+pub const HII_DATABASE_PROTOCOL_GUID: Guid = guid!("ef9fc172-a1b2-4693-b327-6d32fc416042");
 
 /// EFI_HII_STRING_PROTOCOL_GUID
-pub const HII_STRING_PROTOCOL_GUID: Guid = Guid::new(
-    0x0fd96974,
-    0x23aa,
-    0x4cdc,
-    [0xb9, 0xcb, 0x98, 0xd1, 0x77, 0x50, 0x32, 0x2a],
-);
+pub const HII_STRING_PROTOCOL_GUID: Guid = guid!("0fd96974-23aa-4cdc-b9cb-98d17750322a");
 
 /// EFI_HII_FONT_PROTOCOL_GUID
-pub const HII_FONT_PROTOCOL_GUID: Guid = Guid::new(
-    0xe9ca4775,
-    0x8657,
-    0x47fc,
-    [0x97, 0xe7, 0x7e, 0xd6, 0x5a, 0x08, 0x43, 0x24],
-);
+pub const HII_FONT_PROTOCOL_GUID: Guid = guid!("e9ca4775-8657-47fc-97e7-7ed65a084324");
 
 /// EFI_HII_IMAGE_PROTOCOL_GUID
-pub const HII_IMAGE_PROTOCOL_GUID: Guid = Guid::new(
-    0x31a6406a,
-    0x6bdf,
-    0x4e46,
-    [0xb2, 0xa2, 0xeb, 0xaa, 0x89, 0xc4, 0x09, 0x20],
-);
+pub const HII_IMAGE_PROTOCOL_GUID: Guid = guid!("31a6406a-6bdf-4e46-b2a2-ebaa89c40920");
 
 /// EFI_HII_CONFIG_ACCESS_PROTOCOL_GUID
-pub const HII_CONFIG_ACCESS_PROTOCOL_GUID: Guid = Guid::new(
-    0x330d4706,
-    0xf2a0,
-    0x4e4f,
-    [0xa3, 0x69, 0xb6, 0x6f, 0xa8, 0xd5, 0x43, 0x85],
-);
+pub const HII_CONFIG_ACCESS_PROTOCOL_GUID: Guid = guid!("330d4706-f2a0-4e4f-a369-b66fa8d54385");
 
 /// EFI_HII_CONFIG_ROUTING_PROTOCOL_GUID
-pub const HII_CONFIG_ROUTING_PROTOCOL_GUID: Guid = Guid::new(
-    0x587e72d7,
-    0xcc50,
-    0x4f79,
-    [0x82, 0x09, 0xca, 0x29, 0x1f, 0xc1, 0xa1, 0x0f],
-);
+pub const HII_CONFIG_ROUTING_PROTOCOL_GUID: Guid = guid!("587e72d7-cc50-4f79-8209-ca291fc1a10f");
 
 /// HII Handle type
 pub type HiiHandle = *mut core::ffi::c_void;
@@ -152,6 +125,10 @@ pub struct HiiDatabaseProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for HiiDatabaseProtocol {
+    const GUID: Guid = HII_DATABASE_PROTOCOL_GUID;
+}
+
 /// String ID type
 pub type StringId = Uint16;
 
@@ -202,6 +179,10 @@ pub struct HiiStringProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for HiiStringProtocol {
+    const GUID: Guid = HII_STRING_PROTOCOL_GUID;
+}
+
 /// HII Font Information
 #[repr(C)]
 pub struct HiiFontInfo {
@@ -265,6 +246,10 @@ pub struct HiiImageProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for HiiImageProtocol {
+    const GUID: Guid = HII_IMAGE_PROTOCOL_GUID;
+}
+
 /// EFI_HII_FONT_PROTOCOL
 #[repr(C)]
 pub struct HiiFontProtocol {
@@ -310,6 +295,10 @@ pub struct HiiFontProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for HiiFontProtocol {
+    const GUID: Guid = HII_FONT_PROTOCOL_GUID;
+}
+
 /// HII Font Display Info
 #[repr(C)]
 pub struct HiiFontDisplayInfo {
@@ -370,6 +359,10 @@ pub struct HiiConfigAccessProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for HiiConfigAccessProtocol {
+    const GUID: Guid = HII_CONFIG_ACCESS_PROTOCOL_GUID;
+}
+
 /// HII Config Access Value Union
 #[repr(C)]
 pub union HiiConfigAccessValue {
@@ -428,6 +421,10 @@ pub struct HiiConfigRoutingProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for HiiConfigRoutingProtocol {
+    const GUID: Guid = HII_CONFIG_ROUTING_PROTOCOL_GUID;
+}
+
 impl HiiStringProtocol {
     /// Create a new string in the database
     pub unsafe fn new_string(
@@ -435,9 +432,9 @@ impl HiiStringProtocol {
         package_list: HiiHandle,
         language: &[u8],
         string: &[u16],
-    ) -> Result<StringId, Status> {
+    ) -> Result<StringId> {
         let mut string_id: StringId = 0;
-        let status = (self.new_string)(
+        (self.new_string)(
             self,
             package_list,
             &mut string_id,
@@ -445,13 +442,9 @@ impl HiiStringProtocol {
             core::ptr::null(),
             string.as_ptr() as *const _,
             core::ptr::null(),
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(string_id)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(string_id)
     }
 
     /// Get a string from the database
@@ -461,9 +454,9 @@ impl HiiStringProtocol {
         string_id: StringId,
         language: &[u8],
         buffer: &mut [u16],
-    ) -> Result<usize, Status> {
+    ) -> Result<usize> {
         let mut size = buffer.len() * 2;
-        let status = (self.get_string)(
+        (self.get_string)(
             self,
             language.as_ptr() as *mut _,
             package_list,
@@ -471,13 +464,9 @@ impl HiiStringProtocol {
             buffer.as_mut_ptr() as *mut _,
             &mut size,
             core::ptr::null_mut(),
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(size / 2)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(size / 2)
     }
 }
 
@@ -487,20 +476,15 @@ impl HiiDatabaseProtocol {
         &mut self,
         package_list: &HiiPackageListHeader,
         driver_handle: Handle,
-    ) -> Result<HiiHandle, Status> {
+    ) -> Result<HiiHandle> {
         let mut handle: HiiHandle = core::ptr::null_mut();
-        let status =
-            (self.new_package_list)(self, package_list as *const _, driver_handle, &mut handle);
-
-        if status == EFI_SUCCESS {
-            Ok(handle)
-        } else {
-            Err(status)
-        }
+        (self.new_package_list)(self, package_list as *const _, driver_handle, &mut handle)
+            .to_result()?;
+        Ok(handle)
     }
 
     /// Remove a package list
-    pub unsafe fn remove_package_list(&mut self, handle: HiiHandle) -> Status {
-        (self.remove_package_list)(self, handle)
+    pub unsafe fn remove_package_list(&mut self, handle: HiiHandle) -> Result<()> {
+        (self.remove_package_list)(self, handle).to_result()
     }
 }
@@ -1,23 +1,16 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI HTTP Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_HTTP_PROTOCOL_GUID
-pub const HTTP_PROTOCOL_GUID: Guid = Guid::new(
-    0x7A59B29B,
-    0x910B,
-    0x4171,
-    [0x82, 0x42, 0xA8, 0x5A, 0x0D, 0xF2, 0x5B, 0x5B],
-);
+pub const HTTP_PROTOCOL_GUID: Guid = guid!("7a59b29b-910b-4171-8242-a85a0df25b5b");
 
 /// EFI_HTTP_SERVICE_BINDING_PROTOCOL_GUID
-pub const HTTP_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0xbdc8e6af,
-    0xd9bc,
-    0x4379,
-    [0xa7, 0x2a, 0xe0, 0xc4, 0xe7, 0x5d, 0xae, 0x1c],
-);
+pub const HTTP_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("bdc8e6af-d9bc-4379-a72a-e0c4e75dae1c");
 
 /// HTTP Version
 #[repr(u32)]
@@ -158,51 +151,48 @@ pub struct HttpProtocol {
     pub poll: unsafe extern "efiapi" fn(this: *mut HttpProtocol) -> Status,
 }
 
+unsafe impl Protocol for HttpProtocol {
+    const GUID: Guid = HTTP_PROTOCOL_GUID;
+}
+
 impl HttpProtocol {
     /// Get current configuration
-    pub unsafe fn get_mode_data(&mut self) -> Result<HttpConfigData, Status> {
+    pub unsafe fn get_mode_data(&mut self) -> Result<HttpConfigData> {
         let mut config = core::mem::zeroed();
-        let status = (self.get_mode_data)(self, &mut config);
-
-        if status == EFI_SUCCESS {
-            Ok(config)
-        } else {
-            Err(status)
-        }
+        (self.get_mode_data)(self, &mut config).to_result()?;
+        Ok(config)
     }
 
     /// Configure the HTTP instance
-    pub unsafe fn configure(&mut self, config: Option<&HttpConfigData>) -> Status {
+    pub unsafe fn configure(&mut self, config: Option<&HttpConfigData>) -> Result<()> {
         let config_ptr = config.map_or(core::ptr::null(), |c| c as *const _);
-        (self.configure)(self, config_ptr)
+        (self.configure)(self, config_ptr).to_result()
     }
 
     /// Send an HTTP request
-    pub unsafe fn request(&mut self, token: &mut HttpToken) -> Status {
-        (self.request)(self, token as *mut _)
+    pub unsafe fn request(&mut self, token: &mut HttpToken) -> Result<()> {
+        (self.request)(self, token as *mut _).to_result()
     }
 
     /// Cancel a request
-    pub unsafe fn cancel(&mut self, token: &mut HttpToken) -> Status {
-        (self.cancel)(self, token as *mut _)
+    pub unsafe fn cancel(&mut self, token: &mut HttpToken) -> Result<()> {
+        (self.cancel)(self, token as *mut _).to_result()
     }
 
     /// Receive an HTTP response
-    pub unsafe fn response(&mut self, token: &mut HttpToken) -> Status {
-        (self.response)(self, token as *mut _)
+    pub unsafe fn response(&mut self, token: &mut HttpToken) -> Result<()> {
+        (self.response)(self, token as *mut _).to_result()
     }
 
     /// Poll for completion
-    pub unsafe fn poll(&mut self) -> Status {
-        (self.poll)(self)
+    pub unsafe fn poll(&mut self) -> Result<()> {
+        (self.poll)(self).to_result()
     }
 }
 
 /// Helper functions for HTTP
 pub mod http_helpers {
     use super::*;
-    #[cfg(not(feature = "std"))]
-    use alloc::vec::Vec;
 
     /// Create a simple GET request
     pub unsafe fn create_get_request(url: &[u16]) -> HttpRequestData {
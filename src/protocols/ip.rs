@@ -1,121 +1,54 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI IP4/IP6 and Related Network Protocols
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
 use crate::protocols::tcp_udp::{Ipv4Address, Ipv6Address};
+use crate::protocols::Protocol;
 use crate::runtime_services::Time;
 
 /// EFI_IP4_SERVICE_BINDING_PROTOCOL_GUID
-pub const IP4_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0xc51711e7,
-    0xb4bf,
-    0x404a,
-    [0xbf, 0xb8, 0x0a, 0x04, 0x8e, 0xf1, 0xff, 0xe4],
-);
+pub const IP4_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("c51711e7-b4bf-404a-bfb8-0a048ef1ffe4");
 
 /// EFI_IP4_PROTOCOL_GUID
-pub const IP4_PROTOCOL_GUID: Guid = Guid::new(
-    0x41d94cd2,
-    0x35b6,
-    0x455a,
-    [0x82, 0x58, 0xd4, 0xe5, 0x13, 0x34, 0xaa, 0xdd],
-);
+pub const IP4_PROTOCOL_GUID: Guid = guid!("41d94cd2-35b6-455a-8258-d4e51334aadd");
 
 /// EFI_IP6_SERVICE_BINDING_PROTOCOL_GUID
-pub const IP6_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0xec835dd3,
-    0xfe0f,
-    0x617b,
-    [0xa6, 0x21, 0xb3, 0x50, 0xc3, 0xe1, 0x33, 0x88],
-);
+pub const IP6_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("ec835dd3-fe0f-617b-a621-b350c3e13388");
 
 /// EFI_IP6_PROTOCOL_GUID
-pub const IP6_PROTOCOL_GUID: Guid = Guid::new(
-    0x2c8759d5,
-    0x5c2d,
-    0x66ef,
-    [0x92, 0x5f, 0xb6, 0x6c, 0x10, 0x19, 0x57, 0xe2],
-);
+pub const IP6_PROTOCOL_GUID: Guid = guid!("2c8759d5-5c2d-66ef-925f-b66c101957e2");
 
 /// EFI_ARP_SERVICE_BINDING_PROTOCOL_GUID
-pub const ARP_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0xf44c00ee,
-    0x1f2c,
-    0x4a00,
-    [0xaa, 0x09, 0x1c, 0x9f, 0x3e, 0x08, 0x00, 0xa3],
-);
+pub const ARP_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("f44c00ee-1f2c-4a00-aa09-1c9f3e0800a3");
 
 /// EFI_ARP_PROTOCOL_GUID
-pub const ARP_PROTOCOL_GUID: Guid = Guid::new(
-    0xf4b427bb,
-    0xba21,
-    0x4f16,
-    [0xbc, 0x4e, 0x43, 0xe4, 0x16, 0xab, 0x61, 0x9c],
-);
+pub const ARP_PROTOCOL_GUID: Guid = guid!("f4b427bb-ba21-4f16-bc4e-43e416ab619c");
 
 /// EFI_DHCP4_SERVICE_BINDING_PROTOCOL_GUID
-pub const DHCP4_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0x9d9a39d8,
-    0xbd42,
-    0x4a73,
-    [0xa4, 0xd5, 0x8e, 0xe9, 0x4b, 0xe1, 0x13, 0x80],
-);
+pub const DHCP4_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("9d9a39d8-bd42-4a73-a4d5-8ee94be11380");
 
 /// EFI_DHCP4_PROTOCOL_GUID
-pub const DHCP4_PROTOCOL_GUID: Guid = Guid::new(
-    0x8a219718,
-    0x4ef5,
-    0x4761,
-    [0x91, 0xc8, 0xc0, 0xf0, 0x4b, 0xda, 0x9e, 0x56],
-);
+pub const DHCP4_PROTOCOL_GUID: Guid = guid!("8a219718-4ef5-4761-91c8-c0f04bda9e56");
 
 /// EFI_DHCP6_SERVICE_BINDING_PROTOCOL_GUID
-pub const DHCP6_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0x9fb9a8a1,
-    0x2f4a,
-    0x43a6,
-    [0x88, 0x9c, 0xd0, 0xf7, 0xb6, 0xc4, 0x7a, 0xd5],
-);
+pub const DHCP6_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("9fb9a8a1-2f4a-43a6-889c-d0f7b6c47ad5");
 
 /// EFI_DHCP6_PROTOCOL_GUID
-pub const DHCP6_PROTOCOL_GUID: Guid = Guid::new(
-    0x87c8bad7,
-    0x595,
-    0x4053,
-    [0x82, 0x97, 0xde, 0xde, 0x39, 0x5f, 0x5d, 0x5b],
-);
+pub const DHCP6_PROTOCOL_GUID: Guid = guid!("87c8bad7-0595-4053-8297-dede395f5d5b");
 
 /// EFI_DNS4_SERVICE_BINDING_PROTOCOL_GUID
-pub const DNS4_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0xb625b186,
-    0xe063,
-    0x44f7,
-    [0x89, 0x05, 0x6a, 0x74, 0xdc, 0x6f, 0x52, 0xb4],
-);
+pub const DNS4_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("b625b186-e063-44f7-8905-6a74dc6f52b4");
 
 /// EFI_DNS4_PROTOCOL_GUID
-pub const DNS4_PROTOCOL_GUID: Guid = Guid::new(
-    0xae3d28cc,
-    0xe05b,
-    0x4fa1,
-    [0xa0, 0x11, 0x7e, 0xb5, 0x5a, 0x3f, 0x14, 0x01],
-);
+pub const DNS4_PROTOCOL_GUID: Guid = guid!("ae3d28cc-e05b-4fa1-a011-7eb55a3f1401");
 
 /// EFI_DNS6_SERVICE_BINDING_PROTOCOL_GUID
-pub const DNS6_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0x7f1647c8,
-    0xb76e,
-    0x44b2,
-    [0xa5, 0x65, 0xf7, 0x0f, 0xf1, 0x9c, 0xd1, 0x9e],
-);
+pub const DNS6_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("7f1647c8-b76e-44b2-a565-f70ff19cd19e");
 
 /// EFI_DNS6_PROTOCOL_GUID
-pub const DNS6_PROTOCOL_GUID: Guid = Guid::new(
-    0xca37bc1f,
-    0xa327,
-    0x4ae9,
-    [0x82, 0x8a, 0x8c, 0x40, 0xd8, 0x50, 0x6a, 0x17],
-);
+pub const DNS6_PROTOCOL_GUID: Guid = guid!("ca37bc1f-a327-4ae9-828a-8c40d8506a17");
 
 /// IP4 Configuration Data
 #[repr(C)]
@@ -407,6 +340,10 @@ pub struct Ip4Protocol {
     pub poll: unsafe extern "efiapi" fn(this: *mut Ip4Protocol) -> Status,
 }
 
+unsafe impl Protocol for Ip4Protocol {
+    const GUID: Guid = IP4_PROTOCOL_GUID;
+}
+
 /// EFI_IP6_PROTOCOL
 #[repr(C)]
 pub struct Ip6Protocol {
@@ -449,6 +386,10 @@ pub struct Ip6Protocol {
     pub poll: unsafe extern "efiapi" fn(this: *mut Ip6Protocol) -> Status,
 }
 
+unsafe impl Protocol for Ip6Protocol {
+    const GUID: Guid = IP6_PROTOCOL_GUID;
+}
+
 /// ARP Configuration Data
 #[repr(C)]
 pub struct ArpConfigData {
@@ -503,6 +444,10 @@ pub struct ArpProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for ArpProtocol {
+    const GUID: Guid = ARP_PROTOCOL_GUID;
+}
+
 /// DHCP4 State
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -573,6 +518,10 @@ pub struct Dhcp4Protocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for Dhcp4Protocol {
+    const GUID: Guid = DHCP4_PROTOCOL_GUID;
+}
+
 /// DNS Host Address
 #[repr(C)]
 pub struct DnsHostAddress {
@@ -633,38 +582,42 @@ pub struct Dns4Protocol {
         unsafe extern "efiapi" fn(this: *mut Dns4Protocol, token: *mut core::ffi::c_void) -> Status,
 }
 
+unsafe impl Protocol for Dns4Protocol {
+    const GUID: Guid = DNS4_PROTOCOL_GUID;
+}
+
 impl Ip4Protocol {
     /// Configure IP4 instance
-    pub unsafe fn configure(&mut self, config: Option<&Ip4ConfigData>) -> Status {
+    pub unsafe fn configure(&mut self, config: Option<&Ip4ConfigData>) -> Result<()> {
         let config_ptr = config.map_or(core::ptr::null(), |c| c as *const _);
-        (self.configure)(self, config_ptr)
+        (self.configure)(self, config_ptr).to_result()
     }
 
     /// Transmit packet
-    pub unsafe fn transmit(&mut self, token: &mut Ip4CompletionToken) -> Status {
-        (self.transmit)(self, token as *mut _)
+    pub unsafe fn transmit(&mut self, token: &mut Ip4CompletionToken) -> Result<()> {
+        (self.transmit)(self, token as *mut _).to_result()
     }
 
     /// Receive packet
-    pub unsafe fn receive(&mut self, token: &mut Ip4CompletionToken) -> Status {
-        (self.receive)(self, token as *mut _)
+    pub unsafe fn receive(&mut self, token: &mut Ip4CompletionToken) -> Result<()> {
+        (self.receive)(self, token as *mut _).to_result()
     }
 }
 
 impl Ip6Protocol {
     /// Configure IP6 instance
-    pub unsafe fn configure(&mut self, config: Option<&Ip6ConfigData>) -> Status {
+    pub unsafe fn configure(&mut self, config: Option<&Ip6ConfigData>) -> Result<()> {
         let config_ptr = config.map_or(core::ptr::null(), |c| c as *const _);
-        (self.configure)(self, config_ptr)
+        (self.configure)(self, config_ptr).to_result()
     }
 
     /// Transmit packet
-    pub unsafe fn transmit(&mut self, token: &mut Ip6CompletionToken) -> Status {
-        (self.transmit)(self, token as *mut _)
+    pub unsafe fn transmit(&mut self, token: &mut Ip6CompletionToken) -> Result<()> {
+        (self.transmit)(self, token as *mut _).to_result()
     }
 
     /// Receive packet
-    pub unsafe fn receive(&mut self, token: &mut Ip6CompletionToken) -> Status {
-        (self.receive)(self, token as *mut _)
+    pub unsafe fn receive(&mut self, token: &mut Ip6CompletionToken) -> Result<()> {
+        (self.receive)(self, token as *mut _).to_result()
     }
 }
@@ -1,17 +1,15 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Loaded Image Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
 use crate::protocols::DevicePathProtocol;
-use crate::system_table::SystemTable;
+use crate::protocols::Protocol;
+use crate::system_table::RawSystemTable;
 
 /// EFI_LOADED_IMAGE_PROTOCOL_GUID
-pub const LOADED_IMAGE_PROTOCOL_GUID: Guid = Guid::new(
-    0x5B1B31A1,
-    0x9562,
-    0x11d2,
-    [0x8E, 0x3F, 0x00, 0xA0, 0xC9, 0x69, 0x72, 0x3B],
-);
+pub const LOADED_IMAGE_PROTOCOL_GUID: Guid = guid!("5b1b31a1-9562-11d2-8e3f-00a0c969723b");
 
 pub const EFI_LOADED_IMAGE_PROTOCOL_REVISION: Uint32 = 0x1000;
 
@@ -20,7 +18,7 @@ pub const EFI_LOADED_IMAGE_PROTOCOL_REVISION: Uint32 = 0x1000;
 pub struct LoadedImageProtocol {
     pub revision: Uint32,
     pub parent_handle: *mut Handle,
-    pub system_table: *mut SystemTable,
+    pub system_table: *mut RawSystemTable,
     pub device_handle: *mut Handle,
     pub file_path: *mut DevicePathProtocol,
     pub reserved: *mut core::ffi::c_void,
@@ -33,6 +31,10 @@ pub struct LoadedImageProtocol {
     pub unload: unsafe extern "efiapi" fn(image_handle: *mut Handle) -> Status,
 }
 
+unsafe impl Protocol for LoadedImageProtocol {
+    const GUID: Guid = LOADED_IMAGE_PROTOCOL_GUID;
+}
+
 impl LoadedImageProtocol {
     /// Get parent handle
     pub fn parent_handle(&self) -> Option<&Handle> {
@@ -46,7 +48,7 @@ impl LoadedImageProtocol {
     }
 
     /// Get system table
-    pub fn system_table(&self) -> Option<&SystemTable> {
+    pub fn system_table(&self) -> Option<&RawSystemTable> {
         unsafe {
             if self.system_table.is_null() {
                 None
@@ -98,7 +100,7 @@ impl LoadedImageProtocol {
     }
 
     /// Unload the image
-    pub unsafe fn unload(&self, image_handle: *mut Handle) -> Status {
-        (self.unload)(image_handle)
+    pub unsafe fn unload(&self, image_handle: *mut Handle) -> Result<()> {
+        (self.unload)(image_handle).to_result()
     }
 }
@@ -1,71 +1,42 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! Miscellaneous UEFI Protocols - Timestamp, RNG, etc.
 
+use crate::error::{Error, Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
 
 /// EFI_TIMESTAMP_PROTOCOL_GUID
-pub const TIMESTAMP_PROTOCOL_GUID: Guid = Guid::new(
-    0xafbfde41,
-    0x2e6e,
-    0x4262,
-    [0xba, 0x65, 0x62, 0xb9, 0x23, 0x6e, 0x54, 0x95],
-);
+pub const TIMESTAMP_PROTOCOL_GUID: Guid = guid!("afbfde41-2e6e-4262-ba65-62b9236e5495");
 
 /// EFI_RNG_PROTOCOL_GUID
-pub const RNG_PROTOCOL_GUID: Guid = Guid::new(
-    0x3152bca5,
-    0xeade,
-    0x433d,
-    [0x86, 0x2e, 0xc0, 0x1c, 0xdc, 0x29, 0x1f, 0x44],
-);
+pub const RNG_PROTOCOL_GUID: Guid = guid!("3152bca5-eade-433d-862e-c01cdc291f44");
 
 /// EFI_RNG_ALGORITHM_SP800_90_HASH_256_GUID
-pub const RNG_ALGORITHM_SP800_90_HASH_256_GUID: Guid = Guid::new(
-    0xa7af67cb,
-    0x603b,
-    0x4d42,
-    [0xba, 0x21, 0x70, 0xbf, 0xb6, 0x29, 0x3f, 0x96],
-);
+pub const RNG_ALGORITHM_SP800_90_HASH_256_GUID: Guid =
+    guid!("a7af67cb-603b-4d42-ba21-70bfb6293f96");
 
 /// EFI_RNG_ALGORITHM_SP800_90_HMAC_256_GUID
-pub const RNG_ALGORITHM_SP800_90_HMAC_256_GUID: Guid = Guid::new(
-    0xc5149b43,
-    0xae85,
-    0x4f53,
-    [0x99, 0x82, 0xb9, 0x43, 0x35, 0xd3, 0xa9, 0xe7],
-);
+pub const RNG_ALGORITHM_SP800_90_HMAC_256_GUID: Guid =
+    guid!("c5149b43-ae85-4f53-9982-b94335d3a9e7");
 
 /// EFI_RNG_ALGORITHM_SP800_90_CTR_256_GUID
-pub const RNG_ALGORITHM_SP800_90_CTR_256_GUID: Guid = Guid::new(
-    0x44f0de6e,
-    0x4d8c,
-    0x4045,
-    [0xa8, 0xc7, 0x4d, 0xd1, 0x68, 0x85, 0x6b, 0x9e],
-);
+pub const RNG_ALGORITHM_SP800_90_CTR_256_GUID: Guid = guid!("44f0de6e-4d8c-4045-a8c7-4dd168856b9e");
 
 /// EFI_RNG_ALGORITHM_X9_31_3DES_GUID
-pub const RNG_ALGORITHM_X9_31_3DES_GUID: Guid = Guid::new(
-    0x63c4785a,
-    0xca34,
-    0x4012,
-    [0xa3, 0xc8, 0x0b, 0x6a, 0x32, 0x4f, 0x55, 0x46],
-);
+pub const RNG_ALGORITHM_X9_31_3DES_GUID: Guid = guid!("63c4785a-ca34-4012-a3c8-0b6a324f5546");
 
 /// EFI_RNG_ALGORITHM_X9_31_AES_GUID
-pub const RNG_ALGORITHM_X9_31_AES_GUID: Guid = Guid::new(
-    0xacd03321,
-    0x777e,
-    0x4d3d,
-    [0xb1, 0xc8, 0x20, 0xcf, 0xd8, 0x88, 0x20, 0xc9],
-);
+pub const RNG_ALGORITHM_X9_31_AES_GUID: Guid = guid!("acd03321-777e-4d3d-b1c8-20cfd88820c9");
 
 /// EFI_RNG_ALGORITHM_RAW
-pub const RNG_ALGORITHM_RAW: Guid = Guid::new(
-    0xe43176d7,
-    0xb6e8,
-    0x4827,
-    [0xb7, 0x84, 0x7f, 0xfd, 0xc4, 0xb6, 0x85, 0x61],
-);
+pub const RNG_ALGORITHM_RAW: Guid = guid!("e43176d7-b6e8-4827-b784-7ffdc4b68561");
 
 /// Timestamp Properties
 #[repr(C)]
@@ -84,6 +55,10 @@ pub struct TimestampProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for TimestampProtocol {
+    const GUID: Guid = TIMESTAMP_PROTOCOL_GUID;
+}
+
 /// EFI_RNG_PROTOCOL
 #[repr(C)]
 pub struct RngProtocol {
@@ -100,6 +75,10 @@ pub struct RngProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for RngProtocol {
+    const GUID: Guid = RNG_PROTOCOL_GUID;
+}
+
 impl TimestampProtocol {
     /// Get current timestamp counter value
     pub unsafe fn get_timestamp(&mut self) -> u64 {
@@ -107,22 +86,17 @@ impl TimestampProtocol {
     }
 
     /// Get timestamp properties (frequency and end value)
-    pub unsafe fn get_properties(&mut self) -> Result<TimestampProperties, Status> {
+    pub unsafe fn get_properties(&mut self) -> Result<TimestampProperties> {
         let mut props = core::mem::zeroed();
-        let status = (self.get_properties)(self, &mut props);
-
-        if status == EFI_SUCCESS {
-            Ok(props)
-        } else {
-            Err(status)
-        }
+        (self.get_properties)(self, &mut props).to_result()?;
+        Ok(props)
     }
 
     /// Calculate elapsed time in nanoseconds
-    pub unsafe fn elapsed_ns(&mut self, start: u64, end: u64) -> Result<u64, Status> {
+    pub unsafe fn elapsed_ns(&mut self, start: u64, end: u64) -> Result<u64> {
         let props = self.get_properties()?;
         if props.frequency == 0 {
-            return Err(EFI_DEVICE_ERROR);
+            return Err(Error::new(EFI_DEVICE_ERROR));
         }
 
         let ticks = if end >= start {
@@ -141,13 +115,7 @@ impl TimestampProtocol {
 
 impl RngProtocol {
     /// Get list of supported RNG algorithms
-    pub unsafe fn get_info(&mut self) -> Result<Vec<Guid>, Status> {
-        #[cfg(not(feature = "std"))]
-        use alloc::vec::Vec;
-
-        #[cfg(feature = "std")]
-        use std::vec::Vec;
-
+    pub unsafe fn get_info(&mut self) -> Result<Vec<Guid>> {
         let mut size = 0;
 
         // First call to get size
@@ -161,50 +129,35 @@ impl RngProtocol {
         let mut algorithms = Vec::with_capacity(count);
         algorithms.resize(count, Guid::new(0, 0, 0, [0; 8]));
 
-        let status = (self.get_info)(self, &mut size, algorithms.as_mut_ptr());
-
-        if status == EFI_SUCCESS {
-            Ok(algorithms)
-        } else {
-            Err(status)
-        }
+        (self.get_info)(self, &mut size, algorithms.as_mut_ptr()).to_result()?;
+        Ok(algorithms)
     }
 
     /// Generate random bytes using specified algorithm
-    pub unsafe fn get_rng(&mut self, algorithm: Option<&Guid>, buffer: &mut [u8]) -> Status {
+    pub unsafe fn get_rng(&mut self, algorithm: Option<&Guid>, buffer: &mut [u8]) -> Result<()> {
         let algo_ptr = algorithm
             .map(|a| a as *const _)
             .unwrap_or(core::ptr::null());
-        (self.get_rng)(self, algo_ptr, buffer.len(), buffer.as_mut_ptr())
+        (self.get_rng)(self, algo_ptr, buffer.len(), buffer.as_mut_ptr()).to_result()
     }
 
     /// Generate random bytes using default algorithm
-    pub unsafe fn get_random(&mut self, buffer: &mut [u8]) -> Status {
+    pub unsafe fn get_random(&mut self, buffer: &mut [u8]) -> Result<()> {
         self.get_rng(None, buffer)
     }
 
     /// Generate a random u32
-    pub unsafe fn get_random_u32(&mut self) -> Result<u32, Status> {
+    pub unsafe fn get_random_u32(&mut self) -> Result<u32> {
         let mut buf = [0u8; 4];
-        let status = self.get_random(&mut buf);
-
-        if status == EFI_SUCCESS {
-            Ok(u32::from_ne_bytes(buf))
-        } else {
-            Err(status)
-        }
+        self.get_random(&mut buf)?;
+        Ok(u32::from_ne_bytes(buf))
     }
 
     /// Generate a random u64
-    pub unsafe fn get_random_u64(&mut self) -> Result<u64, Status> {
+    pub unsafe fn get_random_u64(&mut self) -> Result<u64> {
         let mut buf = [0u8; 8];
-        let status = self.get_random(&mut buf);
-
-        if status == EFI_SUCCESS {
-            Ok(u64::from_ne_bytes(buf))
-        } else {
-            Err(status)
-        }
+        self.get_random(&mut buf)?;
+        Ok(u64::from_ne_bytes(buf))
     }
 }
 
@@ -226,18 +179,18 @@ pub mod timestamp_utils {
         }
 
         /// Get elapsed time in nanoseconds
-        pub unsafe fn elapsed_ns(&mut self) -> Result<u64, Status> {
+        pub unsafe fn elapsed_ns(&mut self) -> Result<u64> {
             let end = ((*self.protocol).get_timestamp)(self.protocol);
             (*self.protocol).elapsed_ns(self.start, end)
         }
 
         /// Get elapsed time in microseconds
-        pub unsafe fn elapsed_us(&mut self) -> Result<u64, Status> {
+        pub unsafe fn elapsed_us(&mut self) -> Result<u64> {
             Ok(self.elapsed_ns()? / 1_000)
         }
 
         /// Get elapsed time in milliseconds
-        pub unsafe fn elapsed_ms(&mut self) -> Result<u64, Status> {
+        pub unsafe fn elapsed_ms(&mut self) -> Result<u64> {
             Ok(self.elapsed_ns()? / 1_000_000)
         }
 
@@ -253,9 +206,9 @@ pub mod rng_utils {
     use super::*;
 
     /// Generate a random value in range [min, max]
-    pub unsafe fn random_range(rng: &mut RngProtocol, min: u64, max: u64) -> Result<u64, Status> {
+    pub unsafe fn random_range(rng: &mut RngProtocol, min: u64, max: u64) -> Result<u64> {
         if min >= max {
-            return Err(EFI_INVALID_PARAMETER);
+            return Err(Error::new(EFI_INVALID_PARAMETER));
         }
 
         let range = max - min + 1;
@@ -264,18 +217,18 @@ pub mod rng_utils {
     }
 
     /// Generate a random boolean
-    pub unsafe fn random_bool(rng: &mut RngProtocol) -> Result<bool, Status> {
+    pub unsafe fn random_bool(rng: &mut RngProtocol) -> Result<bool> {
         let byte = rng.get_random_u32()?;
         Ok(byte & 1 == 1)
     }
 
     /// Fill a slice with random data
-    pub unsafe fn fill_random(rng: &mut RngProtocol, buffer: &mut [u8]) -> Status {
+    pub unsafe fn fill_random(rng: &mut RngProtocol, buffer: &mut [u8]) -> Result<()> {
         rng.get_random(buffer)
     }
 
     /// Shuffle a slice using Fisher-Yates algorithm
-    pub unsafe fn shuffle<T>(rng: &mut RngProtocol, slice: &mut [T]) -> Result<(), Status> {
+    pub unsafe fn shuffle<T>(rng: &mut RngProtocol, slice: &mut [T]) -> Result<()> {
         let len = slice.len();
         if len <= 1 {
             return Ok(());
@@ -13,14 +13,17 @@ pub mod loaded_image;
 pub mod misc;
 pub mod mp_services;
 pub mod pci_io;
+pub mod protocol;
 pub mod pxe;
 pub mod security;
+pub mod serial_io;
 pub mod shell;
 pub mod simple_file_system;
 pub mod simple_network;
 pub mod simple_text_input;
 pub mod simple_text_output;
 pub mod storage;
+pub mod tcg2;
 pub mod tcp_udp;
 pub mod usb_io;
 
@@ -36,8 +39,10 @@ pub use loaded_image::*;
 pub use misc::*;
 pub use mp_services::*;
 pub use pci_io::*;
+pub use protocol::Protocol;
 pub use pxe::*;
 pub use security::*;
+pub use serial_io::*;
 // Note: shell::* and simple_file_system::* have overlapping exports (EFI_FILE_* constants)
 // Re-export simple_file_system which has the canonical definitions
 pub use simple_file_system::*;
@@ -45,5 +50,6 @@ pub use simple_network::*;
 pub use simple_text_input::*;
 pub use simple_text_output::*;
 pub use storage::*;
+pub use tcg2::*;
 pub use tcp_udp::*;
 pub use usb_io::*;
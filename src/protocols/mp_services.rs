@@ -1,15 +1,13 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Multi-Processor (MP) Services Protocol
 
+use crate::error::{Error, Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_MP_SERVICES_PROTOCOL_GUID
-pub const MP_SERVICES_PROTOCOL_GUID: Guid = Guid::new(
-    0x3fdda605,
-    0xa76e,
-    0x4f46,
-    [0xad, 0x29, 0x12, 0xf4, 0x53, 0x1b, 0x3d, 0x08],
-);
+pub const MP_SERVICES_PROTOCOL_GUID: Guid = guid!("3fdda605-a76e-4f46-ad29-12f4531b3d08");
 
 /// Processor ID
 pub type ProcessorId = Uintn;
@@ -101,34 +99,28 @@ pub struct MpServicesProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for MpServicesProtocol {
+    const GUID: Guid = MP_SERVICES_PROTOCOL_GUID;
+}
+
 impl MpServicesProtocol {
     /// Get the number of processors and enabled processors
-    pub unsafe fn get_number_of_processors(&mut self) -> Result<(usize, usize), Status> {
+    pub unsafe fn get_number_of_processors(&mut self) -> Result<(usize, usize)> {
         let mut total = 0;
         let mut enabled = 0;
 
-        let status = (self.get_number_of_processors)(self, &mut total, &mut enabled);
-
-        if status == EFI_SUCCESS {
-            Ok((total, enabled))
-        } else {
-            Err(status)
-        }
+        (self.get_number_of_processors)(self, &mut total, &mut enabled).to_result()?;
+        Ok((total, enabled))
     }
 
     /// Get information about a specific processor
     pub unsafe fn get_processor_info(
         &mut self,
         processor_number: usize,
-    ) -> Result<ProcessorInformation, Status> {
+    ) -> Result<ProcessorInformation> {
         let mut info: ProcessorInformation = core::mem::zeroed();
-        let status = (self.get_processor_info)(self, processor_number, &mut info);
-
-        if status == EFI_SUCCESS {
-            Ok(info)
-        } else {
-            Err(status)
-        }
+        (self.get_processor_info)(self, processor_number, &mut info).to_result()?;
+        Ok(info)
     }
 
     /// Execute a procedure on all Application Processors
@@ -138,8 +130,8 @@ impl MpServicesProtocol {
         single_thread: bool,
         timeout_us: usize,
         argument: *mut core::ffi::c_void,
-    ) -> Result<(), Status> {
-        let status = (self.startup_all_aps)(
+    ) -> Result<()> {
+        (self.startup_all_aps)(
             self,
             procedure,
             single_thread as Boolean,
@@ -147,13 +139,8 @@ impl MpServicesProtocol {
             timeout_us,
             argument,
             core::ptr::null_mut(),
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()
     }
 
     /// Execute a procedure on a specific Application Processor
@@ -163,8 +150,8 @@ impl MpServicesProtocol {
         processor_number: usize,
         timeout_us: usize,
         argument: *mut core::ffi::c_void,
-    ) -> Result<(), Status> {
-        let status = (self.startup_this_ap)(
+    ) -> Result<()> {
+        (self.startup_this_ap)(
             self,
             procedure,
             processor_number,
@@ -172,18 +159,13 @@ impl MpServicesProtocol {
             timeout_us,
             argument,
             core::ptr::null_mut(),
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()
     }
 
     /// Switch the Bootstrap Processor
-    pub unsafe fn switch_bsp(&mut self, new_bsp: usize, enable_old_bsp: bool) -> Status {
-        (self.switch_bsp)(self, new_bsp, enable_old_bsp as Boolean)
+    pub unsafe fn switch_bsp(&mut self, new_bsp: usize, enable_old_bsp: bool) -> Result<()> {
+        (self.switch_bsp)(self, new_bsp, enable_old_bsp as Boolean).to_result()
     }
 
     /// Enable or disable an Application Processor
@@ -191,28 +173,18 @@ impl MpServicesProtocol {
         &mut self,
         processor_number: usize,
         enable: bool,
-    ) -> Result<u32, Status> {
+    ) -> Result<u32> {
         let mut health_flag = 0;
-        let status =
-            (self.enable_disable_ap)(self, processor_number, enable as Boolean, &mut health_flag);
-
-        if status == EFI_SUCCESS {
-            Ok(health_flag)
-        } else {
-            Err(status)
-        }
+        (self.enable_disable_ap)(self, processor_number, enable as Boolean, &mut health_flag)
+            .to_result()?;
+        Ok(health_flag)
     }
 
     /// Get the current processor number
-    pub unsafe fn who_am_i(&mut self) -> Result<usize, Status> {
+    pub unsafe fn who_am_i(&mut self) -> Result<usize> {
         let mut processor_number = 0;
-        let status = (self.who_am_i)(self, &mut processor_number);
-
-        if status == EFI_SUCCESS {
-            Ok(processor_number)
-        } else {
-            Err(status)
-        }
+        (self.who_am_i)(self, &mut processor_number).to_result()?;
+        Ok(processor_number)
     }
 
     /// Check if a processor is the BSP
@@ -235,16 +207,16 @@ impl MpServicesProtocol {
 pub mod mp_utils {
     use super::*;
 
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    #[cfg(feature = "std")]
+    use std::vec::Vec;
+
     /// Get all processor information
     pub unsafe fn get_all_processors(
         mp: &mut MpServicesProtocol,
-    ) -> Result<Vec<ProcessorInformation>, Status> {
-        #[cfg(not(feature = "std"))]
-        use alloc::vec::Vec;
-
-        #[cfg(feature = "std")]
-        use std::vec::Vec;
-
+    ) -> Result<Vec<ProcessorInformation>> {
         let (total, _) = mp.get_number_of_processors()?;
         let mut processors = Vec::with_capacity(total);
 
@@ -259,7 +231,7 @@ pub mod mp_utils {
     }
 
     /// Find the BSP processor number
-    pub unsafe fn find_bsp(mp: &mut MpServicesProtocol) -> Result<usize, Status> {
+    pub unsafe fn find_bsp(mp: &mut MpServicesProtocol) -> Result<usize> {
         let processors = get_all_processors(mp)?;
 
         for (i, info) in processors.iter().enumerate() {
@@ -268,19 +240,11 @@ pub mod mp_utils {
             }
         }
 
-        Err(EFI_NOT_FOUND)
+        Err(Error::new(EFI_NOT_FOUND))
     }
 
     /// Get all enabled processor numbers
-    pub unsafe fn get_enabled_processors(
-        mp: &mut MpServicesProtocol,
-    ) -> Result<Vec<usize>, Status> {
-        #[cfg(not(feature = "std"))]
-        use alloc::vec::Vec;
-
-        #[cfg(feature = "std")]
-        use std::vec::Vec;
-
+    pub unsafe fn get_enabled_processors(mp: &mut MpServicesProtocol) -> Result<Vec<usize>> {
         let processors = get_all_processors(mp)?;
         let mut enabled = Vec::new();
 
@@ -299,7 +263,7 @@ pub mod mp_utils {
         procedure: ProcessorProcedure,
         argument: *mut core::ffi::c_void,
         timeout_us: usize,
-    ) -> Result<(), Status> {
+    ) -> Result<()> {
         mp.startup_all_aps(procedure, false, timeout_us, argument)
     }
 
@@ -310,7 +274,7 @@ pub mod mp_utils {
         procedure: ProcessorProcedure,
         argument: *mut core::ffi::c_void,
         timeout_us: usize,
-    ) -> Result<(), Status> {
+    ) -> Result<()> {
         mp.startup_this_ap(procedure, processor_number, timeout_us, argument)
     }
 }
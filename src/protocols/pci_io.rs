@@ -1,15 +1,13 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI PCI I/O Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_PCI_IO_PROTOCOL_GUID
-pub const PCI_IO_PROTOCOL_GUID: Guid = Guid::new(
-    0x4cf5b200,
-    0x68b8,
-    0x4ca5,
-    [0x9e, 0xec, 0xb2, 0x3e, 0x3f, 0x50, 0x02, 0x9a],
-);
+pub const PCI_IO_PROTOCOL_GUID: Guid = guid!("4cf5b200-68b8-4ca5-9eec-b23e3f50029a");
 
 /// EFI_PCI_IO_PROTOCOL_WIDTH
 #[repr(u32)]
@@ -184,30 +182,41 @@ pub struct PciIoProtocol {
     pub rom_image: *mut core::ffi::c_void,
 }
 
+unsafe impl Protocol for PciIoProtocol {
+    const GUID: Guid = PCI_IO_PROTOCOL_GUID;
+}
+
 impl PciIoProtocol {
     /// Get PCI location
-    pub unsafe fn get_location(&mut self) -> Result<(usize, usize, usize, usize), Status> {
+    pub unsafe fn get_location(&mut self) -> Result<(usize, usize, usize, usize)> {
         let mut segment = 0;
         let mut bus = 0;
         let mut device = 0;
         let mut function = 0;
-        let status = (self.get_location)(self, &mut segment, &mut bus, &mut device, &mut function);
-        if status == EFI_SUCCESS {
-            Ok((segment, bus, device, function))
-        } else {
-            Err(status)
-        }
+        (self.get_location)(self, &mut segment, &mut bus, &mut device, &mut function)
+            .to_result()?;
+        Ok((segment, bus, device, function))
     }
 
     /// Read from PCI configuration space
-    pub unsafe fn pci_read(&mut self, width: PciIoWidth, offset: u32, buffer: &mut [u8]) -> Status {
+    pub unsafe fn pci_read(
+        &mut self,
+        width: PciIoWidth,
+        offset: u32,
+        buffer: &mut [u8],
+    ) -> Result<()> {
         let count = buffer.len() / (width as usize + 1);
-        (self.pci.read)(self, width, offset, count, buffer.as_mut_ptr() as *mut _)
+        (self.pci.read)(self, width, offset, count, buffer.as_mut_ptr() as *mut _).to_result()
     }
 
     /// Write to PCI configuration space
-    pub unsafe fn pci_write(&mut self, width: PciIoWidth, offset: u32, buffer: &[u8]) -> Status {
+    pub unsafe fn pci_write(
+        &mut self,
+        width: PciIoWidth,
+        offset: u32,
+        buffer: &[u8],
+    ) -> Result<()> {
         let count = buffer.len() / (width as usize + 1);
-        (self.pci.write)(self, width, offset, count, buffer.as_ptr() as *mut _)
+        (self.pci.write)(self, width, offset, count, buffer.as_ptr() as *mut _).to_result()
     }
 }
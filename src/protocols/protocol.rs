@@ -0,0 +1,18 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Protocol GUID association
+
+use crate::ffi::Guid;
+
+/// A UEFI protocol interface identified by a GUID
+///
+/// Implemented for each protocol struct so generic Boot Services helpers
+/// can look up the GUID instead of callers pairing the constant and the
+/// interface type by hand.
+///
+/// # Safety
+/// `GUID` must identify an interface whose layout is exactly `Self`;
+/// opening a protocol casts the firmware's interface pointer to `*mut Self`.
+pub unsafe trait Protocol {
+    /// The protocol GUID
+    const GUID: Guid;
+}
@@ -1,24 +1,18 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI PXE (Preboot eXecution Environment) Base Code Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
 use crate::protocols::tcp_udp::Ipv4Address;
+use crate::protocols::Protocol;
 
 /// EFI_PXE_BASE_CODE_PROTOCOL_GUID
-pub const PXE_BASE_CODE_PROTOCOL_GUID: Guid = Guid::new(
-    0x03c4e603,
-    0xac28,
-    0x11d3,
-    [0x9a, 0x2d, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
-);
+pub const PXE_BASE_CODE_PROTOCOL_GUID: Guid = guid!("03c4e603-ac28-11d3-9a2d-0090273fc14d");
 
 /// EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID
-pub const PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID: Guid = Guid::new(
-    0x245dca21,
-    0xfb7b,
-    0x11d3,
-    [0x8f, 0x01, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID: Guid =
+    guid!("245dca21-fb7b-11d3-8f01-00a0c969723b");
 
 /// PXE Packet Type
 #[repr(u32)]
@@ -244,6 +238,10 @@ pub struct PxeBaseCodeProtocol {
     pub mode: *mut PxeMode,
 }
 
+unsafe impl Protocol for PxeBaseCodeProtocol {
+    const GUID: Guid = PXE_BASE_CODE_PROTOCOL_GUID;
+}
+
 /// PXE Callback Event Type
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -284,20 +282,24 @@ pub struct PxeBaseCodeCallbackProtocol {
     ) -> PxeBaseCodeCallbackStatus,
 }
 
+unsafe impl Protocol for PxeBaseCodeCallbackProtocol {
+    const GUID: Guid = PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID;
+}
+
 impl PxeBaseCodeProtocol {
     /// Start the PXE Base Code
-    pub unsafe fn start(&mut self, use_ipv6: bool) -> Status {
-        (self.start)(self, use_ipv6 as Boolean)
+    pub unsafe fn start(&mut self, use_ipv6: bool) -> Result<()> {
+        (self.start)(self, use_ipv6 as Boolean).to_result()
     }
 
     /// Stop the PXE Base Code
-    pub unsafe fn stop(&mut self) -> Status {
-        (self.stop)(self)
+    pub unsafe fn stop(&mut self) -> Result<()> {
+        (self.stop)(self).to_result()
     }
 
     /// Perform DHCP discovery
-    pub unsafe fn dhcp(&mut self, sort_offers: bool) -> Status {
-        (self.dhcp)(self, sort_offers as Boolean)
+    pub unsafe fn dhcp(&mut self, sort_offers: bool) -> Result<()> {
+        (self.dhcp)(self, sort_offers as Boolean).to_result()
     }
 
     /// Perform PXE server discovery
@@ -306,7 +308,7 @@ impl PxeBaseCodeProtocol {
         boot_type: u16,
         layer: Option<&mut u16>,
         bis_reply: bool,
-    ) -> Status {
+    ) -> Result<()> {
         let layer_ptr = layer.map(|l| l as *mut _).unwrap_or(core::ptr::null_mut());
         (self.discover)(
             self,
@@ -315,6 +317,7 @@ impl PxeBaseCodeProtocol {
             bis_reply as Boolean,
             core::ptr::null_mut(),
         )
+        .to_result()
     }
 
     /// Read data via UDP
@@ -324,11 +327,11 @@ impl PxeBaseCodeProtocol {
         src_ip: &mut PxeIpAddress,
         src_port: &mut u16,
         buffer: &mut [u8],
-    ) -> Result<usize, Status> {
+    ) -> Result<usize> {
         let mut buffer_size = buffer.len();
         let mut dest_ip = core::mem::zeroed();
 
-        let status = (self.udp_read)(
+        (self.udp_read)(
             self,
             0,
             &mut dest_ip,
@@ -339,13 +342,9 @@ impl PxeBaseCodeProtocol {
             core::ptr::null_mut(),
             &mut buffer_size,
             buffer.as_mut_ptr() as *mut _,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(buffer_size)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(buffer_size)
     }
 
     /// Write data via UDP
@@ -354,11 +353,11 @@ impl PxeBaseCodeProtocol {
         dest_ip: &PxeIpAddress,
         dest_port: u16,
         buffer: &[u8],
-    ) -> Result<usize, Status> {
+    ) -> Result<usize> {
         let mut buffer_size = buffer.len();
         let mut port = dest_port;
 
-        let status = (self.udp_write)(
+        (self.udp_write)(
             self,
             0,
             dest_ip as *const _,
@@ -370,25 +369,16 @@ impl PxeBaseCodeProtocol {
             core::ptr::null_mut(),
             &mut buffer_size,
             buffer.as_ptr() as *mut _,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(buffer_size)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(buffer_size)
     }
 
     /// Resolve IP address to MAC address via ARP
-    pub unsafe fn arp(&mut self, ip_addr: &PxeIpAddress) -> Result<PxeMacAddress, Status> {
+    pub unsafe fn arp(&mut self, ip_addr: &PxeIpAddress) -> Result<PxeMacAddress> {
         let mut mac_addr: PxeMacAddress = core::mem::zeroed();
-        let status = (self.arp)(self, ip_addr as *const _, &mut mac_addr);
-
-        if status == EFI_SUCCESS {
-            Ok(mac_addr)
-        } else {
-            Err(status)
-        }
+        (self.arp)(self, ip_addr as *const _, &mut mac_addr).to_result()?;
+        Ok(mac_addr)
     }
 
     /// Get mode data
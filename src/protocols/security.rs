@@ -1,23 +1,18 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Security Protocols - Secure Boot and Authentication
 
+use crate::crypto::hash::{HashAlgorithm, HashValue};
+use crate::error::{Error, Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::simple_file_system::FileProtocol;
+use crate::protocols::Protocol;
 
 /// EFI_SECURITY_ARCH_PROTOCOL_GUID
-pub const SECURITY_ARCH_PROTOCOL_GUID: Guid = Guid::new(
-    0xA46423E3,
-    0x4617,
-    0x49f1,
-    [0xB9, 0xFF, 0xD1, 0xBF, 0xA9, 0x11, 0x58, 0x39],
-);
+pub const SECURITY_ARCH_PROTOCOL_GUID: Guid = guid!("a46423e3-4617-49f1-b9ff-d1bfa9115839");
 
 /// EFI_SECURITY2_ARCH_PROTOCOL_GUID
-pub const SECURITY2_ARCH_PROTOCOL_GUID: Guid = Guid::new(
-    0x94ab2f58,
-    0x1438,
-    0x4ef1,
-    [0x91, 0x52, 0x18, 0x94, 0x1a, 0x3a, 0x0e, 0x68],
-);
+pub const SECURITY2_ARCH_PROTOCOL_GUID: Guid = guid!("94ab2f58-1438-4ef1-9152-18941a3a0e68");
 
 /// EFI_SECURITY2_ARCH_PROTOCOL
 #[repr(C)]
@@ -31,54 +26,47 @@ pub struct Security2ArchProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for Security2ArchProtocol {
+    const GUID: Guid = SECURITY2_ARCH_PROTOCOL_GUID;
+}
+
 /// EFI_HASH_ALGORITHM_SHA1_GUID
-pub const HASH_ALGORITHM_SHA1_GUID: Guid = Guid::new(
-    0x2ae9d80f,
-    0x3fb2,
-    0x4095,
-    [0xb7, 0xb1, 0xe9, 0x31, 0x57, 0xb9, 0x46, 0xb6],
-);
+pub const HASH_ALGORITHM_SHA1_GUID: Guid = guid!("2ae9d80f-3fb2-4095-b7b1-e93157b946b6");
 
 /// EFI_HASH_ALGORITHM_SHA256_GUID
-pub const HASH_ALGORITHM_SHA256_GUID: Guid = Guid::new(
-    0x51aa59de,
-    0xfdf2,
-    0x4ea3,
-    [0xbc, 0x63, 0x87, 0x5f, 0xb7, 0x84, 0x2e, 0xe9],
-);
+pub const HASH_ALGORITHM_SHA256_GUID: Guid = guid!("51aa59de-fdf2-4ea3-bc63-875fb7842ee9");
 
 /// EFI_HASH_ALGORITHM_SHA384_GUID
-pub const HASH_ALGORITHM_SHA384_GUID: Guid = Guid::new(
-    0xefa96432,
-    0xde33,
-    0x4dd2,
-    [0xae, 0xe6, 0x32, 0x8c, 0x33, 0xdf, 0x77, 0x7a],
-);
+pub const HASH_ALGORITHM_SHA384_GUID: Guid = guid!("efa96432-de33-4dd2-aee6-328c33df777a");
 
 /// EFI_HASH_ALGORITHM_SHA512_GUID
-pub const HASH_ALGORITHM_SHA512_GUID: Guid = Guid::new(
-    0xcaa4381e,
-    0x750c,
-    0x4770,
-    [0xb8, 0x70, 0x7a, 0x23, 0xb4, 0xe4, 0x21, 0x30],
-);
+pub const HASH_ALGORITHM_SHA512_GUID: Guid = guid!("caa4381e-750c-4770-b870-7a23b4e42130");
 
 /// EFI_HASH_PROTOCOL_GUID
-pub const HASH_PROTOCOL_GUID: Guid = Guid::new(
-    0xC5184932,
-    0xDBA5,
-    0x46DB,
-    [0xA5, 0xBA, 0xCC, 0x0B, 0xDA, 0x9C, 0x14, 0x35],
-);
+pub const HASH_PROTOCOL_GUID: Guid = guid!("c5184932-dba5-46db-a5ba-cc0bda9c1435");
 
 /// EFI_HASH_OUTPUT
+///
+/// Each member points to a caller-allocated digest buffer; with `extend`
+/// set it must hold the hash being extended.
 #[repr(C)]
+#[derive(Copy, Clone)]
 pub union HashOutput {
-    pub sha1_hash: [u8; 20],
-    pub sha224_hash: [u8; 28],
-    pub sha256_hash: [u8; 32],
-    pub sha384_hash: [u8; 48],
-    pub sha512_hash: [u8; 64],
+    pub md5_hash: *mut [u8; 16],
+    pub sha1_hash: *mut [u8; 20],
+    pub sha224_hash: *mut [u8; 28],
+    pub sha256_hash: *mut [u8; 32],
+    pub sha384_hash: *mut [u8; 48],
+    pub sha512_hash: *mut [u8; 64],
+}
+
+impl HashOutput {
+    /// Point every member at `digest`, which fits the largest hash
+    pub fn new(digest: &mut [u8; 64]) -> Self {
+        HashOutput {
+            sha512_hash: digest,
+        }
+    }
 }
 
 /// EFI_HASH_PROTOCOL
@@ -99,52 +87,165 @@ pub struct HashProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for HashProtocol {
+    const GUID: Guid = HASH_PROTOCOL_GUID;
+}
+
 impl HashProtocol {
     /// Get the size of a hash
-    pub unsafe fn get_hash_size(&mut self, algorithm: &Guid) -> Result<usize, Status> {
+    pub unsafe fn get_hash_size(&mut self, algorithm: &Guid) -> Result<usize> {
         let mut size = 0;
-        let status = (self.get_hash_size)(self, algorithm as *const _, &mut size);
-
-        if status == EFI_SUCCESS {
-            Ok(size)
-        } else {
-            Err(status)
-        }
+        (self.get_hash_size)(self, algorithm as *const _, &mut size).to_result()?;
+        Ok(size)
     }
 
-    /// Compute a hash
+    /// Compute a hash into `digest`
+    ///
+    /// With `extend` set, `digest` must hold the hash to extend. Only the
+    /// first `get_hash_size()` bytes are written.
     pub unsafe fn hash(
         &mut self,
         algorithm: &Guid,
         message: &[u8],
         extend: bool,
-    ) -> Result<HashOutput, Status> {
-        let mut hash_output = core::mem::zeroed();
+        digest: &mut [u8; 64],
+    ) -> Result<()> {
+        let mut hash_output = HashOutput::new(digest);
 
-        let status = (self.hash)(
+        (self.hash)(
             self,
             algorithm as *const _,
             extend as Boolean,
             message.as_ptr(),
             message.len() as u64,
             &mut hash_output,
-        );
+        )
+        .to_result()
+    }
+}
 
-        if status == EFI_SUCCESS {
-            Ok(hash_output)
-        } else {
-            Err(status)
-        }
+/// EFI_HASH2_SERVICE_BINDING_PROTOCOL_GUID
+pub const HASH2_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("da836f8d-217f-4ca0-99c2-1ca4e16077ea");
+
+/// EFI_HASH2_PROTOCOL_GUID
+pub const HASH2_PROTOCOL_GUID: Guid = guid!("55b1d734-c5e1-49db-9647-b16afb0e305b");
+
+/// EFI_SERVICE_BINDING_PROTOCOL for EFI_HASH2_PROTOCOL
+#[repr(C)]
+pub struct Hash2ServiceBinding {
+    pub create_child: unsafe extern "efiapi" fn(
+        this: *mut Hash2ServiceBinding,
+        child_handle: *mut *mut Handle,
+    ) -> Status,
+    pub destroy_child: unsafe extern "efiapi" fn(
+        this: *mut Hash2ServiceBinding,
+        child_handle: *mut Handle,
+    ) -> Status,
+}
+
+unsafe impl Protocol for Hash2ServiceBinding {
+    const GUID: Guid = HASH2_SERVICE_BINDING_PROTOCOL_GUID;
+}
+
+impl Hash2ServiceBinding {
+    /// Create a child handle with a new EFI_HASH2_PROTOCOL instance
+    pub unsafe fn create_child(&mut self) -> Result<*mut Handle> {
+        let mut child = core::ptr::null_mut();
+        (self.create_child)(self, &mut child).to_result()?;
+        Ok(child)
+    }
+
+    /// Destroy a child created by [`Self::create_child`]
+    pub unsafe fn destroy_child(&mut self, child: *mut Handle) -> Result<()> {
+        (self.destroy_child)(self, child).to_result()
+    }
+}
+
+/// EFI_HASH2_OUTPUT
+#[repr(C)]
+pub union Hash2Output {
+    pub md5_hash: [u8; 16],
+    pub sha1_hash: [u8; 20],
+    pub sha224_hash: [u8; 28],
+    pub sha256_hash: [u8; 32],
+    pub sha384_hash: [u8; 48],
+    pub sha512_hash: [u8; 64],
+}
+
+/// EFI_HASH2_PROTOCOL
+#[repr(C)]
+pub struct Hash2Protocol {
+    pub get_hash_size: unsafe extern "efiapi" fn(
+        this: *const Hash2Protocol,
+        hash_algorithm: *const Guid,
+        hash_size: *mut Uintn,
+    ) -> Status,
+    pub hash: unsafe extern "efiapi" fn(
+        this: *const Hash2Protocol,
+        hash_algorithm: *const Guid,
+        message: *const Uint8,
+        message_size: Uintn,
+        hash: *mut Hash2Output,
+    ) -> Status,
+    pub hash_init: unsafe extern "efiapi" fn(
+        this: *const Hash2Protocol,
+        hash_algorithm: *const Guid,
+    ) -> Status,
+    pub hash_update: unsafe extern "efiapi" fn(
+        this: *const Hash2Protocol,
+        message: *const Uint8,
+        message_size: Uintn,
+    ) -> Status,
+    pub hash_final:
+        unsafe extern "efiapi" fn(this: *const Hash2Protocol, hash: *mut Hash2Output) -> Status,
+}
+
+unsafe impl Protocol for Hash2Protocol {
+    const GUID: Guid = HASH2_PROTOCOL_GUID;
+}
+
+impl Hash2Protocol {
+    /// Get the size of a hash
+    pub unsafe fn get_hash_size(&self, algorithm: &Guid) -> Result<usize> {
+        let mut size = 0;
+        (self.get_hash_size)(self, algorithm, &mut size).to_result()?;
+        Ok(size)
+    }
+
+    /// Hash a complete message
+    pub unsafe fn hash(&self, algorithm: &Guid, message: &[u8]) -> Result<Hash2Output> {
+        let mut output = core::mem::zeroed();
+        (self.hash)(
+            self,
+            algorithm,
+            message.as_ptr(),
+            message.len(),
+            &mut output,
+        )
+        .to_result()?;
+        Ok(output)
+    }
+
+    /// Start an incremental hash
+    pub unsafe fn hash_init(&self, algorithm: &Guid) -> Result<()> {
+        (self.hash_init)(self, algorithm).to_result()
+    }
+
+    /// Add data to the hash started by [`Self::hash_init`]
+    pub unsafe fn hash_update(&self, message: &[u8]) -> Result<()> {
+        (self.hash_update)(self, message.as_ptr(), message.len()).to_result()
+    }
+
+    /// Finish the incremental hash
+    pub unsafe fn hash_final(&self) -> Result<Hash2Output> {
+        let mut output = core::mem::zeroed();
+        (self.hash_final)(self, &mut output).to_result()?;
+        Ok(output)
     }
 }
 
 /// EFI_PKCS7_VERIFY_PROTOCOL_GUID
-pub const PKCS7_VERIFY_PROTOCOL_GUID: Guid = Guid::new(
-    0x47889fb2,
-    0xd671,
-    0x4fab,
-    [0xa0, 0xca, 0xdf, 0x0e, 0x44, 0xdf, 0x70, 0xd6],
-);
+pub const PKCS7_VERIFY_PROTOCOL_GUID: Guid = guid!("47889fb2-d671-4fab-a0ca-df0e44df70d6");
 
 /// EFI_PKCS7_VERIFY_PROTOCOL
 #[repr(C)]
@@ -169,6 +270,10 @@ pub struct Pkcs7VerifyProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for Pkcs7VerifyProtocol {
+    const GUID: Guid = PKCS7_VERIFY_PROTOCOL_GUID;
+}
+
 impl Pkcs7VerifyProtocol {
     /// Verify a PKCS7 signature on a hash
     pub unsafe fn verify_buffer(
@@ -176,7 +281,7 @@ impl Pkcs7VerifyProtocol {
         p7_data: &[u8],
         trusted_cert: &[u8],
         image_hash: &[u8],
-    ) -> Status {
+    ) -> Result<()> {
         (self.verify_buffer)(
             self,
             p7_data.as_ptr() as *const _,
@@ -186,6 +291,7 @@ impl Pkcs7VerifyProtocol {
             image_hash.as_ptr() as *const _,
             image_hash.len(),
         )
+        .to_result()
     }
 
     /// Verify a PKCS7 signature on data
@@ -194,7 +300,7 @@ impl Pkcs7VerifyProtocol {
         p7_data: &[u8],
         trusted_cert: &[u8],
         data: &[u8],
-    ) -> Status {
+    ) -> Result<()> {
         (self.verify_signature)(
             self,
             p7_data.as_ptr() as *const _,
@@ -204,6 +310,7 @@ impl Pkcs7VerifyProtocol {
             data.as_ptr() as *const _,
             data.len(),
         )
+        .to_result()
     }
 }
 
@@ -256,38 +363,66 @@ pub struct SignatureList {
 }
 
 /// Certificate Type GUIDs
-pub const CERT_SHA256_GUID: Guid = Guid::new(
-    0xc1c41626,
-    0x504c,
-    0x4092,
-    [0xac, 0xa9, 0x41, 0xf9, 0x36, 0x93, 0x43, 0x28],
-);
-
-pub const CERT_RSA2048_GUID: Guid = Guid::new(
-    0x3c5766e8,
-    0x269c,
-    0x4e34,
-    [0xaa, 0x14, 0xed, 0x77, 0x6e, 0x85, 0xb3, 0xb6],
-);
-
-pub const CERT_X509_GUID: Guid = Guid::new(
-    0xa5c059a1,
-    0x94e4,
-    0x4aa7,
-    [0x87, 0xb5, 0xab, 0x15, 0x5c, 0x2b, 0xf0, 0x72],
-);
+pub const CERT_SHA256_GUID: Guid = guid!("c1c41626-504c-4092-aca9-41f936934328");
+
+pub const CERT_RSA2048_GUID: Guid = guid!("3c5766e8-269c-4e34-aa14-ed776e85b3b6");
+
+pub const CERT_X509_GUID: Guid = guid!("a5c059a1-94e4-4aa7-87b5-ab155c2bf072");
+
+pub const CERT_SHA1_GUID: Guid = guid!("826ca512-cf10-4ac9-b187-be01496631bd");
+
+pub const CERT_SHA224_GUID: Guid = guid!("0b6e5233-a65c-44c9-9407-d9ab83bfc8bd");
+
+pub const CERT_SHA384_GUID: Guid = guid!("ff3e5307-9fd0-48c9-85f1-8ad56c701e01");
+
+pub const CERT_SHA512_GUID: Guid = guid!("093e0fae-a6c4-4f50-9f1b-d41e2b89c19a");
+
+pub const CERT_RSA2048_SHA1_GUID: Guid = guid!("67f8444f-8743-48f1-a328-1eaab8736080");
+
+pub const CERT_RSA2048_SHA256_GUID: Guid = guid!("e2b36190-879b-4a3d-ad8d-f2e7bba32784");
+
+/// X.509 TBSCertificate hashes, each followed by an EFI_TIME revocation time
+pub const CERT_X509_SHA256_GUID: Guid = guid!("3bd2a492-96c0-4079-b420-fcf98ef103ed");
+
+pub const CERT_X509_SHA384_GUID: Guid = guid!("7076876e-80c2-4ee6-aad2-28b349a6865b");
+
+pub const CERT_X509_SHA512_GUID: Guid = guid!("446dbf63-2502-4cda-bcfa-2465d2b0fe9d");
+
+/// EFI_CERT_TYPE_PKCS7_GUID
+pub const CERT_TYPE_PKCS7_GUID: Guid = guid!("4aafd29d-68df-49ee-8aa9-347d375665a7");
+
+// WIN_CERTIFICATE revisions and types
+pub const WIN_CERT_REVISION_1_0: u16 = 0x0100;
+pub const WIN_CERT_REVISION_2_0: u16 = 0x0200;
+pub const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+pub const WIN_CERT_TYPE_EFI_PKCS115: u16 = 0x0EF0;
+pub const WIN_CERT_TYPE_EFI_GUID: u16 = 0x0EF1;
+
+/// WIN_CERTIFICATE
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct WinCertificate {
+    /// Length of the whole certificate including this header
+    pub length: Uint32,
+    pub revision: Uint16,
+    pub certificate_type: Uint16,
+}
+
+/// WIN_CERTIFICATE_UEFI_GUID
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct WinCertificateUefiGuid {
+    pub hdr: WinCertificate,
+    pub cert_type: Guid,
+    // Followed by CertData[variable length]
+}
 
 // ============================================================================
 // TPM 2.0 Protocol
 // ============================================================================
 
 /// EFI_TPM2_PROTOCOL_GUID
-pub const TPM2_PROTOCOL_GUID: Guid = Guid::new(
-    0x607f766c,
-    0x7455,
-    0x42be,
-    [0x93, 0x0b, 0xe4, 0xd7, 0x6d, 0xb2, 0x72, 0x0f],
-);
+pub const TPM2_PROTOCOL_GUID: Guid = guid!("607f766c-7455-42be-930b-e4d76db2720f");
 
 /// TPM2 Submit Command
 pub type Tpm2SubmitCommand = unsafe extern "efiapi" fn(
@@ -327,6 +462,10 @@ pub struct Tpm2Protocol {
     pub get_active_pcr_banks: Tpm2GetActivePcrBanks,
 }
 
+unsafe impl Protocol for Tpm2Protocol {
+    const GUID: Guid = TPM2_PROTOCOL_GUID;
+}
+
 /// TPM2 Boot Service Capability
 #[repr(C)]
 pub struct Tpm2BootServiceCapability {
@@ -369,12 +508,21 @@ pub struct Tpm2ResponseHeader {
 /// TPM2 Command Codes
 pub mod tpm2_commands {
     pub const TPM_ST_NO_SESSIONS: u16 = 0x8001;
+    pub const TPM_ST_SESSIONS: u16 = 0x8002;
+    pub const TPM_CC_NV_WRITE: u32 = 0x00000137;
+    pub const TPM_CC_SEQUENCE_COMPLETE: u32 = 0x0000013E;
     pub const TPM_CC_STARTUP: u32 = 0x00000144;
     pub const TPM_CC_SELF_TEST: u32 = 0x00000143;
+    pub const TPM_CC_NV_READ: u32 = 0x0000014E;
+    pub const TPM_CC_SEQUENCE_UPDATE: u32 = 0x0000015C;
+    pub const TPM_CC_FLUSH_CONTEXT: u32 = 0x00000165;
+    pub const TPM_CC_NV_READ_PUBLIC: u32 = 0x00000169;
+    pub const TPM_CC_READ_PUBLIC: u32 = 0x00000173;
     pub const TPM_CC_GET_CAPABILITY: u32 = 0x0000017A;
     pub const TPM_CC_PCR_READ: u32 = 0x0000017E;
     pub const TPM_CC_PCR_EXTEND: u32 = 0x00000182;
     pub const TPM_CC_GET_RANDOM: u32 = 0x0000017B;
+    pub const TPM_CC_HASH_SEQUENCE_START: u32 = 0x00000186;
 
     pub const TPM_SU_CLEAR: u16 = 0x0000;
     pub const TPM_SU_STATE: u16 = 0x0001;
@@ -387,14 +535,21 @@ pub mod tpm2_commands {
 /// Secure Boot helper functions
 pub mod secure_boot {
     use super::*;
-    use crate::runtime_services::Variable;
     use crate::runtime_services::EFI_IMAGE_SECURITY_DATABASE_GUID;
+    use crate::runtime_services::{Variable, VariableAttributes};
+    use crate::signature_database::SignatureDatabase;
+    use crate::string::CStr16;
+
+    // The name constants above are NUL-terminated UCS-2
+    fn variable_name(name: &'static [u16]) -> &'static CStr16 {
+        unsafe { CStr16::from_u16_with_nul_unchecked(name) }
+    }
 
     /// Check if Secure Boot is enabled
     pub unsafe fn is_secure_boot_enabled(vars: &Variable) -> bool {
         let mut buffer = [0u8; 1];
         if let Ok((_, size)) = vars.get(
-            SECURE_BOOT_MODE_VARIABLE.as_ptr(),
+            variable_name(SECURE_BOOT_MODE_VARIABLE),
             &crate::runtime_services::EFI_GLOBAL_VARIABLE_GUID,
             &mut buffer,
         ) {
@@ -406,12 +561,9 @@ pub mod secure_boot {
 
     /// Check if system is in Setup Mode
     pub unsafe fn is_setup_mode(vars: &Variable) -> bool {
-        let setup_mode_var: &[u16] = &[
-            0x0053, 0x0065, 0x0074, 0x0075, 0x0070, 0x004D, 0x006F, 0x0064, 0x0065, 0x0000,
-        ]; // "SetupMode\0"
         let mut buffer = [0u8; 1];
         if let Ok((_, size)) = vars.get(
-            setup_mode_var.as_ptr(),
+            crate::cstr16!("SetupMode"),
             &crate::runtime_services::EFI_GLOBAL_VARIABLE_GUID,
             &mut buffer,
         ) {
@@ -425,18 +577,21 @@ pub mod secure_boot {
     pub unsafe fn get_platform_key(
         vars: &Variable,
         buffer: &mut [u8],
-    ) -> Result<(u32, usize), Status> {
+    ) -> Result<(VariableAttributes, usize)> {
         vars.get(
-            PLATFORM_KEY_VARIABLE.as_ptr(),
+            variable_name(PLATFORM_KEY_VARIABLE),
             &EFI_IMAGE_SECURITY_DATABASE_GUID,
             buffer,
         )
     }
 
     /// Get Key Exchange Keys (KEK)
-    pub unsafe fn get_kek(vars: &Variable, buffer: &mut [u8]) -> Result<(u32, usize), Status> {
+    pub unsafe fn get_kek(
+        vars: &Variable,
+        buffer: &mut [u8],
+    ) -> Result<(VariableAttributes, usize)> {
         vars.get(
-            KEY_EXCHANGE_KEY_VARIABLE.as_ptr(),
+            variable_name(KEY_EXCHANGE_KEY_VARIABLE),
             &EFI_IMAGE_SECURITY_DATABASE_GUID,
             buffer,
         )
@@ -446,9 +601,9 @@ pub mod secure_boot {
     pub unsafe fn get_signature_database(
         vars: &Variable,
         buffer: &mut [u8],
-    ) -> Result<(u32, usize), Status> {
+    ) -> Result<(VariableAttributes, usize)> {
         vars.get(
-            IMAGE_SECURITY_DATABASE_VARIABLE.as_ptr(),
+            variable_name(IMAGE_SECURITY_DATABASE_VARIABLE),
             &EFI_IMAGE_SECURITY_DATABASE_GUID,
             buffer,
         )
@@ -458,9 +613,9 @@ pub mod secure_boot {
     pub unsafe fn get_forbidden_database(
         vars: &Variable,
         buffer: &mut [u8],
-    ) -> Result<(u32, usize), Status> {
+    ) -> Result<(VariableAttributes, usize)> {
         vars.get(
-            IMAGE_SECURITY_DATABASE1_VARIABLE.as_ptr(),
+            variable_name(IMAGE_SECURITY_DATABASE1_VARIABLE),
             &EFI_IMAGE_SECURITY_DATABASE_GUID,
             buffer,
         )
@@ -470,51 +625,37 @@ pub mod secure_boot {
     pub unsafe fn get_timestamp_database(
         vars: &Variable,
         buffer: &mut [u8],
-    ) -> Result<(u32, usize), Status> {
+    ) -> Result<(VariableAttributes, usize)> {
         vars.get(
-            IMAGE_SECURITY_DATABASE2_VARIABLE.as_ptr(),
+            variable_name(IMAGE_SECURITY_DATABASE2_VARIABLE),
             &EFI_IMAGE_SECURITY_DATABASE_GUID,
             buffer,
         )
     }
 
     /// Check if a hash exists in signature database
+    ///
+    /// A database variable that does not exist holds no hashes. Fails if the
+    /// variable cannot be read or any of its signature lists is malformed.
     pub unsafe fn is_hash_in_database(
         vars: &Variable,
         hash: &[u8],
-        database_name: *const u16,
-    ) -> bool {
-        let mut buffer = [0u8; 4096];
-        if let Ok((_, size)) = vars.get(
-            database_name,
-            &EFI_IMAGE_SECURITY_DATABASE_GUID,
-            &mut buffer,
-        ) {
-            let data = &buffer[..size];
-            for sig_list in SignatureListIter::new(data) {
-                if sig_list.signature_type == CERT_SHA256_GUID && hash.len() == 32 {
-                    let sig_count = (sig_list.signature_list_size as usize
-                        - core::mem::size_of::<SignatureList>()
-                        - sig_list.signature_header_size as usize)
-                        / sig_list.signature_size as usize;
-
-                    let sig_data_offset = core::mem::size_of::<SignatureList>()
-                        + sig_list.signature_header_size as usize;
-
-                    for i in 0..sig_count {
-                        let sig_offset = sig_data_offset + (i * sig_list.signature_size as usize);
-                        let sig_ptr = (sig_list as *const _ as *const u8)
-                            .add(sig_offset + core::mem::size_of::<Guid>());
-                        let sig_hash = core::slice::from_raw_parts(sig_ptr, 32);
-
-                        if sig_hash == hash {
-                            return true;
-                        }
-                    }
-                }
-            }
+        database_name: &CStr16,
+    ) -> Result<bool> {
+        match read_signature_database(vars, database_name) {
+            Ok(db) => Ok(db.contains_hash(hash)),
+            Err(err) if err.status() == EFI_NOT_FOUND => Ok(false),
+            Err(err) => Err(err),
         }
-        false
+    }
+
+    /// Read and parse a signature database variable such as `db` or `dbx`
+    pub unsafe fn read_signature_database(
+        vars: &Variable,
+        name: &CStr16,
+    ) -> Result<SignatureDatabase> {
+        let (_, data) = vars.get_vec(name, &EFI_IMAGE_SECURITY_DATABASE_GUID)?;
+        SignatureDatabase::parse(&data)
     }
 
     /// Enumerate signature database
@@ -572,15 +713,10 @@ impl<'a> SafePkcs7Verify<'a> {
         p7_data: &[u8],
         trusted_cert: &[u8],
         image_hash: &[u8],
-    ) -> Result<(), Status> {
-        let status = unsafe {
+    ) -> Result<()> {
+        unsafe {
             self.protocol
                 .verify_buffer(p7_data, trusted_cert, image_hash)
-        };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
         }
     }
 
@@ -590,13 +726,8 @@ impl<'a> SafePkcs7Verify<'a> {
         p7_data: &[u8],
         trusted_cert: &[u8],
         data: &[u8],
-    ) -> Result<(), Status> {
-        let status = unsafe { self.protocol.verify_signature(p7_data, trusted_cert, data) };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+    ) -> Result<()> {
+        unsafe { self.protocol.verify_signature(p7_data, trusted_cert, data) }
     }
 }
 
@@ -612,31 +743,111 @@ impl<'a> SafeHashProtocol<'a> {
     }
 
     /// Get hash size for an algorithm
-    pub fn get_hash_size(&mut self, algorithm: &Guid) -> Result<usize, Status> {
+    pub fn get_hash_size(&mut self, algorithm: &Guid) -> Result<usize> {
         unsafe { self.protocol.get_hash_size(algorithm) }
     }
 
     /// Compute a hash
-    pub fn hash(&mut self, algorithm: &Guid, message: &[u8]) -> Result<HashOutput, Status> {
-        unsafe { self.protocol.hash(algorithm, message, false) }
+    ///
+    /// The digest occupies the first `get_hash_size()` bytes.
+    pub fn hash(&mut self, algorithm: &Guid, message: &[u8]) -> Result<[u8; 64]> {
+        let mut digest = [0u8; 64];
+        unsafe { self.protocol.hash(algorithm, message, false, &mut digest)? };
+        Ok(digest)
     }
 
     /// Compute SHA256 hash
-    pub fn sha256(&mut self, message: &[u8]) -> Result<[u8; 32], Status> {
-        let output = self.hash(&HASH_ALGORITHM_SHA256_GUID, message)?;
-        Ok(unsafe { output.sha256_hash })
+    pub fn sha256(&mut self, message: &[u8]) -> Result<[u8; 32]> {
+        let digest = self.hash(&HASH_ALGORITHM_SHA256_GUID, message)?;
+        Ok(digest[..32].try_into().unwrap())
     }
 
     /// Compute SHA384 hash
-    pub fn sha384(&mut self, message: &[u8]) -> Result<[u8; 48], Status> {
-        let output = self.hash(&HASH_ALGORITHM_SHA384_GUID, message)?;
-        Ok(unsafe { output.sha384_hash })
+    pub fn sha384(&mut self, message: &[u8]) -> Result<[u8; 48]> {
+        let digest = self.hash(&HASH_ALGORITHM_SHA384_GUID, message)?;
+        Ok(digest[..48].try_into().unwrap())
     }
 
     /// Compute SHA512 hash
-    pub fn sha512(&mut self, message: &[u8]) -> Result<[u8; 64], Status> {
-        let output = self.hash(&HASH_ALGORITHM_SHA512_GUID, message)?;
-        Ok(unsafe { output.sha512_hash })
+    pub fn sha512(&mut self, message: &[u8]) -> Result<[u8; 64]> {
+        self.hash(&HASH_ALGORITHM_SHA512_GUID, message)
+    }
+}
+
+/// Incremental hash through EFI_HASH2_PROTOCOL
+///
+/// An instance can only run one hash at a time, so the context borrows it
+/// exclusively. Dropping an unfinished context calls HashFinal so the
+/// instance can start another hash.
+pub struct Hash2Context<'a> {
+    protocol: &'a mut Hash2Protocol,
+    algorithm: HashAlgorithm,
+    finished: bool,
+}
+
+impl<'a> Hash2Context<'a> {
+    /// Start a hash with `algorithm`
+    pub fn new(protocol: &'a mut Hash2Protocol, algorithm: HashAlgorithm) -> Result<Self> {
+        unsafe { protocol.hash_init(&algorithm.guid()) }?;
+        Ok(Self {
+            protocol,
+            algorithm,
+            finished: false,
+        })
+    }
+
+    /// Algorithm of this hash
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Add data to the hash
+    pub fn update(&mut self, data: &[u8]) -> Result<()> {
+        unsafe { self.protocol.hash_update(data) }
+    }
+
+    /// Hash the rest of `file` from its current position
+    ///
+    /// The file is read in `buffer.len()` sized chunks, so files of any size
+    /// can be hashed without loading them whole. Returns the number of bytes
+    /// hashed.
+    pub unsafe fn update_from_file(
+        &mut self,
+        file: &mut FileProtocol,
+        buffer: &mut [u8],
+    ) -> Result<u64> {
+        if buffer.is_empty() {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        let mut total = 0u64;
+        loop {
+            let read = file.read(buffer)?;
+            if read == 0 {
+                return Ok(total);
+            }
+            self.update(&buffer[..read])?;
+            total += read as u64;
+        }
+    }
+
+    /// Finish the hash and return the digest
+    pub fn finalize(mut self) -> Result<HashValue> {
+        self.finished = true;
+        let output = unsafe { self.protocol.hash_final() }?;
+        // All union members start at offset 0
+        let bytes = unsafe { &output.sha512_hash };
+        Ok(HashValue::new(
+            self.algorithm,
+            &bytes[..self.algorithm.digest_size()],
+        ))
+    }
+}
+
+impl Drop for Hash2Context<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = unsafe { self.protocol.hash_final() };
+        }
     }
 }
 
@@ -652,19 +863,15 @@ impl<'a> SafeTpm2<'a> {
     }
 
     /// Get TPM2 capabilities
-    pub fn get_capability(&mut self) -> Result<Tpm2BootServiceCapability, Status> {
+    pub fn get_capability(&mut self) -> Result<Tpm2BootServiceCapability> {
         let mut cap = unsafe { core::mem::zeroed() };
-        let status = unsafe { (self.protocol.get_capability)(self.protocol, &mut cap) };
-        if status == EFI_SUCCESS {
-            Ok(cap)
-        } else {
-            Err(status)
-        }
+        unsafe { (self.protocol.get_capability)(self.protocol, &mut cap) }.to_result()?;
+        Ok(cap)
     }
 
     /// Submit a TPM2 command
-    pub fn submit_command(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize, Status> {
-        let status = unsafe {
+    pub fn submit_command(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize> {
+        unsafe {
             (self.protocol.submit_command)(
                 self.protocol,
                 input.len() as u32,
@@ -672,34 +879,27 @@ impl<'a> SafeTpm2<'a> {
                 output.len() as u32,
                 output.as_mut_ptr(),
             )
-        };
+        }
+        .to_result()?;
 
-        if status == EFI_SUCCESS {
-            // Parse response size from TPM2 response header
-            if output.len() >= 10 {
-                let size = u32::from_be_bytes([output[2], output[3], output[4], output[5]]);
-                Ok(size as usize)
-            } else {
-                Ok(output.len())
-            }
+        // Parse response size from TPM2 response header
+        if output.len() >= 10 {
+            let size = u32::from_be_bytes([output[2], output[3], output[4], output[5]]);
+            Ok(size as usize)
         } else {
-            Err(status)
+            Ok(output.len())
         }
     }
 
     /// Get active PCR banks
-    pub fn get_active_pcr_banks(&mut self) -> Result<u32, Status> {
+    pub fn get_active_pcr_banks(&mut self) -> Result<u32> {
         let mut banks = 0;
-        let status = unsafe { (self.protocol.get_active_pcr_banks)(self.protocol, &mut banks) };
-        if status == EFI_SUCCESS {
-            Ok(banks)
-        } else {
-            Err(status)
-        }
+        unsafe { (self.protocol.get_active_pcr_banks)(self.protocol, &mut banks) }.to_result()?;
+        Ok(banks)
     }
 
     /// Send TPM2 Startup command
-    pub fn startup(&mut self, startup_type: u16) -> Result<(), Status> {
+    pub fn startup(&mut self, startup_type: u16) -> Result<()> {
         let mut cmd = [0u8; 12];
         // Build command
         cmd[0..2].copy_from_slice(&tpm2_commands::TPM_ST_NO_SESSIONS.to_be_bytes());
@@ -716,12 +916,12 @@ impl<'a> SafeTpm2<'a> {
         if response_code == 0 {
             Ok(())
         } else {
-            Err(EFI_DEVICE_ERROR)
+            Err(Error::new(EFI_DEVICE_ERROR))
         }
     }
 
     /// Send TPM2 PCR Read command (basic version)
-    pub fn pcr_read(&mut self, pcr_index: u32) -> Result<[u8; 32], Status> {
+    pub fn pcr_read(&mut self, pcr_index: u32) -> Result<[u8; 32]> {
         // Simplified PCR read - real implementation would be more complex
         let mut cmd = [0u8; 20];
         cmd[0..2].copy_from_slice(&tpm2_commands::TPM_ST_NO_SESSIONS.to_be_bytes());
@@ -742,7 +942,193 @@ impl<'a> SafeTpm2<'a> {
             pcr_value.copy_from_slice(&response[10..42]);
             Ok(pcr_value)
         } else {
-            Err(EFI_DEVICE_ERROR)
+            Err(Error::new(EFI_DEVICE_ERROR))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::hash::HashContext;
+    use std::cell::RefCell;
+
+    std::thread_local! {
+        static HASH2_STATE: RefCell<Option<HashContext>> = const { RefCell::new(None) };
+    }
+
+    unsafe extern "efiapi" fn get_hash_size(
+        _this: *const Hash2Protocol,
+        algorithm: *const Guid,
+        hash_size: *mut Uintn,
+    ) -> Status {
+        match HashAlgorithm::from_guid(&*algorithm) {
+            Some(algorithm) => {
+                *hash_size = algorithm.digest_size();
+                EFI_SUCCESS
+            }
+            None => EFI_UNSUPPORTED,
+        }
+    }
+
+    unsafe extern "efiapi" fn hash(
+        _this: *const Hash2Protocol,
+        algorithm: *const Guid,
+        message: *const Uint8,
+        message_size: Uintn,
+        hash: *mut Hash2Output,
+    ) -> Status {
+        let Some(algorithm) = HashAlgorithm::from_guid(&*algorithm) else {
+            return EFI_UNSUPPORTED;
+        };
+        let digest = algorithm.digest(core::slice::from_raw_parts(message, message_size));
+        (*hash).sha512_hash[..digest.as_bytes().len()].copy_from_slice(digest.as_bytes());
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn hash_init(
+        _this: *const Hash2Protocol,
+        algorithm: *const Guid,
+    ) -> Status {
+        let Some(algorithm) = HashAlgorithm::from_guid(&*algorithm) else {
+            return EFI_UNSUPPORTED;
+        };
+        HASH2_STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            if state.is_some() {
+                return EFI_ALREADY_STARTED;
+            }
+            *state = Some(HashContext::new(algorithm));
+            EFI_SUCCESS
+        })
+    }
+
+    unsafe extern "efiapi" fn hash_update(
+        _this: *const Hash2Protocol,
+        message: *const Uint8,
+        message_size: Uintn,
+    ) -> Status {
+        HASH2_STATE.with(|state| match state.borrow_mut().as_mut() {
+            Some(context) => {
+                context.update(core::slice::from_raw_parts(message, message_size));
+                EFI_SUCCESS
+            }
+            None => EFI_NOT_READY,
+        })
+    }
+
+    unsafe extern "efiapi" fn hash_final(
+        _this: *const Hash2Protocol,
+        hash: *mut Hash2Output,
+    ) -> Status {
+        HASH2_STATE.with(|state| match state.borrow_mut().take() {
+            Some(context) => {
+                let digest = context.finalize();
+                (*hash).sha512_hash[..digest.as_bytes().len()].copy_from_slice(digest.as_bytes());
+                EFI_SUCCESS
+            }
+            None => EFI_NOT_READY,
+        })
+    }
+
+    fn hash2() -> Hash2Protocol {
+        HASH2_STATE.with(|state| *state.borrow_mut() = None);
+        Hash2Protocol {
+            get_hash_size,
+            hash,
+            hash_init,
+            hash_update,
+            hash_final,
+        }
+    }
+
+    #[test]
+    fn test_is_hash_in_database() {
+        use crate::runtime_services::EFI_IMAGE_SECURITY_DATABASE_GUID;
+        use crate::runtime_services::{mock, Variable, VariableAttributes};
+        use crate::signature_database::SignatureDatabase;
+
+        mock::reset();
+        let rt = mock::runtime_services();
+        let vars = Variable::new(&rt);
+        let dbx = crate::cstr16!("dbx");
+        let hash = [0x5Au8; 32];
+
+        unsafe {
+            assert_eq!(
+                secure_boot::is_hash_in_database(&vars, &hash, dbx),
+                Ok(false)
+            );
+
+            let mut db = SignatureDatabase::new();
+            db.add_hash(EFI_IMAGE_SECURITY_DATABASE_GUID, &hash)
+                .unwrap();
+            let bytes = db.to_bytes().unwrap();
+            let attrs = VariableAttributes::NV_BS_RT;
+            let guid = &EFI_IMAGE_SECURITY_DATABASE_GUID;
+            vars.set(dbx, guid, attrs, &bytes).unwrap();
+            assert_eq!(
+                secure_boot::is_hash_in_database(&vars, &hash, dbx),
+                Ok(true)
+            );
+            assert_eq!(
+                secure_boot::is_hash_in_database(&vars, &[0; 32], dbx),
+                Ok(false)
+            );
+
+            // A truncated list is an error, not a miss
+            vars.set(dbx, guid, attrs, &bytes[..bytes.len() - 1])
+                .unwrap();
+            assert_eq!(
+                secure_boot::is_hash_in_database(&vars, &hash, dbx)
+                    .unwrap_err()
+                    .status(),
+                EFI_BAD_BUFFER_SIZE
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash2_context_streaming() {
+        let mut protocol = hash2();
+        let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+
+        let mut context = Hash2Context::new(&mut protocol, HashAlgorithm::Sha384).unwrap();
+        for chunk in data.chunks(333) {
+            context.update(chunk).unwrap();
+        }
+        let digest = context.finalize().unwrap();
+        assert_eq!(digest, HashAlgorithm::Sha384.digest(&data));
+
+        let one_shot = unsafe { protocol.hash(&HASH_ALGORITHM_SHA384_GUID, &data) }.unwrap();
+        assert_eq!(unsafe { &one_shot.sha384_hash[..] }, digest.as_bytes());
+        assert_eq!(
+            unsafe { protocol.get_hash_size(&HASH_ALGORITHM_SHA384_GUID) }.unwrap(),
+            48
+        );
+    }
+
+    #[test]
+    fn test_hash2_context_drop_resets() {
+        let mut protocol = hash2();
+        {
+            let mut context = Hash2Context::new(&mut protocol, HashAlgorithm::Sha256).unwrap();
+            context.update(b"abandoned").unwrap();
         }
+        let context = Hash2Context::new(&mut protocol, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(
+            context.finalize().unwrap(),
+            HashAlgorithm::Sha256.digest(b"")
+        );
+
+        // A second init without finishing is rejected by the instance
+        unsafe { protocol.hash_init(&HASH_ALGORITHM_SHA256_GUID) }.unwrap();
+        assert_eq!(
+            Hash2Context::new(&mut protocol, HashAlgorithm::Sha256)
+                .err()
+                .unwrap()
+                .status(),
+            EFI_ALREADY_STARTED
+        );
     }
 }
@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! UEFI Serial I/O Protocol
+
+use crate::error::{Error, Result, StatusExt};
+use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
+use core::fmt;
+
+/// EFI_SERIAL_IO_PROTOCOL_GUID
+pub const SERIAL_IO_PROTOCOL_GUID: Guid = guid!("bb25cf6f-f1d4-11d2-9a0c-0090273fc1fd");
+
+pub const EFI_SERIAL_IO_PROTOCOL_REVISION: Uint32 = 0x00010000;
+pub const EFI_SERIAL_IO_PROTOCOL_REVISION1P1: Uint32 = 0x00010001;
+
+// Control bits (SetControl/GetControl)
+pub const EFI_SERIAL_DATA_TERMINAL_READY: Uint32 = 0x0001;
+pub const EFI_SERIAL_REQUEST_TO_SEND: Uint32 = 0x0002;
+pub const EFI_SERIAL_CLEAR_TO_SEND: Uint32 = 0x0010;
+pub const EFI_SERIAL_DATA_SET_READY: Uint32 = 0x0020;
+pub const EFI_SERIAL_RING_INDICATE: Uint32 = 0x0040;
+pub const EFI_SERIAL_CARRIER_DETECT: Uint32 = 0x0080;
+pub const EFI_SERIAL_INPUT_BUFFER_EMPTY: Uint32 = 0x0100;
+pub const EFI_SERIAL_OUTPUT_BUFFER_EMPTY: Uint32 = 0x0200;
+pub const EFI_SERIAL_HARDWARE_LOOPBACK_ENABLE: Uint32 = 0x1000;
+pub const EFI_SERIAL_SOFTWARE_LOOPBACK_ENABLE: Uint32 = 0x2000;
+pub const EFI_SERIAL_HARDWARE_FLOW_CONTROL_ENABLE: Uint32 = 0x4000;
+
+/// EFI_PARITY_TYPE
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Parity {
+    Default = 0,
+    None = 1,
+    Even = 2,
+    Odd = 3,
+    Mark = 4,
+    Space = 5,
+}
+
+/// EFI_STOP_BITS_TYPE
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopBits {
+    Default = 0,
+    One = 1,
+    OneFive = 2,
+    Two = 3,
+}
+
+/// SERIAL_IO_MODE
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SerialIoMode {
+    pub control_mask: Uint32,
+    pub timeout: Uint32,
+    pub baud_rate: Uint64,
+    pub receive_fifo_depth: Uint32,
+    pub data_bits: Uint32,
+    pub parity: Uint32,
+    pub stop_bits: Uint32,
+}
+
+/// EFI_SERIAL_IO_PROTOCOL
+#[repr(C)]
+pub struct SerialIoProtocol {
+    pub revision: Uint32,
+    pub reset: unsafe extern "efiapi" fn(this: *mut SerialIoProtocol) -> Status,
+    pub set_attributes: unsafe extern "efiapi" fn(
+        this: *mut SerialIoProtocol,
+        baud_rate: Uint64,
+        receive_fifo_depth: Uint32,
+        timeout: Uint32,
+        parity: Uint32,
+        data_bits: Uint8,
+        stop_bits: Uint32,
+    ) -> Status,
+    pub set_control:
+        unsafe extern "efiapi" fn(this: *mut SerialIoProtocol, control: Uint32) -> Status,
+    pub get_control:
+        unsafe extern "efiapi" fn(this: *mut SerialIoProtocol, control: *mut Uint32) -> Status,
+    pub write: unsafe extern "efiapi" fn(
+        this: *mut SerialIoProtocol,
+        buffer_size: *mut Uintn,
+        buffer: *const core::ffi::c_void,
+    ) -> Status,
+    pub read: unsafe extern "efiapi" fn(
+        this: *mut SerialIoProtocol,
+        buffer_size: *mut Uintn,
+        buffer: *mut core::ffi::c_void,
+    ) -> Status,
+    pub mode: *mut SerialIoMode,
+    // Revision 1.1
+    pub device_type_guid: *const Guid,
+}
+
+unsafe impl Protocol for SerialIoProtocol {
+    const GUID: Guid = SERIAL_IO_PROTOCOL_GUID;
+}
+
+impl SerialIoProtocol {
+    /// Reset the serial device
+    pub unsafe fn reset(&mut self) -> Result<()> {
+        (self.reset)(self).to_result()
+    }
+
+    /// Set baud rate, FIFO depth, timeout (in microseconds), parity, data bits and stop bits
+    ///
+    /// Zero values select the device defaults.
+    pub unsafe fn set_attributes(
+        &mut self,
+        baud_rate: u64,
+        receive_fifo_depth: u32,
+        timeout: u32,
+        parity: Parity,
+        data_bits: u8,
+        stop_bits: StopBits,
+    ) -> Result<()> {
+        (self.set_attributes)(
+            self,
+            baud_rate,
+            receive_fifo_depth,
+            timeout,
+            parity as u32,
+            data_bits,
+            stop_bits as u32,
+        )
+        .to_result()
+    }
+
+    /// Set the control bits (`EFI_SERIAL_*`)
+    pub unsafe fn set_control(&mut self, control: u32) -> Result<()> {
+        (self.set_control)(self, control).to_result()
+    }
+
+    /// Get the control bits (`EFI_SERIAL_*`)
+    pub unsafe fn get_control(&mut self) -> Result<u32> {
+        let mut control = 0;
+        (self.get_control)(self, &mut control).to_result()?;
+        Ok(control)
+    }
+
+    /// Write bytes, returning how many were written
+    ///
+    /// A timeout after a partial write returns the partial count.
+    pub unsafe fn write(&mut self, data: &[u8]) -> Result<usize> {
+        let mut size = data.len();
+        let status = (self.write)(self, &mut size, data.as_ptr() as *const core::ffi::c_void);
+        match status {
+            EFI_TIMEOUT if size > 0 => Ok(size),
+            _ => status.to_result_with_val(|| size),
+        }
+    }
+
+    /// Read bytes into `buffer`, returning how many were read
+    ///
+    /// A timeout after a partial read returns the partial count.
+    pub unsafe fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        let mut size = buffer.len();
+        let status = (self.read)(
+            self,
+            &mut size,
+            buffer.as_mut_ptr() as *mut core::ffi::c_void,
+        );
+        match status {
+            EFI_TIMEOUT if size > 0 => Ok(size),
+            _ => status.to_result_with_val(|| size),
+        }
+    }
+
+    /// Get the current device mode
+    pub unsafe fn mode(&self) -> Option<&SerialIoMode> {
+        if self.mode.is_null() {
+            None
+        } else {
+            Some(&*self.mode)
+        }
+    }
+}
+
+/// `core::fmt::Write` adapter for a serial port
+///
+/// Bytes are written as-is; callers provide their own `\r\n` line endings.
+pub struct SerialWriter {
+    serial: *mut SerialIoProtocol,
+}
+
+impl SerialWriter {
+    /// Create a writer for a serial I/O protocol instance
+    ///
+    /// # Safety
+    /// The protocol pointer must stay valid while the writer is in use
+    pub unsafe fn new(serial: *mut SerialIoProtocol) -> Self {
+        SerialWriter { serial }
+    }
+
+    /// Write all of `data`, retrying after partial writes
+    pub fn write_all(&mut self, mut data: &[u8]) -> Result<()> {
+        if self.serial.is_null() {
+            return Err(Error::new(EFI_NOT_READY));
+        }
+        while !data.is_empty() {
+            let written = unsafe { (*self.serial).write(data)? };
+            if written == 0 {
+                return Err(Error::new(EFI_DEVICE_ERROR));
+            }
+            data = &data[written..];
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Write for SerialWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    static mut WRITTEN: [u8; 64] = [0; 64];
+    static mut WRITTEN_LEN: usize = 0;
+
+    unsafe extern "efiapi" fn mock_reset(_this: *mut SerialIoProtocol) -> Status {
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn mock_set_attributes(
+        _this: *mut SerialIoProtocol,
+        _baud_rate: Uint64,
+        _receive_fifo_depth: Uint32,
+        _timeout: Uint32,
+        _parity: Uint32,
+        _data_bits: Uint8,
+        _stop_bits: Uint32,
+    ) -> Status {
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn mock_set_control(
+        _this: *mut SerialIoProtocol,
+        _control: Uint32,
+    ) -> Status {
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn mock_get_control(
+        _this: *mut SerialIoProtocol,
+        control: *mut Uint32,
+    ) -> Status {
+        *control = EFI_SERIAL_OUTPUT_BUFFER_EMPTY;
+        EFI_SUCCESS
+    }
+
+    // Accepts at most 3 bytes per call to exercise partial writes
+    unsafe extern "efiapi" fn mock_write(
+        _this: *mut SerialIoProtocol,
+        buffer_size: *mut Uintn,
+        buffer: *const core::ffi::c_void,
+    ) -> Status {
+        let n = (*buffer_size).min(3);
+        let src = core::slice::from_raw_parts(buffer as *const u8, n);
+        let len = WRITTEN_LEN;
+        (*core::ptr::addr_of_mut!(WRITTEN))[len..len + n].copy_from_slice(src);
+        WRITTEN_LEN += n;
+        *buffer_size = n;
+        if n < 3 {
+            EFI_SUCCESS
+        } else {
+            EFI_TIMEOUT
+        }
+    }
+
+    unsafe extern "efiapi" fn mock_read(
+        _this: *mut SerialIoProtocol,
+        buffer_size: *mut Uintn,
+        _buffer: *mut core::ffi::c_void,
+    ) -> Status {
+        *buffer_size = 0;
+        EFI_TIMEOUT
+    }
+
+    fn mock_serial() -> SerialIoProtocol {
+        SerialIoProtocol {
+            revision: EFI_SERIAL_IO_PROTOCOL_REVISION,
+            reset: mock_reset,
+            set_attributes: mock_set_attributes,
+            set_control: mock_set_control,
+            get_control: mock_get_control,
+            write: mock_write,
+            read: mock_read,
+            mode: core::ptr::null_mut(),
+            device_type_guid: core::ptr::null(),
+        }
+    }
+
+    #[test]
+    fn test_serial_writer_retries_partial_writes() {
+        let mut serial = mock_serial();
+        unsafe {
+            WRITTEN_LEN = 0;
+            let mut writer = SerialWriter::new(&mut serial);
+            write!(writer, "hello {}\r\n", 42).unwrap();
+            assert_eq!(
+                &(*core::ptr::addr_of!(WRITTEN))[..WRITTEN_LEN],
+                b"hello 42\r\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_serial_read_timeout_and_control() {
+        let mut serial = mock_serial();
+        let mut buf = [0u8; 4];
+        unsafe {
+            let err = serial.read(&mut buf).unwrap_err();
+            assert_eq!(err.status(), EFI_TIMEOUT);
+            assert_eq!(
+                serial.get_control().unwrap(),
+                EFI_SERIAL_OUTPUT_BUFFER_EMPTY
+            );
+            assert!(serial.mode().is_none());
+        }
+    }
+}
@@ -4,24 +4,18 @@
 //! This module provides bindings for the UEFI Shell protocols, allowing
 //! applications to interact with the UEFI Shell environment.
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 use crate::runtime_services::Time;
+use crate::string::CStr16;
 
 /// EFI_SHELL_PROTOCOL_GUID
-pub const SHELL_PROTOCOL_GUID: Guid = Guid::new(
-    0x6302d008,
-    0x7f9b,
-    0x4f30,
-    [0x87, 0xac, 0x60, 0xc9, 0xfe, 0xf5, 0xda, 0x4e],
-);
+pub const SHELL_PROTOCOL_GUID: Guid = guid!("6302d008-7f9b-4f30-87ac-60c9fef5da4e");
 
 /// EFI_SHELL_PARAMETERS_PROTOCOL_GUID
-pub const SHELL_PARAMETERS_PROTOCOL_GUID: Guid = Guid::new(
-    0x752f3136,
-    0x4e16,
-    0x4fdc,
-    [0xa2, 0x2a, 0xe5, 0xf4, 0x68, 0x12, 0xf4, 0xca],
-);
+pub const SHELL_PARAMETERS_PROTOCOL_GUID: Guid = guid!("752f3136-4e16-4fdc-a22a-e5f46812f4ca");
 
 /// Shell File Handle
 pub type ShellFileHandle = *mut core::ffi::c_void;
@@ -208,6 +202,10 @@ pub struct ShellProtocol {
         unsafe extern "efiapi" fn(name: *const Char16, attributes: *mut Uint32) -> *const Char16,
 }
 
+unsafe impl Protocol for ShellProtocol {
+    const GUID: Guid = SHELL_PROTOCOL_GUID;
+}
+
 /// EFI_SHELL_PARAMETERS_PROTOCOL
 #[repr(C)]
 pub struct ShellParametersProtocol {
@@ -218,88 +216,61 @@ pub struct ShellParametersProtocol {
     pub std_err: ShellFileHandle,
 }
 
+unsafe impl Protocol for ShellParametersProtocol {
+    const GUID: Guid = SHELL_PARAMETERS_PROTOCOL_GUID;
+}
+
 impl ShellProtocol {
     /// Execute a shell command
-    pub unsafe fn execute(
-        &mut self,
-        parent_handle: Handle,
-        command: &[u16],
-    ) -> Result<Status, Status> {
+    pub unsafe fn execute(&mut self, parent_handle: Handle, command: &CStr16) -> Result<Status> {
         let mut status_code = EFI_SUCCESS;
         let handle_ptr = parent_handle.as_ptr() as *mut Handle;
-        let result = (self.execute)(
+        (self.execute)(
             handle_ptr,
-            command.as_ptr() as *const _,
+            command.as_ptr(),
             core::ptr::null_mut(),
             &mut status_code,
-        );
-
-        if result == EFI_SUCCESS {
-            Ok(status_code)
-        } else {
-            Err(result)
-        }
+        )
+        .to_result()?;
+        Ok(status_code)
     }
 
     /// Get environment variable
-    pub unsafe fn get_env(&mut self, name: &[u16]) -> Option<*const Char16> {
-        let result = (self.get_env)(name.as_ptr() as *const _);
-        if result.is_null() {
-            None
-        } else {
-            Some(result)
-        }
+    pub unsafe fn get_env(&mut self, name: &CStr16) -> Option<&CStr16> {
+        CStr16::from_ptr((self.get_env)(name.as_ptr())).ok()
     }
 
     /// Set environment variable
-    pub unsafe fn set_env(&mut self, name: &[u16], value: &[u16], volatile: bool) -> Status {
-        (self.set_env)(
-            name.as_ptr() as *const _,
-            value.as_ptr() as *const _,
-            volatile as Boolean,
-        )
+    pub unsafe fn set_env(&mut self, name: &CStr16, value: &CStr16, volatile: bool) -> Result<()> {
+        (self.set_env)(name.as_ptr(), value.as_ptr(), volatile as Boolean).to_result()
     }
 
     /// Get current directory
-    pub unsafe fn get_cur_dir(&mut self, filesystem: Option<&[u16]>) -> Option<*const Char16> {
-        let fs_ptr = filesystem
-            .map(|s| s.as_ptr() as *const _)
-            .unwrap_or(core::ptr::null());
-        let result = (self.get_cur_dir)(fs_ptr);
-        if result.is_null() {
-            None
-        } else {
-            Some(result)
-        }
+    pub unsafe fn get_cur_dir(&mut self, filesystem: Option<&CStr16>) -> Option<&CStr16> {
+        let fs_ptr = filesystem.map_or(core::ptr::null(), CStr16::as_ptr);
+        CStr16::from_ptr((self.get_cur_dir)(fs_ptr)).ok()
     }
 
     /// Set current directory
-    pub unsafe fn set_cur_dir(&mut self, filesystem: Option<&[u16]>, dir: &[u16]) -> Status {
-        let fs_ptr = filesystem
-            .map(|s| s.as_ptr() as *const _)
-            .unwrap_or(core::ptr::null());
-        (self.set_cur_dir)(fs_ptr, dir.as_ptr() as *const _)
+    pub unsafe fn set_cur_dir(&mut self, filesystem: Option<&CStr16>, dir: &CStr16) -> Result<()> {
+        let fs_ptr = filesystem.map_or(core::ptr::null(), CStr16::as_ptr);
+        (self.set_cur_dir)(fs_ptr, dir.as_ptr()).to_result()
     }
 
     /// Open a file by name
     pub unsafe fn open_file_by_name(
         &mut self,
-        filename: &[u16],
+        filename: &CStr16,
         mode: Uint64,
-    ) -> Result<ShellFileHandle, Status> {
+    ) -> Result<ShellFileHandle> {
         let mut handle: ShellFileHandle = core::ptr::null_mut();
-        let status = (self.open_file_by_name)(filename.as_ptr() as *const _, &mut handle, mode);
-
-        if status == EFI_SUCCESS {
-            Ok(handle)
-        } else {
-            Err(status)
-        }
+        (self.open_file_by_name)(filename.as_ptr(), &mut handle, mode).to_result()?;
+        Ok(handle)
     }
 
     /// Close a file
-    pub unsafe fn close_file(&mut self, handle: ShellFileHandle) -> Status {
-        (self.close_file)(handle)
+    pub unsafe fn close_file(&mut self, handle: ShellFileHandle) -> Result<()> {
+        (self.close_file)(handle).to_result()
     }
 
     /// Read from a file
@@ -307,43 +278,24 @@ impl ShellProtocol {
         &mut self,
         handle: ShellFileHandle,
         buffer: &mut [u8],
-    ) -> Result<usize, Status> {
+    ) -> Result<usize> {
         let mut size = buffer.len();
-        let status = (self.read_file)(handle, &mut size, buffer.as_mut_ptr() as *mut _);
-
-        if status == EFI_SUCCESS {
-            Ok(size)
-        } else {
-            Err(status)
-        }
+        (self.read_file)(handle, &mut size, buffer.as_mut_ptr() as *mut _).to_result()?;
+        Ok(size)
     }
 
     /// Write to a file
-    pub unsafe fn write_file(
-        &mut self,
-        handle: ShellFileHandle,
-        buffer: &[u8],
-    ) -> Result<usize, Status> {
+    pub unsafe fn write_file(&mut self, handle: ShellFileHandle, buffer: &[u8]) -> Result<usize> {
         let mut size = buffer.len();
-        let status = (self.write_file)(handle, &mut size, buffer.as_ptr() as *mut _);
-
-        if status == EFI_SUCCESS {
-            Ok(size)
-        } else {
-            Err(status)
-        }
+        (self.write_file)(handle, &mut size, buffer.as_ptr() as *mut _).to_result()?;
+        Ok(size)
     }
 
     /// Get file size
-    pub unsafe fn get_file_size(&mut self, handle: ShellFileHandle) -> Result<u64, Status> {
+    pub unsafe fn get_file_size(&mut self, handle: ShellFileHandle) -> Result<u64> {
         let mut size: u64 = 0;
-        let status = (self.get_file_size)(handle, &mut size);
-
-        if status == EFI_SUCCESS {
-            Ok(size)
-        } else {
-            Err(status)
-        }
+        (self.get_file_size)(handle, &mut size).to_result()?;
+        Ok(size)
     }
 
     /// Check if running in batch mode
@@ -374,9 +326,9 @@ impl ShellProtocol {
 
 impl ShellParametersProtocol {
     /// Get command line argument at index
-    pub unsafe fn get_arg(&self, index: usize) -> Option<*const Char16> {
+    pub unsafe fn get_arg(&self, index: usize) -> Option<&CStr16> {
         if index < self.argc {
-            Some(*self.argv.add(index))
+            CStr16::from_ptr(*self.argv.add(index)).ok()
         } else {
             None
         }
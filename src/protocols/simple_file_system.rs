@@ -1,23 +1,18 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Simple File System Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
+use crate::runtime_services::Time;
+use crate::string::CStr16;
 
 /// EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID
-pub const SIMPLE_FILE_SYSTEM_PROTOCOL_GUID: Guid = Guid::new(
-    0x964e5b22,
-    0x6459,
-    0x11d2,
-    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const SIMPLE_FILE_SYSTEM_PROTOCOL_GUID: Guid = guid!("964e5b22-6459-11d2-8e39-00a0c969723b");
 
 /// EFI_FILE_PROTOCOL_GUID (Revision 2)
-pub const FILE_PROTOCOL_GUID: Guid = Guid::new(
-    0x09576e93,
-    0x6d3f,
-    0x11d2,
-    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const FILE_PROTOCOL_GUID: Guid = guid!("09576e93-6d3f-11d2-8e39-00a0c969723b");
 
 pub const EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_REVISION: Uint64 = 0x00010000;
 pub const EFI_FILE_PROTOCOL_REVISION: Uint64 = 0x00010000;
@@ -34,7 +29,14 @@ pub struct SimpleFileSystemProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for SimpleFileSystemProtocol {
+    const GUID: Guid = SIMPLE_FILE_SYSTEM_PROTOCOL_GUID;
+}
+
 /// EFI_FILE_PROTOCOL
+///
+/// File handles come from `open_volume`/`open` rather than the handle
+/// database, so this has no `Protocol` impl.
 #[repr(C)]
 pub struct FileProtocol {
     pub revision: Uint64,
@@ -91,12 +93,7 @@ pub const EFI_FILE_ARCHIVE: u64 = 0x0000000000000020;
 pub const EFI_FILE_VALID_ATTR: u64 = 0x0000000000000037;
 
 /// EFI_FILE_INFO_GUID
-pub const FILE_INFO_GUID: Guid = Guid::new(
-    0x09576e92,
-    0x6d3f,
-    0x11d2,
-    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const FILE_INFO_GUID: Guid = guid!("09576e92-6d3f-11d2-8e39-00a0c969723b");
 
 /// EFI_FILE_INFO
 #[repr(C)]
@@ -111,33 +108,12 @@ pub struct FileInfo {
     // Followed by FileName[variable length]
 }
 
-/// EFI_TIME
-#[repr(C)]
-#[derive(Debug, Copy, Clone)]
-pub struct Time {
-    pub year: Uint16,
-    pub month: Uint8,
-    pub day: Uint8,
-    pub hour: Uint8,
-    pub minute: Uint8,
-    pub second: Uint8,
-    pub pad1: Uint8,
-    pub nanosecond: Uint32,
-    pub time_zone: Int16,
-    pub daylight: Uint8,
-    pub pad2: Uint8,
-}
-
 impl SimpleFileSystemProtocol {
     /// Open the root directory
-    pub unsafe fn open_volume(&mut self) -> Result<*mut FileProtocol, Status> {
+    pub unsafe fn open_volume(&mut self) -> Result<*mut FileProtocol> {
         let mut root = core::ptr::null_mut();
-        let status = (self.open_volume)(self, &mut root);
-        if status == EFI_SUCCESS {
-            Ok(root)
-        } else {
-            Err(status)
-        }
+        (self.open_volume)(self, &mut root).to_result()?;
+        Ok(root)
     }
 }
 
@@ -145,65 +121,56 @@ impl FileProtocol {
     /// Open a file
     pub unsafe fn open(
         &mut self,
-        file_name: *const Char16,
+        file_name: &CStr16,
         open_mode: u64,
         attributes: u64,
-    ) -> Result<*mut FileProtocol, Status> {
+    ) -> Result<*mut FileProtocol> {
         let mut new_handle = core::ptr::null_mut();
-        let status = (self.open)(self, &mut new_handle, file_name, open_mode, attributes);
-        if status == EFI_SUCCESS {
-            Ok(new_handle)
-        } else {
-            Err(status)
-        }
+        (self.open)(
+            self,
+            &mut new_handle,
+            file_name.as_ptr(),
+            open_mode,
+            attributes,
+        )
+        .to_result()?;
+        Ok(new_handle)
     }
 
     /// Close the file
-    pub unsafe fn close(&mut self) -> Status {
-        (self.close)(self)
+    pub unsafe fn close(&mut self) -> Result<()> {
+        (self.close)(self).to_result()
     }
 
     /// Delete the file
-    pub unsafe fn delete(&mut self) -> Status {
-        (self.delete)(self)
+    pub unsafe fn delete(&mut self) -> Result<()> {
+        (self.delete)(self).to_result()
     }
 
     /// Read from file
-    pub unsafe fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Status> {
+    pub unsafe fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
         let mut buffer_size = buffer.len();
-        let status = (self.read)(self, &mut buffer_size, buffer.as_mut_ptr() as *mut _);
-        if status == EFI_SUCCESS {
-            Ok(buffer_size)
-        } else {
-            Err(status)
-        }
+        (self.read)(self, &mut buffer_size, buffer.as_mut_ptr() as *mut _).to_result()?;
+        Ok(buffer_size)
     }
 
     /// Write to file
-    pub unsafe fn write(&mut self, buffer: &[u8]) -> Result<usize, Status> {
+    pub unsafe fn write(&mut self, buffer: &[u8]) -> Result<usize> {
         let mut buffer_size = buffer.len();
-        let status = (self.write)(self, &mut buffer_size, buffer.as_ptr() as *const _);
-        if status == EFI_SUCCESS {
-            Ok(buffer_size)
-        } else {
-            Err(status)
-        }
+        (self.write)(self, &mut buffer_size, buffer.as_ptr() as *const _).to_result()?;
+        Ok(buffer_size)
     }
 
     /// Get current position
-    pub unsafe fn get_position(&mut self) -> Result<u64, Status> {
+    pub unsafe fn get_position(&mut self) -> Result<u64> {
         let mut position = 0;
-        let status = (self.get_position)(self, &mut position);
-        if status == EFI_SUCCESS {
-            Ok(position)
-        } else {
-            Err(status)
-        }
+        (self.get_position)(self, &mut position).to_result()?;
+        Ok(position)
     }
 
     /// Set position
-    pub unsafe fn set_position(&mut self, position: u64) -> Status {
-        (self.set_position)(self, position)
+    pub unsafe fn set_position(&mut self, position: u64) -> Result<()> {
+        (self.set_position)(self, position).to_result()
     }
 
     /// Get file information
@@ -212,8 +179,8 @@ impl FileProtocol {
         information_type: &Guid,
         buffer_size: &mut usize,
         buffer: *mut core::ffi::c_void,
-    ) -> Status {
-        (self.get_info)(self, information_type as *const _, buffer_size, buffer)
+    ) -> Result<()> {
+        (self.get_info)(self, information_type as *const _, buffer_size, buffer).to_result()
     }
 
     /// Set file information
@@ -222,12 +189,12 @@ impl FileProtocol {
         information_type: &Guid,
         buffer_size: usize,
         buffer: *const core::ffi::c_void,
-    ) -> Status {
-        (self.set_info)(self, information_type as *const _, buffer_size, buffer)
+    ) -> Result<()> {
+        (self.set_info)(self, information_type as *const _, buffer_size, buffer).to_result()
     }
 
     /// Flush file
-    pub unsafe fn flush(&mut self) -> Status {
-        (self.flush)(self)
+    pub unsafe fn flush(&mut self) -> Result<()> {
+        (self.flush)(self).to_result()
     }
 }
@@ -1,15 +1,13 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Simple Network Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_SIMPLE_NETWORK_PROTOCOL_GUID
-pub const SIMPLE_NETWORK_PROTOCOL_GUID: Guid = Guid::new(
-    0xA19832B9,
-    0xAC25,
-    0x11D3,
-    [0x9A, 0x2D, 0x00, 0x90, 0x27, 0x3F, 0xC1, 0x4D],
-);
+pub const SIMPLE_NETWORK_PROTOCOL_GUID: Guid = guid!("a19832b9-ac25-11d3-9a2d-0090273fc14d");
 
 pub const MAX_MCAST_FILTER_CNT: usize = 16;
 
@@ -157,6 +155,10 @@ pub struct SimpleNetworkProtocol {
     pub mode: *mut SimpleNetworkMode,
 }
 
+unsafe impl Protocol for SimpleNetworkProtocol {
+    const GUID: Guid = SIMPLE_NETWORK_PROTOCOL_GUID;
+}
+
 // Receive filter settings
 pub const EFI_SIMPLE_NETWORK_RECEIVE_UNICAST: u32 = 0x01;
 pub const EFI_SIMPLE_NETWORK_RECEIVE_MULTICAST: u32 = 0x02;
@@ -172,28 +174,28 @@ pub const EFI_SIMPLE_NETWORK_SOFTWARE_INTERRUPT: u32 = 0x08;
 
 impl SimpleNetworkProtocol {
     /// Start the network interface
-    pub unsafe fn start(&mut self) -> Status {
-        (self.start)(self)
+    pub unsafe fn start(&mut self) -> Result<()> {
+        (self.start)(self).to_result()
     }
 
     /// Stop the network interface
-    pub unsafe fn stop(&mut self) -> Status {
-        (self.stop)(self)
+    pub unsafe fn stop(&mut self) -> Result<()> {
+        (self.stop)(self).to_result()
     }
 
     /// Initialize the network interface
-    pub unsafe fn initialize(&mut self, extra_rx_size: usize, extra_tx_size: usize) -> Status {
-        (self.initialize)(self, extra_rx_size, extra_tx_size)
+    pub unsafe fn initialize(&mut self, extra_rx_size: usize, extra_tx_size: usize) -> Result<()> {
+        (self.initialize)(self, extra_rx_size, extra_tx_size).to_result()
     }
 
     /// Reset the network interface
-    pub unsafe fn reset(&mut self, extended_verification: bool) -> Status {
-        (self.reset)(self, extended_verification as Boolean)
+    pub unsafe fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        (self.reset)(self, extended_verification as Boolean).to_result()
     }
 
     /// Shutdown the network interface
-    pub unsafe fn shutdown(&mut self) -> Status {
-        (self.shutdown)(self)
+    pub unsafe fn shutdown(&mut self) -> Result<()> {
+        (self.shutdown)(self).to_result()
     }
 
     /// Transmit a packet
@@ -203,7 +205,7 @@ impl SimpleNetworkProtocol {
         src_addr: Option<&MacAddress>,
         dest_addr: Option<&MacAddress>,
         protocol: Option<u16>,
-    ) -> Status {
+    ) -> Result<()> {
         let src_ptr = src_addr.map_or(core::ptr::null(), |a| a as *const _);
         let dest_ptr = dest_addr.map_or(core::ptr::null(), |a| a as *const _);
         let proto_ptr = protocol
@@ -219,20 +221,21 @@ impl SimpleNetworkProtocol {
             dest_ptr,
             proto_ptr,
         )
+        .to_result()
     }
 
     /// Receive a packet
     pub unsafe fn receive(
         &mut self,
         buffer: &mut [u8],
-    ) -> Result<(usize, MacAddress, MacAddress, u16), Status> {
+    ) -> Result<(usize, MacAddress, MacAddress, u16)> {
         let mut header_size = 0;
         let mut buffer_size = buffer.len();
         let mut src_addr = core::mem::zeroed();
         let mut dest_addr = core::mem::zeroed();
         let mut protocol = 0u16;
 
-        let status = (self.receive)(
+        (self.receive)(
             self,
             &mut header_size,
             &mut buffer_size,
@@ -240,27 +243,18 @@ impl SimpleNetworkProtocol {
             &mut src_addr,
             &mut dest_addr,
             &mut protocol,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok((buffer_size, src_addr, dest_addr, protocol))
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok((buffer_size, src_addr, dest_addr, protocol))
     }
 
     /// Get network statistics
-    pub unsafe fn get_statistics(&mut self, reset: bool) -> Result<NetworkStatistics, Status> {
+    pub unsafe fn get_statistics(&mut self, reset: bool) -> Result<NetworkStatistics> {
         let mut size = core::mem::size_of::<NetworkStatistics>();
         let mut stats = core::mem::zeroed();
 
-        let status = (self.statistics)(self, reset as Boolean, &mut size, &mut stats);
-
-        if status == EFI_SUCCESS {
-            Ok(stats)
-        } else {
-            Err(status)
-        }
+        (self.statistics)(self, reset as Boolean, &mut size, &mut stats).to_result()?;
+        Ok(stats)
     }
 
     /// Get current mode
@@ -1,15 +1,13 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Simple Text Input Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_SIMPLE_TEXT_INPUT_PROTOCOL_GUID
-pub const SIMPLE_TEXT_INPUT_PROTOCOL_GUID: Guid = Guid::new(
-    0x387477c1,
-    0x69c7,
-    0x11d2,
-    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const SIMPLE_TEXT_INPUT_PROTOCOL_GUID: Guid = guid!("387477c1-69c7-11d2-8e39-00a0c969723b");
 
 /// EFI_INPUT_KEY
 #[repr(C)]
@@ -31,6 +29,10 @@ pub struct SimpleTextInputProtocol {
     pub wait_for_key: Event,
 }
 
+unsafe impl Protocol for SimpleTextInputProtocol {
+    const GUID: Guid = SIMPLE_TEXT_INPUT_PROTOCOL_GUID;
+}
+
 // Scan codes
 pub const SCAN_NULL: u16 = 0x0000;
 pub const SCAN_UP: u16 = 0x0001;
@@ -57,21 +59,17 @@ pub const SCAN_ESC: u16 = 0x0017;
 
 impl SimpleTextInputProtocol {
     /// Reset the input device
-    pub unsafe fn reset(&mut self, extended_verification: bool) -> Status {
-        (self.reset)(self, extended_verification as Boolean)
+    pub unsafe fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        (self.reset)(self, extended_verification as Boolean).to_result()
     }
 
     /// Read a keystroke
-    pub unsafe fn read_key_stroke(&mut self) -> Result<InputKey, Status> {
+    pub unsafe fn read_key_stroke(&mut self) -> Result<InputKey> {
         let mut key = InputKey {
             scan_code: 0,
             unicode_char: 0,
         };
-        let status = (self.read_key_stroke)(self, &mut key);
-        if status == EFI_SUCCESS {
-            Ok(key)
-        } else {
-            Err(status)
-        }
+        (self.read_key_stroke)(self, &mut key).to_result()?;
+        Ok(key)
     }
 }
@@ -1,15 +1,14 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Simple Text Output Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
+use crate::string::CStr16;
 
 /// EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID
-pub const SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID: Guid = Guid::new(
-    0x387477c2,
-    0x69c7,
-    0x11d2,
-    [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID: Guid = guid!("387477c2-69c7-11d2-8e39-00a0c969723b");
 
 /// SIMPLE_TEXT_OUTPUT_MODE
 #[repr(C)]
@@ -61,6 +60,10 @@ pub struct SimpleTextOutputProtocol {
     pub mode: *mut SimpleTextOutputMode,
 }
 
+unsafe impl Protocol for SimpleTextOutputProtocol {
+    const GUID: Guid = SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID;
+}
+
 // Text attributes
 pub const EFI_BLACK: usize = 0x00;
 pub const EFI_BLUE: usize = 0x01;
@@ -96,54 +99,50 @@ pub const fn efi_text_attr(foreground: usize, background: usize) -> usize {
 
 impl SimpleTextOutputProtocol {
     /// Reset the output device
-    pub unsafe fn reset(&mut self, extended_verification: bool) -> Status {
-        (self.reset)(self, extended_verification as Boolean)
+    pub unsafe fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        (self.reset)(self, extended_verification as Boolean).to_result()
     }
 
     /// Output a string
-    pub unsafe fn output_string(&mut self, string: *const Char16) -> Status {
-        (self.output_string)(self, string)
+    pub unsafe fn output_string(&mut self, string: &CStr16) -> Result<()> {
+        (self.output_string)(self, string.as_ptr()).to_result()
     }
 
     /// Test if a string can be output
-    pub unsafe fn test_string(&mut self, string: *const Char16) -> Status {
-        (self.test_string)(self, string)
+    pub unsafe fn test_string(&mut self, string: &CStr16) -> Result<()> {
+        (self.test_string)(self, string.as_ptr()).to_result()
     }
 
     /// Query mode information
-    pub unsafe fn query_mode(&mut self, mode_number: usize) -> Result<(usize, usize), Status> {
+    pub unsafe fn query_mode(&mut self, mode_number: usize) -> Result<(usize, usize)> {
         let mut columns = 0;
         let mut rows = 0;
-        let status = (self.query_mode)(self, mode_number, &mut columns, &mut rows);
-        if status == EFI_SUCCESS {
-            Ok((columns, rows))
-        } else {
-            Err(status)
-        }
+        (self.query_mode)(self, mode_number, &mut columns, &mut rows).to_result()?;
+        Ok((columns, rows))
     }
 
     /// Set mode
-    pub unsafe fn set_mode(&mut self, mode_number: usize) -> Status {
-        (self.set_mode)(self, mode_number)
+    pub unsafe fn set_mode(&mut self, mode_number: usize) -> Result<()> {
+        (self.set_mode)(self, mode_number).to_result()
     }
 
     /// Set attribute
-    pub unsafe fn set_attribute(&mut self, attribute: usize) -> Status {
-        (self.set_attribute)(self, attribute)
+    pub unsafe fn set_attribute(&mut self, attribute: usize) -> Result<()> {
+        (self.set_attribute)(self, attribute).to_result()
     }
 
     /// Clear screen
-    pub unsafe fn clear_screen(&mut self) -> Status {
-        (self.clear_screen)(self)
+    pub unsafe fn clear_screen(&mut self) -> Result<()> {
+        (self.clear_screen)(self).to_result()
     }
 
     /// Set cursor position
-    pub unsafe fn set_cursor_position(&mut self, column: usize, row: usize) -> Status {
-        (self.set_cursor_position)(self, column, row)
+    pub unsafe fn set_cursor_position(&mut self, column: usize, row: usize) -> Result<()> {
+        (self.set_cursor_position)(self, column, row).to_result()
     }
 
     /// Enable cursor
-    pub unsafe fn enable_cursor(&mut self, visible: bool) -> Status {
-        (self.enable_cursor)(self, visible as Boolean)
+    pub unsafe fn enable_cursor(&mut self, visible: bool) -> Result<()> {
+        (self.enable_cursor)(self, visible as Boolean).to_result()
     }
 }
@@ -1,55 +1,28 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Storage Protocols - SCSI, NVMe, Disk I/O, Partitions
 
+use crate::error::{Error, Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_SCSI_PASS_THRU_PROTOCOL_GUID
-pub const SCSI_PASS_THRU_PROTOCOL_GUID: Guid = Guid::new(
-    0xa59e8fcf,
-    0xbda0,
-    0x43bb,
-    [0x90, 0xb1, 0xd3, 0x73, 0x2e, 0xca, 0xa8, 0x77],
-);
+pub const SCSI_PASS_THRU_PROTOCOL_GUID: Guid = guid!("a59e8fcf-bda0-43bb-90b1-d3732ecaa877");
 
 /// EFI_EXT_SCSI_PASS_THRU_PROTOCOL_GUID
-pub const EXT_SCSI_PASS_THRU_PROTOCOL_GUID: Guid = Guid::new(
-    0x143b7632,
-    0xb81b,
-    0x4cb7,
-    [0xab, 0xd3, 0xb6, 0x25, 0xa5, 0xb9, 0xbf, 0xfe],
-);
+pub const EXT_SCSI_PASS_THRU_PROTOCOL_GUID: Guid = guid!("143b7632-b81b-4cb7-abd3-b625a5b9bffe");
 
 /// EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID
-pub const NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID: Guid = Guid::new(
-    0x52c78312,
-    0x8edc,
-    0x4233,
-    [0x98, 0xf2, 0x1a, 0x1a, 0xa5, 0xe3, 0x88, 0xa5],
-);
+pub const NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID: Guid = guid!("52c78312-8edc-4233-98f2-1a1aa5e388a5");
 
 /// EFI_DISK_IO_PROTOCOL_GUID
-pub const DISK_IO_PROTOCOL_GUID: Guid = Guid::new(
-    0xce345171,
-    0xba0b,
-    0x11d2,
-    [0x8e, 0x4f, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
-);
+pub const DISK_IO_PROTOCOL_GUID: Guid = guid!("ce345171-ba0b-11d2-8e4f-00a0c969723b");
 
 /// EFI_DISK_IO2_PROTOCOL_GUID
-pub const DISK_IO2_PROTOCOL_GUID: Guid = Guid::new(
-    0x151c8eae,
-    0x7f2c,
-    0x472c,
-    [0x9e, 0x54, 0x98, 0x28, 0x19, 0x4f, 0x6a, 0x88],
-);
+pub const DISK_IO2_PROTOCOL_GUID: Guid = guid!("151c8eae-7f2c-472c-9e54-9828194f6a88");
 
 /// EFI_PARTITION_INFO_PROTOCOL_GUID
-pub const PARTITION_INFO_PROTOCOL_GUID: Guid = Guid::new(
-    0x8cf2f62c,
-    0xbc9b,
-    0x4821,
-    [0x80, 0x8d, 0xec, 0x9e, 0xc4, 0x21, 0xa1, 0xa0],
-);
+pub const PARTITION_INFO_PROTOCOL_GUID: Guid = guid!("8cf2f62c-bc9b-4821-808d-ec9ec421a1a0");
 
 /// SCSI Target ID
 pub type ScsiTarget = Uint32;
@@ -114,6 +87,10 @@ pub struct ScsiPassThruProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for ScsiPassThruProtocol {
+    const GUID: Guid = SCSI_PASS_THRU_PROTOCOL_GUID;
+}
+
 /// SCSI Pass Thru Mode
 #[repr(C)]
 pub struct ScsiPassThruMode {
@@ -179,6 +156,10 @@ pub struct ExtScsiPassThruProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for ExtScsiPassThruProtocol {
+    const GUID: Guid = EXT_SCSI_PASS_THRU_PROTOCOL_GUID;
+}
+
 /// Extended SCSI Pass Thru Mode
 #[repr(C)]
 pub struct ExtScsiPassThruMode {
@@ -254,6 +235,10 @@ pub struct NvmExpressPassThruProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for NvmExpressPassThruProtocol {
+    const GUID: Guid = NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID;
+}
+
 /// NVMe Pass Thru Mode
 #[repr(C)]
 pub struct NvmExpressPassThruMode {
@@ -282,6 +267,10 @@ pub struct DiskIoProtocol {
     ) -> Status,
 }
 
+unsafe impl Protocol for DiskIoProtocol {
+    const GUID: Guid = DISK_IO_PROTOCOL_GUID;
+}
+
 /// Disk I/O Token
 #[repr(C)]
 pub struct DiskIo2Token {
@@ -314,6 +303,10 @@ pub struct DiskIo2Protocol {
         unsafe extern "efiapi" fn(this: *mut DiskIo2Protocol, token: *mut DiskIo2Token) -> Status,
 }
 
+unsafe impl Protocol for DiskIo2Protocol {
+    const GUID: Guid = DISK_IO2_PROTOCOL_GUID;
+}
+
 /// Partition Type
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -373,9 +366,18 @@ pub struct PartitionInfoProtocol {
     pub info: PartitionInfo,
 }
 
+unsafe impl Protocol for PartitionInfoProtocol {
+    const GUID: Guid = PARTITION_INFO_PROTOCOL_GUID;
+}
+
 impl DiskIoProtocol {
     /// Read from disk at byte offset
-    pub unsafe fn read_disk(&mut self, media_id: u32, offset: u64, buffer: &mut [u8]) -> Status {
+    pub unsafe fn read_disk(
+        &mut self,
+        media_id: u32,
+        offset: u64,
+        buffer: &mut [u8],
+    ) -> Result<()> {
         (self.read_disk)(
             self,
             media_id,
@@ -383,10 +385,11 @@ impl DiskIoProtocol {
             buffer.len(),
             buffer.as_mut_ptr() as *mut _,
         )
+        .to_result()
     }
 
     /// Write to disk at byte offset
-    pub unsafe fn write_disk(&mut self, media_id: u32, offset: u64, buffer: &[u8]) -> Status {
+    pub unsafe fn write_disk(&mut self, media_id: u32, offset: u64, buffer: &[u8]) -> Result<()> {
         (self.write_disk)(
             self,
             media_id,
@@ -394,6 +397,7 @@ impl DiskIoProtocol {
             buffer.len(),
             buffer.as_ptr() as *const _,
         )
+        .to_result()
     }
 }
 
@@ -405,7 +409,7 @@ impl DiskIo2Protocol {
         offset: u64,
         token: &mut DiskIo2Token,
         buffer: &mut [u8],
-    ) -> Status {
+    ) -> Result<()> {
         (self.read_disk_ex)(
             self,
             media_id,
@@ -414,6 +418,7 @@ impl DiskIo2Protocol {
             buffer.len(),
             buffer.as_mut_ptr() as *mut _,
         )
+        .to_result()
     }
 
     /// Write to disk asynchronously
@@ -423,7 +428,7 @@ impl DiskIo2Protocol {
         offset: u64,
         token: &mut DiskIo2Token,
         buffer: &[u8],
-    ) -> Status {
+    ) -> Result<()> {
         (self.write_disk_ex)(
             self,
             media_id,
@@ -432,11 +437,12 @@ impl DiskIo2Protocol {
             buffer.len(),
             buffer.as_ptr() as *const _,
         )
+        .to_result()
     }
 
     /// Flush disk cache
-    pub unsafe fn flush_disk_ex(&mut self, token: &mut DiskIo2Token) -> Status {
-        (self.flush_disk_ex)(self, token as *mut _)
+    pub unsafe fn flush_disk_ex(&mut self, token: &mut DiskIo2Token) -> Result<()> {
+        (self.flush_disk_ex)(self, token as *mut _).to_result()
     }
 }
 
@@ -447,13 +453,13 @@ impl ScsiPassThruProtocol {
         target: u32,
         lun: u64,
         packet: &mut ScsiPassThruRequestPacket,
-    ) -> Status {
-        (self.pass_thru)(self, target, lun, packet as *mut _, core::ptr::null_mut())
+    ) -> Result<()> {
+        (self.pass_thru)(self, target, lun, packet as *mut _, core::ptr::null_mut()).to_result()
     }
 
     /// Get next SCSI device
-    pub unsafe fn get_next_device(&mut self, target: &mut u32, lun: &mut u64) -> Status {
-        (self.get_next_device)(self, target as *mut _, lun as *mut _)
+    pub unsafe fn get_next_device(&mut self, target: &mut u32, lun: &mut u64) -> Result<()> {
+        (self.get_next_device)(self, target as *mut _, lun as *mut _).to_result()
     }
 }
 
@@ -463,13 +469,13 @@ impl NvmExpressPassThruProtocol {
         &mut self,
         namespace_id: u32,
         packet: &mut NvmePassThruCommandPacket,
-    ) -> Status {
-        (self.pass_thru)(self, namespace_id, packet as *mut _, core::ptr::null_mut())
+    ) -> Result<()> {
+        (self.pass_thru)(self, namespace_id, packet as *mut _, core::ptr::null_mut()).to_result()
     }
 
     /// Get next NVMe namespace
-    pub unsafe fn get_next_namespace(&mut self, namespace_id: &mut u32) -> Status {
-        (self.get_next_namespace)(self, namespace_id as *mut _)
+    pub unsafe fn get_next_namespace(&mut self, namespace_id: &mut u32) -> Result<()> {
+        (self.get_next_namespace)(self, namespace_id as *mut _).to_result()
     }
 }
 
@@ -492,38 +498,19 @@ pub mod scsi_commands {
 /// GPT Partition Type GUIDs
 pub mod gpt_partition_types {
     use crate::ffi::Guid;
+    use crate::guid;
 
     /// Unused entry
-    pub const UNUSED_ENTRY_GUID: Guid = Guid::new(
-        0x00000000,
-        0x0000,
-        0x0000,
-        [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-    );
+    pub const UNUSED_ENTRY_GUID: Guid = guid!("00000000-0000-0000-0000-000000000000");
 
     /// EFI System Partition
-    pub const EFI_SYSTEM_PARTITION_GUID: Guid = Guid::new(
-        0xc12a7328,
-        0xf81f,
-        0x11d2,
-        [0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b],
-    );
+    pub const EFI_SYSTEM_PARTITION_GUID: Guid = guid!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b");
 
     /// Microsoft Basic Data
-    pub const MICROSOFT_BASIC_DATA_GUID: Guid = Guid::new(
-        0xebd0a0a2,
-        0xb9e5,
-        0x4433,
-        [0x87, 0xc0, 0x68, 0xb6, 0xb7, 0x26, 0x99, 0xc7],
-    );
+    pub const MICROSOFT_BASIC_DATA_GUID: Guid = guid!("ebd0a0a2-b9e5-4433-87c0-68b6b72699c7");
 
     /// Linux Filesystem Data
-    pub const LINUX_FILESYSTEM_DATA_GUID: Guid = Guid::new(
-        0x0fc63daf,
-        0x8483,
-        0x4772,
-        [0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d, 0xe4],
-    );
+    pub const LINUX_FILESYSTEM_DATA_GUID: Guid = guid!("0fc63daf-8483-4772-8e79-3d69d8477de4");
 }
 
 // ============================================================================
@@ -547,42 +534,27 @@ impl<'a> SafeScsiPassThru<'a> {
         target: u32,
         lun: u64,
         packet: &mut ScsiPassThruRequestPacket,
-    ) -> Result<(), Status> {
-        let status = unsafe { self.protocol.pass_thru(target, lun, packet) };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+    ) -> Result<()> {
+        unsafe { self.protocol.pass_thru(target, lun, packet) }
     }
 
     /// Get mode information
-    pub fn mode(&self) -> Result<&ScsiPassThruMode, Status> {
+    pub fn mode(&self) -> Result<&ScsiPassThruMode> {
         if self.protocol.mode.is_null() {
-            Err(EFI_DEVICE_ERROR)
+            Err(Error::new(EFI_DEVICE_ERROR))
         } else {
             Ok(unsafe { &*self.protocol.mode })
         }
     }
 
     /// Reset the SCSI channel
-    pub fn reset_channel(&mut self) -> Result<(), Status> {
-        let status = unsafe { (self.protocol.reset_channel)(self.protocol) };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+    pub fn reset_channel(&mut self) -> Result<()> {
+        unsafe { (self.protocol.reset_channel)(self.protocol) }.to_result()
     }
 
     /// Reset specific SCSI target
-    pub fn reset_target(&mut self, target: u32, lun: u64) -> Result<(), Status> {
-        let status = unsafe { (self.protocol.reset_target)(self.protocol, target, lun) };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+    pub fn reset_target(&mut self, target: u32, lun: u64) -> Result<()> {
+        unsafe { (self.protocol.reset_target)(self.protocol, target, lun) }.to_result()
     }
 
     /// Iterator over SCSI devices
@@ -604,7 +576,7 @@ pub struct ScsiDeviceIterator<'a> {
     first: bool,
 }
 
-impl<'a> Iterator for ScsiDeviceIterator<'a> {
+impl Iterator for ScsiDeviceIterator<'_> {
     type Item = (u32, u64);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -612,16 +584,12 @@ impl<'a> Iterator for ScsiDeviceIterator<'a> {
             self.first = false;
         }
 
-        let status = unsafe {
+        let result = unsafe {
             self.protocol
                 .get_next_device(&mut self.target, &mut self.lun)
         };
 
-        if status == EFI_SUCCESS {
-            Some((self.target, self.lun))
-        } else {
-            None
-        }
+        result.ok().map(|_| (self.target, self.lun))
     }
 }
 
@@ -641,19 +609,14 @@ impl<'a> SafeNvmePassThru<'a> {
         &mut self,
         namespace_id: u32,
         packet: &mut NvmePassThruCommandPacket,
-    ) -> Result<(), Status> {
-        let status = unsafe { self.protocol.pass_thru(namespace_id, packet) };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+    ) -> Result<()> {
+        unsafe { self.protocol.pass_thru(namespace_id, packet) }
     }
 
     /// Get mode information
-    pub fn mode(&self) -> Result<&NvmExpressPassThruMode, Status> {
+    pub fn mode(&self) -> Result<&NvmExpressPassThruMode> {
         if self.protocol.mode.is_null() {
-            Err(EFI_DEVICE_ERROR)
+            Err(Error::new(EFI_DEVICE_ERROR))
         } else {
             Ok(unsafe { &*self.protocol.mode })
         }
@@ -669,7 +632,7 @@ impl<'a> SafeNvmePassThru<'a> {
     }
 
     /// Send NVMe Identify Controller command
-    pub fn identify_controller(&mut self, buffer: &mut [u8; 4096]) -> Result<(), Status> {
+    pub fn identify_controller(&mut self, buffer: &mut [u8; 4096]) -> Result<()> {
         let mut cmd = NvmeCommand {
             cdw0: 0x06, // Identify command
             flags: 0,
@@ -706,11 +669,7 @@ impl<'a> SafeNvmePassThru<'a> {
     }
 
     /// Send NVMe Identify Namespace command
-    pub fn identify_namespace(
-        &mut self,
-        namespace_id: u32,
-        buffer: &mut [u8; 4096],
-    ) -> Result<(), Status> {
+    pub fn identify_namespace(&mut self, namespace_id: u32, buffer: &mut [u8; 4096]) -> Result<()> {
         let mut cmd = NvmeCommand {
             cdw0: 0x06, // Identify command
             flags: 0,
@@ -754,7 +713,7 @@ pub struct NvmeNamespaceIterator<'a> {
     first: bool,
 }
 
-impl<'a> Iterator for NvmeNamespaceIterator<'a> {
+impl Iterator for NvmeNamespaceIterator<'_> {
     type Item = u32;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -762,13 +721,9 @@ impl<'a> Iterator for NvmeNamespaceIterator<'a> {
             self.first = false;
         }
 
-        let status = unsafe { self.protocol.get_next_namespace(&mut self.namespace_id) };
+        let result = unsafe { self.protocol.get_next_namespace(&mut self.namespace_id) };
 
-        if status == EFI_SUCCESS {
-            Some(self.namespace_id)
-        } else {
-            None
-        }
+        result.ok().map(|_| self.namespace_id)
     }
 }
 
@@ -784,31 +739,21 @@ impl<'a> SafeDiskIo<'a> {
     }
 
     /// Read from disk at byte offset
-    pub fn read(&mut self, media_id: u32, offset: u64, buffer: &mut [u8]) -> Result<(), Status> {
+    pub fn read(&mut self, media_id: u32, offset: u64, buffer: &mut [u8]) -> Result<()> {
         if buffer.is_empty() {
             return Ok(());
         }
 
-        let status = unsafe { self.protocol.read_disk(media_id, offset, buffer) };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+        unsafe { self.protocol.read_disk(media_id, offset, buffer) }
     }
 
     /// Write to disk at byte offset
-    pub fn write(&mut self, media_id: u32, offset: u64, buffer: &[u8]) -> Result<(), Status> {
+    pub fn write(&mut self, media_id: u32, offset: u64, buffer: &[u8]) -> Result<()> {
         if buffer.is_empty() {
             return Ok(());
         }
 
-        let status = unsafe { self.protocol.write_disk(media_id, offset, buffer) };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+        unsafe { self.protocol.write_disk(media_id, offset, buffer) }
     }
 
     /// Get revision
@@ -835,16 +780,14 @@ impl<'a> SafeDiskIo2<'a> {
         offset: u64,
         token: &mut DiskIo2Token,
         buffer: &mut [u8],
-    ) -> Result<(), Status> {
+    ) -> Result<()> {
         if buffer.is_empty() {
             return Ok(());
         }
 
-        let status = unsafe { self.protocol.read_disk_ex(media_id, offset, token, buffer) };
-        if status == EFI_SUCCESS || status == EFI_NOT_READY {
-            Ok(())
-        } else {
-            Err(status)
+        match unsafe { self.protocol.read_disk_ex(media_id, offset, token, buffer) } {
+            Err(err) if err.status() == EFI_NOT_READY => Ok(()),
+            result => result,
         }
     }
 
@@ -855,37 +798,28 @@ impl<'a> SafeDiskIo2<'a> {
         offset: u64,
         token: &mut DiskIo2Token,
         buffer: &[u8],
-    ) -> Result<(), Status> {
+    ) -> Result<()> {
         if buffer.is_empty() {
             return Ok(());
         }
 
-        let status = unsafe { self.protocol.write_disk_ex(media_id, offset, token, buffer) };
-        if status == EFI_SUCCESS || status == EFI_NOT_READY {
-            Ok(())
-        } else {
-            Err(status)
+        match unsafe { self.protocol.write_disk_ex(media_id, offset, token, buffer) } {
+            Err(err) if err.status() == EFI_NOT_READY => Ok(()),
+            result => result,
         }
     }
 
     /// Flush disk cache
-    pub fn flush(&mut self, token: &mut DiskIo2Token) -> Result<(), Status> {
-        let status = unsafe { self.protocol.flush_disk_ex(token) };
-        if status == EFI_SUCCESS || status == EFI_NOT_READY {
-            Ok(())
-        } else {
-            Err(status)
+    pub fn flush(&mut self, token: &mut DiskIo2Token) -> Result<()> {
+        match unsafe { self.protocol.flush_disk_ex(token) } {
+            Err(err) if err.status() == EFI_NOT_READY => Ok(()),
+            result => result,
         }
     }
 
     /// Cancel all pending operations
-    pub fn cancel(&mut self) -> Result<(), Status> {
-        let status = unsafe { (self.protocol.cancel)(self.protocol) };
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
-        }
+    pub fn cancel(&mut self) -> Result<()> {
+        unsafe { (self.protocol.cancel)(self.protocol) }.to_result()
     }
 
     /// Get revision
@@ -0,0 +1,467 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! EFI TCG2 Protocol
+//!
+//! Bindings for `EFI_TCG2_PROTOCOL` from the TCG EFI Protocol Specification.
+//! [`Tpm2Protocol`](super::security::Tpm2Protocol) names the same interface with untyped pointers; the
+//! definitions here follow the specification, including SetActivePcrBanks.
+
+use crate::error::{Error, Result, StatusExt};
+use crate::ffi::*;
+use crate::protocols::security::{Tpm2BootServiceCapability, TPM2_PROTOCOL_GUID};
+use crate::protocols::Protocol;
+use crate::tpm2::event_log::{entry_len, EventLog, EventLogFormat, SpecIdEvent};
+use crate::tpm2::{TPM_ALG_SHA1, TPM_ALG_SHA256, TPM_ALG_SHA384, TPM_ALG_SHA512, TPM_ALG_SM3_256};
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// EFI_TCG2_PROTOCOL_GUID, also bound as `TPM2_PROTOCOL_GUID`
+pub const TCG2_PROTOCOL_GUID: Guid = TPM2_PROTOCOL_GUID;
+
+// EFI_TCG2_EVENT_LOG_BITMAP
+pub const EFI_TCG2_EVENT_LOG_FORMAT_TCG_1_2: u32 = 0x0000_0001;
+pub const EFI_TCG2_EVENT_LOG_FORMAT_TCG_2: u32 = 0x0000_0002;
+
+// EFI_TCG2_BOOT_HASH_ALG bitmap
+pub const EFI_TCG2_BOOT_HASH_ALG_SHA1: u32 = 0x0000_0001;
+pub const EFI_TCG2_BOOT_HASH_ALG_SHA256: u32 = 0x0000_0002;
+pub const EFI_TCG2_BOOT_HASH_ALG_SHA384: u32 = 0x0000_0004;
+pub const EFI_TCG2_BOOT_HASH_ALG_SHA512: u32 = 0x0000_0008;
+pub const EFI_TCG2_BOOT_HASH_ALG_SM3_256: u32 = 0x0000_0010;
+
+// HashLogExtendEvent flags
+pub const EFI_TCG2_EXTEND_ONLY: u64 = 0x0000_0000_0000_0001;
+pub const PE_COFF_IMAGE: u64 = 0x0000_0000_0000_0010;
+
+pub const EFI_TCG2_EVENT_HEADER_VERSION: u16 = 1;
+
+const BOOT_HASH_ALGS: [(u32, u16); 5] = [
+    (EFI_TCG2_BOOT_HASH_ALG_SHA1, TPM_ALG_SHA1),
+    (EFI_TCG2_BOOT_HASH_ALG_SHA256, TPM_ALG_SHA256),
+    (EFI_TCG2_BOOT_HASH_ALG_SHA384, TPM_ALG_SHA384),
+    (EFI_TCG2_BOOT_HASH_ALG_SHA512, TPM_ALG_SHA512),
+    (EFI_TCG2_BOOT_HASH_ALG_SM3_256, TPM_ALG_SM3_256),
+];
+
+/// TPM_ALG_IDs of the banks set in an EFI_TCG2_BOOT_HASH_ALG bitmap
+pub fn bank_algorithms(bitmap: u32) -> impl Iterator<Item = u16> {
+    BOOT_HASH_ALGS
+        .into_iter()
+        .filter(move |(bit, _)| bitmap & bit != 0)
+        .map(|(_, alg)| alg)
+}
+
+/// EFI_TCG2_BOOT_SERVICE_CAPABILITY
+pub type Tcg2BootServiceCapability = Tpm2BootServiceCapability;
+
+/// EFI_TCG2_EVENT_HEADER
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct Tcg2EventHeader {
+    pub header_size: Uint32,
+    pub header_version: Uint16,
+    pub pcr_index: Uint32,
+    pub event_type: Uint32,
+}
+
+/// EFI_TCG2_EVENT
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct Tcg2Event {
+    /// Size of the whole structure including the event data
+    pub size: Uint32,
+    pub header: Tcg2EventHeader,
+    // Followed by Event[variable length]
+}
+
+/// EFI_TCG2_PROTOCOL
+#[repr(C)]
+pub struct Tcg2Protocol {
+    pub get_capability: unsafe extern "efiapi" fn(
+        this: *mut Tcg2Protocol,
+        protocol_capability: *mut Tcg2BootServiceCapability,
+    ) -> Status,
+    pub get_event_log: unsafe extern "efiapi" fn(
+        this: *mut Tcg2Protocol,
+        event_log_format: Uint32,
+        event_log_location: *mut PhysicalAddress,
+        event_log_last_entry: *mut PhysicalAddress,
+        event_log_truncated: *mut Boolean,
+    ) -> Status,
+    pub hash_log_extend_event: unsafe extern "efiapi" fn(
+        this: *mut Tcg2Protocol,
+        flags: Uint64,
+        data_to_hash: PhysicalAddress,
+        data_to_hash_len: Uint64,
+        efi_tcg_event: *mut Tcg2Event,
+    ) -> Status,
+    pub submit_command: unsafe extern "efiapi" fn(
+        this: *mut Tcg2Protocol,
+        input_parameter_block_size: Uint32,
+        input_parameter_block: *const Uint8,
+        output_parameter_block_size: Uint32,
+        output_parameter_block: *mut Uint8,
+    ) -> Status,
+    pub get_active_pcr_banks:
+        unsafe extern "efiapi" fn(this: *mut Tcg2Protocol, active_pcr_banks: *mut Uint32) -> Status,
+    pub set_active_pcr_banks:
+        unsafe extern "efiapi" fn(this: *mut Tcg2Protocol, active_pcr_banks: Uint32) -> Status,
+    pub get_result_of_set_active_pcr_banks: unsafe extern "efiapi" fn(
+        this: *mut Tcg2Protocol,
+        operation_present: *mut Uint32,
+        response: *mut Uint32,
+    ) -> Status,
+}
+
+unsafe impl Protocol for Tcg2Protocol {
+    const GUID: Guid = TCG2_PROTOCOL_GUID;
+}
+
+/// Length of the log entry at `entry`, reading only as far as it needs
+unsafe fn raw_entry_len(entry: *const u8, spec_id: Option<&SpecIdEvent>) -> Result<usize> {
+    let mut len = 0;
+    loop {
+        let needed = entry_len(core::slice::from_raw_parts(entry, len), spec_id)?;
+        if needed <= len {
+            return Ok(needed);
+        }
+        len = needed;
+    }
+}
+
+/// Safe wrapper for TCG2 Protocol
+pub struct SafeTcg2<'a> {
+    protocol: &'a mut Tcg2Protocol,
+}
+
+impl<'a> SafeTcg2<'a> {
+    /// Create a new safe wrapper
+    pub fn new(protocol: &'a mut Tcg2Protocol) -> Self {
+        Self { protocol }
+    }
+
+    /// Get TCG2 capabilities
+    pub fn get_capability(&mut self) -> Result<Tcg2BootServiceCapability> {
+        let mut cap: Tcg2BootServiceCapability = unsafe { core::mem::zeroed() };
+        // The caller announces how much of the structure it understands
+        cap.size = core::mem::size_of::<Tcg2BootServiceCapability>() as u8;
+        unsafe { (self.protocol.get_capability)(self.protocol, &mut cap) }.to_result()?;
+        Ok(cap)
+    }
+
+    /// Get the active PCR banks as an EFI_TCG2_BOOT_HASH_ALG bitmap
+    pub fn get_active_pcr_banks(&mut self) -> Result<u32> {
+        let mut banks = 0;
+        unsafe { (self.protocol.get_active_pcr_banks)(self.protocol, &mut banks) }.to_result()?;
+        Ok(banks)
+    }
+
+    /// Request a new set of active PCR banks, applied on the next reset
+    pub fn set_active_pcr_banks(&mut self, banks: u32) -> Result<()> {
+        unsafe { (self.protocol.set_active_pcr_banks)(self.protocol, banks) }.to_result()
+    }
+
+    /// Submit a TPM2 command
+    pub fn submit_command(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize> {
+        unsafe {
+            (self.protocol.submit_command)(
+                self.protocol,
+                input.len() as u32,
+                input.as_ptr(),
+                output.len() as u32,
+                output.as_mut_ptr(),
+            )
+        }
+        .to_result()?;
+
+        // Parse response size from TPM2 response header
+        if output.len() >= 10 {
+            let size = u32::from_be_bytes([output[2], output[3], output[4], output[5]]);
+            Ok(size as usize)
+        } else {
+            Ok(output.len())
+        }
+    }
+
+    /// Hash `data`, extend the digests into `pcr_index` and log the event
+    ///
+    /// `flags` takes `EFI_TCG2_EXTEND_ONLY` to skip the log entry and
+    /// `PE_COFF_IMAGE` to measure `data` as a PE/COFF image.
+    pub fn hash_log_extend_event(
+        &mut self,
+        flags: u64,
+        data: &[u8],
+        pcr_index: u32,
+        event_type: u32,
+        event_data: &[u8],
+    ) -> Result<()> {
+        let header_size = core::mem::size_of::<Tcg2EventHeader>();
+        let size = core::mem::size_of::<Tcg2Event>() + event_data.len();
+        let mut event = Vec::with_capacity(size);
+        event.extend_from_slice(&(size as u32).to_le_bytes());
+        event.extend_from_slice(&(header_size as u32).to_le_bytes());
+        event.extend_from_slice(&EFI_TCG2_EVENT_HEADER_VERSION.to_le_bytes());
+        event.extend_from_slice(&pcr_index.to_le_bytes());
+        event.extend_from_slice(&event_type.to_le_bytes());
+        event.extend_from_slice(event_data);
+        unsafe {
+            (self.protocol.hash_log_extend_event)(
+                self.protocol,
+                flags,
+                data.as_ptr() as PhysicalAddress,
+                data.len() as u64,
+                event.as_mut_ptr() as *mut Tcg2Event,
+            )
+        }
+        .to_result()
+    }
+
+    /// Get the event log in `format`
+    ///
+    /// Returns the log and whether the firmware had to drop events because
+    /// the log was full. Fails with `EFI_NOT_FOUND` if there is no log and
+    /// with `EFI_INVALID_PARAMETER` if the firmware does not keep `format`.
+    ///
+    /// # Safety
+    /// The log lives in boot services memory and must not be used after
+    /// ExitBootServices.
+    pub unsafe fn get_event_log(
+        &mut self,
+        format: EventLogFormat,
+    ) -> Result<(EventLog<'static>, bool)> {
+        let mut location: PhysicalAddress = 0;
+        let mut last_entry: PhysicalAddress = 0;
+        let mut truncated: Boolean = 0;
+        (self.protocol.get_event_log)(
+            self.protocol,
+            format.bits(),
+            &mut location,
+            &mut last_entry,
+            &mut truncated,
+        )
+        .to_result()?;
+        if location == 0 {
+            return Err(Error::new(EFI_NOT_FOUND));
+        }
+
+        // The firmware reports where the last entry starts, not where it ends
+        let start = location as usize as *const u8;
+        let len = match (format, last_entry) {
+            (EventLogFormat::Tcg12, 0) => 0,
+            (EventLogFormat::Tcg2, 0) => raw_entry_len(start, None)?,
+            _ => {
+                let offset = last_entry
+                    .checked_sub(location)
+                    .ok_or(Error::new(EFI_DEVICE_ERROR))? as usize;
+                let spec_id = if format == EventLogFormat::Tcg2 && offset != 0 {
+                    let header = core::slice::from_raw_parts(start, raw_entry_len(start, None)?);
+                    EventLog::parse(header, format)?.spec_id().cloned()
+                } else {
+                    None
+                };
+                offset + raw_entry_len(start.add(offset), spec_id.as_ref())?
+            }
+        };
+        let log = EventLog::parse(core::slice::from_raw_parts(start, len), format)?;
+        Ok((log, truncated != 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm2::event_log::{EventData, EV_EFI_ACTION, EV_SEPARATOR};
+    use crate::tpm2::mock::{log_entry, log_header, MockTpm};
+    use crate::tpm2::{hash_algorithm, Tpm2, Tpm2Transport, Tpml, TpmtHa};
+    use std::cell::RefCell;
+
+    const BANKS: [u16; 2] = [TPM_ALG_SHA1, TPM_ALG_SHA256];
+
+    struct Firmware {
+        tpm: Tpm2<MockTpm>,
+        log: Vec<u8>,
+        last_entry: usize,
+    }
+
+    std::thread_local! {
+        static FIRMWARE: RefCell<Option<Firmware>> = const { RefCell::new(None) };
+    }
+
+    fn with_firmware<R>(f: impl FnOnce(&mut Firmware) -> R) -> R {
+        FIRMWARE.with(|fw| f(fw.borrow_mut().as_mut().unwrap()))
+    }
+
+    unsafe extern "efiapi" fn get_capability(
+        _this: *mut Tcg2Protocol,
+        cap: *mut Tcg2BootServiceCapability,
+    ) -> Status {
+        if ((*cap).size as usize) < core::mem::size_of::<Tcg2BootServiceCapability>() {
+            return EFI_BUFFER_TOO_SMALL;
+        }
+        (*cap).hash_algorithm_bitmap = EFI_TCG2_BOOT_HASH_ALG_SHA1
+            | EFI_TCG2_BOOT_HASH_ALG_SHA256
+            | EFI_TCG2_BOOT_HASH_ALG_SHA384;
+        (*cap).supported_event_logs = EFI_TCG2_EVENT_LOG_FORMAT_TCG_2;
+        (*cap).tpm_present_flag = 1;
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn get_event_log(
+        _this: *mut Tcg2Protocol,
+        format: Uint32,
+        location: *mut PhysicalAddress,
+        last_entry: *mut PhysicalAddress,
+        truncated: *mut Boolean,
+    ) -> Status {
+        if format != EFI_TCG2_EVENT_LOG_FORMAT_TCG_2 {
+            return EFI_INVALID_PARAMETER;
+        }
+        with_firmware(|fw| {
+            *location = fw.log.as_ptr() as PhysicalAddress;
+            *last_entry = *location + fw.last_entry as PhysicalAddress;
+            *truncated = 0;
+        });
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn hash_log_extend_event(
+        _this: *mut Tcg2Protocol,
+        flags: Uint64,
+        data: PhysicalAddress,
+        data_len: Uint64,
+        event: *mut Tcg2Event,
+    ) -> Status {
+        let data = core::slice::from_raw_parts(data as usize as *const u8, data_len as usize);
+        let header = (*event).header;
+        let event_data = core::slice::from_raw_parts(
+            (event as *const u8).add(core::mem::size_of::<Tcg2Event>()),
+            (*event).size as usize - core::mem::size_of::<Tcg2Event>(),
+        );
+        with_firmware(|fw| {
+            let digests = BANKS
+                .iter()
+                .map(|&bank| TpmtHa {
+                    hash_alg: bank,
+                    digest: hash_algorithm(bank)
+                        .unwrap()
+                        .digest(data)
+                        .as_bytes()
+                        .to_vec(),
+                })
+                .collect();
+            fw.tpm.pcr_extend(header.pcr_index, &Tpml(digests)).unwrap();
+            if flags & EFI_TCG2_EXTEND_ONLY == 0 {
+                fw.last_entry = fw.log.len();
+                fw.log.extend(log_entry(
+                    header.pcr_index,
+                    header.event_type,
+                    data,
+                    event_data,
+                    &BANKS,
+                ));
+            }
+        });
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn submit_command(
+        _this: *mut Tcg2Protocol,
+        input_size: Uint32,
+        input: *const Uint8,
+        output_size: Uint32,
+        output: *mut Uint8,
+    ) -> Status {
+        let input = core::slice::from_raw_parts(input, input_size as usize);
+        let output = core::slice::from_raw_parts_mut(output, output_size as usize);
+        match with_firmware(|fw| fw.tpm.transport().submit_command(input, output)) {
+            Ok(_) => EFI_SUCCESS,
+            Err(err) => err.status(),
+        }
+    }
+
+    unsafe extern "efiapi" fn get_active_pcr_banks(
+        _this: *mut Tcg2Protocol,
+        banks: *mut Uint32,
+    ) -> Status {
+        *banks = EFI_TCG2_BOOT_HASH_ALG_SHA1 | EFI_TCG2_BOOT_HASH_ALG_SHA256;
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn set_active_pcr_banks(
+        _this: *mut Tcg2Protocol,
+        _banks: Uint32,
+    ) -> Status {
+        EFI_UNSUPPORTED
+    }
+
+    unsafe extern "efiapi" fn get_result_of_set_active_pcr_banks(
+        _this: *mut Tcg2Protocol,
+        _operation_present: *mut Uint32,
+        _response: *mut Uint32,
+    ) -> Status {
+        EFI_UNSUPPORTED
+    }
+
+    fn protocol() -> Tcg2Protocol {
+        FIRMWARE.with(|fw| {
+            *fw.borrow_mut() = Some(Firmware {
+                tpm: Tpm2::new(MockTpm::new()),
+                log: log_header(&BANKS),
+                last_entry: 0,
+            })
+        });
+        Tcg2Protocol {
+            get_capability,
+            get_event_log,
+            hash_log_extend_event,
+            submit_command,
+            get_active_pcr_banks,
+            set_active_pcr_banks,
+            get_result_of_set_active_pcr_banks,
+        }
+    }
+
+    #[test]
+    fn test_measure_and_replay_event_log() {
+        let mut protocol = protocol();
+        let mut tcg2 = SafeTcg2::new(&mut protocol);
+        let cap = tcg2.get_capability().unwrap();
+        assert_eq!(cap.tpm_present_flag, 1);
+        let active = tcg2.get_active_pcr_banks().unwrap();
+        assert_eq!(bank_algorithms(active).collect::<Vec<_>>(), BANKS);
+
+        // Only the Spec ID event so far
+        let (log, truncated) = unsafe { tcg2.get_event_log(EventLogFormat::Tcg2) }.unwrap();
+        assert!(!truncated);
+        assert_eq!(log.events().count(), 0);
+
+        tcg2.hash_log_extend_event(0, b"config", 1, EV_EFI_ACTION, b"Config")
+            .unwrap();
+        tcg2.hash_log_extend_event(EFI_TCG2_EXTEND_ONLY, b"unlogged", 2, EV_EFI_ACTION, b"")
+            .unwrap();
+        tcg2.hash_log_extend_event(0, &[0; 4], 7, EV_SEPARATOR, &[0; 4])
+            .unwrap();
+
+        let (log, _) = unsafe { tcg2.get_event_log(EventLogFormat::Tcg2) }.unwrap();
+        let events: Vec<_> = log.events().collect::<Result<_>>().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].decode().unwrap(), EventData::Action("Config"));
+        assert_eq!(events[1].pcr_index, 7);
+        let err = unsafe { tcg2.get_event_log(EventLogFormat::Tcg12) }.unwrap_err();
+        assert_eq!(err.status(), EFI_INVALID_PARAMETER);
+
+        // Commands go through the same protocol, so the replay can be
+        // checked against the TPM
+        let expected = log.replay_all().unwrap();
+        let pcrs: Vec<u32> = (0..24).collect();
+        let actual = Tpm2::new(&mut tcg2).pcr_read_all_banks(&pcrs).unwrap();
+        for (expected, actual) in expected.iter().zip(&actual) {
+            assert_eq!(
+                (expected.hash_alg, expected.pcr),
+                (actual.hash_alg, actual.pcr)
+            );
+            // PCR 2 was extended without a log entry
+            assert_eq!(expected.digest == actual.digest, expected.pcr != 2);
+        }
+    }
+}
@@ -1,72 +1,35 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI TCP/UDP Protocol - Network Transport Layer
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 use crate::runtime_services::Time;
 
 /// EFI_TCP4_SERVICE_BINDING_PROTOCOL_GUID
-pub const TCP4_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0x00720665,
-    0x67EB,
-    0x4a99,
-    [0xBA, 0xF7, 0xD3, 0xC3, 0x3A, 0x1C, 0x7C, 0xC9],
-);
+pub const TCP4_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("00720665-67eb-4a99-baf7-d3c33a1c7cc9");
 
 /// EFI_TCP4_PROTOCOL_GUID
-pub const TCP4_PROTOCOL_GUID: Guid = Guid::new(
-    0x65530BC7,
-    0xA359,
-    0x410f,
-    [0xB0, 0x10, 0x5A, 0xAD, 0xC7, 0xEC, 0x2B, 0x62],
-);
+pub const TCP4_PROTOCOL_GUID: Guid = guid!("65530bc7-a359-410f-b010-5aadc7ec2b62");
 
 /// EFI_TCP6_SERVICE_BINDING_PROTOCOL_GUID
-pub const TCP6_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0xec20eb79,
-    0x6c1a,
-    0x4664,
-    [0x9a, 0x0d, 0xd2, 0xe4, 0xcc, 0x16, 0xd6, 0x64],
-);
+pub const TCP6_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("ec20eb79-6c1a-4664-9a0d-d2e4cc16d664");
 
 /// EFI_TCP6_PROTOCOL_GUID
-pub const TCP6_PROTOCOL_GUID: Guid = Guid::new(
-    0x46e44855,
-    0xbd60,
-    0x4ab7,
-    [0xab, 0x0d, 0xa6, 0x79, 0xb9, 0x44, 0x7d, 0x77],
-);
+pub const TCP6_PROTOCOL_GUID: Guid = guid!("46e44855-bd60-4ab7-ab0d-a679b9447d77");
 
 /// EFI_UDP4_SERVICE_BINDING_PROTOCOL_GUID
-pub const UDP4_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0x3ad9df29,
-    0x4501,
-    0x478d,
-    [0xb1, 0xf8, 0x7f, 0x7f, 0xe7, 0x0e, 0x50, 0xf3],
-);
+pub const UDP4_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("83f01464-99bd-45e5-b383-af6305d8e9e6");
 
 /// EFI_UDP4_PROTOCOL_GUID
-pub const UDP4_PROTOCOL_GUID: Guid = Guid::new(
-    0x3ad9df29,
-    0x4501,
-    0x478d,
-    [0xb1, 0xf8, 0x7f, 0x7f, 0xe7, 0x0e, 0x50, 0xf3],
-);
+pub const UDP4_PROTOCOL_GUID: Guid = guid!("3ad9df29-4501-478d-b1f8-7f7fe70e50f3");
 
 /// EFI_UDP6_SERVICE_BINDING_PROTOCOL_GUID
-pub const UDP6_SERVICE_BINDING_PROTOCOL_GUID: Guid = Guid::new(
-    0x66ed4721,
-    0x3c98,
-    0x4d3e,
-    [0x81, 0xe3, 0xd0, 0x3d, 0xd3, 0x9a, 0x72, 0x54],
-);
+pub const UDP6_SERVICE_BINDING_PROTOCOL_GUID: Guid = guid!("66ed4721-3c98-4d3e-81e3-d03dd39a7254");
 
 /// EFI_UDP6_PROTOCOL_GUID
-pub const UDP6_PROTOCOL_GUID: Guid = Guid::new(
-    0x4f948815,
-    0xb4b9,
-    0x43cb,
-    [0x8a, 0x33, 0x90, 0xe0, 0x60, 0xb3, 0x49, 0x55],
-);
+pub const UDP6_PROTOCOL_GUID: Guid = guid!("4f948815-b4b9-43cb-8a33-90e060b34955");
 
 /// TCP Connection State
 #[repr(u32)]
@@ -318,6 +281,10 @@ pub struct Tcp4Protocol {
     pub poll: unsafe extern "efiapi" fn(this: *mut Tcp4Protocol) -> Status,
 }
 
+unsafe impl Protocol for Tcp4Protocol {
+    const GUID: Guid = TCP4_PROTOCOL_GUID;
+}
+
 /// EFI_TCP6_PROTOCOL
 #[repr(C)]
 pub struct Tcp6Protocol {
@@ -356,6 +323,10 @@ pub struct Tcp6Protocol {
     pub poll: unsafe extern "efiapi" fn(this: *mut Tcp6Protocol) -> Status,
 }
 
+unsafe impl Protocol for Tcp6Protocol {
+    const GUID: Guid = TCP6_PROTOCOL_GUID;
+}
+
 /// UDP4 Configuration Data
 #[repr(C)]
 pub struct Udp4ConfigData {
@@ -536,6 +507,10 @@ pub struct Udp4Protocol {
     pub poll: unsafe extern "efiapi" fn(this: *mut Udp4Protocol) -> Status,
 }
 
+unsafe impl Protocol for Udp4Protocol {
+    const GUID: Guid = UDP4_PROTOCOL_GUID;
+}
+
 /// EFI_UDP6_PROTOCOL
 #[repr(C)]
 pub struct Udp6Protocol {
@@ -570,94 +545,98 @@ pub struct Udp6Protocol {
     pub poll: unsafe extern "efiapi" fn(this: *mut Udp6Protocol) -> Status,
 }
 
+unsafe impl Protocol for Udp6Protocol {
+    const GUID: Guid = UDP6_PROTOCOL_GUID;
+}
+
 impl Tcp4Protocol {
     /// Configure TCP4 connection
-    pub unsafe fn configure(&mut self, config: Option<&Tcp4ConfigData>) -> Status {
+    pub unsafe fn configure(&mut self, config: Option<&Tcp4ConfigData>) -> Result<()> {
         let config_ptr = config.map_or(core::ptr::null(), |c| c as *const _);
-        (self.configure)(self, config_ptr)
+        (self.configure)(self, config_ptr).to_result()
     }
 
     /// Connect to remote endpoint
-    pub unsafe fn connect(&mut self, token: &mut Tcp4CompletionToken) -> Status {
-        (self.connect)(self, token as *mut _)
+    pub unsafe fn connect(&mut self, token: &mut Tcp4CompletionToken) -> Result<()> {
+        (self.connect)(self, token as *mut _).to_result()
     }
 
     /// Transmit data
-    pub unsafe fn transmit(&mut self, token: &mut Tcp4IoToken) -> Status {
-        (self.transmit)(self, token as *mut _)
+    pub unsafe fn transmit(&mut self, token: &mut Tcp4IoToken) -> Result<()> {
+        (self.transmit)(self, token as *mut _).to_result()
     }
 
     /// Receive data
-    pub unsafe fn receive(&mut self, token: &mut Tcp4IoToken) -> Status {
-        (self.receive)(self, token as *mut _)
+    pub unsafe fn receive(&mut self, token: &mut Tcp4IoToken) -> Result<()> {
+        (self.receive)(self, token as *mut _).to_result()
     }
 
     /// Close connection
-    pub unsafe fn close(&mut self, token: &mut Tcp4CompletionToken) -> Status {
-        (self.close)(self, token as *mut _)
+    pub unsafe fn close(&mut self, token: &mut Tcp4CompletionToken) -> Result<()> {
+        (self.close)(self, token as *mut _).to_result()
     }
 }
 
 impl Tcp6Protocol {
     /// Configure TCP6 connection
-    pub unsafe fn configure(&mut self, config: Option<&Tcp6ConfigData>) -> Status {
+    pub unsafe fn configure(&mut self, config: Option<&Tcp6ConfigData>) -> Result<()> {
         let config_ptr = config.map_or(core::ptr::null(), |c| c as *const _);
-        (self.configure)(self, config_ptr)
+        (self.configure)(self, config_ptr).to_result()
     }
 
     /// Connect to remote endpoint
-    pub unsafe fn connect(&mut self, token: &mut Tcp6CompletionToken) -> Status {
-        (self.connect)(self, token as *mut _)
+    pub unsafe fn connect(&mut self, token: &mut Tcp6CompletionToken) -> Result<()> {
+        (self.connect)(self, token as *mut _).to_result()
     }
 
     /// Transmit data
-    pub unsafe fn transmit(&mut self, token: &mut Tcp6IoToken) -> Status {
-        (self.transmit)(self, token as *mut _)
+    pub unsafe fn transmit(&mut self, token: &mut Tcp6IoToken) -> Result<()> {
+        (self.transmit)(self, token as *mut _).to_result()
     }
 
     /// Receive data
-    pub unsafe fn receive(&mut self, token: &mut Tcp6IoToken) -> Status {
-        (self.receive)(self, token as *mut _)
+    pub unsafe fn receive(&mut self, token: &mut Tcp6IoToken) -> Result<()> {
+        (self.receive)(self, token as *mut _).to_result()
     }
 
     /// Close connection
-    pub unsafe fn close(&mut self, token: &mut Tcp6CompletionToken) -> Status {
-        (self.close)(self, token as *mut _)
+    pub unsafe fn close(&mut self, token: &mut Tcp6CompletionToken) -> Result<()> {
+        (self.close)(self, token as *mut _).to_result()
     }
 }
 
 impl Udp4Protocol {
     /// Configure UDP4
-    pub unsafe fn configure(&mut self, config: Option<&Udp4ConfigData>) -> Status {
+    pub unsafe fn configure(&mut self, config: Option<&Udp4ConfigData>) -> Result<()> {
         let config_ptr = config.map_or(core::ptr::null(), |c| c as *const _);
-        (self.configure)(self, config_ptr)
+        (self.configure)(self, config_ptr).to_result()
     }
 
     /// Transmit datagram
-    pub unsafe fn transmit(&mut self, token: &mut Udp4CompletionToken) -> Status {
-        (self.transmit)(self, token as *mut _)
+    pub unsafe fn transmit(&mut self, token: &mut Udp4CompletionToken) -> Result<()> {
+        (self.transmit)(self, token as *mut _).to_result()
     }
 
     /// Receive datagram
-    pub unsafe fn receive(&mut self, token: &mut Udp4CompletionToken) -> Status {
-        (self.receive)(self, token as *mut _)
+    pub unsafe fn receive(&mut self, token: &mut Udp4CompletionToken) -> Result<()> {
+        (self.receive)(self, token as *mut _).to_result()
     }
 }
 
 impl Udp6Protocol {
     /// Configure UDP6
-    pub unsafe fn configure(&mut self, config: Option<&Udp6ConfigData>) -> Status {
+    pub unsafe fn configure(&mut self, config: Option<&Udp6ConfigData>) -> Result<()> {
         let config_ptr = config.map_or(core::ptr::null(), |c| c as *const _);
-        (self.configure)(self, config_ptr)
+        (self.configure)(self, config_ptr).to_result()
     }
 
     /// Transmit datagram
-    pub unsafe fn transmit(&mut self, token: &mut Udp6CompletionToken) -> Status {
-        (self.transmit)(self, token as *mut _)
+    pub unsafe fn transmit(&mut self, token: &mut Udp6CompletionToken) -> Result<()> {
+        (self.transmit)(self, token as *mut _).to_result()
     }
 
     /// Receive datagram
-    pub unsafe fn receive(&mut self, token: &mut Udp6CompletionToken) -> Status {
-        (self.receive)(self, token as *mut _)
+    pub unsafe fn receive(&mut self, token: &mut Udp6CompletionToken) -> Result<()> {
+        (self.receive)(self, token as *mut _).to_result()
     }
 }
@@ -1,15 +1,13 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI USB I/O Protocol
 
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::guid;
+use crate::protocols::Protocol;
 
 /// EFI_USB_IO_PROTOCOL_GUID
-pub const USB_IO_PROTOCOL_GUID: Guid = Guid::new(
-    0x2B2F68D6,
-    0x0CD2,
-    0x44cf,
-    [0x8E, 0x8B, 0xBB, 0xA2, 0x0B, 0x1B, 0x5B, 0x75],
-);
+pub const USB_IO_PROTOCOL_GUID: Guid = guid!("2b2f68d6-0cd2-44cf-8e8b-bba20b1b5b75");
 
 /// EFI_USB_DATA_DIRECTION
 #[repr(u32)]
@@ -175,6 +173,10 @@ pub struct UsbIoProtocol {
     pub usb_port_reset: unsafe extern "efiapi" fn(this: *mut UsbIoProtocol) -> Status,
 }
 
+unsafe impl Protocol for UsbIoProtocol {
+    const GUID: Guid = USB_IO_PROTOCOL_GUID;
+}
+
 impl UsbIoProtocol {
     /// Perform a USB control transfer
     pub unsafe fn control_transfer(
@@ -183,14 +185,14 @@ impl UsbIoProtocol {
         direction: UsbDataDirection,
         timeout: u32,
         data: Option<&mut [u8]>,
-    ) -> Result<u32, Status> {
+    ) -> Result<u32> {
         let mut usb_status = 0u32;
         let (data_ptr, data_len) = match data {
             Some(buf) => (buf.as_mut_ptr() as *mut core::ffi::c_void, buf.len()),
             None => (core::ptr::null_mut(), 0),
         };
 
-        let status = (self.usb_control_transfer)(
+        (self.usb_control_transfer)(
             self,
             request,
             direction,
@@ -198,13 +200,9 @@ impl UsbIoProtocol {
             data_ptr,
             data_len,
             &mut usb_status,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok(usb_status)
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok(usb_status)
     }
 
     /// Perform a USB bulk transfer
@@ -213,65 +211,46 @@ impl UsbIoProtocol {
         endpoint: u8,
         data: &mut [u8],
         timeout: usize,
-    ) -> Result<(usize, u32), Status> {
+    ) -> Result<(usize, u32)> {
         let mut data_length = data.len();
         let mut usb_status = 0u32;
 
-        let status = (self.usb_bulk_transfer)(
+        (self.usb_bulk_transfer)(
             self,
             endpoint,
             data.as_mut_ptr() as *mut core::ffi::c_void,
             &mut data_length,
             timeout,
             &mut usb_status,
-        );
-
-        if status == EFI_SUCCESS {
-            Ok((data_length, usb_status))
-        } else {
-            Err(status)
-        }
+        )
+        .to_result()?;
+        Ok((data_length, usb_status))
     }
 
     /// Get device descriptor
-    pub unsafe fn get_device_descriptor(&mut self) -> Result<UsbDeviceDescriptor, Status> {
+    pub unsafe fn get_device_descriptor(&mut self) -> Result<UsbDeviceDescriptor> {
         let mut descriptor = core::mem::zeroed();
-        let status = (self.usb_get_device_descriptor)(self, &mut descriptor);
-
-        if status == EFI_SUCCESS {
-            Ok(descriptor)
-        } else {
-            Err(status)
-        }
+        (self.usb_get_device_descriptor)(self, &mut descriptor).to_result()?;
+        Ok(descriptor)
     }
 
     /// Get configuration descriptor
-    pub unsafe fn get_config_descriptor(&mut self) -> Result<UsbConfigDescriptor, Status> {
+    pub unsafe fn get_config_descriptor(&mut self) -> Result<UsbConfigDescriptor> {
         let mut descriptor = core::mem::zeroed();
-        let status = (self.usb_get_config_descriptor)(self, &mut descriptor);
-
-        if status == EFI_SUCCESS {
-            Ok(descriptor)
-        } else {
-            Err(status)
-        }
+        (self.usb_get_config_descriptor)(self, &mut descriptor).to_result()?;
+        Ok(descriptor)
     }
 
     /// Get interface descriptor
-    pub unsafe fn get_interface_descriptor(&mut self) -> Result<UsbInterfaceDescriptor, Status> {
+    pub unsafe fn get_interface_descriptor(&mut self) -> Result<UsbInterfaceDescriptor> {
         let mut descriptor = core::mem::zeroed();
-        let status = (self.usb_get_interface_descriptor)(self, &mut descriptor);
-
-        if status == EFI_SUCCESS {
-            Ok(descriptor)
-        } else {
-            Err(status)
-        }
+        (self.usb_get_interface_descriptor)(self, &mut descriptor).to_result()?;
+        Ok(descriptor)
     }
 
     /// Reset USB port
-    pub unsafe fn port_reset(&mut self) -> Status {
-        (self.usb_port_reset)(self)
+    pub unsafe fn port_reset(&mut self) -> Result<()> {
+        (self.usb_port_reset)(self).to_result()
     }
 }
 
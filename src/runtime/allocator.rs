@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Runtime Services Data Allocator
+//!
+//! Allocations come from `EfiRuntimeServicesData` pool, which the firmware
+//! keeps mapped after `ExitBootServices()` and relocates with the rest of
+//! the runtime image at `SetVirtualAddressMap()`.
+
+use crate::allocator::{pool_alloc, pool_free};
+use crate::boot_services::BootServices;
+use crate::ffi::*;
+use crate::runtime::guard::boot_services_available;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Allocator for data that must survive into the OS runtime
+///
+/// Once boot services are gone allocations fail and frees are ignored;
+/// memory handed out before then stays valid.
+///
+/// ```ignore
+/// static RUNTIME_ALLOCATOR: RuntimeAllocator = RuntimeAllocator::new();
+///
+/// unsafe { RUNTIME_ALLOCATOR.init(system_table.boot_services()) };
+/// ```
+pub struct RuntimeAllocator {
+    bs: AtomicPtr<BootServices>,
+}
+
+impl RuntimeAllocator {
+    /// Create an allocator that fails until [`RuntimeAllocator::init`]
+    pub const fn new() -> Self {
+        RuntimeAllocator {
+            bs: AtomicPtr::new(null_mut()),
+        }
+    }
+
+    /// Start allocating through `bs`
+    ///
+    /// # Safety
+    /// `bs` must be the firmware's Boot Services table.
+    pub unsafe fn init(&self, bs: &'static BootServices) {
+        self.bs.store(
+            bs as *const BootServices as *mut BootServices,
+            Ordering::SeqCst,
+        );
+    }
+
+    fn boot_services(&self) -> Option<&BootServices> {
+        if !boot_services_available() {
+            return None;
+        }
+        unsafe { self.bs.load(Ordering::SeqCst).as_ref() }
+    }
+}
+
+impl Default for RuntimeAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for RuntimeAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.boot_services() {
+            Some(bs) => pool_alloc(bs, MemoryType::RuntimeServicesData, layout),
+            None => null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(bs) = self.boot_services() {
+            pool_free(bs, ptr, layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boot_services::mock as bs_mock;
+    use crate::runtime::guard;
+    use std::boxed::Box;
+
+    #[test]
+    fn test_runtime_allocator_stops_after_exit() {
+        guard::reset();
+        bs_mock::reset();
+        let bs: &'static BootServices = Box::leak(Box::new(bs_mock::boot_services()));
+        let allocator = RuntimeAllocator::new();
+        let layout = Layout::from_size_align(64, 32).unwrap();
+        assert!(unsafe { allocator.alloc(layout) }.is_null());
+
+        unsafe { allocator.init(bs) };
+        let ptr = unsafe { allocator.alloc(layout) };
+        assert!(!ptr.is_null());
+        assert_eq!(ptr as usize % 32, 0);
+        unsafe { allocator.dealloc(ptr, layout) };
+
+        let kept = unsafe { allocator.alloc(layout) };
+        guard::exit_boot_services();
+        assert!(unsafe { allocator.alloc(layout) }.is_null());
+        // Frees after the exit leave the memory in place
+        unsafe { allocator.dealloc(kept, layout) };
+        unsafe { kept.write(0xAA) };
+
+        guard::reset();
+        bs_mock::reset();
+    }
+}
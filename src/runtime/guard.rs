@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Boot Services Availability Guards
+//!
+//! Boot services disappear at `ExitBootServices()`, but runtime drivers keep
+//! references to the table around. The exit is recorded here, either by
+//! [`SystemTable::exit_boot_services`](crate::system_table::SystemTable::exit_boot_services)
+//! or by the event from [`register_exit_boot_services_event`], and
+//! [`BootServicesGuard`] refuses access afterwards. The global allocator,
+//! logger and panic handler check the same flag.
+
+use crate::boot_services::{BootServices, EventWrapper, EVT_SIGNAL_EXIT_BOOT_SERVICES};
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(not(test))]
+static BOOT_SERVICES_EXITED: AtomicBool = AtomicBool::new(false);
+
+// Per thread in unit tests, so tests exiting boot services run in parallel
+// with tests that still need them
+#[cfg(test)]
+std::thread_local! {
+    static BOOT_SERVICES_EXITED: AtomicBool = const { AtomicBool::new(false) };
+}
+
+fn set_exited(exited: bool) {
+    #[cfg(not(test))]
+    BOOT_SERVICES_EXITED.store(exited, Ordering::SeqCst);
+    #[cfg(test)]
+    BOOT_SERVICES_EXITED.with(|flag| flag.store(exited, Ordering::SeqCst));
+}
+
+/// Record that boot services are no longer available
+pub fn exit_boot_services() {
+    set_exited(true);
+}
+
+/// Check whether boot services can still be called
+pub fn boot_services_available() -> bool {
+    #[cfg(not(test))]
+    let exited = BOOT_SERVICES_EXITED.load(Ordering::SeqCst);
+    #[cfg(test)]
+    let exited = BOOT_SERVICES_EXITED.with(|flag| flag.load(Ordering::SeqCst));
+    !exited
+}
+
+/// Fail with `EFI_UNSUPPORTED` once boot services are gone
+pub fn ensure_boot_services() -> Result<()> {
+    if boot_services_available() {
+        Ok(())
+    } else {
+        Err(Error::new(EFI_UNSUPPORTED))
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn reset() {
+    set_exited(false);
+}
+
+unsafe extern "efiapi" fn exit_boot_services_notify(
+    _event: Event,
+    _context: *mut core::ffi::c_void,
+) {
+    exit_boot_services();
+}
+
+/// Create an `EVT_SIGNAL_EXIT_BOOT_SERVICES` event that records the exit
+///
+/// Drivers do not call `ExitBootServices()` themselves, so this is how they
+/// learn about it. The event stays registered for the life of the driver.
+pub unsafe fn register_exit_boot_services_event(bs: &BootServices) -> Result<Event> {
+    let event = EventWrapper::create(
+        bs,
+        EVT_SIGNAL_EXIT_BOOT_SERVICES,
+        TPL_NOTIFY,
+        Some(exit_boot_services_notify),
+        core::ptr::null_mut(),
+    )?;
+    let raw = event.as_raw();
+    core::mem::forget(event);
+    Ok(raw)
+}
+
+/// Boot Services reference that checks availability on every use
+///
+/// Dereferencing after `ExitBootServices()` panics rather than calling into
+/// a table the firmware has torn down; [`BootServicesGuard::get`] reports
+/// the same condition as an error.
+#[derive(Clone, Copy)]
+pub struct BootServicesGuard<'a> {
+    bs: &'a BootServices,
+}
+
+impl<'a> BootServicesGuard<'a> {
+    /// Wrap a Boot Services table
+    pub fn new(bs: &'a BootServices) -> Self {
+        BootServicesGuard { bs }
+    }
+
+    /// Get the table, or `EFI_UNSUPPORTED` after `ExitBootServices()`
+    pub fn get(&self) -> Result<&'a BootServices> {
+        ensure_boot_services()?;
+        Ok(self.bs)
+    }
+}
+
+impl core::ops::Deref for BootServicesGuard<'_> {
+    type Target = BootServices;
+
+    fn deref(&self) -> &BootServices {
+        assert!(
+            boot_services_available(),
+            "boot services used after ExitBootServices"
+        );
+        self.bs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boot_services::{mock as bs_mock, EventNotifyFn};
+    use std::cell::Cell;
+
+    std::thread_local! {
+        static NOTIFY: Cell<Option<(u32, usize)>> = const { Cell::new(None) };
+    }
+
+    unsafe extern "efiapi" fn create_event(
+        event_type: u32,
+        _notify_tpl: Tpl,
+        notify_function: *mut core::ffi::c_void,
+        _notify_context: *mut core::ffi::c_void,
+        event: *mut Event,
+    ) -> Status {
+        NOTIFY.with(|n| n.set(Some((event_type, notify_function as usize))));
+        *event = 0x6000 as Event;
+        EFI_SUCCESS
+    }
+
+    #[test]
+    fn test_guard_stops_after_exit() {
+        reset();
+        bs_mock::reset();
+        let bs = bs_mock::boot_services();
+        let boot = BootServicesGuard::new(&bs);
+        assert!(ensure_boot_services().is_ok());
+        assert!(boot.get().is_ok());
+        assert_eq!(boot.hdr.signature, bs.hdr.signature);
+
+        exit_boot_services();
+        assert!(!boot_services_available());
+        assert_eq!(
+            ensure_boot_services().unwrap_err().status(),
+            EFI_UNSUPPORTED
+        );
+        assert_eq!(boot.get().err().unwrap().status(), EFI_UNSUPPORTED);
+        assert!(std::panic::catch_unwind(|| boot.hdr.revision).is_err());
+        reset();
+    }
+
+    #[test]
+    fn test_exit_boot_services_event() {
+        reset();
+        bs_mock::reset();
+        let bs = BootServices {
+            create_event,
+            ..bs_mock::boot_services()
+        };
+        let event = unsafe { register_exit_boot_services_event(&bs) }.unwrap();
+        assert_eq!(event, 0x6000 as Event);
+        assert!(boot_services_available());
+
+        // Signal the event the way ExitBootServices() would
+        let (event_type, notify) = NOTIFY.with(Cell::get).unwrap();
+        assert_eq!(event_type, EVT_SIGNAL_EXIT_BOOT_SERVICES);
+        let notify: EventNotifyFn = unsafe { core::mem::transmute(notify) };
+        unsafe { notify(event, core::ptr::null_mut()) };
+        assert!(!boot_services_available());
+        reset();
+    }
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Runtime Driver Support
+//!
+//! What a runtime DXE driver needs to keep working once the OS owns the
+//! machine: [`PointerRegistry`] relocates global pointers at
+//! `SetVirtualAddressMap()`, [`RuntimeAllocator`] hands out
+//! `EfiRuntimeServicesData` pool, and [`guard`] catches boot services use
+//! after `ExitBootServices()`.
+
+pub mod allocator;
+pub mod guard;
+pub mod pointers;
+
+pub use allocator::RuntimeAllocator;
+pub use guard::{
+    boot_services_available, ensure_boot_services, register_exit_boot_services_event,
+    BootServicesGuard,
+};
+pub use pointers::*;
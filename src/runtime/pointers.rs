@@ -0,0 +1,255 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Pointer Conversion for SetVirtualAddressMap
+//!
+//! When the OS calls `SetVirtualAddressMap()`, every pointer a runtime
+//! driver keeps in its globals must be passed through `ConvertPointer()`
+//! from the `EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE` notification.
+//! [`PointerRegistry`] records those locations and converts them all.
+
+use crate::boot_services::{BootServices, EventWrapper, EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE};
+use crate::error::{Error, Result, StatusExt};
+use crate::ffi::*;
+use crate::runtime_services::RuntimeServices;
+
+/// Number of locations a [`PointerRegistry`] can hold
+pub const MAX_CONVERTED_POINTERS: usize = 64;
+
+/// ConvertPointer() disposition allowing a NULL pointer
+pub const EFI_OPTIONAL_PTR: Uintn = 0x00000001;
+
+#[derive(Copy, Clone)]
+struct Entry {
+    location: *mut *mut core::ffi::c_void,
+    optional: bool,
+}
+
+/// Locations of pointers to relocate at SetVirtualAddressMap()
+///
+/// Storage is fixed-size so the registry can live in a `static` and the
+/// conversion never allocates; it runs after `ExitBootServices()`.
+///
+/// ```ignore
+/// static mut RUNTIME: *const RuntimeServices = core::ptr::null();
+/// static mut REGISTRY: PointerRegistry = PointerRegistry::new();
+///
+/// let registry = &mut *core::ptr::addr_of_mut!(REGISTRY);
+/// registry.register(core::ptr::addr_of_mut!(RUNTIME) as *mut *mut RuntimeServices)?;
+/// registry.register_virtual_address_change(bs, rt)?;
+/// ```
+pub struct PointerRegistry {
+    entries: [Entry; MAX_CONVERTED_POINTERS],
+    len: usize,
+    runtime_services: *const RuntimeServices,
+    converted: bool,
+}
+
+impl PointerRegistry {
+    /// Create an empty registry
+    pub const fn new() -> Self {
+        PointerRegistry {
+            entries: [Entry {
+                location: core::ptr::null_mut(),
+                optional: false,
+            }; MAX_CONVERTED_POINTERS],
+            len: 0,
+            runtime_services: core::ptr::null(),
+            converted: false,
+        }
+    }
+
+    fn add(&mut self, location: *mut *mut core::ffi::c_void, optional: bool) -> Result<()> {
+        if location.is_null() {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        if self.converted {
+            return Err(Error::new(EFI_ALREADY_STARTED));
+        }
+        // Converting a location twice would relocate it twice
+        if self.entries[..self.len]
+            .iter()
+            .any(|e| e.location == location)
+        {
+            return Ok(());
+        }
+        if self.len == MAX_CONVERTED_POINTERS {
+            return Err(Error::new(EFI_OUT_OF_RESOURCES));
+        }
+        self.entries[self.len] = Entry { location, optional };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Register a pointer that must be non-NULL when converted
+    pub fn register<T>(&mut self, location: *mut *mut T) -> Result<()> {
+        self.add(location as *mut *mut core::ffi::c_void, false)
+    }
+
+    /// Register a pointer that may still be NULL when converted
+    pub fn register_optional<T>(&mut self, location: *mut *mut T) -> Result<()> {
+        self.add(location as *mut *mut core::ffi::c_void, true)
+    }
+
+    /// Number of registered locations
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether nothing is registered
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check whether the pointers have been converted to virtual addresses
+    pub fn is_virtual(&self) -> bool {
+        self.converted
+    }
+
+    /// Convert every registered pointer
+    ///
+    /// All locations are attempted; the first failure is returned.
+    ///
+    /// # Safety
+    /// Only valid from within the virtual address change notification, and
+    /// every registered location must still be writable.
+    pub unsafe fn convert_all(&mut self, rt: &RuntimeServices) -> Result<()> {
+        let mut result = Ok(());
+        for entry in &self.entries[..self.len] {
+            let disposition = if entry.optional { EFI_OPTIONAL_PTR } else { 0 };
+            let status = (rt.convert_pointer)(disposition, entry.location).to_result();
+            if result.is_ok() {
+                result = status;
+            }
+        }
+        self.converted = true;
+        result
+    }
+
+    /// Create the `EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE` event converting this registry
+    ///
+    /// `rt` is the physical Runtime Services table; it is only used from the
+    /// notification, which runs before the switch to virtual mode.
+    pub unsafe fn register_virtual_address_change(
+        &'static mut self,
+        bs: &BootServices,
+        rt: &'static RuntimeServices,
+    ) -> Result<Event> {
+        self.runtime_services = rt;
+        let event = EventWrapper::create(
+            bs,
+            EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE,
+            TPL_NOTIFY,
+            Some(virtual_address_change_notify),
+            self as *mut PointerRegistry as *mut core::ffi::c_void,
+        )?;
+        let raw = event.as_raw();
+        core::mem::forget(event);
+        Ok(raw)
+    }
+}
+
+impl Default for PointerRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe extern "efiapi" fn virtual_address_change_notify(
+    _event: Event,
+    context: *mut core::ffi::c_void,
+) {
+    let registry = &mut *(context as *mut PointerRegistry);
+    if let Some(rt) = registry.runtime_services.as_ref() {
+        // Nothing can report an error from here
+        let _ = registry.convert_all(rt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boot_services::{mock as bs_mock, EventNotifyFn};
+    use crate::runtime_services::mock;
+    use std::boxed::Box;
+    use std::cell::Cell;
+
+    const VIRTUAL_OFFSET: usize = 0x4000_0000_0000;
+
+    std::thread_local! {
+        static NOTIFY: Cell<Option<(u32, usize, usize)>> = const { Cell::new(None) };
+    }
+
+    unsafe extern "efiapi" fn create_event(
+        event_type: u32,
+        _notify_tpl: Tpl,
+        notify_function: *mut core::ffi::c_void,
+        notify_context: *mut core::ffi::c_void,
+        event: *mut Event,
+    ) -> Status {
+        NOTIFY.with(|n| {
+            n.set(Some((
+                event_type,
+                notify_function as usize,
+                notify_context as usize,
+            )))
+        });
+        *event = 0x5000 as Event;
+        EFI_SUCCESS
+    }
+
+    unsafe extern "efiapi" fn convert_pointer(
+        debug_disposition: Uintn,
+        address: *mut *mut core::ffi::c_void,
+    ) -> Status {
+        if (*address).is_null() {
+            return if debug_disposition & EFI_OPTIONAL_PTR != 0 {
+                EFI_SUCCESS
+            } else {
+                EFI_INVALID_PARAMETER
+            };
+        }
+        *address = (*address as usize + VIRTUAL_OFFSET) as *mut core::ffi::c_void;
+        EFI_SUCCESS
+    }
+
+    #[test]
+    fn test_pointers_converted_on_virtual_address_change() {
+        mock::reset();
+        let rt: &'static RuntimeServices = Box::leak(Box::new(RuntimeServices {
+            convert_pointer,
+            ..mock::runtime_services()
+        }));
+        let bs = BootServices {
+            create_event,
+            ..bs_mock::boot_services()
+        };
+
+        let data = Box::leak(Box::new([0u8; 4]));
+        let pointers = Box::leak(Box::new([data.as_mut_ptr(), core::ptr::null_mut()]));
+        let physical = pointers[0] as usize;
+
+        let registry = Box::leak(Box::new(PointerRegistry::new()));
+        registry.register(&mut pointers[0]).unwrap();
+        registry.register(&mut pointers[0]).unwrap();
+        registry.register_optional(&mut pointers[1]).unwrap();
+        assert_eq!(registry.len(), 2);
+        let registry: *mut PointerRegistry = registry;
+
+        let event = unsafe { (*registry).register_virtual_address_change(&bs, rt) }.unwrap();
+        assert_eq!(event, 0x5000 as Event);
+
+        // Signal the event the way SetVirtualAddressMap() would
+        let (event_type, notify, context) = NOTIFY.with(Cell::get).unwrap();
+        assert_eq!(event_type, EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE);
+        let notify: EventNotifyFn = unsafe { core::mem::transmute(notify) };
+        unsafe { notify(event, context as *mut core::ffi::c_void) };
+
+        assert_eq!(pointers[0] as usize, physical + VIRTUAL_OFFSET);
+        assert!(pointers[1].is_null());
+        let registry = unsafe { &mut *registry };
+        assert!(registry.is_virtual());
+        assert_eq!(
+            registry.register(&mut pointers[1]).unwrap_err().status(),
+            EFI_ALREADY_STARTED
+        );
+    }
+}
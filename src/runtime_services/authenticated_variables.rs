@@ -0,0 +1,345 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Time-based Authenticated Variables
+//!
+//! Building and parsing of `EFI_VARIABLE_AUTHENTICATION_2` payloads, as
+//! written to `PK`, `KEK`, `db` and `dbx`. The signing itself happens
+//! elsewhere: [`data_to_sign`] gives the bytes to sign, and the resulting
+//! PKCS#7 SignedData is wrapped with [`build_payload`].
+
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::protocols::security::{
+    WinCertificate, WinCertificateUefiGuid, CERT_TYPE_PKCS7_GUID, WIN_CERT_REVISION_2_0,
+    WIN_CERT_TYPE_EFI_GUID,
+};
+use crate::runtime_services::{Time, Variable, VariableAttributes, VariableCodec};
+use crate::string::CStr16;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const TIME_SIZE: usize = core::mem::size_of::<Time>();
+
+/// Size of the WIN_CERTIFICATE_UEFI_GUID header before the certificate data
+pub const WIN_CERTIFICATE_UEFI_GUID_HEADER_SIZE: usize =
+    core::mem::size_of::<WinCertificateUefiGuid>();
+
+/// Convert a time to the form required for authenticated variables
+///
+/// The time is converted to UTC and the nanosecond, time zone and daylight
+/// fields are cleared, as the spec requires.
+pub fn auth_timestamp(time: &Time) -> Time {
+    let mut utc = time.with_time_zone(0, 0).unwrap_or(*time);
+    utc.pad1 = 0;
+    utc.nanosecond = 0;
+    utc.time_zone = 0;
+    utc.daylight = 0;
+    utc.pad2 = 0;
+    utc
+}
+
+/// Check that a timestamp has only its date and time fields set
+pub fn is_auth_timestamp(time: &Time) -> bool {
+    time.is_valid()
+        && time.pad1 == 0
+        && time.nanosecond == 0
+        && time.time_zone == 0
+        && time.daylight == 0
+        && time.pad2 == 0
+}
+
+/// Bytes that must be signed to authorize a write
+///
+/// This is `VariableName || VendorGuid || Attributes || TimeStamp || Data`,
+/// with the name lacking its terminating NUL. Fails with
+/// `EFI_INVALID_PARAMETER` unless `attributes` has
+/// `TIME_BASED_AUTHENTICATED_WRITE_ACCESS` and `timestamp` passes
+/// [`is_auth_timestamp`].
+pub fn data_to_sign(
+    name: &CStr16,
+    vendor: &Guid,
+    attributes: VariableAttributes,
+    timestamp: &Time,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    if !attributes.contains(VariableAttributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS)
+        || !is_auth_timestamp(timestamp)
+    {
+        return Err(Error::new(EFI_INVALID_PARAMETER));
+    }
+
+    let mut out = Vec::with_capacity(name.num_bytes() + 16 + 4 + TIME_SIZE + data.len());
+    for code in name.iter() {
+        out.extend_from_slice(&code.to_le_bytes());
+    }
+    vendor.encode(&mut out);
+    attributes.bits().encode(&mut out);
+    timestamp.encode(&mut out);
+    out.extend_from_slice(data);
+    Ok(out)
+}
+
+/// Build an `EFI_VARIABLE_AUTHENTICATION_2` descriptor followed by `data`
+///
+/// `signed_data` is the DER encoded PKCS#7 SignedData over
+/// [`data_to_sign`]. Fails with `EFI_INVALID_PARAMETER` if `timestamp` does
+/// not pass [`is_auth_timestamp`] or `signed_data` is empty.
+pub fn build_payload(timestamp: &Time, signed_data: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    if !is_auth_timestamp(timestamp) || signed_data.is_empty() {
+        return Err(Error::new(EFI_INVALID_PARAMETER));
+    }
+    let length = u32::try_from(WIN_CERTIFICATE_UEFI_GUID_HEADER_SIZE + signed_data.len())
+        .map_err(|_| Error::new(EFI_BAD_BUFFER_SIZE))?;
+
+    let mut out = Vec::with_capacity(TIME_SIZE + length as usize + data.len());
+    timestamp.encode(&mut out);
+    length.encode(&mut out);
+    WIN_CERT_REVISION_2_0.encode(&mut out);
+    WIN_CERT_TYPE_EFI_GUID.encode(&mut out);
+    CERT_TYPE_PKCS7_GUID.encode(&mut out);
+    out.extend_from_slice(signed_data);
+    out.extend_from_slice(data);
+    Ok(out)
+}
+
+/// Parsed `EFI_VARIABLE_AUTHENTICATION_2` payload
+#[derive(Debug, Copy, Clone)]
+pub struct AuthenticatedPayload<'a> {
+    pub timestamp: Time,
+    /// WIN_CERTIFICATE header of the AuthInfo field
+    pub cert_header: WinCertificate,
+    /// CertType of the AuthInfo field, always `EFI_CERT_TYPE_PKCS7_GUID`
+    pub cert_type: Guid,
+    /// DER encoded PKCS#7 SignedData
+    pub signed_data: &'a [u8],
+    /// New variable contents
+    pub data: &'a [u8],
+}
+
+impl<'a> AuthenticatedPayload<'a> {
+    /// Parse a payload as passed to `SetVariable`
+    ///
+    /// Fails with `EFI_BAD_BUFFER_SIZE` if the certificate length does not
+    /// fit the payload and with `EFI_UNSUPPORTED` if the certificate is not
+    /// a revision 2.0 `WIN_CERT_TYPE_EFI_GUID` carrying PKCS#7.
+    pub fn parse(payload: &'a [u8]) -> Result<Self> {
+        let bad = || Error::new(EFI_BAD_BUFFER_SIZE);
+        let header_end = TIME_SIZE + WIN_CERTIFICATE_UEFI_GUID_HEADER_SIZE;
+        if payload.len() < header_end {
+            return Err(bad());
+        }
+
+        let timestamp = Time::decode(&payload[..TIME_SIZE])?;
+        let cert_header = WinCertificate {
+            length: u32::decode(&payload[16..20])?,
+            revision: u16::decode(&payload[20..22])?,
+            certificate_type: u16::decode(&payload[22..24])?,
+        };
+        let cert_type = Guid::decode(&payload[24..header_end])?;
+
+        if cert_header.revision != WIN_CERT_REVISION_2_0
+            || cert_header.certificate_type != WIN_CERT_TYPE_EFI_GUID
+            || cert_type != CERT_TYPE_PKCS7_GUID
+        {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+
+        let cert_length = cert_header.length as usize;
+        if cert_length < WIN_CERTIFICATE_UEFI_GUID_HEADER_SIZE {
+            return Err(bad());
+        }
+        let cert_end = TIME_SIZE.checked_add(cert_length).ok_or_else(bad)?;
+        if cert_end > payload.len() {
+            return Err(bad());
+        }
+
+        Ok(AuthenticatedPayload {
+            timestamp,
+            cert_header,
+            cert_type,
+            signed_data: &payload[header_end..cert_end],
+            data: &payload[cert_end..],
+        })
+    }
+
+    /// Bytes the signature in this payload must cover
+    pub fn data_to_sign(
+        &self,
+        name: &CStr16,
+        vendor: &Guid,
+        attributes: VariableAttributes,
+    ) -> Result<Vec<u8>> {
+        data_to_sign(name, vendor, attributes, &self.timestamp, self.data)
+    }
+}
+
+impl Variable<'_> {
+    /// Write an `EFI_VARIABLE_AUTHENTICATION_2` payload
+    ///
+    /// `attributes` must include `TIME_BASED_AUTHENTICATED_WRITE_ACCESS`.
+    /// The payload is checked to be well formed before it is passed to
+    /// firmware, which verifies the signature.
+    pub unsafe fn set_authenticated(
+        &self,
+        name: &CStr16,
+        vendor_guid: &Guid,
+        attributes: VariableAttributes,
+        payload: &[u8],
+    ) -> Result<()> {
+        if !attributes.contains(VariableAttributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS) {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        AuthenticatedPayload::parse(payload)?;
+        self.set(name, vendor_guid, attributes, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cstr16;
+    use crate::runtime_services::{
+        mock, EFI_GLOBAL_VARIABLE_GUID, EFI_IMAGE_SECURITY_DATABASE_GUID,
+    };
+
+    fn attributes() -> VariableAttributes {
+        VariableAttributes::NV_BS_RT | VariableAttributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS
+    }
+
+    fn timestamp() -> Time {
+        auth_timestamp(&Time::new(2024, 6, 1, 8, 30, 0))
+    }
+
+    #[test]
+    fn test_auth_timestamp_normalizes() {
+        let mut local = Time::new(2024, 6, 1, 10, 30, 0);
+        local.time_zone = 120;
+        local.nanosecond = 5;
+        let ts = auth_timestamp(&local);
+        assert!(is_auth_timestamp(&ts));
+        assert_eq!(
+            (ts.hour, ts.minute, ts.nanosecond, ts.time_zone),
+            (8, 30, 0, 0)
+        );
+        assert!(!is_auth_timestamp(&local));
+    }
+
+    #[test]
+    fn test_data_to_sign_layout() {
+        let bytes = data_to_sign(
+            cstr16!("db"),
+            &EFI_IMAGE_SECURITY_DATABASE_GUID,
+            attributes(),
+            &timestamp(),
+            &[0xAA, 0xBB],
+        )
+        .unwrap();
+        assert_eq!(bytes[..4], [b'd', 0, b'b', 0]);
+        assert_eq!(
+            Guid::decode(&bytes[4..20]).unwrap(),
+            EFI_IMAGE_SECURITY_DATABASE_GUID
+        );
+        assert_eq!(bytes[20..24], [0x27, 0, 0, 0]);
+        assert_eq!(bytes[24..26], 2024u16.to_le_bytes());
+        assert_eq!(bytes[26..28], [6, 1]);
+        assert_eq!(bytes[40..], [0xAA, 0xBB]);
+        assert_eq!(bytes.len(), 4 + 16 + 4 + 16 + 2);
+
+        let no_auth = data_to_sign(
+            cstr16!("db"),
+            &EFI_IMAGE_SECURITY_DATABASE_GUID,
+            VariableAttributes::NV_BS_RT,
+            &timestamp(),
+            &[],
+        );
+        assert_eq!(no_auth.unwrap_err().status(), EFI_INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_build_and_parse() {
+        let pkcs7 = [0x30, 0x82, 0x01, 0x02, 0x03];
+        let payload = build_payload(&timestamp(), &pkcs7, b"esl-data").unwrap();
+        assert_eq!(payload.len(), 16 + 24 + pkcs7.len() + 8);
+        assert_eq!(u32::decode(&payload[16..20]).unwrap(), 24 + 5);
+
+        let parsed = AuthenticatedPayload::parse(&payload).unwrap();
+        assert_eq!(parsed.timestamp, timestamp());
+        assert_eq!(parsed.cert_header.revision, WIN_CERT_REVISION_2_0);
+        assert_eq!(parsed.cert_type, CERT_TYPE_PKCS7_GUID);
+        assert_eq!(parsed.signed_data, pkcs7);
+        assert_eq!(parsed.data, b"esl-data");
+
+        // Empty data deletes the variable
+        let delete = build_payload(&timestamp(), &pkcs7, &[]).unwrap();
+        assert!(AuthenticatedPayload::parse(&delete)
+            .unwrap()
+            .data
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        let payload = build_payload(&timestamp(), &[1, 2, 3], b"x").unwrap();
+        let status = |data: &[u8]| AuthenticatedPayload::parse(data).unwrap_err().status();
+
+        assert_eq!(status(&payload[..39]), EFI_BAD_BUFFER_SIZE);
+
+        let mut long = payload.clone();
+        long[16] = 0xFF;
+        assert_eq!(status(&long), EFI_BAD_BUFFER_SIZE);
+
+        let mut short = payload.clone();
+        short[16] = 8;
+        assert_eq!(status(&short), EFI_BAD_BUFFER_SIZE);
+
+        let mut revision = payload.clone();
+        revision[21] = 0x01;
+        assert_eq!(status(&revision), EFI_UNSUPPORTED);
+
+        let mut cert_type = payload.clone();
+        cert_type[24] ^= 0xFF;
+        assert_eq!(status(&cert_type), EFI_UNSUPPORTED);
+    }
+
+    #[test]
+    fn test_set_authenticated() {
+        mock::reset();
+        let rt = mock::runtime_services();
+        let vars = Variable::new(&rt);
+        let payload = build_payload(&timestamp(), &[1, 2, 3], b"kek").unwrap();
+
+        unsafe {
+            assert_eq!(
+                vars.set_authenticated(
+                    cstr16!("KEK"),
+                    &EFI_GLOBAL_VARIABLE_GUID,
+                    VariableAttributes::NV_BS_RT,
+                    &payload
+                )
+                .unwrap_err()
+                .status(),
+                EFI_INVALID_PARAMETER
+            );
+            assert!(vars
+                .set_authenticated(
+                    cstr16!("KEK"),
+                    &EFI_GLOBAL_VARIABLE_GUID,
+                    attributes(),
+                    &payload[..20]
+                )
+                .is_err());
+            vars.set_authenticated(
+                cstr16!("KEK"),
+                &EFI_GLOBAL_VARIABLE_GUID,
+                attributes(),
+                &payload,
+            )
+            .unwrap();
+            let (attrs, stored) = vars
+                .get_vec(cstr16!("KEK"), &EFI_GLOBAL_VARIABLE_GUID)
+                .unwrap();
+            assert_eq!(attrs, attributes());
+            assert_eq!(stored, payload);
+        }
+    }
+}
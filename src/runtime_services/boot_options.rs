@@ -0,0 +1,497 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Boot Manager Load Options
+//!
+//! Parsing and building of `EFI_LOAD_OPTION` and helpers for the `Boot####`,
+//! `Driver####` and `SysPrep####` variables and their order lists.
+
+use crate::cstr16;
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::protocols::DevicePathNode;
+use crate::runtime_services::{
+    RuntimeServices, Variable, VariableAttributes, EFI_GLOBAL_VARIABLE_GUID,
+};
+use crate::string::{CStr16, CString16};
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+// Load option attributes
+pub const LOAD_OPTION_ACTIVE: u32 = 0x00000001;
+pub const LOAD_OPTION_FORCE_RECONNECT: u32 = 0x00000002;
+pub const LOAD_OPTION_HIDDEN: u32 = 0x00000008;
+pub const LOAD_OPTION_CATEGORY: u32 = 0x00001F00;
+pub const LOAD_OPTION_CATEGORY_BOOT: u32 = 0x00000000;
+pub const LOAD_OPTION_CATEGORY_APP: u32 = 0x00000100;
+
+/// Kind of load option variable
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadOptionType {
+    Boot,
+    Driver,
+    SysPrep,
+}
+
+impl LoadOptionType {
+    /// Variable name prefix, e.g. `Boot` for `Boot0001`
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            LoadOptionType::Boot => "Boot",
+            LoadOptionType::Driver => "Driver",
+            LoadOptionType::SysPrep => "SysPrep",
+        }
+    }
+
+    /// Name of the order variable, e.g. `BootOrder`
+    pub fn order_variable(&self) -> &'static CStr16 {
+        match self {
+            LoadOptionType::Boot => cstr16!("BootOrder"),
+            LoadOptionType::Driver => cstr16!("DriverOrder"),
+            LoadOptionType::SysPrep => cstr16!("SysPrepOrder"),
+        }
+    }
+
+    /// Name of the option variable numbered `number`, e.g. `Boot000A`
+    pub fn option_variable(&self, number: u16) -> CString16 {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+        let mut name = CString16::new();
+        for c in self.prefix().chars() {
+            // Prefixes are ASCII
+            let _ = name.push(c);
+        }
+        for shift in [12, 8, 4, 0] {
+            let _ = name.push(HEX[(number >> shift) as usize & 0xF] as char);
+        }
+        name
+    }
+
+    /// Get the option number from a variable name such as `Boot000A`
+    ///
+    /// The four hex digits must be uppercase, as the spec requires.
+    pub fn parse_option_variable(&self, name: &CStr16) -> Option<u16> {
+        let prefix = self.prefix();
+        if name.len() != prefix.len() + 4 {
+            return None;
+        }
+        let mut chars = name.chars();
+        if !prefix.chars().all(|c| chars.next() == Some(c)) {
+            return None;
+        }
+        chars.try_fold(0u16, |acc, c| match c {
+            '0'..='9' | 'A'..='F' => Some((acc << 4) | c.to_digit(16)? as u16),
+            _ => None,
+        })
+    }
+}
+
+/// EFI_LOAD_OPTION
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadOption {
+    /// `LOAD_OPTION_*` flags
+    pub attributes: u32,
+    pub description: CString16,
+    /// Device path nodes including their end nodes
+    pub file_path: Vec<DevicePathNode>,
+    pub optional_data: Vec<u8>,
+}
+
+impl LoadOption {
+    /// Create an active option for a single device path
+    ///
+    /// The end node is appended to `file_path` if missing.
+    pub fn new(description: &CStr16, mut file_path: Vec<DevicePathNode>) -> Self {
+        if !file_path.last().is_some_and(|node| node.is_end_entire()) {
+            file_path.push(DevicePathNode::end_entire());
+        }
+        LoadOption {
+            attributes: LOAD_OPTION_ACTIVE,
+            description: description.into(),
+            file_path,
+            optional_data: Vec::new(),
+        }
+    }
+
+    /// Check if the option is active
+    pub fn is_active(&self) -> bool {
+        self.attributes & LOAD_OPTION_ACTIVE != 0
+    }
+
+    /// Check if the option is hidden from boot menus
+    pub fn is_hidden(&self) -> bool {
+        self.attributes & LOAD_OPTION_HIDDEN != 0
+    }
+
+    /// Set or clear `LOAD_OPTION_ACTIVE`
+    pub fn set_active(&mut self, active: bool) {
+        if active {
+            self.attributes |= LOAD_OPTION_ACTIVE;
+        } else {
+            self.attributes &= !LOAD_OPTION_ACTIVE;
+        }
+    }
+
+    /// Parse a serialized load option
+    ///
+    /// Fails with `EFI_BAD_BUFFER_SIZE` if the description is not terminated,
+    /// the file path list overruns the data, or its nodes are malformed.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let bad = || Error::new(EFI_BAD_BUFFER_SIZE);
+        if data.len() < 6 {
+            return Err(bad());
+        }
+        let attributes = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let file_path_length = u16::from_le_bytes([data[4], data[5]]) as usize;
+
+        let mut description = Vec::new();
+        let mut offset = 6;
+        loop {
+            let bytes = data.get(offset..offset + 2).ok_or_else(bad)?;
+            let code = u16::from_le_bytes([bytes[0], bytes[1]]);
+            description.push(code);
+            offset += 2;
+            if code == 0 {
+                break;
+            }
+        }
+        let description = CString16::try_from(description)?;
+
+        let file_path_end = offset.checked_add(file_path_length).ok_or_else(bad)?;
+        let file_path_bytes = data.get(offset..file_path_end).ok_or_else(bad)?;
+        let file_path = DevicePathNode::parse_list(file_path_bytes)?;
+
+        Ok(LoadOption {
+            attributes,
+            description,
+            file_path,
+            optional_data: data[file_path_end..].to_vec(),
+        })
+    }
+
+    /// Serialize to the `EFI_LOAD_OPTION` layout
+    ///
+    /// Fails with `EFI_BAD_BUFFER_SIZE` if the file path list exceeds 64 KiB.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut file_path = Vec::new();
+        for node in &self.file_path {
+            node.write_to(&mut file_path)?;
+        }
+        let file_path_length =
+            u16::try_from(file_path.len()).map_err(|_| Error::new(EFI_BAD_BUFFER_SIZE))?;
+
+        let mut out = Vec::with_capacity(6 + self.description.num_bytes() + file_path.len());
+        out.extend_from_slice(&self.attributes.to_le_bytes());
+        out.extend_from_slice(&file_path_length.to_le_bytes());
+        for code in self.description.as_slice_with_nul() {
+            out.extend_from_slice(&code.to_le_bytes());
+        }
+        out.extend_from_slice(&file_path);
+        out.extend_from_slice(&self.optional_data);
+        Ok(out)
+    }
+}
+
+/// Access to load options and the boot manager control variables
+pub struct BootManager<'a> {
+    vars: Variable<'a>,
+}
+
+impl<'a> BootManager<'a> {
+    /// Create a new boot manager wrapper
+    pub fn new(rt: &'a RuntimeServices) -> Self {
+        BootManager {
+            vars: Variable::new(rt),
+        }
+    }
+
+    /// Read and parse a load option
+    pub unsafe fn get_option(&self, kind: LoadOptionType, number: u16) -> Result<LoadOption> {
+        let name = kind.option_variable(number);
+        let (_, data) = self.vars.get_vec(&name, &EFI_GLOBAL_VARIABLE_GUID)?;
+        LoadOption::parse(&data)
+    }
+
+    /// Write a load option without changing the order list
+    pub unsafe fn set_option(
+        &self,
+        kind: LoadOptionType,
+        number: u16,
+        option: &LoadOption,
+    ) -> Result<()> {
+        let name = kind.option_variable(number);
+        let data = option.to_bytes()?;
+        self.vars.set(
+            &name,
+            &EFI_GLOBAL_VARIABLE_GUID,
+            VariableAttributes::NV_BS_RT,
+            &data,
+        )
+    }
+
+    /// Write a load option under the lowest free number and insert it into
+    /// the order list at `position` (or at the end)
+    ///
+    /// Returns the option number.
+    pub unsafe fn add_option(
+        &self,
+        kind: LoadOptionType,
+        option: &LoadOption,
+        position: Option<usize>,
+    ) -> Result<u16> {
+        let number = self.free_number(kind)?;
+        self.set_option(kind, number, option)?;
+
+        let mut order = self.get_order(kind)?;
+        order.retain(|&n| n != number);
+        let position = position.unwrap_or(order.len()).min(order.len());
+        order.insert(position, number);
+        self.set_order(kind, &order)?;
+        Ok(number)
+    }
+
+    /// Delete a load option and remove it from the order list
+    ///
+    /// A missing option variable is not an error, so stale order entries
+    /// can be cleaned up this way.
+    pub unsafe fn delete_option(&self, kind: LoadOptionType, number: u16) -> Result<()> {
+        let name = kind.option_variable(number);
+        match self.vars.delete(&name, &EFI_GLOBAL_VARIABLE_GUID) {
+            Err(err) if err.status() != EFI_NOT_FOUND => return Err(err),
+            _ => {}
+        }
+
+        let mut order = self.get_order(kind)?;
+        let len = order.len();
+        order.retain(|&n| n != number);
+        if order.len() != len {
+            self.set_order(kind, &order)?;
+        }
+        Ok(())
+    }
+
+    /// Numbers of all existing option variables of `kind`, in name order
+    pub unsafe fn option_numbers(&self, kind: LoadOptionType) -> Result<Vec<u16>> {
+        let mut numbers = Vec::new();
+        for key in self.vars.keys() {
+            let key = key?;
+            if key.vendor != EFI_GLOBAL_VARIABLE_GUID {
+                continue;
+            }
+            if let Some(number) = kind.parse_option_variable(&key.name) {
+                numbers.push(number);
+            }
+        }
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    /// Get the order list, empty if the variable does not exist
+    pub unsafe fn get_order(&self, kind: LoadOptionType) -> Result<Vec<u16>> {
+        match self
+            .vars
+            .get_value(kind.order_variable(), &EFI_GLOBAL_VARIABLE_GUID)
+        {
+            Err(err) if err.status() == EFI_NOT_FOUND => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    /// Replace the order list
+    ///
+    /// Fails with `EFI_INVALID_PARAMETER` if a number appears twice.
+    pub unsafe fn set_order(&self, kind: LoadOptionType, order: &[u16]) -> Result<()> {
+        for (i, number) in order.iter().enumerate() {
+            if order[i + 1..].contains(number) {
+                return Err(Error::new(EFI_INVALID_PARAMETER));
+            }
+        }
+        let data: Vec<u8> = order.iter().flat_map(|n| n.to_le_bytes()).collect();
+        self.vars.set(
+            kind.order_variable(),
+            &EFI_GLOBAL_VARIABLE_GUID,
+            VariableAttributes::NV_BS_RT,
+            &data,
+        )
+    }
+
+    /// Get `BootNext`, if set
+    pub unsafe fn boot_next(&self) -> Result<Option<u16>> {
+        match self
+            .vars
+            .get_value(cstr16!("BootNext"), &EFI_GLOBAL_VARIABLE_GUID)
+        {
+            Ok(number) => Ok(Some(number)),
+            Err(err) if err.status() == EFI_NOT_FOUND => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Boot `number` on the next boot only
+    pub unsafe fn set_boot_next(&self, number: u16) -> Result<()> {
+        self.vars.set_value(
+            cstr16!("BootNext"),
+            &EFI_GLOBAL_VARIABLE_GUID,
+            VariableAttributes::NV_BS_RT,
+            &number,
+        )
+    }
+
+    /// Delete `BootNext`
+    pub unsafe fn clear_boot_next(&self) -> Result<()> {
+        match self
+            .vars
+            .delete(cstr16!("BootNext"), &EFI_GLOBAL_VARIABLE_GUID)
+        {
+            Err(err) if err.status() != EFI_NOT_FOUND => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Get `BootCurrent`, the option used for the current boot
+    pub unsafe fn boot_current(&self) -> Result<u16> {
+        self.vars
+            .get_value(cstr16!("BootCurrent"), &EFI_GLOBAL_VARIABLE_GUID)
+    }
+
+    // Lowest number without an option variable
+    unsafe fn free_number(&self, kind: LoadOptionType) -> Result<u16> {
+        let used = self.option_numbers(kind)?;
+        (0..=u16::MAX)
+            .find(|n| used.binary_search(n).is_err())
+            .ok_or(Error::new(EFI_OUT_OF_RESOURCES))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{MEDIA_DEVICE_PATH, MEDIA_FILEPATH_DP};
+    use crate::runtime_services::mock;
+
+    fn shell_option() -> LoadOption {
+        let path = DevicePathNode::file_path(cstr16!("\\EFI\\BOOT\\BOOTX64.EFI"));
+        LoadOption::new(cstr16!("UEFI Shell"), vec![path])
+    }
+
+    #[test]
+    fn test_option_variable_names() {
+        let name = LoadOptionType::Boot.option_variable(0x00AF);
+        assert_eq!(name, "Boot00AF");
+        assert_eq!(
+            LoadOptionType::Boot.parse_option_variable(&name),
+            Some(0xAF)
+        );
+        assert_eq!(
+            LoadOptionType::SysPrep.parse_option_variable(cstr16!("SysPrep0001")),
+            Some(1)
+        );
+        assert_eq!(
+            LoadOptionType::Boot.parse_option_variable(cstr16!("Boot00af")),
+            None
+        );
+        assert_eq!(
+            LoadOptionType::Boot.parse_option_variable(cstr16!("BootOrder")),
+            None
+        );
+        assert_eq!(
+            LoadOptionType::Driver.parse_option_variable(cstr16!("Boot0001")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_option_round_trip() {
+        let mut option = shell_option();
+        option.optional_data = vec![0xDE, 0xAD];
+        let bytes = option.to_bytes().unwrap();
+
+        // Attributes, FilePathListLength, "UEFI Shell\0"
+        assert_eq!(bytes[..4], [1, 0, 0, 0]);
+        let path_len = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+        assert_eq!(path_len, 4 + 22 * 2 + 4);
+        assert_eq!(bytes[6..8], [b'U', 0]);
+        assert_eq!(bytes.len(), 6 + 11 * 2 + path_len + 2);
+
+        let parsed = LoadOption::parse(&bytes).unwrap();
+        assert_eq!(parsed, option);
+        assert!(parsed.is_active());
+        assert_eq!(parsed.file_path[0].node_type, MEDIA_DEVICE_PATH);
+        assert_eq!(parsed.file_path[0].sub_type, MEDIA_FILEPATH_DP);
+        assert!(parsed.file_path[1].is_end_entire());
+    }
+
+    #[test]
+    fn test_load_option_rejects_corrupt_data() {
+        let bytes = shell_option().to_bytes().unwrap();
+        let bad = |data: &[u8]| LoadOption::parse(data).unwrap_err().status();
+
+        // Truncated anywhere inside the header, description or file path
+        for len in 0..bytes.len() {
+            assert_eq!(bad(&bytes[..len]), EFI_BAD_BUFFER_SIZE, "len {}", len);
+        }
+
+        // FilePathListLength past the end of the data
+        let mut long = bytes.clone();
+        long[4] = 0xFF;
+        assert_eq!(bad(&long), EFI_BAD_BUFFER_SIZE);
+
+        // Node length shorter than a header
+        let mut short_node = bytes.clone();
+        short_node[6 + 22 + 2] = 2;
+        short_node[6 + 22 + 3] = 0;
+        assert_eq!(bad(&short_node), EFI_BAD_BUFFER_SIZE);
+
+        // File path without a terminating end node
+        let mut no_end = bytes.clone();
+        let end = no_end.len() - 4;
+        no_end[end] = MEDIA_DEVICE_PATH;
+        assert_eq!(bad(&no_end), EFI_BAD_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn test_add_delete_and_reorder() {
+        mock::reset();
+        let rt = mock::runtime_services();
+        let bm = BootManager::new(&rt);
+        let kind = LoadOptionType::Boot;
+
+        unsafe {
+            assert!(bm.get_order(kind).unwrap().is_empty());
+            let first = bm.add_option(kind, &shell_option(), None).unwrap();
+            let second = bm.add_option(kind, &shell_option(), Some(0)).unwrap();
+            assert_eq!((first, second), (0, 1));
+            assert_eq!(bm.get_order(kind).unwrap(), [1, 0]);
+            assert_eq!(bm.get_option(kind, 1).unwrap(), shell_option());
+            assert_eq!(bm.option_numbers(kind).unwrap(), [0, 1]);
+
+            bm.set_order(kind, &[0, 1]).unwrap();
+            assert_eq!(bm.get_order(kind).unwrap(), [0, 1]);
+            assert_eq!(
+                bm.set_order(kind, &[0, 0]).unwrap_err().status(),
+                EFI_INVALID_PARAMETER
+            );
+
+            bm.delete_option(kind, 0).unwrap();
+            assert_eq!(bm.get_order(kind).unwrap(), [1]);
+            assert_eq!(bm.get_option(kind, 0).unwrap_err().status(), EFI_NOT_FOUND);
+            assert_eq!(bm.add_option(kind, &shell_option(), None).unwrap(), 0);
+            assert_eq!(bm.get_order(kind).unwrap(), [1, 0]);
+        }
+    }
+
+    #[test]
+    fn test_boot_next() {
+        mock::reset();
+        let rt = mock::runtime_services();
+        let bm = BootManager::new(&rt);
+
+        unsafe {
+            assert_eq!(bm.boot_next().unwrap(), None);
+            bm.set_boot_next(0x0003).unwrap();
+            assert_eq!(bm.boot_next().unwrap(), Some(3));
+            bm.clear_boot_next().unwrap();
+            bm.clear_boot_next().unwrap();
+            assert_eq!(bm.boot_next().unwrap(), None);
+            assert_eq!(bm.boot_current().unwrap_err().status(), EFI_NOT_FOUND);
+        }
+    }
+}
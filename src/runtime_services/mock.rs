@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! In-memory Runtime Services for unit tests
+//!
+//! Variables are kept per thread, so tests running in parallel do not see
+//! each other's stores. Call [`reset`] at the start of each test.
+
+use crate::ffi::*;
+use crate::runtime_services::EFI_VARIABLE_APPEND_WRITE;
+use crate::runtime_services::{ResetType, RuntimeServices, Time, TimeCapabilities};
+use std::cell::RefCell;
+use std::vec::Vec;
+
+struct StoredVariable {
+    name: Vec<Char16>,
+    vendor: Guid,
+    attributes: u32,
+    data: Vec<u8>,
+}
+
+std::thread_local! {
+    static VARIABLES: RefCell<Vec<StoredVariable>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Clear the variable store of the current thread
+pub(crate) fn reset() {
+    VARIABLES.with(|vars| vars.borrow_mut().clear());
+}
+
+// Name including the terminating NUL
+unsafe fn name_of(ptr: *const Char16) -> Vec<Char16> {
+    let len = crate::string::ucs2_strlen(ptr);
+    core::slice::from_raw_parts(ptr, len + 1).to_vec()
+}
+
+unsafe extern "efiapi" fn get_variable(
+    variable_name: *const Char16,
+    vendor_guid: *const Guid,
+    attributes: *mut Uint32,
+    data_size: *mut Uintn,
+    data: *mut core::ffi::c_void,
+) -> Status {
+    let name = name_of(variable_name);
+    VARIABLES.with(|vars| {
+        let vars = vars.borrow();
+        let Some(var) = vars
+            .iter()
+            .find(|v| v.name == name && v.vendor == *vendor_guid)
+        else {
+            return EFI_NOT_FOUND;
+        };
+        if !attributes.is_null() {
+            *attributes = var.attributes;
+        }
+        if *data_size < var.data.len() {
+            *data_size = var.data.len();
+            return EFI_BUFFER_TOO_SMALL;
+        }
+        *data_size = var.data.len();
+        core::ptr::copy_nonoverlapping(var.data.as_ptr(), data as *mut u8, var.data.len());
+        EFI_SUCCESS
+    })
+}
+
+unsafe extern "efiapi" fn get_next_variable_name(
+    variable_name_size: *mut Uintn,
+    variable_name: *mut Char16,
+    vendor_guid: *mut Guid,
+) -> Status {
+    let current = name_of(variable_name);
+    VARIABLES.with(|vars| {
+        let vars = vars.borrow();
+        let next = if current == [0] {
+            0
+        } else {
+            match vars
+                .iter()
+                .position(|v| v.name == current && v.vendor == *vendor_guid)
+            {
+                Some(i) => i + 1,
+                None => return EFI_INVALID_PARAMETER,
+            }
+        };
+        let Some(var) = vars.get(next) else {
+            return EFI_NOT_FOUND;
+        };
+        let size = core::mem::size_of_val(var.name.as_slice());
+        if *variable_name_size < size {
+            *variable_name_size = size;
+            return EFI_BUFFER_TOO_SMALL;
+        }
+        *variable_name_size = size;
+        core::ptr::copy_nonoverlapping(var.name.as_ptr(), variable_name, var.name.len());
+        *vendor_guid = var.vendor;
+        EFI_SUCCESS
+    })
+}
+
+unsafe extern "efiapi" fn set_variable(
+    variable_name: *const Char16,
+    vendor_guid: *const Guid,
+    attributes: Uint32,
+    data_size: Uintn,
+    data: *const core::ffi::c_void,
+) -> Status {
+    let name = name_of(variable_name);
+    let data = if data_size == 0 {
+        &[][..]
+    } else {
+        core::slice::from_raw_parts(data as *const u8, data_size)
+    };
+    VARIABLES.with(|vars| {
+        let mut vars = vars.borrow_mut();
+        let existing = vars
+            .iter()
+            .position(|v| v.name == name && v.vendor == *vendor_guid);
+        let append = attributes & EFI_VARIABLE_APPEND_WRITE != 0;
+        match existing {
+            Some(i) if append => vars[i].data.extend_from_slice(data),
+            Some(i) if data.is_empty() || attributes == 0 => {
+                vars.remove(i);
+            }
+            Some(i) => {
+                vars[i].attributes = attributes;
+                vars[i].data = data.to_vec();
+            }
+            None if data.is_empty() || attributes == 0 => return EFI_NOT_FOUND,
+            None => vars.push(StoredVariable {
+                name,
+                vendor: *vendor_guid,
+                attributes: attributes & !EFI_VARIABLE_APPEND_WRITE,
+                data: data.to_vec(),
+            }),
+        }
+        EFI_SUCCESS
+    })
+}
+
+unsafe extern "efiapi" fn query_variable_info(
+    _attributes: Uint32,
+    maximum_variable_storage_size: *mut Uint64,
+    remaining_variable_storage_size: *mut Uint64,
+    maximum_variable_size: *mut Uint64,
+) -> Status {
+    *maximum_variable_storage_size = 0x10000;
+    *remaining_variable_storage_size = 0x8000;
+    *maximum_variable_size = 0x1000;
+    EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn get_time(_time: *mut Time, _caps: *mut TimeCapabilities) -> Status {
+    EFI_UNSUPPORTED
+}
+
+unsafe extern "efiapi" fn set_time(_time: *const Time) -> Status {
+    EFI_UNSUPPORTED
+}
+
+unsafe extern "efiapi" fn get_wakeup_time(
+    _enabled: *mut Boolean,
+    _pending: *mut Boolean,
+    _time: *mut Time,
+) -> Status {
+    EFI_UNSUPPORTED
+}
+
+unsafe extern "efiapi" fn set_wakeup_time(_enable: Boolean, _time: *const Time) -> Status {
+    EFI_UNSUPPORTED
+}
+
+unsafe extern "efiapi" fn set_virtual_address_map(
+    _memory_map_size: Uintn,
+    _descriptor_size: Uintn,
+    _descriptor_version: Uint32,
+    _virtual_map: *mut MemoryDescriptor,
+) -> Status {
+    EFI_UNSUPPORTED
+}
+
+unsafe extern "efiapi" fn convert_pointer(
+    _debug_disposition: Uintn,
+    _address: *mut *mut core::ffi::c_void,
+) -> Status {
+    EFI_UNSUPPORTED
+}
+
+unsafe extern "efiapi" fn get_next_high_mono_count(_high_count: *mut Uint32) -> Status {
+    EFI_UNSUPPORTED
+}
+
+unsafe extern "efiapi" fn reset_system(
+    _reset_type: ResetType,
+    _reset_status: Status,
+    _data_size: Uintn,
+    _reset_data: *const core::ffi::c_void,
+) -> ! {
+    panic!("reset_system called in test")
+}
+
+unsafe extern "efiapi" fn update_capsule(
+    _capsule_header_array: *mut *mut core::ffi::c_void,
+    _capsule_count: Uintn,
+    _scatter_gather_list: PhysicalAddress,
+) -> Status {
+    EFI_UNSUPPORTED
+}
+
+unsafe extern "efiapi" fn query_capsule_capabilities(
+    _capsule_header_array: *mut *mut core::ffi::c_void,
+    _capsule_count: Uintn,
+    _maximum_capsule_size: *mut Uint64,
+    _reset_type: *mut Uint32,
+) -> Status {
+    EFI_UNSUPPORTED
+}
+
+/// Runtime Services table backed by the thread's variable store
+pub(crate) fn runtime_services() -> RuntimeServices {
+    RuntimeServices {
+        hdr: TableHeader {
+            signature: crate::runtime_services::EFI_RUNTIME_SERVICES_SIGNATURE,
+            revision: 0,
+            header_size: core::mem::size_of::<RuntimeServices>() as u32,
+            crc32: 0,
+            reserved: 0,
+        },
+        get_time,
+        set_time,
+        get_wakeup_time,
+        set_wakeup_time,
+        set_virtual_address_map,
+        convert_pointer,
+        get_variable,
+        get_next_variable_name,
+        set_variable,
+        get_next_high_mono_count,
+        reset_system,
+        update_capsule,
+        query_capsule_capabilities,
+        query_variable_info,
+    }
+}
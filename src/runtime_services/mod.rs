@@ -1,14 +1,19 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Runtime Services Table
 
+use crate::error::Error;
 use crate::ffi::*;
 
+pub mod authenticated_variables;
+pub mod boot_options;
+#[cfg(test)]
+pub(crate) mod mock;
 pub mod safe_wrappers;
 pub mod time;
 pub mod variables;
 
 pub use safe_wrappers::RuntimeServicesWrapper;
-pub use time::{Time, TimeCapabilities, TimeService};
+pub use time::{Time, TimeCapabilities, TimeService, WakeupTime, Weekday};
 pub use variables::*;
 
 /// EFI_RESET_TYPE
@@ -21,6 +26,21 @@ pub enum ResetType {
     EfiResetPlatformSpecific = 3,
 }
 
+impl TryFrom<u32> for ResetType {
+    type Error = Error;
+
+    /// Fails with `EFI_INVALID_PARAMETER` for values outside EFI_RESET_TYPE
+    fn try_from(value: u32) -> core::result::Result<Self, Error> {
+        match value {
+            0 => Ok(ResetType::EfiResetCold),
+            1 => Ok(ResetType::EfiResetWarm),
+            2 => Ok(ResetType::EfiResetShutdown),
+            3 => Ok(ResetType::EfiResetPlatformSpecific),
+            _ => Err(Error::new(EFI_INVALID_PARAMETER)),
+        }
+    }
+}
+
 /// EFI_RUNTIME_SERVICES Table
 #[repr(C)]
 pub struct RuntimeServices {
@@ -89,7 +109,7 @@ pub struct RuntimeServices {
         capsule_header_array: *mut *mut core::ffi::c_void,
         capsule_count: Uintn,
         maximum_capsule_size: *mut Uint64,
-        reset_type: *mut ResetType,
+        reset_type: *mut Uint32,
     ) -> Status,
 
     // Miscellaneous UEFI 2.0 Service
@@ -1,11 +1,12 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! Safe wrappers around Runtime Services
 
+use crate::error::{Error, StatusExt};
 use crate::ffi::*;
-use crate::runtime_services::{ResetType, RuntimeServices, Time};
+use crate::runtime_services::{ResetType, RuntimeServices, Time, WakeupTime};
 
 /// Result type for UEFI operations
-pub type Result<T> = core::result::Result<T, Status>;
+pub use crate::error::Result;
 
 /// Safe wrapper for Runtime Services
 pub struct RuntimeServicesWrapper<'a> {
@@ -34,24 +35,40 @@ impl<'a> RuntimeServicesWrapper<'a> {
             pad2: 0,
         };
 
-        let status = unsafe { (self.rs.get_time)(&mut time, core::ptr::null_mut()) };
-
-        if status == EFI_SUCCESS {
-            Ok(time)
-        } else {
-            Err(status)
-        }
+        unsafe { (self.rs.get_time)(&mut time, core::ptr::null_mut()) }.to_result()?;
+        Ok(time)
     }
 
     /// Set the current time
     pub fn set_time(&self, time: &Time) -> Result<()> {
-        let status = unsafe { (self.rs.set_time)(time) };
+        unsafe { (self.rs.set_time)(time) }.to_result()
+    }
+
+    /// Get the state of the RTC wakeup alarm
+    pub fn get_wakeup_time(&self) -> Result<WakeupTime> {
+        let mut enabled = FALSE;
+        let mut pending = FALSE;
+        let mut time = Time::new(1900, 1, 1, 0, 0, 0);
 
-        if status == EFI_SUCCESS {
-            Ok(())
-        } else {
-            Err(status)
+        unsafe { (self.rs.get_wakeup_time)(&mut enabled, &mut pending, &mut time) }.to_result()?;
+        Ok(WakeupTime {
+            enabled: enabled != 0,
+            pending: pending != 0,
+            time,
+        })
+    }
+
+    /// Arm the RTC wakeup alarm to wake the system at `time`
+    pub fn set_wakeup_time(&self, time: &Time) -> Result<()> {
+        if !time.is_valid() {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
         }
+        unsafe { (self.rs.set_wakeup_time)(TRUE, time) }.to_result()
+    }
+
+    /// Disarm the RTC wakeup alarm
+    pub fn disable_wakeup_time(&self) -> Result<()> {
+        unsafe { (self.rs.set_wakeup_time)(FALSE, core::ptr::null()) }.to_result()
     }
 
     /// Reset the system
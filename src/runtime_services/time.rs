@@ -1,10 +1,20 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Time Services
 
+use crate::error::{Error, Result, StatusExt};
 use crate::ffi::*;
 use crate::runtime_services::RuntimeServices;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Add, Sub};
+use core::str::FromStr;
+use core::time::Duration;
 
 /// EFI_TIME
+///
+/// Comparison and arithmetic work on the instant the time denotes, so two
+/// values in different time zones compare equal when they name the same
+/// moment. A time with [`EFI_UNSPECIFIED_TIMEZONE`] is treated as UTC.
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct Time {
@@ -35,6 +45,71 @@ pub const EFI_TIME_ADJUST_DAYLIGHT: u8 = 0x01;
 pub const EFI_TIME_IN_DAYLIGHT: u8 = 0x02;
 pub const EFI_UNSPECIFIED_TIMEZONE: i16 = 0x07FF;
 
+const SECS_PER_DAY: i64 = 86_400;
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+/// Day of the week
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Weekday {
+    Sunday = 0,
+    Monday = 1,
+    Tuesday = 2,
+    Wednesday = 3,
+    Thursday = 4,
+    Friday = 5,
+    Saturday = 6,
+}
+
+/// State of the RTC wakeup alarm
+#[derive(Debug, Copy, Clone)]
+pub struct WakeupTime {
+    pub enabled: bool,
+    pub pending: bool,
+    pub time: Time,
+}
+
+/// Check for a Gregorian leap year
+pub fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Number of days in `month` (1 - 12) of `year`
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+// Days since 1970-01-01 (Howard Hinnant's days_from_civil)
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Inverse of days_from_civil
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
 impl Time {
     /// Create a new time
     pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
@@ -61,7 +136,7 @@ impl Time {
         if self.month < 1 || self.month > 12 {
             return false;
         }
-        if self.day < 1 || self.day > 31 {
+        if self.day < 1 || self.day > days_in_month(self.year, self.month) {
             return false;
         }
         if self.hour > 23 {
@@ -83,6 +158,274 @@ impl Time {
         }
         true
     }
+
+    /// Offset of this local time from UTC in minutes
+    ///
+    /// Includes the extra hour when `EFI_TIME_IN_DAYLIGHT` is set. An
+    /// unspecified time zone counts as UTC.
+    pub fn utc_offset(&self) -> i32 {
+        if self.time_zone == EFI_UNSPECIFIED_TIMEZONE {
+            return 0;
+        }
+        let dst = if self.daylight & EFI_TIME_IN_DAYLIGHT != 0 {
+            60
+        } else {
+            0
+        };
+        self.time_zone as i32 + dst
+    }
+
+    /// Seconds since 1970-01-01T00:00:00Z
+    pub fn to_unix_timestamp(&self) -> i64 {
+        let days = days_from_civil(self.year as i64, self.month, self.day);
+        let secs = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        days * SECS_PER_DAY + secs - self.utc_offset() as i64 * 60
+    }
+
+    /// Create a UTC time from seconds since 1970-01-01T00:00:00Z
+    ///
+    /// Returns `None` outside the years 1900 - 9999.
+    pub fn from_unix_timestamp(secs: i64) -> Option<Self> {
+        Self::from_unix_parts(secs, 0, 0, 0)
+    }
+
+    /// Express the same instant in another time zone
+    ///
+    /// `time_zone` is in minutes from UTC (or [`EFI_UNSPECIFIED_TIMEZONE`])
+    /// and `daylight` takes the `EFI_TIME_*` flags.
+    pub fn with_time_zone(&self, time_zone: i16, daylight: u8) -> Option<Self> {
+        if time_zone != EFI_UNSPECIFIED_TIMEZONE && !(-1440..=1440).contains(&time_zone) {
+            return None;
+        }
+        Self::from_unix_parts(
+            self.to_unix_timestamp(),
+            self.nanosecond,
+            time_zone,
+            daylight,
+        )
+    }
+
+    fn from_unix_parts(secs: i64, nanosecond: u32, time_zone: i16, daylight: u8) -> Option<Self> {
+        let mut time = Time::new(1970, 1, 1, 0, 0, 0);
+        time.time_zone = time_zone;
+        time.daylight = daylight;
+        let local = secs.checked_add(time.utc_offset() as i64 * 60)?;
+        let (year, month, day) = civil_from_days(local.div_euclid(SECS_PER_DAY));
+        if !(1900..=9999).contains(&year) {
+            return None;
+        }
+        let secs_of_day = local.rem_euclid(SECS_PER_DAY);
+        time.year = year as u16;
+        time.month = month;
+        time.day = day;
+        time.hour = (secs_of_day / 3600) as u8;
+        time.minute = (secs_of_day / 60 % 60) as u8;
+        time.second = (secs_of_day % 60) as u8;
+        time.nanosecond = nanosecond;
+        Some(time)
+    }
+
+    /// Day of the week of the local date
+    pub fn weekday(&self) -> Weekday {
+        let days = days_from_civil(self.year as i64, self.month, self.day);
+        // 1970-01-01 was a Thursday
+        match (days + 4).rem_euclid(7) {
+            0 => Weekday::Sunday,
+            1 => Weekday::Monday,
+            2 => Weekday::Tuesday,
+            3 => Weekday::Wednesday,
+            4 => Weekday::Thursday,
+            5 => Weekday::Friday,
+            _ => Weekday::Saturday,
+        }
+    }
+
+    /// Add a duration, keeping the time zone
+    ///
+    /// Returns `None` if the result falls outside the years 1900 - 9999.
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        let mut secs = i64::try_from(duration.as_secs()).ok()?;
+        let mut nanos = self.nanosecond + duration.subsec_nanos();
+        if nanos >= NANOS_PER_SEC {
+            nanos -= NANOS_PER_SEC;
+            secs = secs.checked_add(1)?;
+        }
+        let secs = self.to_unix_timestamp().checked_add(secs)?;
+        Self::from_unix_parts(secs, nanos, self.time_zone, self.daylight)
+    }
+
+    /// Subtract a duration, keeping the time zone
+    ///
+    /// Returns `None` if the result falls outside the years 1900 - 9999.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        let mut secs = i64::try_from(duration.as_secs()).ok()?;
+        let mut nanos = self.nanosecond;
+        if nanos < duration.subsec_nanos() {
+            nanos += NANOS_PER_SEC;
+            secs = secs.checked_add(1)?;
+        }
+        nanos -= duration.subsec_nanos();
+        let secs = self.to_unix_timestamp().checked_sub(secs)?;
+        Self::from_unix_parts(secs, nanos, self.time_zone, self.daylight)
+    }
+
+    /// Time elapsed from `earlier` to `self`
+    ///
+    /// Returns `None` if `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: &Time) -> Option<Duration> {
+        let mut secs = self.to_unix_timestamp() - earlier.to_unix_timestamp();
+        let mut nanos = self.nanosecond as i64 - earlier.nanosecond as i64;
+        if nanos < 0 {
+            nanos += NANOS_PER_SEC as i64;
+            secs -= 1;
+        }
+        if secs < 0 {
+            return None;
+        }
+        Some(Duration::new(secs as u64, nanos as u32))
+    }
+
+    /// Parse an ISO 8601 date and time
+    ///
+    /// Accepts `YYYY-MM-DDTHH:MM:SS` with an optional fraction of up to nine
+    /// digits and an optional `Z` or `+HH:MM`/`-HH:MM` offset. Without an
+    /// offset the time zone is unspecified.
+    pub fn parse_iso8601(s: &str) -> Option<Self> {
+        let b = s.as_bytes();
+        if b.len() < 19
+            || b[4] != b'-'
+            || b[7] != b'-'
+            || b[10] != b'T'
+            || b[13] != b':'
+            || b[16] != b':'
+        {
+            return None;
+        }
+
+        let mut time = Time::new(
+            parse_digits(&b[0..4])? as u16,
+            parse_digits(&b[5..7])? as u8,
+            parse_digits(&b[8..10])? as u8,
+            parse_digits(&b[11..13])? as u8,
+            parse_digits(&b[14..16])? as u8,
+            parse_digits(&b[17..19])? as u8,
+        );
+
+        let mut rest = &b[19..];
+        if let Some((b'.', frac)) = rest.split_first() {
+            let len = frac.iter().take_while(|c| c.is_ascii_digit()).count();
+            if len == 0 || len > 9 {
+                return None;
+            }
+            time.nanosecond = parse_digits(&frac[..len])? * 10u32.pow(9 - len as u32);
+            rest = &frac[len..];
+        }
+
+        match rest {
+            [] => {}
+            [b'Z'] => time.time_zone = 0,
+            [sign @ (b'+' | b'-'), h1, h2, b':', m1, m2] => {
+                let hours = parse_digits(&[*h1, *h2])? as i16;
+                let minutes = parse_digits(&[*m1, *m2])? as i16;
+                if minutes > 59 {
+                    return None;
+                }
+                let offset = hours * 60 + minutes;
+                time.time_zone = if *sign == b'-' { -offset } else { offset };
+            }
+            _ => return None,
+        }
+
+        if time.is_valid() {
+            Some(time)
+        } else {
+            None
+        }
+    }
+}
+
+fn parse_digits(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0u32, |acc, &c| {
+        if c.is_ascii_digit() {
+            Some(acc * 10 + (c - b'0') as u32)
+        } else {
+            None
+        }
+    })
+}
+
+impl PartialEq for Time {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Time {}
+
+impl PartialOrd for Time {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Time {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.to_unix_timestamp(), self.nanosecond)
+            .cmp(&(other.to_unix_timestamp(), other.nanosecond))
+    }
+}
+
+impl Add<Duration> for Time {
+    type Output = Time;
+
+    fn add(self, duration: Duration) -> Time {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to time")
+    }
+}
+
+impl Sub<Duration> for Time {
+    type Output = Time;
+
+    fn sub(self, duration: Duration) -> Time {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from time")
+    }
+}
+
+/// Formats as ISO 8601, e.g. `2024-03-01T12:30:00.250000000+01:00`
+///
+/// The fraction is omitted when zero and the offset when the time zone is
+/// unspecified. The offset includes daylight saving time.
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+        if self.nanosecond != 0 {
+            write!(f, ".{:09}", self.nanosecond)?;
+        }
+        if self.time_zone == EFI_UNSPECIFIED_TIMEZONE {
+            return Ok(());
+        }
+        let offset = self.utc_offset();
+        if offset == 0 {
+            return f.write_str("Z");
+        }
+        let sign = if offset < 0 { '-' } else { '+' };
+        let offset = offset.unsigned_abs();
+        write!(f, "{}{:02}:{:02}", sign, offset / 60, offset % 60)
+    }
+}
+
+impl FromStr for Time {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Time::parse_iso8601(s).ok_or(Error::new(EFI_INVALID_PARAMETER))
+    }
 }
 
 /// Time service wrapper
@@ -97,42 +440,133 @@ impl<'a> TimeService<'a> {
     }
 
     /// Get the current time
-    pub unsafe fn get_time(&self) -> Result<(Time, TimeCapabilities), Status> {
+    pub unsafe fn get_time(&self) -> Result<(Time, TimeCapabilities)> {
         let mut time = core::mem::zeroed();
         let mut capabilities = core::mem::zeroed();
 
-        let status = (self.rt.get_time)(&mut time, &mut capabilities);
-
-        if status == EFI_SUCCESS {
-            Ok((time, capabilities))
-        } else {
-            Err(status)
-        }
+        (self.rt.get_time)(&mut time, &mut capabilities).to_result()?;
+        Ok((time, capabilities))
     }
 
     /// Set the current time
-    pub unsafe fn set_time(&self, time: &Time) -> Status {
-        (self.rt.set_time)(time as *const _)
+    pub unsafe fn set_time(&self, time: &Time) -> Result<()> {
+        (self.rt.set_time)(time as *const _).to_result()
     }
 
     /// Get wakeup time
-    pub unsafe fn get_wakeup_time(&self) -> Result<(bool, bool, Time), Status> {
+    pub unsafe fn get_wakeup_time(&self) -> Result<(bool, bool, Time)> {
         let mut enabled = 0u8;
         let mut pending = 0u8;
         let mut time = core::mem::zeroed();
 
-        let status = (self.rt.get_wakeup_time)(&mut enabled, &mut pending, &mut time);
-
-        if status == EFI_SUCCESS {
-            Ok((enabled != 0, pending != 0, time))
-        } else {
-            Err(status)
-        }
+        (self.rt.get_wakeup_time)(&mut enabled, &mut pending, &mut time).to_result()?;
+        Ok((enabled != 0, pending != 0, time))
     }
 
     /// Set wakeup time
-    pub unsafe fn set_wakeup_time(&self, enable: bool, time: Option<&Time>) -> Status {
+    pub unsafe fn set_wakeup_time(&self, enable: bool, time: Option<&Time>) -> Result<()> {
         let time_ptr = time.map_or(core::ptr::null(), |t| t as *const _);
-        (self.rt.set_wakeup_time)(enable as Boolean, time_ptr)
+        (self.rt.set_wakeup_time)(enable as Boolean, time_ptr).to_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_timestamp_round_trip() {
+        let epoch = Time::from_unix_timestamp(0).unwrap();
+        assert_eq!((epoch.year, epoch.month, epoch.day), (1970, 1, 1));
+        assert_eq!(epoch.time_zone, 0);
+
+        let t = Time::new(2024, 2, 29, 23, 59, 59);
+        assert_eq!(t.to_unix_timestamp(), 1_709_251_199);
+        let back = Time::from_unix_timestamp(1_709_251_199).unwrap();
+        assert_eq!(
+            (back.month, back.day, back.hour, back.second),
+            (2, 29, 23, 59)
+        );
+
+        let old = Time::new(1900, 1, 1, 0, 0, 0);
+        assert_eq!(old.to_unix_timestamp(), -2_208_988_800);
+        assert!(Time::from_unix_timestamp(-2_208_988_801).is_none());
+    }
+
+    #[test]
+    fn test_time_zone_and_daylight() {
+        let mut cest = Time::new(2024, 7, 1, 14, 0, 0);
+        cest.time_zone = 60;
+        cest.daylight = EFI_TIME_ADJUST_DAYLIGHT | EFI_TIME_IN_DAYLIGHT;
+        assert_eq!(cest.utc_offset(), 120);
+
+        let utc = Time::new(2024, 7, 1, 12, 0, 0);
+        let mut utc_zoned = utc;
+        utc_zoned.time_zone = 0;
+        assert_eq!(cest, utc_zoned);
+        // Unspecified time zone is treated as UTC
+        assert_eq!(cest, utc);
+
+        let ny = cest.with_time_zone(-300, 0).unwrap();
+        assert_eq!((ny.day, ny.hour), (1, 7));
+        assert_eq!(ny.to_string(), "2024-07-01T07:00:00-05:00");
+        assert!(cest.with_time_zone(2000, 0).is_none());
+    }
+
+    #[test]
+    fn test_weekday_and_ordering() {
+        assert_eq!(Time::new(1970, 1, 1, 0, 0, 0).weekday(), Weekday::Thursday);
+        assert_eq!(Time::new(2000, 2, 29, 0, 0, 0).weekday(), Weekday::Tuesday);
+        assert_eq!(Time::new(1900, 1, 1, 0, 0, 0).weekday(), Weekday::Monday);
+
+        let a = Time::new(2023, 12, 31, 23, 59, 59);
+        let mut b = a;
+        b.nanosecond = 1;
+        assert!(a < b);
+        assert!(Time::new(2024, 1, 1, 0, 0, 0) > b);
+    }
+
+    #[test]
+    fn test_duration_arithmetic() {
+        let t = Time::new(2023, 12, 31, 23, 59, 59);
+        let next = t + Duration::from_millis(1500);
+        assert_eq!((next.year, next.month, next.day), (2024, 1, 1));
+        assert_eq!((next.second, next.nanosecond), (0, 500_000_000));
+        assert_eq!(next.time_zone, EFI_UNSPECIFIED_TIMEZONE);
+
+        let back = next - Duration::from_millis(1500);
+        assert_eq!(back, t);
+        assert_eq!(back.nanosecond, 0);
+        assert_eq!(next.duration_since(&t), Some(Duration::from_millis(1500)));
+        assert_eq!(t.duration_since(&next), None);
+
+        let leap = Time::new(2024, 2, 28, 12, 0, 0) + Duration::from_secs(86_400);
+        assert_eq!((leap.month, leap.day), (2, 29));
+        assert!(Time::new(9999, 12, 31, 23, 59, 59)
+            .checked_add(Duration::from_secs(1))
+            .is_none());
+    }
+
+    #[test]
+    fn test_iso8601() {
+        let t: Time = "2024-03-01T12:30:05.25+01:30".parse().unwrap();
+        assert_eq!((t.year, t.month, t.day), (2024, 3, 1));
+        assert_eq!((t.hour, t.minute, t.second), (12, 30, 5));
+        assert_eq!(t.nanosecond, 250_000_000);
+        assert_eq!(t.time_zone, 90);
+        assert_eq!(t.to_string(), "2024-03-01T12:30:05.250000000+01:30");
+
+        let z = Time::parse_iso8601("1999-12-31T23:59:59Z").unwrap();
+        assert_eq!(z.time_zone, 0);
+        assert_eq!(z.to_string(), "1999-12-31T23:59:59Z");
+
+        let local = Time::parse_iso8601("2024-01-02T03:04:05").unwrap();
+        assert_eq!(local.time_zone, EFI_UNSPECIFIED_TIMEZONE);
+        assert_eq!(local.to_string(), "2024-01-02T03:04:05");
+
+        assert!(Time::parse_iso8601("2023-02-29T00:00:00Z").is_none());
+        assert!(Time::parse_iso8601("2024-01-02 03:04:05").is_none());
+        assert!(Time::parse_iso8601("2024-01-02T03:04:05+0100").is_none());
+        assert!(Time::parse_iso8601("2024-01-02T03:04:05.").is_none());
     }
 }
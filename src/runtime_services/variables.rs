@@ -1,8 +1,15 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Variable Services
 
+use crate::error::{Error, Result, StatusExt};
 use crate::ffi::*;
-use crate::runtime_services::RuntimeServices;
+use crate::guid;
+use crate::runtime_services::{RuntimeServices, Time};
+use crate::string::{CStr16, CString16};
+use core::ops::{BitAnd, BitOr, BitOrAssign};
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
 
 // Variable attributes
 pub const EFI_VARIABLE_NON_VOLATILE: u32 = 0x00000001;
@@ -13,6 +20,248 @@ pub const EFI_VARIABLE_AUTHENTICATED_WRITE_ACCESS: u32 = 0x00000010;
 pub const EFI_VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS: u32 = 0x00000020;
 pub const EFI_VARIABLE_APPEND_WRITE: u32 = 0x00000040;
 
+/// Variable attribute flags (`EFI_VARIABLE_*`)
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VariableAttributes(u32);
+
+impl VariableAttributes {
+    pub const NON_VOLATILE: Self = Self(EFI_VARIABLE_NON_VOLATILE);
+    pub const BOOTSERVICE_ACCESS: Self = Self(EFI_VARIABLE_BOOTSERVICE_ACCESS);
+    pub const RUNTIME_ACCESS: Self = Self(EFI_VARIABLE_RUNTIME_ACCESS);
+    pub const HARDWARE_ERROR_RECORD: Self = Self(EFI_VARIABLE_HARDWARE_ERROR_RECORD);
+    pub const AUTHENTICATED_WRITE_ACCESS: Self = Self(EFI_VARIABLE_AUTHENTICATED_WRITE_ACCESS);
+    pub const TIME_BASED_AUTHENTICATED_WRITE_ACCESS: Self =
+        Self(EFI_VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS);
+    pub const APPEND_WRITE: Self = Self(EFI_VARIABLE_APPEND_WRITE);
+
+    /// Non-volatile, boot services and runtime access, as used by most
+    /// persistent settings such as `BootOrder`
+    pub const NV_BS_RT: Self = Self(
+        EFI_VARIABLE_NON_VOLATILE | EFI_VARIABLE_BOOTSERVICE_ACCESS | EFI_VARIABLE_RUNTIME_ACCESS,
+    );
+
+    const ALL: u32 = 0x7F;
+
+    /// No flags set
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Create from raw bits, failing if unknown bits are set
+    pub const fn from_bits(bits: u32) -> Option<Self> {
+        if bits & !Self::ALL == 0 {
+            Some(Self(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Create from raw bits, dropping unknown bits
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        Self(bits & Self::ALL)
+    }
+
+    /// Get the raw bits
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Check that every flag in `other` is set
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check if no flags are set
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Set the flags in `other`
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clear the flags in `other`
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for VariableAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for VariableAttributes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for VariableAttributes {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+/// Conversion between a Rust value and the bytes of a variable
+///
+/// Multi-byte values are stored little-endian, as UEFI defines them.
+pub trait VariableCodec: Sized {
+    /// Append the encoded value to `out`
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decode a value from the complete variable data
+    ///
+    /// Fails with `EFI_BAD_BUFFER_SIZE` if the length does not fit the type.
+    fn decode(data: &[u8]) -> Result<Self>;
+}
+
+/// Plain data that can be stored as its in-memory bytes
+///
+/// # Safety
+/// The type must have no padding and every bit pattern must be a valid
+/// value, e.g. `#[repr(C)]` structs built only from integers and arrays.
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for i64 {}
+unsafe impl Pod for Guid {}
+unsafe impl Pod for Time {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+fn pod_bytes<T: Pod>(value: &T) -> &[u8] {
+    // Safe because Pod types have no padding
+    unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    }
+}
+
+impl<T: Pod> VariableCodec for T {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(pod_bytes(self));
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != core::mem::size_of::<T>() {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        Ok(unsafe { core::ptr::read_unaligned(data.as_ptr() as *const T) })
+    }
+}
+
+/// Packed array of values, e.g. `BootOrder` as `Vec<u16>`
+impl<T: Pod> VariableCodec for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        for value in self {
+            out.extend_from_slice(pod_bytes(value));
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let size = core::mem::size_of::<T>();
+        if size == 0 || data.len() % size != 0 {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        Ok(data
+            .chunks_exact(size)
+            .map(|chunk| unsafe { core::ptr::read_unaligned(chunk.as_ptr() as *const T) })
+            .collect())
+    }
+}
+
+/// NUL-terminated UCS-2 string, e.g. `PlatformLang`-style text variables
+impl VariableCodec for CString16 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        for code in self.as_slice_with_nul() {
+            out.extend_from_slice(&code.to_le_bytes());
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let codes = Vec::<Char16>::decode(data)?;
+        if codes.last() != Some(&0) {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        CString16::try_from(codes)
+    }
+}
+
+/// Name and vendor GUID of a variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableKey {
+    pub name: CString16,
+    pub vendor: Guid,
+}
+
+/// Iterator over all variable names, see [`Variable::keys`]
+pub struct VariableKeys<'a> {
+    vars: &'a Variable<'a>,
+    name: Vec<Char16>,
+    vendor: Guid,
+    done: bool,
+}
+
+impl Iterator for VariableKeys<'_> {
+    type Item = Result<VariableKey>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let mut size = core::mem::size_of_val(self.name.as_slice());
+            let result = unsafe {
+                self.vars.get_next_variable_name(
+                    self.name.as_mut_ptr(),
+                    &mut size,
+                    &mut self.vendor,
+                )
+            };
+            match result {
+                Ok(()) => break,
+                Err(err) if err.status() == EFI_BUFFER_TOO_SMALL => {
+                    // The buffer keeps the previous name, which the next call needs
+                    let len = err.required_size().unwrap_or(size).div_ceil(2);
+                    self.name.resize(len.max(self.name.len() + 1), 0);
+                }
+                Err(err) => {
+                    self.done = true;
+                    return if err.status() == EFI_NOT_FOUND {
+                        None
+                    } else {
+                        Some(Err(err))
+                    };
+                }
+            }
+        }
+
+        let len = self
+            .name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(self.name.len());
+        let mut codes = self.name[..len].to_vec();
+        codes.push(0);
+        Some(CString16::try_from(codes).map(|name| VariableKey {
+            name,
+            vendor: self.vendor,
+        }))
+    }
+}
+
 /// Variable wrapper for safe access
 pub struct Variable<'a> {
     rt: &'a RuntimeServices,
@@ -25,104 +274,270 @@ impl<'a> Variable<'a> {
     }
 
     /// Get a variable
+    ///
+    /// Fails with `EFI_BUFFER_TOO_SMALL` carrying the required size if `data`
+    /// is too short.
     pub unsafe fn get(
         &self,
-        variable_name: *const Char16,
+        name: &CStr16,
         vendor_guid: &Guid,
         data: &mut [u8],
-    ) -> Result<(u32, usize), Status> {
+    ) -> Result<(VariableAttributes, usize)> {
         let mut attributes = 0u32;
         let mut data_size = data.len();
 
-        let status = (self.rt.get_variable)(
-            variable_name,
+        (self.rt.get_variable)(
+            name.as_ptr(),
             vendor_guid as *const _,
             &mut attributes,
             &mut data_size,
             data.as_mut_ptr() as *mut core::ffi::c_void,
-        );
+        )
+        .to_result_with_size(data_size)?;
+        Ok((
+            VariableAttributes::from_bits_truncate(attributes),
+            data_size,
+        ))
+    }
 
-        if status == EFI_SUCCESS {
-            Ok((attributes, data_size))
-        } else {
-            Err(status)
+    /// Get a variable into a buffer sized to fit
+    pub unsafe fn get_vec(
+        &self,
+        name: &CStr16,
+        vendor_guid: &Guid,
+    ) -> Result<(VariableAttributes, Vec<u8>)> {
+        let mut data = Vec::new();
+        loop {
+            match self.get(name, vendor_guid, &mut data) {
+                Ok((attributes, size)) => {
+                    data.truncate(size);
+                    return Ok((attributes, data));
+                }
+                // Retry, since the variable may grow between calls
+                Err(err) if err.status() == EFI_BUFFER_TOO_SMALL => {
+                    let size = err.required_size().unwrap_or(data.len() * 2 + 64);
+                    data.resize(size.max(data.len() + 1), 0);
+                }
+                Err(err) => return Err(err),
+            }
         }
     }
 
+    /// Get a variable and decode it
+    pub unsafe fn get_value<T: VariableCodec>(
+        &self,
+        name: &CStr16,
+        vendor_guid: &Guid,
+    ) -> Result<T> {
+        let (_, data) = self.get_vec(name, vendor_guid)?;
+        T::decode(&data)
+    }
+
     /// Get next variable name
     pub unsafe fn get_next_variable_name(
         &self,
         variable_name: *mut Char16,
         name_size: &mut usize,
         vendor_guid: &mut Guid,
-    ) -> Status {
+    ) -> Result<()> {
         (self.rt.get_next_variable_name)(
             name_size as *mut usize,
             variable_name,
             vendor_guid as *mut _,
         )
+        .to_result_with_size(*name_size)
+    }
+
+    /// Iterate over the names and vendor GUIDs of all variables
+    ///
+    /// Variables must not be created or deleted while iterating.
+    pub unsafe fn keys(&self) -> VariableKeys<'_> {
+        VariableKeys {
+            vars: self,
+            name: vec![0; 64],
+            vendor: Guid::null(),
+            done: false,
+        }
     }
 
     /// Set a variable
     pub unsafe fn set(
         &self,
-        variable_name: *const Char16,
+        name: &CStr16,
         vendor_guid: &Guid,
-        attributes: u32,
+        attributes: VariableAttributes,
         data: &[u8],
-    ) -> Status {
+    ) -> Result<()> {
         (self.rt.set_variable)(
-            variable_name,
+            name.as_ptr(),
             vendor_guid as *const _,
-            attributes,
+            attributes.bits(),
             data.len(),
             data.as_ptr() as *const core::ffi::c_void,
         )
+        .to_result()
+    }
+
+    /// Encode a value and set it as a variable
+    pub unsafe fn set_value<T: VariableCodec>(
+        &self,
+        name: &CStr16,
+        vendor_guid: &Guid,
+        attributes: VariableAttributes,
+        value: &T,
+    ) -> Result<()> {
+        let mut data = Vec::new();
+        value.encode(&mut data);
+        self.set(name, vendor_guid, attributes, &data)
     }
 
     /// Delete a variable (set with size 0)
-    pub unsafe fn delete(&self, variable_name: *const Char16, vendor_guid: &Guid) -> Status {
+    pub unsafe fn delete(&self, name: &CStr16, vendor_guid: &Guid) -> Result<()> {
         (self.rt.set_variable)(
-            variable_name,
+            name.as_ptr(),
             vendor_guid as *const _,
             0,
             0,
             core::ptr::null(),
         )
+        .to_result()
     }
 
     /// Query variable info
-    pub unsafe fn query_variable_info(&self, attributes: u32) -> Result<(u64, u64, u64), Status> {
+    pub unsafe fn query_variable_info(
+        &self,
+        attributes: VariableAttributes,
+    ) -> Result<(u64, u64, u64)> {
         let mut max_storage = 0u64;
         let mut remaining_storage = 0u64;
         let mut max_variable_size = 0u64;
 
-        let status = (self.rt.query_variable_info)(
-            attributes,
+        (self.rt.query_variable_info)(
+            attributes.bits(),
             &mut max_storage,
             &mut remaining_storage,
             &mut max_variable_size,
+        )
+        .to_result()?;
+        Ok((max_storage, remaining_storage, max_variable_size))
+    }
+}
+
+// Well-known variable GUIDs
+pub const EFI_GLOBAL_VARIABLE_GUID: Guid = guid!("8be4df61-93ca-11d2-aa0d-00e098032b8c");
+
+pub const EFI_IMAGE_SECURITY_DATABASE_GUID: Guid = guid!("d719b2cb-3d3a-4596-a3bc-dad00e67656f");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cstr16;
+    use crate::runtime_services::mock;
+
+    const VENDOR: Guid = guid!("11111111-2222-3333-4444-555555555555");
+
+    #[test]
+    fn test_attributes() {
+        let attrs = VariableAttributes::NON_VOLATILE | VariableAttributes::BOOTSERVICE_ACCESS;
+        assert_eq!(attrs.bits(), 0x3);
+        assert!(VariableAttributes::NV_BS_RT.contains(attrs));
+        assert!(!attrs.contains(VariableAttributes::RUNTIME_ACCESS));
+        assert_eq!(VariableAttributes::from_bits(0x80), None);
+        assert_eq!(VariableAttributes::from_bits_truncate(0x87).bits(), 0x07);
+
+        let mut attrs = attrs;
+        attrs.remove(VariableAttributes::NON_VOLATILE);
+        assert_eq!(attrs, VariableAttributes::BOOTSERVICE_ACCESS);
+    }
+
+    #[test]
+    fn test_codecs() {
+        let mut out = Vec::new();
+        vec![0x0001u16, 0x0003, 0x0002].encode(&mut out);
+        assert_eq!(out, [1, 0, 3, 0, 2, 0]);
+        assert_eq!(Vec::<u16>::decode(&out).unwrap(), [1, 3, 2]);
+        assert_eq!(
+            Vec::<u16>::decode(&out[..5]).unwrap_err().status(),
+            EFI_BAD_BUFFER_SIZE
         );
 
-        if status == EFI_SUCCESS {
-            Ok((max_storage, remaining_storage, max_variable_size))
-        } else {
-            Err(status)
+        assert_eq!(u32::decode(&[0x78, 0x56, 0x34, 0x12]).unwrap(), 0x12345678);
+        assert!(u32::decode(&[0; 3]).is_err());
+
+        let mut out = Vec::new();
+        EFI_GLOBAL_VARIABLE_GUID.encode(&mut out);
+        assert_eq!(out[..4], [0x61, 0xdf, 0xe4, 0x8b]);
+        assert_eq!(Guid::decode(&out).unwrap(), EFI_GLOBAL_VARIABLE_GUID);
+
+        let lang = CString16::try_from("en-US").unwrap();
+        let mut out = Vec::new();
+        lang.encode(&mut out);
+        assert_eq!(out.len(), 12);
+        assert_eq!(CString16::decode(&out).unwrap(), lang);
+        assert!(CString16::decode(&out[..10]).is_err());
+    }
+
+    #[test]
+    fn test_get_vec_and_typed_values() {
+        mock::reset();
+        let rt = mock::runtime_services();
+        let vars = Variable::new(&rt);
+        let name = cstr16!("BootOrder");
+        let big = [0xA5u8; 300];
+
+        unsafe {
+            assert_eq!(
+                vars.get_vec(name, &VENDOR).unwrap_err().status(),
+                EFI_NOT_FOUND
+            );
+            vars.set(name, &VENDOR, VariableAttributes::NV_BS_RT, &big)
+                .unwrap();
+            let (attrs, data) = vars.get_vec(name, &VENDOR).unwrap();
+            assert_eq!(attrs, VariableAttributes::NV_BS_RT);
+            assert_eq!(data, big);
+
+            let mut small = [0u8; 4];
+            let err = vars.get(name, &VENDOR, &mut small).unwrap_err();
+            assert_eq!(err.required_size(), Some(300));
+
+            vars.set_value(
+                name,
+                &VENDOR,
+                VariableAttributes::NV_BS_RT,
+                &vec![3u16, 1, 2],
+            )
+            .unwrap();
+            assert_eq!(
+                vars.get_value::<Vec<u16>>(name, &VENDOR).unwrap(),
+                [3, 1, 2]
+            );
+
+            vars.delete(name, &VENDOR).unwrap();
+            assert!(vars.get_value::<Vec<u16>>(name, &VENDOR).is_err());
         }
     }
-}
 
-// Well-known variable GUIDs
-pub const EFI_GLOBAL_VARIABLE_GUID: Guid = Guid::new(
-    0x8BE4DF61,
-    0x93CA,
-    0x11d2,
-    [0xAA, 0x0D, 0x00, 0xE0, 0x98, 0x03, 0x2B, 0x8C],
-);
-
-pub const EFI_IMAGE_SECURITY_DATABASE_GUID: Guid = Guid::new(
-    0xd719b2cb,
-    0x3d3a,
-    0x4596,
-    [0xa3, 0xbc, 0xda, 0xd0, 0x0e, 0x67, 0x65, 0x6f],
-);
+    #[test]
+    fn test_keys_grow_name_buffer() {
+        mock::reset();
+        let rt = mock::runtime_services();
+        let vars = Variable::new(&rt);
+        let long = "L".repeat(100);
+        let long_name = CString16::try_from(long.as_str()).unwrap();
+
+        unsafe {
+            let attrs = VariableAttributes::BOOTSERVICE_ACCESS;
+            vars.set(cstr16!("A"), &VENDOR, attrs, &[1]).unwrap();
+            vars.set(&long_name, &EFI_GLOBAL_VARIABLE_GUID, attrs, &[2])
+                .unwrap();
+            vars.set(cstr16!("B"), &VENDOR, attrs, &[3]).unwrap();
+
+            let keys: Vec<VariableKey> = vars.keys().collect::<Result<_>>().unwrap();
+            assert_eq!(keys.len(), 3);
+            assert_eq!(keys[0].name, "A");
+            assert_eq!(keys[1].name, long.as_str());
+            assert_eq!(keys[1].vendor, EFI_GLOBAL_VARIABLE_GUID);
+            assert_eq!(keys[2].name, "B");
+        }
+    }
+}
@@ -0,0 +1,602 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! Secure Boot Signature Databases
+//!
+//! Owned model of the `EFI_SIGNATURE_LIST` sequences stored in the `PK`,
+//! `KEK`, `db`, `dbx` and `dbt` variables. Parsing is strict about sizes so
+//! a corrupt variable is rejected rather than misread.
+
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::protocols::security::*;
+use crate::runtime_services::{Time, VariableCodec};
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Size of the EFI_SIGNATURE_LIST header
+pub const SIGNATURE_LIST_HEADER_SIZE: usize = core::mem::size_of::<SignatureList>();
+
+/// Size of the owner GUID that starts every EFI_SIGNATURE_DATA
+pub const SIGNATURE_OWNER_SIZE: usize = core::mem::size_of::<Guid>();
+
+const TIME_SIZE: usize = core::mem::size_of::<Time>();
+
+/// Known `EFI_CERT_*` signature types
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureKind {
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+    Rsa2048,
+    Rsa2048Sha1,
+    Rsa2048Sha256,
+    X509,
+    X509Sha256,
+    X509Sha384,
+    X509Sha512,
+}
+
+impl SignatureKind {
+    /// Look up the kind for a signature type GUID
+    pub fn from_guid(guid: &Guid) -> Option<Self> {
+        const KINDS: [SignatureKind; 12] = [
+            SignatureKind::Sha1,
+            SignatureKind::Sha224,
+            SignatureKind::Sha256,
+            SignatureKind::Sha384,
+            SignatureKind::Sha512,
+            SignatureKind::Rsa2048,
+            SignatureKind::Rsa2048Sha1,
+            SignatureKind::Rsa2048Sha256,
+            SignatureKind::X509,
+            SignatureKind::X509Sha256,
+            SignatureKind::X509Sha384,
+            SignatureKind::X509Sha512,
+        ];
+        KINDS.into_iter().find(|kind| kind.guid() == *guid)
+    }
+
+    /// Signature type GUID
+    pub fn guid(&self) -> Guid {
+        match self {
+            SignatureKind::Sha1 => CERT_SHA1_GUID,
+            SignatureKind::Sha224 => CERT_SHA224_GUID,
+            SignatureKind::Sha256 => CERT_SHA256_GUID,
+            SignatureKind::Sha384 => CERT_SHA384_GUID,
+            SignatureKind::Sha512 => CERT_SHA512_GUID,
+            SignatureKind::Rsa2048 => CERT_RSA2048_GUID,
+            SignatureKind::Rsa2048Sha1 => CERT_RSA2048_SHA1_GUID,
+            SignatureKind::Rsa2048Sha256 => CERT_RSA2048_SHA256_GUID,
+            SignatureKind::X509 => CERT_X509_GUID,
+            SignatureKind::X509Sha256 => CERT_X509_SHA256_GUID,
+            SignatureKind::X509Sha384 => CERT_X509_SHA384_GUID,
+            SignatureKind::X509Sha512 => CERT_X509_SHA512_GUID,
+        }
+    }
+
+    /// Size of the hash for plain hash and X.509 TBS hash kinds
+    pub fn hash_size(&self) -> Option<usize> {
+        match self {
+            SignatureKind::Sha1 => Some(20),
+            SignatureKind::Sha224 => Some(28),
+            SignatureKind::Sha256 | SignatureKind::X509Sha256 => Some(32),
+            SignatureKind::Sha384 | SignatureKind::X509Sha384 => Some(48),
+            SignatureKind::Sha512 | SignatureKind::X509Sha512 => Some(64),
+            _ => None,
+        }
+    }
+
+    /// Check for a plain image hash kind (`EFI_CERT_SHA*`)
+    pub fn is_hash(&self) -> bool {
+        matches!(
+            self,
+            SignatureKind::Sha1
+                | SignatureKind::Sha224
+                | SignatureKind::Sha256
+                | SignatureKind::Sha384
+                | SignatureKind::Sha512
+        )
+    }
+
+    /// Check for an X.509 TBSCertificate hash kind (`EFI_CERT_X509_SHA*`)
+    pub fn is_x509_tbs_hash(&self) -> bool {
+        matches!(
+            self,
+            SignatureKind::X509Sha256 | SignatureKind::X509Sha384 | SignatureKind::X509Sha512
+        )
+    }
+
+    /// Size of the signature data after the owner GUID, `None` if variable
+    pub fn data_size(&self) -> Option<usize> {
+        match self {
+            SignatureKind::X509 => None,
+            SignatureKind::Rsa2048 | SignatureKind::Rsa2048Sha1 | SignatureKind::Rsa2048Sha256 => {
+                Some(256)
+            }
+            kind if kind.is_x509_tbs_hash() => kind.hash_size().map(|size| size + TIME_SIZE),
+            kind => kind.hash_size(),
+        }
+    }
+}
+
+/// One EFI_SIGNATURE_DATA entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureEntry {
+    pub owner: Guid,
+    pub data: Vec<u8>,
+}
+
+impl SignatureEntry {
+    /// Split an X.509 TBS hash entry into the hash and its revocation time
+    ///
+    /// Returns `None` unless the data is `hash_size` bytes plus an EFI_TIME.
+    pub fn x509_tbs_hash(&self, hash_size: usize) -> Option<(&[u8], Time)> {
+        if self.data.len() != hash_size + TIME_SIZE {
+            return None;
+        }
+        let (hash, time) = self.data.split_at(hash_size);
+        Some((hash, Time::decode(time).ok()?))
+    }
+}
+
+/// Owned EFI_SIGNATURE_LIST
+///
+/// Every entry of a list has the same size; X.509 certificates therefore
+/// normally get one list each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedSignatureList {
+    pub signature_type: Guid,
+    /// Type-specific header, empty for all `EFI_CERT_*` types
+    pub header: Vec<u8>,
+    pub entries: Vec<SignatureEntry>,
+}
+
+impl OwnedSignatureList {
+    /// Create an empty list
+    pub fn new(signature_type: Guid) -> Self {
+        OwnedSignatureList {
+            signature_type,
+            header: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Get the kind of the list if it is a known type
+    pub fn kind(&self) -> Option<SignatureKind> {
+        SignatureKind::from_guid(&self.signature_type)
+    }
+
+    // Size of each EFI_SIGNATURE_DATA, if the entries agree
+    fn signature_size(&self) -> Option<usize> {
+        let first = self.entries.first()?.data.len();
+        if self.entries.iter().all(|entry| entry.data.len() == first) {
+            Some(SIGNATURE_OWNER_SIZE + first)
+        } else {
+            None
+        }
+    }
+
+    /// Append the list in its on-disk form
+    ///
+    /// Fails with `EFI_INVALID_PARAMETER` if the entries differ in size and
+    /// with `EFI_BAD_BUFFER_SIZE` if the list exceeds 4 GiB.
+    pub fn write_to(&self, out: &mut Vec<u8>) -> Result<()> {
+        let signature_size = match self.signature_size() {
+            Some(size) => size,
+            None if self.entries.is_empty() => 0,
+            None => return Err(Error::new(EFI_INVALID_PARAMETER)),
+        };
+        let list_size =
+            SIGNATURE_LIST_HEADER_SIZE + self.header.len() + signature_size * self.entries.len();
+        let to_u32 = |n: usize| u32::try_from(n).map_err(|_| Error::new(EFI_BAD_BUFFER_SIZE));
+
+        self.signature_type.encode(out);
+        out.extend_from_slice(&to_u32(list_size)?.to_le_bytes());
+        out.extend_from_slice(&to_u32(self.header.len())?.to_le_bytes());
+        out.extend_from_slice(&to_u32(signature_size)?.to_le_bytes());
+        out.extend_from_slice(&self.header);
+        for entry in &self.entries {
+            entry.owner.encode(out);
+            out.extend_from_slice(&entry.data);
+        }
+        Ok(())
+    }
+
+    /// Parse one list from the start of `data`, returning it and its size
+    fn parse(data: &[u8]) -> Result<(Self, usize)> {
+        let bad = || Error::new(EFI_BAD_BUFFER_SIZE);
+        if data.len() < SIGNATURE_LIST_HEADER_SIZE {
+            return Err(bad());
+        }
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ]) as usize
+        };
+        let signature_type = Guid::decode(&data[..SIGNATURE_OWNER_SIZE])?;
+        let list_size = read_u32(16);
+        let header_size = read_u32(20);
+        let signature_size = read_u32(24);
+
+        if list_size > data.len() || signature_size < SIGNATURE_OWNER_SIZE {
+            return Err(bad());
+        }
+        let body = list_size
+            .checked_sub(SIGNATURE_LIST_HEADER_SIZE)
+            .and_then(|n| n.checked_sub(header_size))
+            .ok_or_else(bad)?;
+        if body % signature_size != 0 {
+            return Err(bad());
+        }
+
+        let kind = SignatureKind::from_guid(&signature_type);
+        if let Some(expected) = kind.and_then(|kind| kind.data_size()) {
+            if signature_size != SIGNATURE_OWNER_SIZE + expected {
+                return Err(bad());
+            }
+        }
+
+        let header_end = SIGNATURE_LIST_HEADER_SIZE + header_size;
+        let entries = data[header_end..list_size]
+            .chunks_exact(signature_size)
+            .map(|chunk| {
+                let (owner, data) = chunk.split_at(SIGNATURE_OWNER_SIZE);
+                Ok(SignatureEntry {
+                    owner: Guid::decode(owner)?,
+                    data: data.to_vec(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let list = OwnedSignatureList {
+            signature_type,
+            header: data[SIGNATURE_LIST_HEADER_SIZE..header_end].to_vec(),
+            entries,
+        };
+        Ok((list, list_size))
+    }
+}
+
+/// A complete signature database such as `db` or `dbx`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureDatabase {
+    pub lists: Vec<OwnedSignatureList>,
+}
+
+impl SignatureDatabase {
+    /// Create an empty database
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the contents of a signature database variable
+    ///
+    /// Fails with `EFI_BAD_BUFFER_SIZE` if any list is truncated, its sizes
+    /// are inconsistent, or a known type has the wrong signature size.
+    /// Lists of unknown types are kept as-is.
+    pub fn parse(mut data: &[u8]) -> Result<Self> {
+        let mut lists = Vec::new();
+        while !data.is_empty() {
+            let (list, size) = OwnedSignatureList::parse(data)?;
+            lists.push(list);
+            data = &data[size..];
+        }
+        Ok(SignatureDatabase { lists })
+    }
+
+    /// Serialize the database
+    ///
+    /// Empty lists are skipped.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for list in self.lists.iter().filter(|list| !list.entries.is_empty()) {
+            list.write_to(&mut out)?;
+        }
+        Ok(out)
+    }
+
+    /// Number of entries in all lists
+    pub fn len(&self) -> usize {
+        self.lists.iter().map(|list| list.entries.len()).sum()
+    }
+
+    /// Check if the database has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over all entries with their signature type
+    pub fn iter(&self) -> impl Iterator<Item = (&Guid, &SignatureEntry)> + '_ {
+        self.lists.iter().flat_map(|list| {
+            list.entries
+                .iter()
+                .map(move |entry| (&list.signature_type, entry))
+        })
+    }
+
+    /// Check if an entry with this type and data exists
+    pub fn contains(&self, kind: SignatureKind, data: &[u8]) -> bool {
+        let guid = kind.guid();
+        self.iter()
+            .any(|(ty, entry)| *ty == guid && entry.data == data)
+    }
+
+    /// Add an entry unless the same type and data is already present
+    ///
+    /// Certificates get a list of their own; other entries join an existing
+    /// list of the same type. Returns whether the entry was added. Fails
+    /// with `EFI_INVALID_PARAMETER` if `data` has the wrong size for `kind`.
+    pub fn add(&mut self, kind: SignatureKind, owner: Guid, data: &[u8]) -> Result<bool> {
+        match kind.data_size() {
+            Some(size) if size != data.len() => return Err(Error::new(EFI_INVALID_PARAMETER)),
+            None if data.is_empty() => return Err(Error::new(EFI_INVALID_PARAMETER)),
+            _ => {}
+        }
+        if self.contains(kind, data) {
+            return Ok(false);
+        }
+
+        let entry = SignatureEntry {
+            owner,
+            data: data.to_vec(),
+        };
+        let guid = kind.guid();
+        let existing = self.lists.iter_mut().find(|list| {
+            kind != SignatureKind::X509 && list.signature_type == guid && list.header.is_empty()
+        });
+        match existing {
+            Some(list) => list.entries.push(entry),
+            None => {
+                let mut list = OwnedSignatureList::new(guid);
+                list.entries.push(entry);
+                self.lists.push(list);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Add an image hash, choosing the type from its length
+    ///
+    /// Fails with `EFI_INVALID_PARAMETER` for lengths that match no SHA type.
+    pub fn add_hash(&mut self, owner: Guid, hash: &[u8]) -> Result<bool> {
+        let kind = hash_kind(hash.len()).ok_or(Error::new(EFI_INVALID_PARAMETER))?;
+        self.add(kind, owner, hash)
+    }
+
+    /// Add a DER encoded X.509 certificate
+    pub fn add_x509(&mut self, owner: Guid, der: &[u8]) -> Result<bool> {
+        self.add(SignatureKind::X509, owner, der)
+    }
+
+    /// Add an X.509 TBSCertificate hash with its revocation time
+    pub fn add_x509_tbs_hash(
+        &mut self,
+        owner: Guid,
+        hash: &[u8],
+        revocation_time: &Time,
+    ) -> Result<bool> {
+        let kind = match hash.len() {
+            32 => SignatureKind::X509Sha256,
+            48 => SignatureKind::X509Sha384,
+            64 => SignatureKind::X509Sha512,
+            _ => return Err(Error::new(EFI_INVALID_PARAMETER)),
+        };
+        let mut data = hash.to_vec();
+        revocation_time.encode(&mut data);
+        self.add(kind, owner, &data)
+    }
+
+    /// Remove entries with this type and data, returning how many were removed
+    pub fn remove(&mut self, kind: SignatureKind, data: &[u8]) -> usize {
+        let guid = kind.guid();
+        self.retain(|ty, entry| !(*ty == guid && entry.data == data))
+    }
+
+    /// Remove every entry owned by `owner`, returning how many were removed
+    pub fn remove_owner(&mut self, owner: &Guid) -> usize {
+        self.retain(|_, entry| entry.owner != *owner)
+    }
+
+    /// Remove repeated entries of the same type and data, keeping the first
+    ///
+    /// Returns how many were removed.
+    pub fn dedup(&mut self) -> usize {
+        let mut seen: Vec<(Guid, Vec<u8>)> = Vec::new();
+        self.retain(|ty, entry| {
+            if seen.iter().any(|(g, d)| g == ty && *d == entry.data) {
+                false
+            } else {
+                seen.push((*ty, entry.data.clone()));
+                true
+            }
+        })
+    }
+
+    /// Check for an image hash of any SHA type
+    pub fn contains_hash(&self, hash: &[u8]) -> bool {
+        self.iter().any(|(ty, entry)| {
+            SignatureKind::from_guid(ty).is_some_and(|kind| kind.is_hash()) && entry.data == hash
+        })
+    }
+
+    /// Check for a DER encoded X.509 certificate
+    pub fn contains_x509(&self, der: &[u8]) -> bool {
+        self.contains(SignatureKind::X509, der)
+    }
+
+    /// Find a revoked TBSCertificate hash and its revocation time
+    pub fn find_x509_tbs_hash(&self, hash: &[u8]) -> Option<Time> {
+        self.iter().find_map(|(ty, entry)| {
+            let kind = SignatureKind::from_guid(ty).filter(|kind| kind.is_x509_tbs_hash())?;
+            let (tbs_hash, time) = entry.x509_tbs_hash(kind.hash_size()?)?;
+            (tbs_hash == hash).then_some(time)
+        })
+    }
+
+    // Keep entries matching `keep`, drop emptied lists and count removals
+    fn retain(&mut self, mut keep: impl FnMut(&Guid, &SignatureEntry) -> bool) -> usize {
+        let before = self.len();
+        for list in &mut self.lists {
+            let ty = list.signature_type;
+            list.entries.retain(|entry| keep(&ty, entry));
+        }
+        self.lists.retain(|list| !list.entries.is_empty());
+        before - self.len()
+    }
+}
+
+/// SHA signature kind for a hash length
+fn hash_kind(len: usize) -> Option<SignatureKind> {
+    match len {
+        20 => Some(SignatureKind::Sha1),
+        28 => Some(SignatureKind::Sha224),
+        32 => Some(SignatureKind::Sha256),
+        48 => Some(SignatureKind::Sha384),
+        64 => Some(SignatureKind::Sha512),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guid;
+
+    const OWNER: Guid = guid!("77fa9abd-0359-4d32-bd60-28f4e78f784b");
+    const OTHER: Guid = guid!("00000000-0000-0000-0000-000000000001");
+
+    fn sample() -> SignatureDatabase {
+        let mut db = SignatureDatabase::new();
+        db.add_hash(OWNER, &[0x11; 32]).unwrap();
+        db.add_hash(OWNER, &[0x22; 32]).unwrap();
+        db.add_hash(OTHER, &[0x33; 48]).unwrap();
+        db.add_x509(OWNER, b"\x30\x82cert-one").unwrap();
+        db.add_x509(OTHER, b"\x30\x82cert-number-two").unwrap();
+        db
+    }
+
+    #[test]
+    fn test_round_trip_layout() {
+        let db = sample();
+        assert_eq!(db.lists.len(), 4);
+        assert_eq!(db.len(), 5);
+
+        let bytes = db.to_bytes().unwrap();
+        // First list: SHA-256 with two entries of 16 + 32 bytes
+        assert_eq!(Guid::decode(&bytes[..16]).unwrap(), CERT_SHA256_GUID);
+        assert_eq!(u32::decode(&bytes[16..20]).unwrap(), 28 + 2 * 48);
+        assert_eq!(u32::decode(&bytes[20..24]).unwrap(), 0);
+        assert_eq!(u32::decode(&bytes[24..28]).unwrap(), 48);
+        assert_eq!(Guid::decode(&bytes[28..44]).unwrap(), OWNER);
+
+        assert_eq!(SignatureDatabase::parse(&bytes).unwrap(), db);
+        assert!(SignatureDatabase::parse(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_corrupt_lists() {
+        let bytes = sample().to_bytes().unwrap();
+        let bad = |data: &[u8]| SignatureDatabase::parse(data).unwrap_err().status();
+
+        assert_eq!(bad(&bytes[..bytes.len() - 1]), EFI_BAD_BUFFER_SIZE);
+        assert_eq!(bad(&bytes[..20]), EFI_BAD_BUFFER_SIZE);
+
+        // Header size larger than the list
+        let mut corrupt = bytes.clone();
+        corrupt[20] = 0xFF;
+        assert_eq!(bad(&corrupt), EFI_BAD_BUFFER_SIZE);
+
+        // Signature size that does not divide the body
+        let mut corrupt = bytes.clone();
+        corrupt[24] = 47;
+        assert_eq!(bad(&corrupt), EFI_BAD_BUFFER_SIZE);
+
+        // Zero signature size
+        let mut corrupt = bytes.clone();
+        corrupt[24] = 0;
+        assert_eq!(bad(&corrupt), EFI_BAD_BUFFER_SIZE);
+
+        // SHA-256 list with SHA-1 sized entries
+        let mut db = SignatureDatabase::new();
+        db.lists.push(OwnedSignatureList {
+            signature_type: CERT_SHA256_GUID,
+            header: Vec::new(),
+            entries: vec![SignatureEntry {
+                owner: OWNER,
+                data: vec![0; 20],
+            }],
+        });
+        assert_eq!(bad(&db.to_bytes().unwrap()), EFI_BAD_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn test_unknown_types_are_preserved() {
+        let mut db = SignatureDatabase::new();
+        db.lists.push(OwnedSignatureList {
+            signature_type: OTHER,
+            header: vec![1, 2, 3],
+            entries: vec![SignatureEntry {
+                owner: OWNER,
+                data: vec![9; 5],
+            }],
+        });
+        let bytes = db.to_bytes().unwrap();
+        assert_eq!(SignatureDatabase::parse(&bytes).unwrap(), db);
+    }
+
+    #[test]
+    fn test_lookup_all_types() {
+        let mut db = sample();
+        db.add_hash(OWNER, &[0x44; 20]).unwrap();
+        db.add_hash(OWNER, &[0x55; 28]).unwrap();
+        db.add_hash(OWNER, &[0x66; 64]).unwrap();
+        let revoked = Time::new(2023, 5, 9, 0, 0, 0);
+        db.add_x509_tbs_hash(OWNER, &[0x77; 48], &revoked).unwrap();
+
+        for hash in [
+            &[0x44u8; 20][..],
+            &[0x55; 28],
+            &[0x11; 32],
+            &[0x33; 48],
+            &[0x66; 64],
+        ] {
+            assert!(db.contains_hash(hash));
+        }
+        assert!(!db.contains_hash(&[0x77; 48]));
+        assert!(db.contains_x509(b"\x30\x82cert-one"));
+        assert!(!db.contains_x509(b"\x30\x82cert"));
+        assert_eq!(db.find_x509_tbs_hash(&[0x77; 48]), Some(revoked));
+        assert_eq!(db.find_x509_tbs_hash(&[0x11; 32]), None);
+
+        let parsed = SignatureDatabase::parse(&db.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.find_x509_tbs_hash(&[0x77; 48]), Some(revoked));
+        assert!(db.add_hash(OWNER, &[0; 33]).is_err());
+    }
+
+    #[test]
+    fn test_add_remove_dedup() {
+        let mut db = sample();
+        assert!(!db.add_hash(OTHER, &[0x11; 32]).unwrap());
+        assert_eq!(db.len(), 5);
+
+        // Duplicates from a merged variable
+        let mut merged = db.clone();
+        merged.lists.extend(db.lists.iter().cloned());
+        assert_eq!(merged.len(), 10);
+        assert_eq!(merged.dedup(), 5);
+        assert_eq!(merged.len(), 5);
+
+        assert_eq!(db.remove(SignatureKind::Sha256, &[0x11; 32]), 1);
+        assert!(!db.contains_hash(&[0x11; 32]));
+        assert_eq!(db.remove_owner(&OTHER), 2);
+        assert_eq!(db.len(), 2);
+        assert_eq!(db.remove(SignatureKind::Sha256, &[0x22; 32]), 1);
+        // Emptied lists are dropped
+        assert_eq!(db.lists.len(), 1);
+    }
+}
@@ -1,13 +1,412 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI String Utilities - UCS-2/UTF-16 conversion and manipulation
 
+use crate::error::{Error, Result};
 use crate::ffi::*;
+use core::borrow::Borrow;
+use core::fmt;
+use core::ops::Deref;
+
 #[cfg(not(feature = "std"))]
-use alloc::string::String;
-#[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+
+/// Convert a character to a UCS-2 code unit
+///
+/// Returns `None` for characters outside the Basic Multilingual Plane and
+/// for surrogate code points, which UCS-2 cannot represent.
+pub const fn char_to_ucs2(c: char) -> Option<Char16> {
+    let c = c as u32;
+    if c > 0xFFFF || (c >= 0xD800 && c <= 0xDFFF) {
+        None
+    } else {
+        Some(c as Char16)
+    }
+}
+
+/// Borrowed, NUL-terminated UCS-2 string
+///
+/// Guaranteed to end with exactly one NUL and to contain no surrogate code
+/// units, so it can be passed straight to firmware. Create one from a
+/// literal with [`cstr16!`](crate::cstr16).
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CStr16([Char16]);
+
+impl CStr16 {
+    /// Wrap a NUL-terminated string owned by firmware
+    ///
+    /// Fails with `EFI_INVALID_PARAMETER` if `ptr` is null or the string
+    /// contains surrogates.
+    ///
+    /// # Safety
+    /// A non-null `ptr` must point to a NUL-terminated UCS-2 string that
+    /// outlives `'a`
+    pub unsafe fn from_ptr<'a>(ptr: *const Char16) -> Result<&'a CStr16> {
+        if ptr.is_null() {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        let len = ucs2_strlen(ptr);
+        Self::from_u16_with_nul(core::slice::from_raw_parts(ptr, len + 1))
+    }
+
+    /// Validate a NUL-terminated slice
+    ///
+    /// Fails with `EFI_INVALID_PARAMETER` if the slice does not end in NUL,
+    /// has an interior NUL or contains surrogates.
+    pub fn from_u16_with_nul(codes: &[Char16]) -> Result<&CStr16> {
+        match codes.split_last() {
+            Some((0, chars)) if chars.iter().all(|&c| c != 0 && !is_surrogate(c)) => {
+                Ok(unsafe { Self::from_u16_with_nul_unchecked(codes) })
+            }
+            _ => Err(Error::new(EFI_INVALID_PARAMETER)),
+        }
+    }
+
+    /// Wrap a NUL-terminated slice without validating it
+    ///
+    /// # Safety
+    /// `codes` must end with its only NUL and contain no surrogates
+    pub const unsafe fn from_u16_with_nul_unchecked(codes: &[Char16]) -> &CStr16 {
+        &*(codes as *const [Char16] as *const CStr16)
+    }
+
+    /// Encode `s` into `buf` without allocating
+    ///
+    /// Fails with `EFI_INVALID_PARAMETER` for NUL or non-BMP characters, and
+    /// with `EFI_BUFFER_TOO_SMALL` (carrying the required length in code
+    /// units) if `buf` cannot hold the string and its terminator.
+    pub fn from_str_with_buf<'a>(s: &str, buf: &'a mut [Char16]) -> Result<&'a CStr16> {
+        let mut len = 0;
+        for c in s.chars() {
+            let code = match char_to_ucs2(c) {
+                Some(0) | None => return Err(Error::new(EFI_INVALID_PARAMETER)),
+                Some(code) => code,
+            };
+            if let Some(slot) = buf.get_mut(len) {
+                *slot = code;
+            }
+            len += 1;
+        }
+        if len >= buf.len() {
+            return Err(Error::buffer_too_small(len + 1));
+        }
+        buf[len] = 0;
+        Ok(unsafe { Self::from_u16_with_nul_unchecked(&buf[..=len]) })
+    }
+
+    /// Get a pointer suitable for passing to firmware
+    pub const fn as_ptr(&self) -> *const Char16 {
+        self.0.as_ptr()
+    }
+
+    /// Get the code units without the terminating NUL
+    pub fn as_slice(&self) -> &[Char16] {
+        &self.0[..self.0.len() - 1]
+    }
+
+    /// Get the code units including the terminating NUL
+    pub const fn as_slice_with_nul(&self) -> &[Char16] {
+        &self.0
+    }
+
+    /// Number of characters, not counting the NUL
+    pub fn len(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    /// Whether the string has no characters
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Size in bytes including the NUL, as firmware APIs expect
+    pub fn num_bytes(&self) -> usize {
+        core::mem::size_of_val(&self.0)
+    }
+
+    /// Iterate over the code units, without the NUL
+    pub fn iter(&self) -> core::iter::Copied<core::slice::Iter<'_, Char16>> {
+        self.as_slice().iter().copied()
+    }
+
+    /// Iterate over the characters
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        // Validated as BMP without surrogates, so every unit is a char
+        self.iter()
+            .map(|c| char::from_u32(c as u32).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+}
+
+fn is_surrogate(c: Char16) -> bool {
+    (0xD800..=0xDFFF).contains(&c)
+}
+
+impl fmt::Display for CStr16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use core::fmt::Write;
+        for c in self.chars() {
+            f.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CStr16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
+
+impl PartialEq<str> for CStr16 {
+    fn eq(&self, other: &str) -> bool {
+        self.chars().eq(other.chars())
+    }
+}
+
+impl PartialEq<&str> for CStr16 {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl AsRef<CStr16> for CStr16 {
+    fn as_ref(&self) -> &CStr16 {
+        self
+    }
+}
+
+impl<'a> IntoIterator for &'a CStr16 {
+    type Item = Char16;
+    type IntoIter = core::iter::Copied<core::slice::Iter<'a, Char16>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl ToOwned for CStr16 {
+    type Owned = CString16;
+
+    fn to_owned(&self) -> CString16 {
+        CString16(self.0.to_vec())
+    }
+}
+
+/// Owned, NUL-terminated UCS-2 string
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CString16(Vec<Char16>);
+
+impl CString16 {
+    /// Create an empty string
+    pub fn new() -> Self {
+        CString16(vec![0])
+    }
+
+    /// Append a character
+    ///
+    /// Fails with `EFI_INVALID_PARAMETER` for NUL or non-BMP characters.
+    pub fn push(&mut self, c: char) -> Result<()> {
+        match char_to_ucs2(c) {
+            Some(0) | None => Err(Error::new(EFI_INVALID_PARAMETER)),
+            Some(code) => {
+                let nul = self.0.len() - 1;
+                self.0.insert(nul, code);
+                Ok(())
+            }
+        }
+    }
+
+    /// Append another string
+    pub fn push_str(&mut self, s: &CStr16) {
+        self.0.pop();
+        self.0.extend_from_slice(s.as_slice_with_nul());
+    }
+
+    /// Get the code units including the terminating NUL
+    pub fn into_vec(self) -> Vec<Char16> {
+        self.0
+    }
+}
+
+impl Default for CString16 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TryFrom<&str> for CString16 {
+    type Error = Error;
+
+    /// Fails with `EFI_INVALID_PARAMETER` for NUL or non-BMP characters
+    fn try_from(s: &str) -> Result<Self> {
+        let mut codes = Vec::with_capacity(s.len() + 1);
+        for c in s.chars() {
+            match char_to_ucs2(c) {
+                Some(0) | None => return Err(Error::new(EFI_INVALID_PARAMETER)),
+                Some(code) => codes.push(code),
+            }
+        }
+        codes.push(0);
+        Ok(CString16(codes))
+    }
+}
+
+impl TryFrom<Vec<Char16>> for CString16 {
+    type Error = Error;
+
+    /// Take ownership of a NUL-terminated vector after validating it
+    fn try_from(codes: Vec<Char16>) -> Result<Self> {
+        CStr16::from_u16_with_nul(&codes)?;
+        Ok(CString16(codes))
+    }
+}
+
+impl From<&CStr16> for CString16 {
+    fn from(s: &CStr16) -> Self {
+        s.to_owned()
+    }
+}
+
+impl Deref for CString16 {
+    type Target = CStr16;
+
+    fn deref(&self) -> &CStr16 {
+        unsafe { CStr16::from_u16_with_nul_unchecked(&self.0) }
+    }
+}
+
+impl AsRef<CStr16> for CString16 {
+    fn as_ref(&self) -> &CStr16 {
+        self
+    }
+}
+
+impl Borrow<CStr16> for CString16 {
+    fn borrow(&self) -> &CStr16 {
+        self
+    }
+}
+
+impl fmt::Display for CString16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl fmt::Debug for CString16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl PartialEq<str> for CString16 {
+    fn eq(&self, other: &str) -> bool {
+        **self == *other
+    }
+}
+
+impl PartialEq<&str> for CString16 {
+    fn eq(&self, other: &&str) -> bool {
+        **self == **other
+    }
+}
+
+impl PartialEq<CStr16> for CString16 {
+    fn eq(&self, other: &CStr16) -> bool {
+        **self == *other
+    }
+}
+
+/// Number of UCS-2 code units needed for `s`, without the NUL
+///
+/// Used by [`cstr16!`](crate::cstr16); panics (a compile error in const
+/// context) on NUL or characters outside the Basic Multilingual Plane.
+#[doc(hidden)]
+pub const fn ucs2_const_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let mut len = 0;
+    while i < bytes.len() {
+        let (c, width) = decode_utf8_const(bytes, i);
+        if c == 0 {
+            panic!("cstr16! input contains a NUL character");
+        }
+        if c > 0xFFFF || (c >= 0xD800 && c <= 0xDFFF) {
+            panic!("cstr16! input contains a character outside the Basic Multilingual Plane");
+        }
+        i += width;
+        len += 1;
+    }
+    len
+}
+
+/// Encode `s` as NUL-terminated UCS-2 into an array of `N` code units
+///
+/// Used by [`cstr16!`](crate::cstr16); `N` must be `ucs2_const_len(s) + 1`.
+#[doc(hidden)]
+pub const fn ucs2_const_encode<const N: usize>(s: &str) -> [Char16; N] {
+    let bytes = s.as_bytes();
+    let mut out = [0; N];
+    let mut i = 0;
+    let mut j = 0;
+    while i < bytes.len() {
+        let (c, width) = decode_utf8_const(bytes, i);
+        out[j] = c as Char16;
+        i += width;
+        j += 1;
+    }
+    out
+}
+
+/// Decode the UTF-8 sequence starting at `bytes[i]`
+const fn decode_utf8_const(bytes: &[u8], i: usize) -> (u32, usize) {
+    let b0 = bytes[i] as u32;
+    if b0 < 0x80 {
+        (b0, 1)
+    } else if b0 < 0xE0 {
+        (((b0 & 0x1F) << 6) | (bytes[i + 1] as u32 & 0x3F), 2)
+    } else if b0 < 0xF0 {
+        (
+            ((b0 & 0x0F) << 12)
+                | ((bytes[i + 1] as u32 & 0x3F) << 6)
+                | (bytes[i + 2] as u32 & 0x3F),
+            3,
+        )
+    } else {
+        (
+            ((b0 & 0x07) << 18)
+                | ((bytes[i + 1] as u32 & 0x3F) << 12)
+                | ((bytes[i + 2] as u32 & 0x3F) << 6)
+                | (bytes[i + 3] as u32 & 0x3F),
+            4,
+        )
+    }
+}
+
+/// Create a `&'static CStr16` from a string literal
+///
+/// The literal is checked at compile time: NUL and characters outside the
+/// Basic Multilingual Plane are rejected.
+///
+/// ```ignore
+/// console.output_string(cstr16!("Hello, world!\r\n"))?;
+/// ```
+#[macro_export]
+macro_rules! cstr16 {
+    ($s:literal) => {{
+        const S: &str = $s;
+        const N: usize = $crate::string::ucs2_const_len(S) + 1;
+        const CODES: [u16; N] = $crate::string::ucs2_const_encode::<N>(S);
+        const CSTR: &$crate::string::CStr16 =
+            unsafe { $crate::string::CStr16::from_u16_with_nul_unchecked(&CODES) };
+        CSTR
+    }};
+}
 
 /// Convert a Rust string slice to a null-terminated UCS-2 (Char16) vector
+///
+/// Characters outside the Basic Multilingual Plane become surrogate pairs,
+/// which UCS-2 firmware cannot display; prefer [`CString16`].
 pub fn str_to_ucs2(s: &str) -> Vec<Char16> {
     let mut result: Vec<Char16> = s.encode_utf16().collect();
     result.push(0); // Null terminator
@@ -219,4 +618,83 @@ mod tests {
         assert_eq!(cmp1, 0);
         assert!(cmp2 < 0);
     }
+
+    #[test]
+    fn test_cstr16_macro() {
+        let s = crate::cstr16!("Boot0001 é");
+        assert_eq!(s.len(), 10);
+        assert_eq!(s.as_slice_with_nul().last(), Some(&0));
+        assert_eq!(s.as_slice()[9], 0xE9);
+        assert_eq!(s, "Boot0001 é");
+        assert_eq!(s.num_bytes(), 22);
+        assert!(crate::cstr16!("").is_empty());
+    }
+
+    #[test]
+    fn test_cstr16_from_u16_with_nul() {
+        let ok = [b'A' as u16, b'B' as u16, 0];
+        let s = CStr16::from_u16_with_nul(&ok).unwrap();
+        assert_eq!(s.iter().collect::<Vec<_>>(), [b'A' as u16, b'B' as u16]);
+
+        assert!(CStr16::from_u16_with_nul(&[b'A' as u16]).is_err());
+        assert!(CStr16::from_u16_with_nul(&[b'A' as u16, 0, b'B' as u16, 0]).is_err());
+        assert!(CStr16::from_u16_with_nul(&[0xD83D, 0xDE00, 0]).is_err());
+        assert!(CStr16::from_u16_with_nul(&[b'A' as u16, 0xD800, 0]).is_err());
+        assert!(CStr16::from_u16_with_nul(&[0xDFFF, 0]).is_err());
+    }
+
+    #[test]
+    fn test_cstr16_from_ptr() {
+        let ok = [b'O' as u16, b'K' as u16, 0];
+        let s = unsafe { CStr16::from_ptr(ok.as_ptr()) }.unwrap();
+        assert_eq!(s, crate::cstr16!("OK"));
+
+        let err = unsafe { CStr16::from_ptr(core::ptr::null()) }.unwrap_err();
+        assert_eq!(err.status(), EFI_INVALID_PARAMETER);
+        let lone = [b'A' as u16, 0xDC00, 0];
+        assert!(unsafe { CStr16::from_ptr(lone.as_ptr()) }.is_err());
+    }
+
+    #[test]
+    fn test_cstr16_from_str_with_buf() {
+        let mut buf = [0u16; 8];
+        let s = CStr16::from_str_with_buf("EFI", &mut buf).unwrap();
+        assert_eq!(
+            s.as_slice_with_nul(),
+            &[b'E' as u16, b'F' as u16, b'I' as u16, 0]
+        );
+
+        let mut small = [0u16; 3];
+        let err = CStr16::from_str_with_buf("EFI", &mut small).unwrap_err();
+        assert_eq!(err.status(), EFI_BUFFER_TOO_SMALL);
+        assert_eq!(err.required_size(), Some(4));
+
+        let err = CStr16::from_str_with_buf("smile 😀", &mut buf).unwrap_err();
+        assert_eq!(err.status(), EFI_INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_cstring16_conversions() {
+        let mut owned = CString16::try_from("Hello").unwrap();
+        assert_eq!(owned, "Hello");
+        assert_eq!(format!("{}", owned), "Hello");
+        assert_eq!(format!("{:?}", owned), "\"Hello\"");
+
+        owned.push(',').unwrap();
+        owned.push_str(crate::cstr16!(" world"));
+        assert_eq!(owned, "Hello, world");
+        assert_eq!(owned.len(), 12);
+        assert!(owned.push('\0').is_err());
+        assert!(owned.push('😀').is_err());
+
+        assert!(CString16::try_from("a\0b").is_err());
+        assert!(CString16::try_from("😀").is_err());
+
+        let borrowed: &CStr16 = &owned;
+        assert_eq!(borrowed.to_owned(), owned);
+        assert!(crate::cstr16!("abc") < crate::cstr16!("abd"));
+
+        let round_trip = unsafe { ucs2_to_string(owned.as_ptr()).unwrap() };
+        assert_eq!(round_trip, "Hello, world");
+    }
 }
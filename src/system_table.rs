@@ -1,9 +1,16 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! EFI System Table
 
-use crate::boot_services::BootServices;
+use crate::boot_services::{BootServices, MemoryMap};
+use crate::error::{Result, StatusExt};
 use crate::ffi::*;
+use crate::protocols;
 use crate::runtime_services::RuntimeServices;
+use crate::string::CStr16;
+use crate::tables::configuration::ConfigurationTableIter;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
 
 pub use crate::tables::configuration::ConfigurationTable;
 
@@ -31,9 +38,9 @@ pub struct SimpleTextInputProtocol {
     // Additional fields omitted for brevity
 }
 
-/// EFI System Table
+/// EFI System Table (raw firmware layout)
 #[repr(C)]
-pub struct SystemTable {
+pub struct RawSystemTable {
     pub hdr: TableHeader,
     pub firmware_vendor: *const Char16,
     pub firmware_revision: Uint32,
@@ -49,7 +56,7 @@ pub struct SystemTable {
     pub configuration_table: *mut ConfigurationTable,
 }
 
-impl SystemTable {
+impl RawSystemTable {
     /// Get a reference to boot services
     pub unsafe fn boot_services(&self) -> &BootServices {
         &*self.boot_services
@@ -68,3 +75,221 @@ impl SystemTable {
 
 // System Table signature
 pub const EFI_SYSTEM_TABLE_SIGNATURE: u64 = 0x5453595320494249;
+
+/// Number of times to retry `ExitBootServices` after a stale map key
+const EXIT_BOOT_SERVICES_RETRIES: usize = 4;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Marker trait for the phases a [`SystemTable`] can be viewed in
+pub trait SystemTableView: private::Sealed {}
+
+/// Boot-time view: boot and runtime services are both available
+pub struct Boot;
+
+/// Runtime view: only runtime services remain after `ExitBootServices`
+pub struct Runtime;
+
+impl private::Sealed for Boot {}
+impl private::Sealed for Runtime {}
+impl SystemTableView for Boot {}
+impl SystemTableView for Runtime {}
+
+/// Typed handle to the EFI System Table
+///
+/// The `View` parameter tracks whether boot services are still available.
+/// [`SystemTable::exit_boot_services`] consumes the `Boot` view and returns a
+/// `Runtime` view, so touching boot services afterwards does not compile.
+///
+/// This is pointer-sized and can be used directly as the `efi_main`
+/// system table argument.
+#[repr(transparent)]
+pub struct SystemTable<View: SystemTableView> {
+    table: NonNull<RawSystemTable>,
+    _view: PhantomData<View>,
+}
+
+impl<View: SystemTableView> SystemTable<View> {
+    /// Get the raw system table pointer
+    pub fn as_ptr(&self) -> *mut RawSystemTable {
+        self.table.as_ptr()
+    }
+
+    fn raw(&self) -> &RawSystemTable {
+        unsafe { self.table.as_ref() }
+    }
+
+    /// Get the table header
+    pub fn header(&self) -> &TableHeader {
+        &self.raw().hdr
+    }
+
+    /// Get the firmware vendor string (null-terminated UCS-2)
+    pub fn firmware_vendor(&self) -> *const Char16 {
+        self.raw().firmware_vendor
+    }
+
+    /// Get the firmware revision
+    pub fn firmware_revision(&self) -> u32 {
+        self.raw().firmware_revision
+    }
+
+    /// Get a reference to runtime services
+    pub fn runtime_services(&self) -> &RuntimeServices {
+        unsafe { &*self.raw().runtime_services }
+    }
+
+    /// Iterate over the configuration tables
+    pub fn config_tables(&self) -> ConfigurationTableIter<'_> {
+        ConfigurationTableIter::new(self.raw())
+    }
+}
+
+impl SystemTable<Boot> {
+    /// Create a boot-time view from the pointer handed to `efi_main`
+    ///
+    /// # Safety
+    /// The pointer must reference the firmware's system table and boot
+    /// services must not have been exited yet.
+    pub unsafe fn from_ptr(ptr: *mut RawSystemTable) -> Option<Self> {
+        NonNull::new(ptr).map(|table| SystemTable {
+            table,
+            _view: PhantomData,
+        })
+    }
+
+    /// Get a reference to boot services
+    pub fn boot_services(&self) -> &BootServices {
+        unsafe { &*self.raw().boot_services }
+    }
+
+    /// Get the console output, or `None` on a headless system
+    pub fn stdout(&self) -> Option<Stdout<'_>> {
+        NonNull::new(self.raw().con_out as *mut protocols::SimpleTextOutputProtocol).map(
+            |console| Stdout {
+                console,
+                _table: PhantomData,
+            },
+        )
+    }
+
+    /// Exit boot services and switch to the runtime view
+    ///
+    /// Fetches the final memory map, closes the logger's file sinks, records
+    /// the exit in [`crate::runtime::guard`] and calls `ExitBootServices`,
+    /// refreshing the map and retrying if the map key went stale in between.
+    /// The global allocator, logger and panic handler check the guard and
+    /// stop using boot services from then on.
+    ///
+    /// Once `ExitBootServices` has been attempted the firmware may already
+    /// have torn down boot services, so the guard stays set even if this
+    /// returns an error.
+    ///
+    /// The `Boot` view is consumed, so boot services cannot be reached
+    /// through it afterwards:
+    ///
+    /// ```compile_fail,E0382
+    /// # use uefi_rust_intergration::{Boot, Handle, SystemTable};
+    /// # unsafe fn demo(st: SystemTable<Boot>, image: *mut Handle) {
+    /// let _ = st.exit_boot_services(image);
+    /// st.boot_services();
+    /// # }
+    /// ```
+    ///
+    /// # Safety
+    /// `image_handle` must be the handle passed to this image's entry point
+    pub unsafe fn exit_boot_services(
+        self,
+        image_handle: *mut Handle,
+    ) -> Result<(SystemTable<Runtime>, MemoryMap)> {
+        let bs = self.boot_services();
+        let mut memory_map = MemoryMap::get(bs)?;
+
+        // Nothing below may allocate or print through boot services
+        crate::logger::close_files();
+        crate::runtime::guard::exit_boot_services();
+
+        let mut status = (bs.exit_boot_services)(image_handle, memory_map.map_key());
+        for _ in 0..EXIT_BOOT_SERVICES_RETRIES {
+            if status != EFI_INVALID_PARAMETER {
+                break;
+            }
+            // Stale map key: only GetMemoryMap and ExitBootServices are
+            // allowed now, and the refresh reuses the existing buffer
+            memory_map.refresh(bs)?;
+            status = (bs.exit_boot_services)(image_handle, memory_map.map_key());
+        }
+        status.to_result()?;
+
+        let runtime = SystemTable {
+            table: self.table,
+            _view: PhantomData,
+        };
+        Ok((runtime, memory_map))
+    }
+}
+
+/// Console output borrowed from a [`SystemTable`]
+///
+/// The logger and panic handler write to the same console, so this keeps
+/// the protocol as a pointer and only dereferences it for each call.
+pub struct Stdout<'a> {
+    console: NonNull<protocols::SimpleTextOutputProtocol>,
+    _table: PhantomData<&'a RawSystemTable>,
+}
+
+impl Stdout<'_> {
+    /// Get the raw protocol pointer
+    pub fn as_ptr(&self) -> *mut protocols::SimpleTextOutputProtocol {
+        self.console.as_ptr()
+    }
+
+    /// Output a string
+    pub fn output_string(&mut self, string: &CStr16) -> Result<()> {
+        unsafe { (*self.console.as_ptr()).output_string(string) }
+    }
+
+    /// Set the foreground and background colors
+    pub fn set_attribute(&mut self, attribute: usize) -> Result<()> {
+        unsafe { (*self.console.as_ptr()).set_attribute(attribute) }
+    }
+
+    /// Clear the screen
+    pub fn clear_screen(&mut self) -> Result<()> {
+        unsafe { (*self.console.as_ptr()).clear_screen() }
+    }
+
+    fn flush_chunk(&mut self, buf: &mut [Char16], len: usize) -> fmt::Result {
+        buf[len] = 0;
+        let chunk = unsafe { CStr16::from_u16_with_nul_unchecked(&buf[..=len]) };
+        self.output_string(chunk).map_err(|_| fmt::Error)
+    }
+}
+
+impl fmt::Write for Stdout<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Flush through a small stack buffer so printing never allocates
+        const CHUNK: usize = 64;
+        let mut buf = [0 as Char16; CHUNK + 1];
+        let mut len = 0;
+        for c in s.chars() {
+            // UCS-2 consoles cannot show characters outside the BMP
+            buf[len] = if (c as u32) > 0xFFFF {
+                0xFFFD
+            } else {
+                c as u32 as Char16
+            };
+            len += 1;
+            if len == CHUNK {
+                self.flush_chunk(&mut buf, len)?;
+                len = 0;
+            }
+        }
+        if len > 0 {
+            self.flush_chunk(&mut buf, len)?;
+        }
+        Ok(())
+    }
+}
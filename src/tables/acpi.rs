@@ -2,21 +2,12 @@
 //! ACPI Table Parsing
 
 use crate::ffi::*;
+use crate::guid;
 
 /// ACPI Table GUIDs
-pub const ACPI_TABLE_GUID: Guid = Guid::new(
-    0xeb9d2d30,
-    0x2d88,
-    0x11d3,
-    [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
-);
-
-pub const ACPI_20_TABLE_GUID: Guid = Guid::new(
-    0x8868e871,
-    0xe4f1,
-    0x11d3,
-    [0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81],
-);
+pub const ACPI_TABLE_GUID: Guid = guid!("eb9d2d30-2d88-11d3-9a16-0090273fc14d");
+
+pub const ACPI_20_TABLE_GUID: Guid = guid!("8868e871-e4f1-11d3-bc22-0080c73c8881");
 
 /// RSDP (Root System Description Pointer) structure for ACPI 1.0
 #[repr(C, packed)]
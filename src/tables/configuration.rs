@@ -1,8 +1,10 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 //! UEFI Configuration Table Access
 
+use crate::error::{Error, Result};
 use crate::ffi::*;
-use crate::system_table::SystemTable;
+use crate::system_table::RawSystemTable;
+use crate::tables::esrt::{Esrt, SYSTEM_RESOURCE_TABLE_GUID};
 
 /// EFI_CONFIGURATION_TABLE
 #[repr(C)]
@@ -39,7 +41,7 @@ pub struct ConfigurationTableIter<'a> {
 
 impl<'a> ConfigurationTableIter<'a> {
     /// Create a new configuration table iterator
-    pub fn new(system_table: &'a SystemTable) -> Self {
+    pub fn new(system_table: &'a RawSystemTable) -> Self {
         let tables = unsafe {
             if system_table.configuration_table.is_null()
                 || system_table.number_of_table_entries == 0
@@ -60,6 +62,14 @@ impl<'a> ConfigurationTableIter<'a> {
     pub fn find_by_guid(&self, guid: &Guid) -> Option<&'a ConfigurationTable> {
         self.tables.iter().find(|t| t.matches_guid(guid))
     }
+
+    /// Read the EFI System Resource Table
+    pub fn esrt(&self) -> Result<Esrt> {
+        let table = self
+            .find_by_guid(&SYSTEM_RESOURCE_TABLE_GUID)
+            .ok_or(Error::new(EFI_NOT_FOUND))?;
+        unsafe { Esrt::from_ptr(table.vendor_table) }
+    }
 }
 
 impl<'a> Iterator for ConfigurationTableIter<'a> {
@@ -83,28 +93,28 @@ pub mod config_helpers {
     use crate::tables::smbios::*;
 
     /// Find ACPI RSDP 2.0 table
-    pub fn find_acpi_20_table(system_table: &SystemTable) -> Option<&RsdpDescriptor20> {
+    pub fn find_acpi_20_table(system_table: &RawSystemTable) -> Option<&RsdpDescriptor20> {
         let iter = ConfigurationTableIter::new(system_table);
         iter.find_by_guid(&ACPI_20_TABLE_GUID)
             .and_then(|t| unsafe { t.as_table::<RsdpDescriptor20>() })
     }
 
     /// Find ACPI RSDP 1.0 table
-    pub fn find_acpi_10_table(system_table: &SystemTable) -> Option<&RsdpDescriptor10> {
+    pub fn find_acpi_10_table(system_table: &RawSystemTable) -> Option<&RsdpDescriptor10> {
         let iter = ConfigurationTableIter::new(system_table);
         iter.find_by_guid(&ACPI_TABLE_GUID)
             .and_then(|t| unsafe { t.as_table::<RsdpDescriptor10>() })
     }
 
     /// Find SMBIOS 3.0 table
-    pub fn find_smbios3_table(system_table: &SystemTable) -> Option<&Smbios3EntryPoint> {
+    pub fn find_smbios3_table(system_table: &RawSystemTable) -> Option<&Smbios3EntryPoint> {
         let iter = ConfigurationTableIter::new(system_table);
         iter.find_by_guid(&SMBIOS3_TABLE_GUID)
             .and_then(|t| unsafe { t.as_table::<Smbios3EntryPoint>() })
     }
 
     /// Find SMBIOS table
-    pub fn find_smbios_table(system_table: &SystemTable) -> Option<&SmbiosEntryPoint> {
+    pub fn find_smbios_table(system_table: &RawSystemTable) -> Option<&SmbiosEntryPoint> {
         let iter = ConfigurationTableIter::new(system_table);
         iter.find_by_guid(&SMBIOS_TABLE_GUID)
             .and_then(|t| unsafe { t.as_table::<SmbiosEntryPoint>() })
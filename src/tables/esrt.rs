@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! EFI System Resource Table (ESRT)
+//!
+//! Lists the updatable firmware resources of the platform by firmware class
+//! GUID, with their current version and the outcome of the last update.
+
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::guid;
+use crate::protocols::firmware_management::LastAttemptStatus;
+use crate::runtime_services::VariableCodec;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// EFI_SYSTEM_RESOURCE_TABLE_GUID
+pub const SYSTEM_RESOURCE_TABLE_GUID: Guid = guid!("b122a263-3661-4f68-9929-78f8b0d62180");
+
+/// EFI_SYSTEM_RESOURCE_TABLE_FIRMWARE_RESOURCE_VERSION
+pub const ESRT_FIRMWARE_RESOURCE_VERSION: u64 = 1;
+
+/// ESRT Firmware Types
+pub const ESRT_FW_TYPE_UNKNOWN: u32 = 0x00000000;
+pub const ESRT_FW_TYPE_SYSTEMFIRMWARE: u32 = 0x00000001;
+pub const ESRT_FW_TYPE_DEVICEFIRMWARE: u32 = 0x00000002;
+pub const ESRT_FW_TYPE_UEFIDRIVER: u32 = 0x00000003;
+
+/// Size of EFI_SYSTEM_RESOURCE_TABLE without its entries
+pub const ESRT_HEADER_SIZE: usize = 16;
+/// Size of EFI_SYSTEM_RESOURCE_ENTRY
+pub const ESRT_ENTRY_SIZE: usize = core::mem::size_of::<EsrtEntry>();
+
+/// EFI_SYSTEM_RESOURCE_ENTRY
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EsrtEntry {
+    pub fw_class: Guid,
+    pub fw_type: u32,
+    pub fw_version: u32,
+    pub lowest_supported_fw_version: u32,
+    pub capsule_flags: u32,
+    pub last_attempt_version: u32,
+    pub last_attempt_status: u32,
+}
+
+impl EsrtEntry {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let u32_at = |off: usize| u32::from_le_bytes(data[off..off + 4].try_into().unwrap());
+        Ok(EsrtEntry {
+            fw_class: Guid::decode(&data[..16])?,
+            fw_type: u32_at(16),
+            fw_version: u32_at(20),
+            lowest_supported_fw_version: u32_at(24),
+            capsule_flags: u32_at(28),
+            last_attempt_version: u32_at(32),
+            last_attempt_status: u32_at(36),
+        })
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.fw_class.encode(out);
+        for field in [
+            self.fw_type,
+            self.fw_version,
+            self.lowest_supported_fw_version,
+            self.capsule_flags,
+            self.last_attempt_version,
+            self.last_attempt_status,
+        ] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    /// Decode the status of the last update attempt
+    pub fn last_attempt_status(&self) -> LastAttemptStatus {
+        LastAttemptStatus::from_raw(self.last_attempt_status)
+    }
+
+    /// Check whether the resource accepts an image of `version`
+    pub fn accepts_version(&self, version: u32) -> bool {
+        version >= self.lowest_supported_fw_version
+    }
+}
+
+/// EFI_SYSTEM_RESOURCE_TABLE
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Esrt {
+    pub fw_resource_count_max: u32,
+    pub entries: Vec<EsrtEntry>,
+}
+
+impl Esrt {
+    /// Parse a table from its serialized form
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let count = Self::entry_count(data)?;
+        let end = ESRT_HEADER_SIZE + count * ESRT_ENTRY_SIZE;
+        if data.len() < end {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let entries = data[ESRT_HEADER_SIZE..end]
+            .chunks_exact(ESRT_ENTRY_SIZE)
+            .map(EsrtEntry::parse)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Esrt {
+            fw_resource_count_max: u32::from_le_bytes(data[4..8].try_into().unwrap()),
+            entries,
+        })
+    }
+
+    /// Read the table a configuration table entry points at
+    ///
+    /// # Safety
+    /// `table` must point at a valid EFI_SYSTEM_RESOURCE_TABLE.
+    pub unsafe fn from_ptr(table: *const core::ffi::c_void) -> Result<Self> {
+        if table.is_null() {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        let header = core::slice::from_raw_parts(table as *const u8, ESRT_HEADER_SIZE);
+        let size = ESRT_HEADER_SIZE + Self::entry_count(header)? * ESRT_ENTRY_SIZE;
+        Self::parse(core::slice::from_raw_parts(table as *const u8, size))
+    }
+
+    // Validate the header and return FwResourceCount
+    fn entry_count(data: &[u8]) -> Result<usize> {
+        if data.len() < ESRT_HEADER_SIZE {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let version = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        if version != ESRT_FIRMWARE_RESOURCE_VERSION {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        let count = u32::from_le_bytes(data[..4].try_into().unwrap());
+        let max = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        if count > max {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        Ok(count as usize)
+    }
+
+    /// Serialize the table
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(ESRT_HEADER_SIZE + self.entries.len() * ESRT_ENTRY_SIZE);
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        out.extend_from_slice(
+            &self
+                .fw_resource_count_max
+                .max(self.entries.len() as u32)
+                .to_le_bytes(),
+        );
+        out.extend_from_slice(&ESRT_FIRMWARE_RESOURCE_VERSION.to_le_bytes());
+        for entry in &self.entries {
+            entry.encode(&mut out);
+        }
+        out
+    }
+
+    /// Find the entry for firmware class `fw_class`
+    pub fn find(&self, fw_class: &Guid) -> Option<&EsrtEntry> {
+        self.entries.iter().find(|e| e.fw_class == *fw_class)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capsule::{FmpCapsule, FmpPayload, FmpPayloadHeader};
+    use crate::protocols::firmware_management::LAST_ATTEMPT_STATUS_ERROR_UNSUCCESSFUL_VENDOR_RANGE_MIN;
+
+    const SYSTEM_FW: Guid = guid!("3b2e0f3a-5a41-4c7f-9d2b-7f1e8c6a4d21");
+    const DEVICE_FW: Guid = guid!("e0b2a1f5-4c3d-4e6f-8a9b-1c2d3e4f5a6b");
+
+    fn entry(fw_class: Guid, fw_version: u32, lowest: u32, status: u32) -> EsrtEntry {
+        EsrtEntry {
+            fw_class,
+            fw_type: ESRT_FW_TYPE_SYSTEMFIRMWARE,
+            fw_version,
+            lowest_supported_fw_version: lowest,
+            capsule_flags: 0,
+            last_attempt_version: fw_version,
+            last_attempt_status: status,
+        }
+    }
+
+    #[test]
+    fn test_esrt_parse_and_match_capsule() {
+        let esrt = Esrt {
+            fw_resource_count_max: 4,
+            entries: vec![
+                entry(SYSTEM_FW, 0x200, 0x100, 0),
+                entry(
+                    DEVICE_FW,
+                    7,
+                    5,
+                    LAST_ATTEMPT_STATUS_ERROR_UNSUCCESSFUL_VENDOR_RANGE_MIN + 2,
+                ),
+            ],
+        };
+        let bytes = esrt.to_bytes();
+        assert_eq!(bytes.len(), ESRT_HEADER_SIZE + 2 * ESRT_ENTRY_SIZE);
+        assert_eq!(Esrt::parse(&bytes).unwrap(), esrt);
+        assert_eq!(
+            unsafe { Esrt::from_ptr(bytes.as_ptr() as *const _) },
+            Ok(esrt.clone())
+        );
+
+        let device = esrt.find(&DEVICE_FW).unwrap();
+        assert_eq!(
+            device.last_attempt_status(),
+            LastAttemptStatus::Vendor(0x1002)
+        );
+        // The vendor range includes both ends
+        assert_eq!(
+            LastAttemptStatus::from_raw(0x4000),
+            LastAttemptStatus::Vendor(0x4000)
+        );
+        assert_eq!(
+            LastAttemptStatus::from_raw(0x4001),
+            LastAttemptStatus::Unknown(0x4001)
+        );
+        assert!(esrt
+            .find(&SYSTEM_FW)
+            .unwrap()
+            .last_attempt_status()
+            .is_success());
+
+        let mut capsule = FmpCapsule::new();
+        let image = FmpPayloadHeader::new(6, 5).wrap(b"fw");
+        capsule.payloads.push(FmpPayload::new(DEVICE_FW, 1, &image));
+        let payload = capsule.payloads_for(&device.fw_class).next().unwrap();
+        let (header, _) = FmpPayloadHeader::parse(&payload.image).unwrap();
+        assert!(device.accepts_version(header.fw_version));
+        assert!(!device.accepts_version(4));
+
+        assert_eq!(
+            Esrt::parse(&bytes[..bytes.len() - 1]).unwrap_err().status(),
+            EFI_BAD_BUFFER_SIZE
+        );
+        let mut bad_version = bytes.clone();
+        bad_version[8] = 2;
+        assert_eq!(
+            Esrt::parse(&bad_version).unwrap_err().status(),
+            EFI_UNSUPPORTED
+        );
+    }
+}
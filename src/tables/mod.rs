@@ -4,9 +4,11 @@
 pub mod acpi;
 pub mod acpi_advanced;
 pub mod configuration;
+pub mod esrt;
 pub mod smbios;
 
 pub use acpi::*;
 pub use acpi_advanced::*;
 pub use configuration::*;
+pub use esrt::*;
 pub use smbios::*;
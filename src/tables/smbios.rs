@@ -2,22 +2,13 @@
 //! SMBIOS Table Parsing
 
 use crate::ffi::*;
+use crate::guid;
 
 /// SMBIOS Table GUID
-pub const SMBIOS_TABLE_GUID: Guid = Guid::new(
-    0xeb9d2d31,
-    0x2d88,
-    0x11d3,
-    [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
-);
+pub const SMBIOS_TABLE_GUID: Guid = guid!("eb9d2d31-2d88-11d3-9a16-0090273fc14d");
 
 /// SMBIOS 3.0 Table GUID
-pub const SMBIOS3_TABLE_GUID: Guid = Guid::new(
-    0xf2fd1544,
-    0x9794,
-    0x4a2c,
-    [0x99, 0x2e, 0xe5, 0xbb, 0xcf, 0x20, 0xe3, 0x94],
-);
+pub const SMBIOS3_TABLE_GUID: Guid = guid!("f2fd1544-9794-4a2c-992e-e5bbcf20e394");
 
 /// SMBIOS Entry Point Structure (32-bit)
 #[repr(C, packed)]
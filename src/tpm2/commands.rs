@@ -0,0 +1,825 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! TPM 2.0 Command Layer
+//!
+//! [`Tpm2`] marshals commands, submits them through a [`Tpm2Transport`] and
+//! decodes the responses. Commands that need authorization use password
+//! sessions.
+
+use super::marshal::{Marshal, Reader};
+use super::types::*;
+use super::{
+    digest_size, ResponseCode, Tpm2Transport, TPM_CAP_ALGS, TPM_CAP_PCRS, TPM_CAP_TPM_PROPERTIES,
+    TPM_PT_INPUT_BUFFER, TPM_PT_NV_BUFFER_MAX, TPM_RH_NULL,
+};
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::protocols::security::tpm2_commands::*;
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+/// Size of the command and response headers
+pub const TPM2_HEADER_SIZE: usize = 10;
+
+/// Response buffer size, the largest response of a PC Client TPM
+const RESPONSE_BUFFER_SIZE: usize = 4096;
+
+/// Chunk size used when the TPM does not report TPM_PT_NV_BUFFER_MAX
+const DEFAULT_NV_BUFFER_SIZE: usize = 512;
+
+/// Chunk size used when the TPM does not report TPM_PT_INPUT_BUFFER
+const DEFAULT_INPUT_BUFFER_SIZE: usize = 1024;
+
+/// A command to be marshalled
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub code: u32,
+    pub handles: Vec<u32>,
+    pub sessions: Vec<AuthCommand>,
+    pub parameters: Vec<u8>,
+}
+
+impl Command {
+    pub fn new(code: u32) -> Self {
+        Command {
+            code,
+            handles: Vec::new(),
+            sessions: Vec::new(),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Append a handle
+    pub fn handle(mut self, handle: u32) -> Self {
+        self.handles.push(handle);
+        self
+    }
+
+    /// Append an authorization session
+    pub fn session(mut self, session: AuthCommand) -> Self {
+        self.sessions.push(session);
+        self
+    }
+
+    /// Append a parameter
+    pub fn parameter<T: Marshal>(mut self, value: &T) -> Self {
+        value.marshal(&mut self.parameters);
+        self
+    }
+
+    /// Marshal the complete command including its header
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(TPM2_HEADER_SIZE + self.parameters.len() + 64);
+        let tag = if self.sessions.is_empty() {
+            TPM_ST_NO_SESSIONS
+        } else {
+            TPM_ST_SESSIONS
+        };
+        tag.marshal(&mut out);
+        0u32.marshal(&mut out);
+        self.code.marshal(&mut out);
+        for handle in &self.handles {
+            handle.marshal(&mut out);
+        }
+        if !self.sessions.is_empty() {
+            let mut auth = Vec::new();
+            for session in &self.sessions {
+                session.marshal(&mut auth);
+            }
+            (auth.len() as u32).marshal(&mut out);
+            out.extend_from_slice(&auth);
+        }
+        out.extend_from_slice(&self.parameters);
+        let size = out.len() as u32;
+        out[2..6].copy_from_slice(&size.to_be_bytes());
+        out
+    }
+}
+
+/// A successful response with the authorization area removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub handles: Vec<u32>,
+    pub parameters: Vec<u8>,
+}
+
+impl Response {
+    /// Decode a response with `handle_count` response handles
+    ///
+    /// A failing TPM_RC is returned as an error carrying the code. Fails
+    /// with `EFI_BAD_BUFFER_SIZE` if the sizes are inconsistent and with
+    /// `EFI_DEVICE_ERROR` for an unknown tag.
+    pub fn parse(data: &[u8], handle_count: usize) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        let tag = reader.read::<u16>()?;
+        let size = reader.read::<u32>()? as usize;
+        let code = ResponseCode(reader.read()?);
+        if size < TPM2_HEADER_SIZE || size > data.len() {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        code.to_result()?;
+
+        let mut reader = Reader::new(&data[TPM2_HEADER_SIZE..size]);
+        let mut handles = Vec::with_capacity(handle_count);
+        for _ in 0..handle_count {
+            handles.push(reader.read()?);
+        }
+        let parameters = match tag {
+            TPM_ST_NO_SESSIONS => reader.remaining(),
+            TPM_ST_SESSIONS => {
+                let parameter_size = reader.read::<u32>()?;
+                reader.bytes(parameter_size as usize)?
+            }
+            _ => return Err(Error::new(EFI_DEVICE_ERROR)),
+        };
+        Ok(Response {
+            handles,
+            parameters: parameters.to_vec(),
+        })
+    }
+
+    /// Reader over the response parameters
+    pub fn reader(&self) -> Reader<'_> {
+        Reader::new(&self.parameters)
+    }
+}
+
+/// Output of TPM2_PCR_Read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcrReadResponse {
+    pub pcr_update_counter: u32,
+    /// PCRs the digests belong to, in the order of `digests`
+    pub selection: TpmlPcrSelection,
+    pub digests: TpmlDigest,
+}
+
+/// Value of one PCR in one bank
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcrValue {
+    pub pcr: u32,
+    pub hash_alg: u16,
+    pub digest: Vec<u8>,
+}
+
+/// Output of TPM2_NV_ReadPublic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NvReadPublicResponse {
+    pub public: TpmsNvPublic,
+    pub name: Tpm2bName,
+}
+
+/// Output of TPM2_ReadPublic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadPublicResponse {
+    /// Marshalled TPMT_PUBLIC
+    pub public_area: Vec<u8>,
+    pub name: Tpm2bName,
+    pub qualified_name: Tpm2bName,
+}
+
+impl ReadPublicResponse {
+    /// Parse the algorithm-independent fields of the public area
+    pub fn header(&self) -> Result<TpmtPublicHeader> {
+        TpmtPublicHeader::parse(&self.public_area)
+    }
+}
+
+/// Output of TPM2_SequenceComplete
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashSequenceResult {
+    pub digest: Vec<u8>,
+    pub validation: TpmtTkHashcheck,
+}
+
+/// TPM 2.0 command interface
+pub struct Tpm2<T: Tpm2Transport> {
+    transport: T,
+    response: Vec<u8>,
+}
+
+impl<T: Tpm2Transport> Tpm2<T> {
+    pub fn new(transport: T) -> Self {
+        Tpm2 {
+            transport,
+            response: vec![0; RESPONSE_BUFFER_SIZE],
+        }
+    }
+
+    /// The underlying transport
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Submit `command` and decode a response with `handle_count` handles
+    pub fn execute(&mut self, command: &Command, handle_count: usize) -> Result<Response> {
+        let bytes = command.to_bytes();
+        let len = self.transport.submit_command(&bytes, &mut self.response)?;
+        let len = core::cmp::min(len, self.response.len());
+        Response::parse(&self.response[..len], handle_count)
+    }
+
+    // ------------------------------------------------------------------------
+    // PCRs
+    // ------------------------------------------------------------------------
+
+    /// TPM2_PCR_Extend with one digest per bank
+    pub fn pcr_extend(&mut self, pcr: u32, digests: &TpmlDigestValues) -> Result<()> {
+        let command = Command::new(TPM_CC_PCR_EXTEND)
+            .handle(pcr)
+            .session(AuthCommand::password(&[]))
+            .parameter(digests);
+        self.execute(&command, 0).map(|_| ())
+    }
+
+    /// TPM2_PCR_Read
+    ///
+    /// The TPM may return fewer PCRs than selected; `selection` in the
+    /// result says which ones were read.
+    pub fn pcr_read(&mut self, selection: &TpmlPcrSelection) -> Result<PcrReadResponse> {
+        let command = Command::new(TPM_CC_PCR_READ).parameter(selection);
+        let response = self.execute(&command, 0)?;
+        let mut reader = response.reader();
+        let result = PcrReadResponse {
+            pcr_update_counter: reader.read()?,
+            selection: reader.read()?,
+            digests: reader.read()?,
+        };
+        reader.finish()?;
+        Ok(result)
+    }
+
+    /// Read `pcrs` from every active PCR bank
+    ///
+    /// Issues as many TPM2_PCR_Read commands as the TPM needs. Values are
+    /// returned bank by bank, in ascending PCR order within each bank.
+    pub fn pcr_read_all_banks(&mut self, pcrs: &[u32]) -> Result<Vec<PcrValue>> {
+        let mut pending = Tpml(
+            self.active_pcr_banks()?
+                .into_iter()
+                .map(|hash| TpmsPcrSelection::new(hash, pcrs))
+                .collect(),
+        );
+        let mut values = Vec::new();
+        while pending.0.iter().any(|bank| !bank.is_empty()) {
+            let result = self.pcr_read(&pending)?;
+            let mut digests = result.digests.0.into_iter();
+            let mut read_any = false;
+            for bank in &result.selection.0 {
+                for pcr in bank.pcrs() {
+                    let digest = digests.next().ok_or(Error::new(EFI_DEVICE_ERROR))?;
+                    // PCRs that were not asked for, or were already read,
+                    // do not count as progress
+                    let Some(pending) = pending
+                        .0
+                        .iter_mut()
+                        .find(|p| p.hash == bank.hash && p.contains(pcr))
+                    else {
+                        continue;
+                    };
+                    pending.deselect(pcr);
+                    values.push(PcrValue {
+                        pcr,
+                        hash_alg: bank.hash,
+                        digest: digest.0,
+                    });
+                    read_any = true;
+                }
+            }
+            if !read_any {
+                // The TPM refused the remaining selection, or keeps returning
+                // PCRs already read
+                return Err(Error::new(EFI_DEVICE_ERROR));
+            }
+        }
+        values.sort_by_key(|value| {
+            let bank = pending.0.iter().position(|b| b.hash == value.hash_alg);
+            (bank, value.pcr)
+        });
+        Ok(values)
+    }
+
+    // ------------------------------------------------------------------------
+    // Random numbers
+    // ------------------------------------------------------------------------
+
+    /// Fill `buffer` with TPM2_GetRandom output
+    pub fn get_random(&mut self, buffer: &mut [u8]) -> Result<()> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            let requested = core::cmp::min(buffer.len() - filled, u16::MAX as usize) as u16;
+            let command = Command::new(TPM_CC_GET_RANDOM).parameter(&requested);
+            let response = self.execute(&command, 0)?;
+            let bytes: Tpm2bDigest = response.reader().read()?;
+            if bytes.0.is_empty() || bytes.0.len() > requested as usize {
+                return Err(Error::new(EFI_DEVICE_ERROR));
+            }
+            buffer[filled..filled + bytes.0.len()].copy_from_slice(&bytes.0);
+            filled += bytes.0.len();
+        }
+        Ok(())
+    }
+
+    // ------------------------------------------------------------------------
+    // Capabilities
+    // ------------------------------------------------------------------------
+
+    /// TPM2_GetCapability
+    ///
+    /// Returns the data and whether more is available.
+    pub fn get_capability(
+        &mut self,
+        capability: u32,
+        property: u32,
+        property_count: u32,
+    ) -> Result<(bool, CapabilityData)> {
+        let command = Command::new(TPM_CC_GET_CAPABILITY)
+            .parameter(&capability)
+            .parameter(&property)
+            .parameter(&property_count);
+        let response = self.execute(&command, 0)?;
+        let mut reader = response.reader();
+        let more_data = reader.read()?;
+        let data: CapabilityData = reader.read()?;
+        if data.capability() != capability {
+            return Err(Error::new(EFI_DEVICE_ERROR));
+        }
+        Ok((more_data, data))
+    }
+
+    /// All algorithms the TPM implements
+    pub fn algorithms(&mut self) -> Result<Vec<TpmsAlgProperty>> {
+        let mut algorithms: Vec<TpmsAlgProperty> = Vec::new();
+        let mut next = 0u32;
+        loop {
+            let (more, data) = self.get_capability(TPM_CAP_ALGS, next, 64)?;
+            let CapabilityData::Algorithms(list) = data else {
+                return Err(Error::new(EFI_DEVICE_ERROR));
+            };
+            let Some(last) = list.0.last() else {
+                return Ok(algorithms);
+            };
+            next = last.alg as u32 + 1;
+            algorithms.extend(list.0);
+            if !more {
+                return Ok(algorithms);
+            }
+        }
+    }
+
+    /// Allocated PCR banks and the PCRs enabled in each
+    pub fn pcr_banks(&mut self) -> Result<TpmlPcrSelection> {
+        match self.get_capability(TPM_CAP_PCRS, 0, 1)? {
+            (_, CapabilityData::Pcrs(banks)) => Ok(banks),
+            _ => Err(Error::new(EFI_DEVICE_ERROR)),
+        }
+    }
+
+    /// Hash algorithms of the PCR banks with at least one PCR enabled
+    pub fn active_pcr_banks(&mut self) -> Result<Vec<u16>> {
+        Ok(self
+            .pcr_banks()?
+            .0
+            .into_iter()
+            .filter(|bank| !bank.is_empty())
+            .map(|bank| bank.hash)
+            .collect())
+    }
+
+    /// TPM properties starting at `first`, at most `count` of them
+    pub fn properties(&mut self, first: u32, count: u32) -> Result<Vec<TpmsTaggedProperty>> {
+        let mut properties: Vec<TpmsTaggedProperty> = Vec::new();
+        let mut next = first;
+        while (properties.len() as u32) < count {
+            let wanted = count - properties.len() as u32;
+            let (more, data) = self.get_capability(TPM_CAP_TPM_PROPERTIES, next, wanted)?;
+            let CapabilityData::TpmProperties(list) = data else {
+                return Err(Error::new(EFI_DEVICE_ERROR));
+            };
+            let Some(last) = list.0.last() else {
+                break;
+            };
+            next = last.property + 1;
+            properties.extend(list.0);
+            if !more {
+                break;
+            }
+        }
+        properties.truncate(count as usize);
+        Ok(properties)
+    }
+
+    /// Value of one TPM property, if the TPM reports it
+    pub fn property(&mut self, property: u32) -> Result<Option<u32>> {
+        Ok(self
+            .properties(property, 1)?
+            .first()
+            .filter(|p| p.property == property)
+            .map(|p| p.value))
+    }
+
+    fn chunk_size(&mut self, property: u32, default: usize) -> usize {
+        match self.property(property) {
+            Ok(Some(size)) if size > 0 => size as usize,
+            _ => default,
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // NV storage
+    // ------------------------------------------------------------------------
+
+    /// TPM2_NV_ReadPublic
+    pub fn nv_read_public(&mut self, nv_index: u32) -> Result<NvReadPublicResponse> {
+        let command = Command::new(TPM_CC_NV_READ_PUBLIC).handle(nv_index);
+        let response = self.execute(&command, 0)?;
+        let mut reader = response.reader();
+        let Tpm2bNvPublic(public) = reader.read()?;
+        let name = reader.read()?;
+        reader.finish()?;
+        Ok(NvReadPublicResponse { public, name })
+    }
+
+    /// TPM2_NV_Read of up to TPM_PT_NV_BUFFER_MAX bytes
+    pub fn nv_read_chunk(
+        &mut self,
+        auth_handle: u32,
+        nv_index: u32,
+        auth: &[u8],
+        offset: u16,
+        size: u16,
+    ) -> Result<Vec<u8>> {
+        let command = Command::new(TPM_CC_NV_READ)
+            .handle(auth_handle)
+            .handle(nv_index)
+            .session(AuthCommand::password(auth))
+            .parameter(&size)
+            .parameter(&offset);
+        let response = self.execute(&command, 0)?;
+        let data: Tpm2bMaxNvBuffer = response.reader().read()?;
+        Ok(data.0)
+    }
+
+    /// Read `size` bytes at `offset` of an NV index
+    ///
+    /// `auth_handle` is the index itself, TPM_RH_OWNER or TPM_RH_PLATFORM,
+    /// depending on the index attributes. Large reads are split to fit the
+    /// TPM's NV buffer.
+    pub fn nv_read(
+        &mut self,
+        auth_handle: u32,
+        nv_index: u32,
+        auth: &[u8],
+        offset: u16,
+        size: u16,
+    ) -> Result<Vec<u8>> {
+        let chunk = self.chunk_size(TPM_PT_NV_BUFFER_MAX, DEFAULT_NV_BUFFER_SIZE);
+        let mut data = Vec::with_capacity(size as usize);
+        while data.len() < size as usize {
+            let len = core::cmp::min(chunk, size as usize - data.len()) as u16;
+            let at = offset
+                .checked_add(data.len() as u16)
+                .ok_or(Error::new(EFI_INVALID_PARAMETER))?;
+            let part = self.nv_read_chunk(auth_handle, nv_index, auth, at, len)?;
+            if part.len() != len as usize {
+                return Err(Error::new(EFI_DEVICE_ERROR));
+            }
+            data.extend_from_slice(&part);
+        }
+        Ok(data)
+    }
+
+    /// Read the whole contents of an NV index using its own authorization
+    pub fn nv_read_all(&mut self, nv_index: u32, auth: &[u8]) -> Result<Vec<u8>> {
+        let size = self.nv_read_public(nv_index)?.public.data_size;
+        self.nv_read(nv_index, nv_index, auth, 0, size)
+    }
+
+    /// TPM2_NV_Write of up to TPM_PT_NV_BUFFER_MAX bytes
+    pub fn nv_write_chunk(
+        &mut self,
+        auth_handle: u32,
+        nv_index: u32,
+        auth: &[u8],
+        offset: u16,
+        data: &[u8],
+    ) -> Result<()> {
+        let command = Command::new(TPM_CC_NV_WRITE)
+            .handle(auth_handle)
+            .handle(nv_index)
+            .session(AuthCommand::password(auth))
+            .parameter(&Tpm2b::new(data))
+            .parameter(&offset);
+        self.execute(&command, 0).map(|_| ())
+    }
+
+    /// Write `data` at `offset` of an NV index, split to fit the NV buffer
+    pub fn nv_write(
+        &mut self,
+        auth_handle: u32,
+        nv_index: u32,
+        auth: &[u8],
+        offset: u16,
+        data: &[u8],
+    ) -> Result<()> {
+        if offset as usize + data.len() > u16::MAX as usize {
+            return Err(Error::new(EFI_INVALID_PARAMETER));
+        }
+        let chunk = self.chunk_size(TPM_PT_NV_BUFFER_MAX, DEFAULT_NV_BUFFER_SIZE);
+        for (i, part) in data.chunks(chunk).enumerate() {
+            let at = offset + (i * chunk) as u16;
+            self.nv_write_chunk(auth_handle, nv_index, auth, at, part)?;
+        }
+        Ok(())
+    }
+
+    // ------------------------------------------------------------------------
+    // Objects
+    // ------------------------------------------------------------------------
+
+    /// TPM2_ReadPublic
+    pub fn read_public(&mut self, object_handle: u32) -> Result<ReadPublicResponse> {
+        let command = Command::new(TPM_CC_READ_PUBLIC).handle(object_handle);
+        let response = self.execute(&command, 0)?;
+        let mut reader = response.reader();
+        let Tpm2b(public_area) = reader.read()?;
+        let result = ReadPublicResponse {
+            public_area,
+            name: reader.read()?,
+            qualified_name: reader.read()?,
+        };
+        reader.finish()?;
+        Ok(result)
+    }
+
+    // ------------------------------------------------------------------------
+    // Hash sequences
+    // ------------------------------------------------------------------------
+
+    /// TPM2_HashSequenceStart, returning the sequence handle
+    pub fn hash_sequence_start(&mut self, hash_alg: u16, auth: &[u8]) -> Result<u32> {
+        let command = Command::new(TPM_CC_HASH_SEQUENCE_START)
+            .parameter(&Tpm2b::new(auth))
+            .parameter(&hash_alg);
+        let response = self.execute(&command, 1)?;
+        Ok(response.handles[0])
+    }
+
+    /// TPM2_SequenceUpdate with at most TPM_PT_INPUT_BUFFER bytes
+    pub fn sequence_update(&mut self, sequence: u32, auth: &[u8], data: &[u8]) -> Result<()> {
+        let command = Command::new(TPM_CC_SEQUENCE_UPDATE)
+            .handle(sequence)
+            .session(AuthCommand::password(auth))
+            .parameter(&Tpm2b::new(data));
+        self.execute(&command, 0).map(|_| ())
+    }
+
+    /// TPM2_SequenceComplete with at most TPM_PT_INPUT_BUFFER bytes
+    pub fn sequence_complete(
+        &mut self,
+        sequence: u32,
+        auth: &[u8],
+        data: &[u8],
+        hierarchy: u32,
+    ) -> Result<HashSequenceResult> {
+        let command = Command::new(TPM_CC_SEQUENCE_COMPLETE)
+            .handle(sequence)
+            .session(AuthCommand::password(auth))
+            .parameter(&Tpm2b::new(data))
+            .parameter(&hierarchy);
+        let response = self.execute(&command, 0)?;
+        let mut reader = response.reader();
+        let Tpm2b(digest) = reader.read()?;
+        let validation = reader.read()?;
+        reader.finish()?;
+        Ok(HashSequenceResult { digest, validation })
+    }
+
+    /// Hash `data` of any length on the TPM with a hash sequence
+    pub fn hash_sequence(&mut self, hash_alg: u16, data: &[u8]) -> Result<HashSequenceResult> {
+        if digest_size(hash_alg).is_none() {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        let chunk = self.chunk_size(TPM_PT_INPUT_BUFFER, DEFAULT_INPUT_BUFFER_SIZE);
+        let sequence = self.hash_sequence_start(hash_alg, &[])?;
+        let split = data.len() - data.len() % chunk;
+        let split = if split == data.len() && split > 0 {
+            split - chunk
+        } else {
+            split
+        };
+        let result = data[..split]
+            .chunks(chunk)
+            .try_for_each(|part| self.sequence_update(sequence, &[], part))
+            .and_then(|()| self.sequence_complete(sequence, &[], &data[split..], TPM_RH_NULL));
+        if result.is_err() {
+            // A failed update or complete leaves the sequence object loaded,
+            // holding one of the TPM's few transient slots
+            let _ = self.flush_context(sequence);
+        }
+        result
+    }
+
+    /// TPM2_FlushContext, unloading a transient object, session or sequence
+    pub fn flush_context(&mut self, handle: u32) -> Result<()> {
+        let command = Command::new(TPM_CC_FLUSH_CONTEXT).parameter(&handle);
+        self.execute(&command, 0).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::hash::HashAlgorithm;
+    use crate::tpm2::mock::{MockTpm, MOCK_KEY_HANDLE, MOCK_NV_AUTH, MOCK_NV_INDEX};
+    use crate::tpm2::*;
+
+    #[test]
+    fn test_command_marshalling() {
+        let command = Command::new(TPM_CC_PCR_EXTEND)
+            .handle(7)
+            .session(AuthCommand::password(&[]))
+            .parameter(&Tpml(vec![TpmtHa {
+                hash_alg: TPM_ALG_SHA1,
+                digest: vec![0xAA; 20],
+            }]));
+        let bytes = command.to_bytes();
+        assert_eq!(bytes[..2], TPM_ST_SESSIONS.to_be_bytes());
+        assert_eq!(bytes[2..6], (bytes.len() as u32).to_be_bytes());
+        assert_eq!(bytes[6..10], TPM_CC_PCR_EXTEND.to_be_bytes());
+        assert_eq!(bytes[10..14], 7u32.to_be_bytes());
+        // authorizationSize, then TPM_RS_PW with empty nonce and password
+        assert_eq!(bytes[14..18], 9u32.to_be_bytes());
+        assert_eq!(bytes[18..22], TPM_RS_PW.to_be_bytes());
+        assert_eq!(bytes[27..31], 1u32.to_be_bytes());
+
+        let plain = Command::new(TPM_CC_GET_RANDOM).parameter(&8u16).to_bytes();
+        assert_eq!(plain, [0x80, 0x01, 0, 0, 0, 12, 0, 0, 1, 0x7B, 0, 8]);
+
+        // Failing response codes surface with the code attached
+        let failed = [0x80, 0x01, 0, 0, 0, 10, 0, 0, 0x01, 0x8B];
+        let err = Response::parse(&failed, 0).unwrap_err();
+        assert_eq!(err.tpm_response_code(), Some(0x18B));
+        assert_eq!(
+            Response::parse(&failed[..8], 0).unwrap_err().status(),
+            EFI_BAD_BUFFER_SIZE
+        );
+    }
+
+    #[test]
+    fn test_pcr_extend_and_read_all_banks() {
+        let mut tpm = Tpm2::new(MockTpm::new());
+        assert_eq!(
+            tpm.active_pcr_banks().unwrap(),
+            [TPM_ALG_SHA1, TPM_ALG_SHA256]
+        );
+
+        let measurement = b"kernel";
+        let digests = Tpml(
+            [HashAlgorithm::Sha1, HashAlgorithm::Sha256]
+                .iter()
+                .map(|&alg| TpmtHa {
+                    hash_alg: hash_alg_id(alg),
+                    digest: alg.digest(measurement).as_bytes().to_vec(),
+                })
+                .collect(),
+        );
+        tpm.pcr_extend(4, &digests).unwrap();
+
+        // Twenty PCRs in two banks need several reads of at most 8 digests
+        let pcrs: Vec<u32> = (0..20).collect();
+        let values = tpm.pcr_read_all_banks(&pcrs).unwrap();
+        assert_eq!(values.len(), 40);
+        let reads = tpm.transport().commands_of(TPM_CC_PCR_READ);
+        assert!(reads >= 5);
+        assert_eq!((values[0].hash_alg, values[0].pcr), (TPM_ALG_SHA1, 0));
+        assert_eq!((values[20].hash_alg, values[20].pcr), (TPM_ALG_SHA256, 0));
+
+        for value in values.iter().filter(|v| v.pcr == 4) {
+            let alg = hash_algorithm(value.hash_alg).unwrap();
+            let mut extended = vec![0u8; alg.digest_size()];
+            extended.extend_from_slice(alg.digest(measurement).as_bytes());
+            assert_eq!(value.digest, alg.digest(&extended).as_bytes());
+        }
+        assert!(values.iter().filter(|v| v.pcr != 4).all(|v| {
+            let size = v.digest.len();
+            v.digest == event_log::startup_pcr_value(v.pcr, 0, size)
+        }));
+
+        // Extending a bank the TPM does not have fails with the TPM's code
+        let bad = Tpml(vec![TpmtHa {
+            hash_alg: TPM_ALG_SHA512,
+            digest: vec![0; 64],
+        }]);
+        let err = tpm.pcr_extend(4, &bad).unwrap_err();
+        assert_eq!(ResponseCode(err.tpm_response_code().unwrap()).base(), 0x083);
+
+        // A TPM that keeps returning PCRs already read must not loop forever
+        tpm.transport().stale_pcr_read = true;
+        assert_eq!(
+            tpm.pcr_read_all_banks(&pcrs).unwrap_err().status(),
+            EFI_DEVICE_ERROR
+        );
+        assert_eq!(tpm.transport().commands_of(TPM_CC_PCR_READ), reads + 2);
+    }
+
+    #[test]
+    fn test_get_random_and_capabilities() {
+        let mut tpm = Tpm2::new(MockTpm::new());
+        let mut random = [0u8; 40];
+        tpm.get_random(&mut random).unwrap();
+        assert!(random.iter().all(|&b| b != 0));
+        assert_eq!(tpm.transport().commands_of(TPM_CC_GET_RANDOM), 3);
+
+        let algorithms: Vec<u16> = tpm.algorithms().unwrap().iter().map(|a| a.alg).collect();
+        assert_eq!(
+            algorithms,
+            [TPM_ALG_RSA, TPM_ALG_SHA1, TPM_ALG_SHA256, TPM_ALG_SHA384]
+        );
+
+        assert_eq!(tpm.property(TPM_PT_PCR_COUNT).unwrap(), Some(24));
+        assert_eq!(tpm.property(TPM_PT_FIXED + 1).unwrap(), None);
+        let fixed = tpm.properties(TPM_PT_FIXED, 100).unwrap();
+        assert_eq!(fixed.len(), 4);
+        assert!(fixed.windows(2).all(|w| w[0].property < w[1].property));
+
+        let banks = tpm.pcr_banks().unwrap();
+        assert_eq!(banks.0.len(), 3);
+        assert!(banks.0[2].is_empty());
+    }
+
+    #[test]
+    fn test_nv_read_write() {
+        let mut tpm = Tpm2::new(MockTpm::new());
+        let public = tpm.nv_read_public(MOCK_NV_INDEX).unwrap();
+        assert_eq!(public.public.data_size, 100);
+        assert!(public.public.attributes & TPMA_NV_AUTHREAD != 0);
+        assert_eq!(public.name.0[..2], TPM_ALG_SHA256.to_be_bytes());
+
+        // Larger than the mock's 32 byte NV buffer
+        let data: Vec<u8> = (0..70).collect();
+        tpm.nv_write(MOCK_NV_INDEX, MOCK_NV_INDEX, MOCK_NV_AUTH, 10, &data)
+            .unwrap();
+        assert_eq!(tpm.transport().commands_of(TPM_CC_NV_WRITE), 3);
+        let all = tpm.nv_read_all(MOCK_NV_INDEX, MOCK_NV_AUTH).unwrap();
+        assert_eq!(all.len(), 100);
+        assert_eq!(all[10..80], data[..]);
+
+        let err = tpm
+            .nv_read(MOCK_NV_INDEX, MOCK_NV_INDEX, b"wrong", 0, 4)
+            .unwrap_err();
+        assert_eq!(err.status(), EFI_SECURITY_VIOLATION);
+        let err = tpm
+            .nv_read(MOCK_NV_INDEX, MOCK_NV_INDEX, MOCK_NV_AUTH, 90, 20)
+            .unwrap_err();
+        assert_eq!(
+            ResponseCode(err.tpm_response_code().unwrap()).name(),
+            Some("TPM_RC_NV_RANGE")
+        );
+    }
+
+    #[test]
+    fn test_read_public_and_hash_sequence() {
+        let mut tpm = Tpm2::new(MockTpm::new());
+        let public = tpm.read_public(MOCK_KEY_HANDLE).unwrap();
+        let header = public.header().unwrap();
+        assert_eq!(header.object_type, TPM_ALG_RSA);
+        assert_eq!(header.name_alg, TPM_ALG_SHA256);
+        assert_eq!(public.name.0.len(), 34);
+        let err = tpm.read_public(0x8100_0002).unwrap_err();
+        assert_eq!(
+            ResponseCode(err.tpm_response_code().unwrap()).handle(),
+            Some(1)
+        );
+
+        // Sizes around the mock's 64 byte input buffer
+        for len in [0usize, 1, 64, 65, 128, 200] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let result = tpm.hash_sequence(TPM_ALG_SHA384, &data).unwrap();
+            assert_eq!(
+                result.digest,
+                HashAlgorithm::Sha384.digest(&data).as_bytes(),
+                "length {}",
+                len
+            );
+            assert_eq!(result.validation.hierarchy, TPM_RH_NULL);
+        }
+        assert_eq!(tpm.transport().open_sequences(), 0);
+
+        // Failed updates and completes flush the sequence
+        for failing in [TPM_CC_SEQUENCE_UPDATE, TPM_CC_SEQUENCE_COMPLETE] {
+            tpm.transport().failing = Some(failing);
+            let flushes = tpm.transport().commands_of(TPM_CC_FLUSH_CONTEXT);
+            let err = tpm.hash_sequence(TPM_ALG_SHA256, &[0x11; 100]).unwrap_err();
+            assert_eq!(err.tpm_response_code(), Some(0x101));
+            assert_eq!(
+                tpm.transport().commands_of(TPM_CC_FLUSH_CONTEXT),
+                flushes + 1
+            );
+            assert_eq!(tpm.transport().open_sequences(), 0);
+        }
+    }
+}
@@ -0,0 +1,765 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! TCG Event Log
+//!
+//! Parses the measured boot log returned by `EFI_TCG2_PROTOCOL.GetEventLog`,
+//! in both the TCG 1.2 (TCG_PCR_EVENT, SHA-1 only) and the crypto agile
+//! (TCG_PCR_EVENT2) format of the TCG PC Client Platform Firmware Profile,
+//! and replays it into the PCR values it should have produced. Unlike TPM
+//! commands, all log fields are little-endian.
+
+use super::commands::PcrValue;
+use super::types::TpmtHa;
+use super::{hash_algorithm, TPM_ALG_SHA1};
+use crate::crypto::hash::HashContext;
+use crate::error::{Error, Result};
+use crate::ffi::*;
+use crate::protocols::tcg2::{EFI_TCG2_EVENT_LOG_FORMAT_TCG_1_2, EFI_TCG2_EVENT_LOG_FORMAT_TCG_2};
+use crate::runtime_services::VariableCodec;
+use crate::string::CString16;
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+// Event types
+pub const EV_PREBOOT_CERT: u32 = 0x0000_0000;
+pub const EV_POST_CODE: u32 = 0x0000_0001;
+pub const EV_NO_ACTION: u32 = 0x0000_0003;
+pub const EV_SEPARATOR: u32 = 0x0000_0004;
+pub const EV_ACTION: u32 = 0x0000_0005;
+pub const EV_EVENT_TAG: u32 = 0x0000_0006;
+pub const EV_S_CRTM_CONTENTS: u32 = 0x0000_0007;
+pub const EV_S_CRTM_VERSION: u32 = 0x0000_0008;
+pub const EV_CPU_MICROCODE: u32 = 0x0000_0009;
+pub const EV_PLATFORM_CONFIG_FLAGS: u32 = 0x0000_000A;
+pub const EV_TABLE_OF_DEVICES: u32 = 0x0000_000B;
+pub const EV_COMPACT_HASH: u32 = 0x0000_000C;
+pub const EV_IPL: u32 = 0x0000_000D;
+pub const EV_IPL_PARTITION_DATA: u32 = 0x0000_000E;
+pub const EV_NONHOST_CODE: u32 = 0x0000_000F;
+pub const EV_NONHOST_CONFIG: u32 = 0x0000_0010;
+pub const EV_NONHOST_INFO: u32 = 0x0000_0011;
+pub const EV_OMIT_BOOT_DEVICE_EVENTS: u32 = 0x0000_0012;
+
+pub const EV_EFI_EVENT_BASE: u32 = 0x8000_0000;
+pub const EV_EFI_VARIABLE_DRIVER_CONFIG: u32 = EV_EFI_EVENT_BASE + 0x1;
+pub const EV_EFI_VARIABLE_BOOT: u32 = EV_EFI_EVENT_BASE + 0x2;
+pub const EV_EFI_BOOT_SERVICES_APPLICATION: u32 = EV_EFI_EVENT_BASE + 0x3;
+pub const EV_EFI_BOOT_SERVICES_DRIVER: u32 = EV_EFI_EVENT_BASE + 0x4;
+pub const EV_EFI_RUNTIME_SERVICES_DRIVER: u32 = EV_EFI_EVENT_BASE + 0x5;
+pub const EV_EFI_GPT_EVENT: u32 = EV_EFI_EVENT_BASE + 0x6;
+pub const EV_EFI_ACTION: u32 = EV_EFI_EVENT_BASE + 0x7;
+pub const EV_EFI_PLATFORM_FIRMWARE_BLOB: u32 = EV_EFI_EVENT_BASE + 0x8;
+pub const EV_EFI_HANDOFF_TABLES: u32 = EV_EFI_EVENT_BASE + 0x9;
+pub const EV_EFI_PLATFORM_FIRMWARE_BLOB2: u32 = EV_EFI_EVENT_BASE + 0xA;
+pub const EV_EFI_HANDOFF_TABLES2: u32 = EV_EFI_EVENT_BASE + 0xB;
+pub const EV_EFI_VARIABLE_BOOT2: u32 = EV_EFI_EVENT_BASE + 0xC;
+pub const EV_EFI_HCRTM_EVENT: u32 = EV_EFI_EVENT_BASE + 0x10;
+pub const EV_EFI_VARIABLE_AUTHORITY: u32 = EV_EFI_EVENT_BASE + 0xE0;
+pub const EV_EFI_SPDM_FIRMWARE_BLOB: u32 = EV_EFI_EVENT_BASE + 0xE1;
+pub const EV_EFI_SPDM_FIRMWARE_CONFIG: u32 = EV_EFI_EVENT_BASE + 0xE2;
+
+/// PCRs of a PC Client TPM
+pub const PCR_COUNT: u32 = 24;
+
+/// PCRs a PC Client TPM sets to all 0xFF at TPM2_Startup
+///
+/// Only a dynamic launch resets them, to zero.
+pub const DRTM_PCRS: core::ops::RangeInclusive<u32> = 17..=22;
+
+/// Signature of the TCG_EfiSpecIDEventStruct that opens a crypto agile log
+pub const SPEC_ID_EVENT_SIGNATURE: &[u8; 16] = b"Spec ID Event03\0";
+
+/// Signature of the EV_NO_ACTION event recording the startup locality
+pub const STARTUP_LOCALITY_SIGNATURE: &[u8; 16] = b"StartupLocality\0";
+
+/// Size of a TCG_PCR_EVENT without its event data
+pub const TCG_PCR_EVENT_HEADER_SIZE: usize = 32;
+
+/// Layout of the event log
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventLogFormat {
+    /// TCG_PCR_EVENT entries with SHA-1 digests
+    Tcg12,
+    /// A Spec ID event followed by TCG_PCR_EVENT2 entries
+    Tcg2,
+}
+
+impl EventLogFormat {
+    /// EFI_TCG2_EVENT_LOG_FORMAT value
+    pub fn bits(self) -> u32 {
+        match self {
+            EventLogFormat::Tcg12 => EFI_TCG2_EVENT_LOG_FORMAT_TCG_1_2,
+            EventLogFormat::Tcg2 => EFI_TCG2_EVENT_LOG_FORMAT_TCG_2,
+        }
+    }
+}
+
+/// Little-endian cursor over log bytes
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        self.bytes(N).map(|bytes| bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.array().map(u8::from_le_bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+}
+
+/// TCG_EfiSpecIdEventAlgorithmSize
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AlgorithmSize {
+    pub algorithm_id: u16,
+    pub digest_size: u16,
+}
+
+/// TCG_EfiSpecIDEventStruct, the event data of the first crypto agile entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecIdEvent {
+    pub platform_class: u32,
+    pub spec_version_minor: u8,
+    pub spec_version_major: u8,
+    pub spec_errata: u8,
+    pub uintn_size: u8,
+    /// Banks recorded in every TCG_PCR_EVENT2, with their digest sizes
+    pub digest_sizes: Vec<AlgorithmSize>,
+    pub vendor_info: Vec<u8>,
+}
+
+impl SpecIdEvent {
+    /// Parse the event data of a Spec ID event
+    ///
+    /// Fails with `EFI_UNSUPPORTED` if the signature does not match and
+    /// with `EFI_BAD_BUFFER_SIZE` if the data is truncated.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut cursor = Cursor { data };
+        if cursor.bytes(16)? != SPEC_ID_EVENT_SIGNATURE {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        let platform_class = cursor.u32()?;
+        let spec_version_minor = cursor.u8()?;
+        let spec_version_major = cursor.u8()?;
+        let spec_errata = cursor.u8()?;
+        let uintn_size = cursor.u8()?;
+        let count = cursor.u32()?;
+        let mut digest_sizes = Vec::new();
+        for _ in 0..count {
+            digest_sizes.push(AlgorithmSize {
+                algorithm_id: cursor.u16()?,
+                digest_size: cursor.u16()?,
+            });
+        }
+        let vendor_info_size = cursor.u8()?;
+        Ok(SpecIdEvent {
+            platform_class,
+            spec_version_minor,
+            spec_version_major,
+            spec_errata,
+            uintn_size,
+            digest_sizes,
+            vendor_info: cursor.bytes(vendor_info_size as usize)?.to_vec(),
+        })
+    }
+
+    /// Digest size recorded for `hash_alg`
+    pub fn digest_size(&self, hash_alg: u16) -> Option<usize> {
+        self.digest_sizes
+            .iter()
+            .find(|size| size.algorithm_id == hash_alg)
+            .map(|size| size.digest_size as usize)
+    }
+}
+
+/// UEFI_VARIABLE_DATA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableData<'a> {
+    pub vendor: Guid,
+    pub name: CString16,
+    pub data: &'a [u8],
+}
+
+/// UEFI_IMAGE_LOAD_EVENT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageLoadEvent<'a> {
+    pub image_location_in_memory: u64,
+    pub image_length_in_memory: u64,
+    pub image_link_time_address: u64,
+    /// Device path of the image, as raw device path nodes
+    pub device_path: &'a [u8],
+}
+
+/// UEFI_PLATFORM_FIRMWARE_BLOB and UEFI_PLATFORM_FIRMWARE_BLOB2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareBlob<'a> {
+    /// Empty for EV_EFI_PLATFORM_FIRMWARE_BLOB
+    pub description: &'a [u8],
+    pub base: u64,
+    pub length: u64,
+}
+
+/// Typed event data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventData<'a> {
+    SpecId(SpecIdEvent),
+    /// Locality the TPM was started from, which sets the initial PCR 0
+    StartupLocality(u8),
+    /// Any other EV_NO_ACTION event
+    NoAction(&'a [u8]),
+    Separator(u32),
+    /// EV_ACTION and EV_EFI_ACTION text
+    Action(&'a str),
+    /// EV_EFI_VARIABLE_DRIVER_CONFIG, EV_EFI_VARIABLE_BOOT(2) and
+    /// EV_EFI_VARIABLE_AUTHORITY
+    Variable(VariableData<'a>),
+    /// EV_EFI_BOOT_SERVICES_APPLICATION, EV_EFI_BOOT_SERVICES_DRIVER and
+    /// EV_EFI_RUNTIME_SERVICES_DRIVER
+    ImageLoad(ImageLoadEvent<'a>),
+    FirmwareBlob(FirmwareBlob<'a>),
+    /// Event types without a typed decoding
+    Raw(&'a [u8]),
+}
+
+/// One log entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcrEvent<'a> {
+    pub pcr_index: u32,
+    pub event_type: u32,
+    pub digests: Vec<TpmtHa>,
+    pub data: &'a [u8],
+}
+
+impl<'a> PcrEvent<'a> {
+    /// Digest recorded for the bank `hash_alg`
+    pub fn digest(&self, hash_alg: u16) -> Option<&[u8]> {
+        self.digests
+            .iter()
+            .find(|digest| digest.hash_alg == hash_alg)
+            .map(|digest| digest.digest.as_slice())
+    }
+
+    /// Decode the event data according to the event type
+    ///
+    /// Fails with `EFI_BAD_BUFFER_SIZE` if the data of a typed event is
+    /// malformed.
+    pub fn decode(&self) -> Result<EventData<'a>> {
+        let bad = || Error::new(EFI_BAD_BUFFER_SIZE);
+        let data = self.data;
+        let mut cursor = Cursor { data };
+        match self.event_type {
+            EV_NO_ACTION if data.starts_with(SPEC_ID_EVENT_SIGNATURE) => {
+                SpecIdEvent::parse(data).map(EventData::SpecId)
+            }
+            EV_NO_ACTION if data.starts_with(STARTUP_LOCALITY_SIGNATURE) => {
+                cursor.bytes(16)?;
+                cursor.u8().map(EventData::StartupLocality)
+            }
+            EV_NO_ACTION => Ok(EventData::NoAction(data)),
+            EV_SEPARATOR if data.len() == 4 => cursor.u32().map(EventData::Separator),
+            EV_ACTION | EV_EFI_ACTION => core::str::from_utf8(data)
+                .map(EventData::Action)
+                .map_err(|_| bad()),
+            EV_EFI_VARIABLE_DRIVER_CONFIG
+            | EV_EFI_VARIABLE_BOOT
+            | EV_EFI_VARIABLE_BOOT2
+            | EV_EFI_VARIABLE_AUTHORITY => {
+                let vendor = Guid::decode(cursor.bytes(16)?)?;
+                let name_length = usize::try_from(cursor.u64()?).map_err(|_| bad())?;
+                let data_length = usize::try_from(cursor.u64()?).map_err(|_| bad())?;
+                let name_bytes = cursor.bytes(name_length.checked_mul(2).ok_or_else(bad)?)?;
+                let mut name = Vec::<Char16>::decode(name_bytes)?;
+                name.push(0);
+                Ok(EventData::Variable(VariableData {
+                    vendor,
+                    name: CString16::try_from(name).map_err(|_| bad())?,
+                    data: cursor.bytes(data_length)?,
+                }))
+            }
+            EV_EFI_BOOT_SERVICES_APPLICATION
+            | EV_EFI_BOOT_SERVICES_DRIVER
+            | EV_EFI_RUNTIME_SERVICES_DRIVER => {
+                let image_location_in_memory = cursor.u64()?;
+                let image_length_in_memory = cursor.u64()?;
+                let image_link_time_address = cursor.u64()?;
+                let path_length = usize::try_from(cursor.u64()?).map_err(|_| bad())?;
+                Ok(EventData::ImageLoad(ImageLoadEvent {
+                    image_location_in_memory,
+                    image_length_in_memory,
+                    image_link_time_address,
+                    device_path: cursor.bytes(path_length)?,
+                }))
+            }
+            EV_EFI_PLATFORM_FIRMWARE_BLOB | EV_EFI_PLATFORM_FIRMWARE_BLOB2 => {
+                let description = if self.event_type == EV_EFI_PLATFORM_FIRMWARE_BLOB2 {
+                    let size = cursor.u8()?;
+                    cursor.bytes(size as usize)?
+                } else {
+                    &[]
+                };
+                Ok(EventData::FirmwareBlob(FirmwareBlob {
+                    description,
+                    base: cursor.u64()?,
+                    length: cursor.u64()?,
+                }))
+            }
+            _ => Ok(EventData::Raw(data)),
+        }
+    }
+}
+
+fn read_u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Length of the entry at the start of `data`
+///
+/// If `data` is too short to tell, returns how many bytes are needed to get
+/// further, which is then larger than `data.len()`. Call again with at least
+/// that many bytes until the result fits. `spec_id` gives the digest sizes
+/// of TCG_PCR_EVENT2 entries; without it the entry is a TCG_PCR_EVENT.
+/// Fails with `EFI_UNSUPPORTED` for a digest of a bank the log does not
+/// declare.
+pub fn entry_len(data: &[u8], spec_id: Option<&SpecIdEvent>) -> Result<usize> {
+    let Some(spec_id) = spec_id else {
+        if data.len() < TCG_PCR_EVENT_HEADER_SIZE {
+            return Ok(TCG_PCR_EVENT_HEADER_SIZE);
+        }
+        let event_size = read_u32_at(data, TCG_PCR_EVENT_HEADER_SIZE - 4) as usize;
+        return Ok(TCG_PCR_EVENT_HEADER_SIZE.saturating_add(event_size));
+    };
+
+    // pcrIndex, eventType and the digest count
+    let mut offset = 12;
+    if data.len() < offset {
+        return Ok(offset);
+    }
+    for _ in 0..read_u32_at(data, 8) {
+        if data.len() < offset + 2 {
+            return Ok(offset + 2);
+        }
+        let hash_alg = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let size = spec_id
+            .digest_size(hash_alg)
+            .ok_or(Error::new(EFI_UNSUPPORTED))?;
+        offset += 2 + size;
+    }
+    if data.len() < offset + 4 {
+        return Ok(offset + 4);
+    }
+    let event_size = read_u32_at(data, offset) as usize;
+    Ok((offset + 4).saturating_add(event_size))
+}
+
+/// Parse one complete entry, as measured by [`entry_len`]
+fn parse_entry<'a>(entry: &'a [u8], spec_id: Option<&SpecIdEvent>) -> Result<PcrEvent<'a>> {
+    let mut cursor = Cursor { data: entry };
+    let pcr_index = cursor.u32()?;
+    let event_type = cursor.u32()?;
+    let digests = match spec_id {
+        None => vec![TpmtHa {
+            hash_alg: TPM_ALG_SHA1,
+            digest: cursor.bytes(20)?.to_vec(),
+        }],
+        Some(spec_id) => {
+            let count = cursor.u32()?;
+            let mut digests = Vec::new();
+            for _ in 0..count {
+                let hash_alg = cursor.u16()?;
+                let size = spec_id
+                    .digest_size(hash_alg)
+                    .ok_or(Error::new(EFI_UNSUPPORTED))?;
+                digests.push(TpmtHa {
+                    hash_alg,
+                    digest: cursor.bytes(size)?.to_vec(),
+                });
+            }
+            digests
+        }
+    };
+    let event_size = cursor.u32()?;
+    Ok(PcrEvent {
+        pcr_index,
+        event_type,
+        digests,
+        data: cursor.bytes(event_size as usize)?,
+    })
+}
+
+/// A parsed measured boot log
+#[derive(Debug, Clone)]
+pub struct EventLog<'a> {
+    format: EventLogFormat,
+    spec_id: Option<SpecIdEvent>,
+    /// Entries after the Spec ID event
+    entries: &'a [u8],
+}
+
+impl<'a> EventLog<'a> {
+    /// Parse the header of a log in `format`
+    ///
+    /// For [`EventLogFormat::Tcg2`] the log must open with a Spec ID event;
+    /// if it does not, this fails with `EFI_UNSUPPORTED`. Entries are only
+    /// parsed by [`EventLog::events`].
+    pub fn parse(data: &'a [u8], format: EventLogFormat) -> Result<Self> {
+        if format == EventLogFormat::Tcg12 {
+            return Ok(EventLog {
+                format,
+                spec_id: None,
+                entries: data,
+            });
+        }
+        let len = entry_len(data, None)?;
+        let header = data.get(..len).ok_or(Error::new(EFI_BAD_BUFFER_SIZE))?;
+        let header = parse_entry(header, None)?;
+        if header.pcr_index != 0 || header.event_type != EV_NO_ACTION {
+            return Err(Error::new(EFI_UNSUPPORTED));
+        }
+        Ok(EventLog {
+            format,
+            spec_id: Some(SpecIdEvent::parse(header.data)?),
+            entries: &data[len..],
+        })
+    }
+
+    pub fn format(&self) -> EventLogFormat {
+        self.format
+    }
+
+    /// The Spec ID event of a crypto agile log
+    pub fn spec_id(&self) -> Option<&SpecIdEvent> {
+        self.spec_id.as_ref()
+    }
+
+    /// Banks every entry carries a digest for
+    pub fn algorithms(&self) -> Vec<u16> {
+        match &self.spec_id {
+            Some(spec_id) => spec_id
+                .digest_sizes
+                .iter()
+                .map(|s| s.algorithm_id)
+                .collect(),
+            None => vec![TPM_ALG_SHA1],
+        }
+    }
+
+    /// Iterate over the entries, without the Spec ID event
+    ///
+    /// A malformed entry yields an error and ends the iteration.
+    pub fn events(&self) -> Events<'_, 'a> {
+        Events {
+            spec_id: self.spec_id.as_ref(),
+            rest: self.entries,
+        }
+    }
+
+    /// Recompute PCRs 0 to 23 of the bank `hash_alg` from the log
+    ///
+    /// PCRs start from their [`startup_pcr_value`]. EV_NO_ACTION events are
+    /// not extended, except that a StartupLocality event sets the initial
+    /// value of PCR 0. Fails with `EFI_UNSUPPORTED`
+    /// if the log has no such bank or the crate cannot compute its hash,
+    /// and with `EFI_NOT_FOUND` if an entry lacks a digest for it.
+    pub fn replay(&self, hash_alg: u16) -> Result<Vec<Vec<u8>>> {
+        let algorithm = hash_algorithm(hash_alg)
+            .filter(|_| self.algorithms().contains(&hash_alg))
+            .ok_or(Error::new(EFI_UNSUPPORTED))?;
+        let size = algorithm.digest_size();
+        let mut pcrs: Vec<Vec<u8>> = (0..PCR_COUNT)
+            .map(|pcr| startup_pcr_value(pcr, 0, size))
+            .collect();
+        for event in self.events() {
+            let event = event?;
+            if event.event_type == EV_NO_ACTION {
+                if let Ok(EventData::StartupLocality(locality)) = event.decode() {
+                    pcrs[0] = startup_pcr_value(0, locality, size);
+                }
+                continue;
+            }
+            let pcr = pcrs
+                .get_mut(event.pcr_index as usize)
+                .ok_or(Error::new(EFI_UNSUPPORTED))?;
+            let digest = event.digest(hash_alg).ok_or(Error::new(EFI_NOT_FOUND))?;
+            let mut context = HashContext::new(algorithm);
+            context.update(pcr);
+            context.update(digest);
+            *pcr = context.finalize().as_bytes().to_vec();
+        }
+        Ok(pcrs)
+    }
+
+    /// Replay every bank the crate can hash, in the order of
+    /// [`EventLog::algorithms`]
+    ///
+    /// The result is laid out like [`super::Tpm2::pcr_read_all_banks`] for
+    /// PCRs 0 to 23, so the two can be compared directly.
+    pub fn replay_all(&self) -> Result<Vec<PcrValue>> {
+        let mut values = Vec::new();
+        for hash_alg in self.algorithms() {
+            if hash_algorithm(hash_alg).is_none() {
+                continue;
+            }
+            for (pcr, digest) in self.replay(hash_alg)?.into_iter().enumerate() {
+                values.push(PcrValue {
+                    pcr: pcr as u32,
+                    hash_alg,
+                    digest,
+                });
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Value of `pcr` right after TPM2_Startup from `locality`
+///
+/// PCR 0 ends in the startup locality byte, [`DRTM_PCRS`] are all 0xFF and
+/// the others are zero.
+pub fn startup_pcr_value(pcr: u32, locality: u8, digest_size: usize) -> Vec<u8> {
+    let mut value = vec![0u8; digest_size];
+    if DRTM_PCRS.contains(&pcr) {
+        value.fill(0xFF);
+    } else if pcr == 0 {
+        if let Some(last) = value.last_mut() {
+            *last = locality;
+        }
+    }
+    value
+}
+
+/// Iterator over the entries of an [`EventLog`]
+pub struct Events<'l, 'a> {
+    spec_id: Option<&'l SpecIdEvent>,
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Events<'_, 'a> {
+    type Item = Result<PcrEvent<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let entry = entry_len(self.rest, self.spec_id).and_then(|len| {
+            let entry = self
+                .rest
+                .get(..len)
+                .ok_or(Error::new(EFI_BAD_BUFFER_SIZE))?;
+            Ok((len, parse_entry(entry, self.spec_id)?))
+        });
+        match entry {
+            Ok((len, event)) => {
+                self.rest = &self.rest[len..];
+                Some(Ok(event))
+            }
+            Err(err) => {
+                self.rest = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::hash::HashAlgorithm;
+    use crate::runtime_services::EFI_GLOBAL_VARIABLE_GUID;
+    use crate::tpm2::mock::{log_event, log_header, MockTpm};
+    use crate::tpm2::{hash_alg_id, Tpm2, Tpml, TPM_ALG_SHA256, TPM_ALG_SHA384};
+
+    const BANKS: [u16; 2] = [TPM_ALG_SHA1, TPM_ALG_SHA256];
+
+    fn variable_event(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut event = Vec::new();
+        EFI_GLOBAL_VARIABLE_GUID.encode(&mut event);
+        event.extend_from_slice(&(name.len() as u64).to_le_bytes());
+        event.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        for c in name.encode_utf16() {
+            event.extend_from_slice(&c.to_le_bytes());
+        }
+        event.extend_from_slice(data);
+        event
+    }
+
+    fn image_event(device_path: &[u8]) -> Vec<u8> {
+        let mut event = Vec::new();
+        for value in [0x7000_0000u64, 0x2_0000, 0, device_path.len() as u64] {
+            event.extend_from_slice(&value.to_le_bytes());
+        }
+        event.extend_from_slice(device_path);
+        event
+    }
+
+    /// Crypto agile log measuring SecureBoot, a separator and an application
+    fn sample_log() -> Vec<u8> {
+        let mut locality = STARTUP_LOCALITY_SIGNATURE.to_vec();
+        locality.push(3);
+        let mut log = log_header(&BANKS);
+        log.extend(log_event(0, EV_NO_ACTION, &locality, &BANKS));
+        log.extend(log_event(0, EV_S_CRTM_VERSION, &[0, 0], &BANKS));
+        log.extend(log_event(
+            7,
+            EV_EFI_VARIABLE_DRIVER_CONFIG,
+            &variable_event("SecureBoot", &[1]),
+            &BANKS,
+        ));
+        log.extend(log_event(7, EV_SEPARATOR, &[0; 4], &BANKS));
+        log.extend(log_event(
+            4,
+            EV_EFI_BOOT_SERVICES_APPLICATION,
+            &image_event(&[0x7F, 0xFF, 4, 0]),
+            &BANKS,
+        ));
+        log.extend(log_event(
+            5,
+            EV_EFI_ACTION,
+            b"Exit Boot Services Invocation",
+            &BANKS,
+        ));
+        log
+    }
+
+    #[test]
+    fn test_crypto_agile_log_decoding() {
+        let data = sample_log();
+        let log = EventLog::parse(&data, EventLogFormat::Tcg2).unwrap();
+        let spec_id = log.spec_id().unwrap();
+        assert_eq!(spec_id.spec_version_major, 2);
+        assert_eq!(spec_id.uintn_size, 2);
+        assert_eq!(log.algorithms(), BANKS);
+
+        let events: Vec<PcrEvent> = log.events().collect::<Result<_>>().unwrap();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[0].decode().unwrap(), EventData::StartupLocality(3));
+        assert_eq!(events[3].decode().unwrap(), EventData::Separator(0));
+        assert_eq!(
+            events[5].decode().unwrap(),
+            EventData::Action("Exit Boot Services Invocation")
+        );
+        match events[2].decode().unwrap() {
+            EventData::Variable(variable) => {
+                assert_eq!(variable.vendor, EFI_GLOBAL_VARIABLE_GUID);
+                assert_eq!(variable.name, "SecureBoot");
+                assert_eq!(variable.data, [1]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match events[4].decode().unwrap() {
+            EventData::ImageLoad(image) => {
+                assert_eq!(image.image_location_in_memory, 0x7000_0000);
+                assert_eq!(image.device_path, [0x7F, 0xFF, 4, 0]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let sha256 = HashAlgorithm::Sha256.digest(b"Exit Boot Services Invocation");
+        assert_eq!(events[5].digest(TPM_ALG_SHA256), Some(sha256.as_bytes()));
+        assert_eq!(events[5].digest(TPM_ALG_SHA384), None);
+
+        // Every prefix that cuts an entry short is reported, not skipped
+        let cut = data.len() - 3;
+        let log = EventLog::parse(&data[..cut], EventLogFormat::Tcg2).unwrap();
+        let results: Vec<_> = log.events().collect();
+        assert_eq!(results.len(), 6);
+        assert_eq!(
+            results[5].as_ref().unwrap_err().status(),
+            EFI_BAD_BUFFER_SIZE
+        );
+        // A TCG 1.2 log is not a crypto agile one
+        let legacy = log_event(0, EV_SEPARATOR, &[0; 4], &[]);
+        assert_eq!(
+            EventLog::parse(&legacy, EventLogFormat::Tcg2)
+                .unwrap_err()
+                .status(),
+            EFI_UNSUPPORTED
+        );
+    }
+
+    #[test]
+    fn test_replay_matches_tpm() {
+        let data = sample_log();
+        let log = EventLog::parse(&data, EventLogFormat::Tcg2).unwrap();
+
+        // Measure the same events into a TPM started from locality 3
+        let mut tpm = Tpm2::new(MockTpm::with_startup_locality(3));
+        for event in log.events() {
+            let event = event.unwrap();
+            if event.event_type != EV_NO_ACTION {
+                tpm.pcr_extend(event.pcr_index, &Tpml(event.digests.clone()))
+                    .unwrap();
+            }
+        }
+        let pcrs: Vec<u32> = (0..PCR_COUNT).collect();
+        let mut actual = tpm.pcr_read_all_banks(&pcrs).unwrap();
+        let expected = log.replay_all().unwrap();
+        assert_eq!(expected.len(), 48);
+        assert_eq!(expected, actual);
+
+        // PCR 0 started at ...03 and was extended with the CRTM version
+        let sha1 = HashAlgorithm::Sha1;
+        let mut start = vec![0u8; 20];
+        start[19] = 3;
+        start.extend_from_slice(sha1.digest(&[0, 0]).as_bytes());
+        assert_eq!(expected[0].digest, sha1.digest(&start).as_bytes());
+        // PCR 17 is a DRTM PCR, left at its all 0xFF startup value
+        assert_eq!(
+            (expected[17].pcr, &expected[17].digest),
+            (17, &vec![0xFF; 20])
+        );
+        assert!(actual[17].digest.iter().all(|&b| b == 0xFF));
+
+        actual.retain(|value| value.pcr == 7 && value.hash_alg == TPM_ALG_SHA256);
+        assert_eq!(log.replay(TPM_ALG_SHA256).unwrap()[7], actual[0].digest);
+        assert_eq!(
+            log.replay(TPM_ALG_SHA384).unwrap_err().status(),
+            EFI_UNSUPPORTED
+        );
+    }
+
+    #[test]
+    fn test_tcg12_log() {
+        let mut data = log_event(0, EV_S_CRTM_VERSION, &[0, 0], &[]);
+        data.extend(log_event(7, EV_SEPARATOR, &[0; 4], &[]));
+        let log = EventLog::parse(&data, EventLogFormat::Tcg12).unwrap();
+        assert!(log.spec_id().is_none());
+        assert_eq!(log.algorithms(), [TPM_ALG_SHA1]);
+        assert_eq!(entry_len(&data[..8], None).unwrap(), 32);
+        assert_eq!(entry_len(&data, None).unwrap(), 34);
+
+        let events: Vec<PcrEvent> = log.events().collect::<Result<_>>().unwrap();
+        assert_eq!(events[1].pcr_index, 7);
+        let sha1 = HashAlgorithm::Sha1;
+        assert_eq!(
+            events[1].digest(hash_alg_id(sha1)),
+            Some(sha1.digest(&[0; 4]).as_bytes())
+        );
+
+        let replayed = log.replay(TPM_ALG_SHA1).unwrap();
+        let mut extend = vec![0u8; 20];
+        extend.extend_from_slice(sha1.digest(&[0; 4]).as_bytes());
+        assert_eq!(replayed[7], sha1.digest(&extend).as_bytes());
+        assert!(replayed[1].iter().all(|&b| b == 0));
+    }
+}
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! TPM 2.0 Wire Format
+//!
+//! All integers are big-endian. Sized buffers (TPM2B) carry a `u16` length
+//! and lists (TPML) a `u32` count.
+
+use crate::error::{Error, Result};
+use crate::ffi::*;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Serialize into a command buffer
+pub trait Marshal {
+    fn marshal(&self, out: &mut Vec<u8>);
+}
+
+/// Deserialize from a response buffer
+pub trait Unmarshal: Sized {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self>;
+}
+
+/// Cursor over response bytes
+///
+/// Reads past the end fail with `EFI_BAD_BUFFER_SIZE`.
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    /// Bytes not read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Check whether everything has been read
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Take the next `len` bytes
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    /// Unmarshal the next value
+    pub fn read<T: Unmarshal>(&mut self) -> Result<T> {
+        T::unmarshal(self)
+    }
+
+    /// Fail with `EFI_BAD_BUFFER_SIZE` if bytes are left over
+    pub fn finish(&self) -> Result<()> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::new(EFI_BAD_BUFFER_SIZE))
+        }
+    }
+}
+
+macro_rules! impl_marshal_int {
+    ($($ty:ty),*) => {$(
+        impl Marshal for $ty {
+            fn marshal(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+        }
+
+        impl Unmarshal for $ty {
+            fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+                let bytes = reader.bytes(core::mem::size_of::<$ty>())?;
+                Ok(<$ty>::from_be_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+impl_marshal_int!(u8, u16, u32, u64);
+
+impl Marshal for bool {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl Unmarshal for bool {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(reader.read::<u8>()? != 0)
+    }
+}
+
+/// Marshal `value` preceded by its `u16` size, as for TPM2B_PUBLIC
+pub fn marshal_sized<T: Marshal>(value: &T, out: &mut Vec<u8>) {
+    let start = out.len();
+    out.extend_from_slice(&[0, 0]);
+    value.marshal(out);
+    let size = (out.len() - start - 2) as u16;
+    out[start..start + 2].copy_from_slice(&size.to_be_bytes());
+}
+
+/// Unmarshal a value preceded by its `u16` size, which it must fill exactly
+pub fn unmarshal_sized<T: Unmarshal>(reader: &mut Reader<'_>) -> Result<T> {
+    let size = reader.read::<u16>()?;
+    let mut inner = Reader::new(reader.bytes(size as usize)?);
+    let value = inner.read()?;
+    inner.finish()?;
+    Ok(value)
+}
@@ -0,0 +1,521 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! In-memory TPM for unit tests
+//!
+//! Implements just enough of TPM2_PCR_Extend, PCR_Read, GetRandom,
+//! GetCapability, NV_ReadPublic, NV_Read, NV_Write, ReadPublic, FlushContext
+//! and the hash sequence commands to exercise [`super::Tpm2`]. Buffers are kept small so
+//! the chunking paths run. Also builds event log entries.
+
+use super::event_log::{startup_pcr_value, EV_NO_ACTION, SPEC_ID_EVENT_SIGNATURE};
+use super::marshal::{Marshal, Reader};
+use super::types::*;
+use super::*;
+use crate::crypto::hash::HashContext;
+use crate::protocols::security::tpm2_commands::*;
+use std::vec::Vec;
+
+/// NV index defined by the mock, 100 bytes with AUTHREAD | AUTHWRITE
+pub(crate) const MOCK_NV_INDEX: u32 = 0x0150_0000;
+/// Password of [`MOCK_NV_INDEX`]
+pub(crate) const MOCK_NV_AUTH: &[u8] = b"nvpass";
+/// Persistent RSA key handle
+pub(crate) const MOCK_KEY_HANDLE: u32 = 0x8100_0001;
+
+const PCR_COUNT: u32 = 24;
+const NV_SIZE: u16 = 100;
+const NV_BUFFER_MAX: usize = 32;
+const INPUT_BUFFER: usize = 64;
+const MAX_RANDOM: usize = 16;
+const MAX_PCR_DIGESTS: usize = 8;
+const SEQUENCE_HANDLE: u32 = 0x8000_0000;
+
+const ALGORITHMS: [u16; 4] = [TPM_ALG_RSA, TPM_ALG_SHA1, TPM_ALG_SHA256, TPM_ALG_SHA384];
+const PROPERTIES: [(u32, u32); 4] = [
+    (TPM_PT_MANUFACTURER, 0x4D4F_434B),
+    (TPM_PT_INPUT_BUFFER, INPUT_BUFFER as u32),
+    (TPM_PT_PCR_COUNT, PCR_COUNT),
+    (TPM_PT_NV_BUFFER_MAX, NV_BUFFER_MAX as u32),
+];
+
+// Response codes returned by the mock
+const RC_FAILURE: u32 = 0x101;
+const RC_COMMAND_CODE: u32 = 0x143;
+const RC_NV_RANGE: u32 = 0x146;
+const RC_HASH_PARAMETER_1: u32 = 0x1C3;
+const RC_HASH_PARAMETER_2: u32 = 0x2C3;
+const RC_VALUE_PARAMETER_1: u32 = 0x1C4;
+const RC_SIZE_PARAMETER_1: u32 = 0x1D5;
+const RC_HANDLE_1: u32 = 0x18B;
+const RC_HANDLE_2: u32 = 0x28B;
+const RC_AUTH_FAIL_SESSION_1: u32 = 0x98E;
+
+/// Handles in the handle area of each command
+fn handle_count(code: u32) -> usize {
+    match code {
+        TPM_CC_NV_READ | TPM_CC_NV_WRITE => 2,
+        TPM_CC_PCR_EXTEND
+        | TPM_CC_SEQUENCE_UPDATE
+        | TPM_CC_SEQUENCE_COMPLETE
+        | TPM_CC_NV_READ_PUBLIC
+        | TPM_CC_READ_PUBLIC => 1,
+        _ => 0,
+    }
+}
+
+struct Request<'a> {
+    handles: Vec<u32>,
+    sessions: Vec<AuthCommand>,
+    parameters: Reader<'a>,
+}
+
+/// Handles and parameters of a successful response
+#[derive(Default)]
+struct Reply {
+    handles: Vec<u32>,
+    parameters: Vec<u8>,
+}
+
+type Outcome = core::result::Result<Reply, u32>;
+
+pub(crate) struct MockTpm {
+    /// Command codes in submission order
+    commands: Vec<u32>,
+    /// Active banks with their PCR values
+    banks: Vec<(u16, Vec<Vec<u8>>)>,
+    pcr_update_counter: u32,
+    nv_data: Vec<u8>,
+    random_state: u8,
+    sequences: Vec<(u32, HashContext)>,
+    next_sequence: u32,
+    /// Command code answered with TPM_RC_FAILURE
+    pub(crate) failing: Option<u32>,
+    /// Answer every PCR_Read with PCR 0 of each bank, whatever was selected
+    pub(crate) stale_pcr_read: bool,
+}
+
+impl MockTpm {
+    pub(crate) fn new() -> Self {
+        Self::with_startup_locality(0)
+    }
+
+    /// TPM whose PCRs hold their values after TPM2_Startup from `locality`
+    pub(crate) fn with_startup_locality(locality: u8) -> Self {
+        let bank = |alg: u16| {
+            let size = digest_size(alg).unwrap();
+            let pcrs = (0..PCR_COUNT)
+                .map(|pcr| startup_pcr_value(pcr, locality, size))
+                .collect();
+            (alg, pcrs)
+        };
+        MockTpm {
+            commands: Vec::new(),
+            banks: vec![bank(TPM_ALG_SHA1), bank(TPM_ALG_SHA256)],
+            pcr_update_counter: 0,
+            nv_data: vec![0xFF; NV_SIZE as usize],
+            random_state: 0,
+            sequences: Vec::new(),
+            next_sequence: SEQUENCE_HANDLE,
+            failing: None,
+            stale_pcr_read: false,
+        }
+    }
+
+    /// Number of submitted commands with command code `code`
+    pub(crate) fn commands_of(&self, code: u32) -> usize {
+        self.commands.iter().filter(|&&cc| cc == code).count()
+    }
+
+    /// Number of hash sequences still loaded
+    pub(crate) fn open_sequences(&self) -> usize {
+        self.sequences.len()
+    }
+
+    fn nv_public() -> TpmsNvPublic {
+        TpmsNvPublic {
+            nv_index: MOCK_NV_INDEX,
+            name_alg: TPM_ALG_SHA256,
+            attributes: TPMA_NV_AUTHREAD | TPMA_NV_AUTHWRITE | TPMA_NV_WRITTEN,
+            auth_policy: Tpm2b::default(),
+            data_size: NV_SIZE,
+        }
+    }
+
+    /// TPMT_PUBLIC of a 2048-bit RSA storage key
+    fn key_public() -> Vec<u8> {
+        let mut out = Vec::new();
+        TPM_ALG_RSA.marshal(&mut out);
+        TPM_ALG_SHA256.marshal(&mut out);
+        0x0003_0072u32.marshal(&mut out);
+        Tpm2b::default().marshal(&mut out);
+        TPM_ALG_NULL.marshal(&mut out);
+        TPM_ALG_NULL.marshal(&mut out);
+        2048u16.marshal(&mut out);
+        0u32.marshal(&mut out);
+        Tpm2b::new(&[0xA5; 256]).marshal(&mut out);
+        out
+    }
+
+    /// SHA-256 name of a marshalled public area
+    fn name(public: &[u8]) -> Tpm2b {
+        let mut name = TPM_ALG_SHA256.to_be_bytes().to_vec();
+        name.extend_from_slice(HashAlgorithm::Sha256.digest(public).as_bytes());
+        Tpm2b(name)
+    }
+
+    fn check_nv_auth(handles: &[u32], sessions: &[AuthCommand]) -> core::result::Result<(), u32> {
+        if handles[1] != MOCK_NV_INDEX {
+            return Err(RC_HANDLE_2);
+        }
+        match sessions.first() {
+            Some(session) if session.hmac.as_bytes() == MOCK_NV_AUTH => Ok(()),
+            _ => Err(RC_AUTH_FAIL_SESSION_1),
+        }
+    }
+
+    fn dispatch(&mut self, code: u32, request: Request<'_>) -> Outcome {
+        let Request {
+            handles,
+            sessions,
+            mut parameters,
+        } = request;
+        let parameters = &mut parameters;
+        let mut reply = Reply::default();
+        let out = &mut reply.parameters;
+        if self.failing == Some(code) {
+            return Err(RC_FAILURE);
+        }
+        match code {
+            TPM_CC_PCR_EXTEND => {
+                let pcr = handles[0];
+                if pcr >= PCR_COUNT {
+                    return Err(RC_HANDLE_1);
+                }
+                let digests: TpmlDigestValues =
+                    parameters.read().map_err(|_| RC_HASH_PARAMETER_1)?;
+                for digest in &digests.0 {
+                    let alg = hash_algorithm(digest.hash_alg).ok_or(RC_HASH_PARAMETER_1)?;
+                    let Some((_, pcrs)) =
+                        self.banks.iter_mut().find(|(h, _)| *h == digest.hash_alg)
+                    else {
+                        return Err(RC_HASH_PARAMETER_1);
+                    };
+                    let mut context = HashContext::new(alg);
+                    context.update(&pcrs[pcr as usize]);
+                    context.update(&digest.digest);
+                    pcrs[pcr as usize] = context.finalize().as_bytes().to_vec();
+                }
+                self.pcr_update_counter += 1;
+            }
+            TPM_CC_PCR_READ => {
+                let mut selection: TpmlPcrSelection =
+                    parameters.read().map_err(|_| RC_VALUE_PARAMETER_1)?;
+                if self.stale_pcr_read {
+                    for bank in &mut selection.0 {
+                        *bank = TpmsPcrSelection::new(bank.hash, &[0]);
+                    }
+                }
+                let mut read = Tpml(Vec::new());
+                let mut digests: TpmlDigest = Tpml(Vec::new());
+                for bank in &selection.0 {
+                    let mut done = TpmsPcrSelection {
+                        hash: bank.hash,
+                        pcr_select: vec![0; bank.pcr_select.len()],
+                    };
+                    if let Some((_, pcrs)) = self.banks.iter().find(|(h, _)| *h == bank.hash) {
+                        for pcr in bank.pcrs().filter(|&pcr| pcr < PCR_COUNT) {
+                            if digests.0.len() == MAX_PCR_DIGESTS {
+                                break;
+                            }
+                            done.select(pcr);
+                            digests.0.push(Tpm2b::new(&pcrs[pcr as usize]));
+                        }
+                    }
+                    read.0.push(done);
+                }
+                self.pcr_update_counter.marshal(out);
+                read.marshal(out);
+                digests.marshal(out);
+            }
+            TPM_CC_GET_RANDOM => {
+                let requested = parameters.read::<u16>().map_err(|_| RC_SIZE_PARAMETER_1)?;
+                let bytes: Vec<u8> = (0..core::cmp::min(requested as usize, MAX_RANDOM))
+                    .map(|_| {
+                        self.random_state = self.random_state.wrapping_mul(5).wrapping_add(1);
+                        self.random_state | 1
+                    })
+                    .collect();
+                Tpm2b(bytes).marshal(out);
+            }
+            TPM_CC_GET_CAPABILITY => {
+                let read = |reader: &mut Reader<'_>| {
+                    reader.read::<u32>().map_err(|_| RC_VALUE_PARAMETER_1)
+                };
+                let (capability, property, count) =
+                    (read(parameters)?, read(parameters)?, read(parameters)?);
+                let (more, data) = match capability {
+                    TPM_CAP_ALGS => {
+                        let all: Vec<TpmsAlgProperty> = ALGORITHMS
+                            .iter()
+                            .filter(|&&alg| alg as u32 >= property)
+                            .map(|&alg| TpmsAlgProperty {
+                                alg,
+                                alg_properties: 0,
+                            })
+                            .collect();
+                        let count = core::cmp::min(count as usize, 2);
+                        let more = all.len() > count;
+                        let list = all.into_iter().take(count).collect();
+                        (more, CapabilityData::Algorithms(Tpml(list)))
+                    }
+                    TPM_CAP_PCRS => {
+                        let mut banks: Vec<TpmsPcrSelection> = self
+                            .banks
+                            .iter()
+                            .map(|(hash, _)| {
+                                TpmsPcrSelection::new(*hash, &(0..PCR_COUNT).collect::<Vec<_>>())
+                            })
+                            .collect();
+                        banks.push(TpmsPcrSelection::new(TPM_ALG_SHA384, &[]));
+                        (false, CapabilityData::Pcrs(Tpml(banks)))
+                    }
+                    TPM_CAP_TPM_PROPERTIES => {
+                        let all: Vec<TpmsTaggedProperty> = PROPERTIES
+                            .iter()
+                            .filter(|(tag, _)| *tag >= property)
+                            .map(|&(property, value)| TpmsTaggedProperty { property, value })
+                            .collect();
+                        let more = all.len() > count as usize;
+                        let list = all.into_iter().take(count as usize).collect();
+                        (more, CapabilityData::TpmProperties(Tpml(list)))
+                    }
+                    _ => return Err(RC_VALUE_PARAMETER_1),
+                };
+                more.marshal(out);
+                data.marshal(out);
+            }
+            TPM_CC_NV_READ_PUBLIC => {
+                if handles[0] != MOCK_NV_INDEX {
+                    return Err(RC_HANDLE_1);
+                }
+                let public = Tpm2bNvPublic(Self::nv_public());
+                let mut marshalled = Vec::new();
+                public.0.marshal(&mut marshalled);
+                public.marshal(out);
+                Self::name(&marshalled).marshal(out);
+            }
+            TPM_CC_NV_READ => {
+                Self::check_nv_auth(&handles, &sessions)?;
+                let size = parameters.read::<u16>().map_err(|_| RC_SIZE_PARAMETER_1)?;
+                let offset = parameters.read::<u16>().map_err(|_| RC_VALUE_PARAMETER_1)?;
+                if size as usize > NV_BUFFER_MAX {
+                    return Err(RC_VALUE_PARAMETER_1);
+                }
+                let end = offset as usize + size as usize;
+                let data = self.nv_data.get(offset as usize..end).ok_or(RC_NV_RANGE)?;
+                Tpm2b::new(data).marshal(out);
+            }
+            TPM_CC_NV_WRITE => {
+                Self::check_nv_auth(&handles, &sessions)?;
+                let data: Tpm2bMaxNvBuffer = parameters.read().map_err(|_| RC_SIZE_PARAMETER_1)?;
+                let offset = parameters.read::<u16>().map_err(|_| RC_VALUE_PARAMETER_1)?;
+                if data.0.len() > NV_BUFFER_MAX {
+                    return Err(RC_SIZE_PARAMETER_1);
+                }
+                let end = offset as usize + data.0.len();
+                self.nv_data
+                    .get_mut(offset as usize..end)
+                    .ok_or(RC_NV_RANGE)?
+                    .copy_from_slice(&data.0);
+            }
+            TPM_CC_READ_PUBLIC => {
+                if handles[0] != MOCK_KEY_HANDLE {
+                    return Err(RC_HANDLE_1);
+                }
+                let public = Self::key_public();
+                let name = Self::name(&public);
+                Tpm2b(public).marshal(out);
+                name.marshal(out);
+                Self::name(&name.0).marshal(out);
+            }
+            TPM_CC_HASH_SEQUENCE_START => {
+                let _auth: Tpm2bAuth = parameters.read().map_err(|_| RC_SIZE_PARAMETER_1)?;
+                let hash_alg = parameters.read::<u16>().map_err(|_| RC_HASH_PARAMETER_2)?;
+                let alg = hash_algorithm(hash_alg).ok_or(RC_HASH_PARAMETER_2)?;
+                let handle = self.next_sequence;
+                self.next_sequence += 1;
+                self.sequences.push((handle, HashContext::new(alg)));
+                reply.handles.push(handle);
+            }
+            TPM_CC_SEQUENCE_UPDATE | TPM_CC_SEQUENCE_COMPLETE => {
+                let position = self
+                    .sequences
+                    .iter()
+                    .position(|(handle, _)| *handle == handles[0])
+                    .ok_or(RC_HANDLE_1)?;
+                let data: Tpm2bMaxBuffer = parameters.read().map_err(|_| RC_SIZE_PARAMETER_1)?;
+                if data.0.len() > INPUT_BUFFER {
+                    return Err(RC_SIZE_PARAMETER_1);
+                }
+                self.sequences[position].1.update(&data.0);
+                if code == TPM_CC_SEQUENCE_COMPLETE {
+                    let (_, context) = self.sequences.remove(position);
+                    Tpm2b::new(context.finalize().as_bytes()).marshal(out);
+                    TpmtTkHashcheck {
+                        tag: 0x8024,
+                        hierarchy: TPM_RH_NULL,
+                        digest: Tpm2b::default(),
+                    }
+                    .marshal(out);
+                }
+            }
+            TPM_CC_FLUSH_CONTEXT => {
+                let handle = parameters.read::<u32>().map_err(|_| RC_VALUE_PARAMETER_1)?;
+                let position = self
+                    .sequences
+                    .iter()
+                    .position(|(h, _)| *h == handle)
+                    .ok_or(RC_VALUE_PARAMETER_1)?;
+                self.sequences.remove(position);
+            }
+            _ => return Err(RC_COMMAND_CODE),
+        }
+        Ok(reply)
+    }
+
+    fn parse(command: &[u8]) -> Option<(u32, Request<'_>)> {
+        let mut reader = Reader::new(command);
+        let tag = reader.read::<u16>().ok()?;
+        let size = reader.read::<u32>().ok()?;
+        let code = reader.read::<u32>().ok()?;
+        if size as usize != command.len() {
+            return None;
+        }
+        let handles = (0..handle_count(code))
+            .map(|_| reader.read())
+            .collect::<Result<Vec<u32>>>()
+            .ok()?;
+        let mut sessions = Vec::new();
+        if tag == TPM_ST_SESSIONS {
+            let auth_size = reader.read::<u32>().ok()?;
+            let mut auth = Reader::new(reader.bytes(auth_size as usize).ok()?);
+            while !auth.is_empty() {
+                sessions.push(auth.read().ok()?);
+            }
+        }
+        Some((
+            code,
+            Request {
+                handles,
+                sessions,
+                parameters: reader,
+            },
+        ))
+    }
+}
+
+impl Tpm2Transport for MockTpm {
+    fn submit_command(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize> {
+        let mut out = Vec::new();
+        match Self::parse(command) {
+            None => {
+                // TPM_RC_COMMAND_SIZE
+                header(&mut out, TPM_ST_NO_SESSIONS, 0x142);
+            }
+            Some((code, request)) => {
+                self.commands.push(code);
+                let sessions = request.sessions.len();
+                match self.dispatch(code, request) {
+                    Err(rc) => header(&mut out, TPM_ST_NO_SESSIONS, rc),
+                    Ok(reply) if sessions == 0 => {
+                        header(&mut out, TPM_ST_NO_SESSIONS, 0);
+                        for handle in &reply.handles {
+                            handle.marshal(&mut out);
+                        }
+                        out.extend_from_slice(&reply.parameters);
+                    }
+                    Ok(reply) => {
+                        header(&mut out, TPM_ST_SESSIONS, 0);
+                        for handle in &reply.handles {
+                            handle.marshal(&mut out);
+                        }
+                        (reply.parameters.len() as u32).marshal(&mut out);
+                        out.extend_from_slice(&reply.parameters);
+                        for _ in 0..sessions {
+                            // Empty nonce, attributes and acknowledgment
+                            out.extend_from_slice(&[0, 0, 0, 0, 0]);
+                        }
+                    }
+                }
+            }
+        }
+        let size = out.len() as u32;
+        out[2..6].copy_from_slice(&size.to_be_bytes());
+        let target = response
+            .get_mut(..out.len())
+            .ok_or(Error::new(EFI_BUFFER_TOO_SMALL))?;
+        target.copy_from_slice(&out);
+        Ok(out.len())
+    }
+}
+
+fn header(out: &mut Vec<u8>, tag: u16, rc: u32) {
+    tag.marshal(out);
+    0u32.marshal(out);
+    rc.marshal(out);
+}
+
+/// Crypto agile log header: a TCG_PCR_EVENT carrying a Spec ID event for
+/// `banks`
+pub(crate) fn log_header(banks: &[u16]) -> Vec<u8> {
+    let mut spec_id = SPEC_ID_EVENT_SIGNATURE.to_vec();
+    spec_id.extend_from_slice(&0u32.to_le_bytes());
+    spec_id.extend_from_slice(&[0, 2, 0, 2]);
+    spec_id.extend_from_slice(&(banks.len() as u32).to_le_bytes());
+    for &bank in banks {
+        spec_id.extend_from_slice(&bank.to_le_bytes());
+        spec_id.extend_from_slice(&(digest_size(bank).unwrap() as u16).to_le_bytes());
+    }
+    spec_id.push(0);
+
+    let mut entry = Vec::new();
+    entry.extend_from_slice(&0u32.to_le_bytes());
+    entry.extend_from_slice(&EV_NO_ACTION.to_le_bytes());
+    entry.extend_from_slice(&[0; 20]);
+    entry.extend_from_slice(&(spec_id.len() as u32).to_le_bytes());
+    entry.extend_from_slice(&spec_id);
+    entry
+}
+
+/// Log entry measuring `data` into `pcr`
+///
+/// A TCG_PCR_EVENT2 with a digest per bank, or a SHA-1 TCG_PCR_EVENT if
+/// `banks` is empty.
+pub(crate) fn log_event(pcr: u32, event_type: u32, data: &[u8], banks: &[u16]) -> Vec<u8> {
+    log_entry(pcr, event_type, data, data, banks)
+}
+
+/// Log entry with digests of `measured` and `data` as its event data
+pub(crate) fn log_entry(
+    pcr: u32,
+    event_type: u32,
+    measured: &[u8],
+    data: &[u8],
+    banks: &[u16],
+) -> Vec<u8> {
+    let mut entry = Vec::new();
+    entry.extend_from_slice(&pcr.to_le_bytes());
+    entry.extend_from_slice(&event_type.to_le_bytes());
+    if banks.is_empty() {
+        entry.extend_from_slice(HashAlgorithm::Sha1.digest(measured).as_bytes());
+    } else {
+        entry.extend_from_slice(&(banks.len() as u32).to_le_bytes());
+        for &bank in banks {
+            entry.extend_from_slice(&bank.to_le_bytes());
+            let digest = hash_algorithm(bank).unwrap().digest(measured);
+            entry.extend_from_slice(digest.as_bytes());
+        }
+    }
+    entry.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    entry.extend_from_slice(data);
+    entry
+}
@@ -0,0 +1,381 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! TPM 2.0 Commands
+//!
+//! Marshalling of TPM 2.0 structures (TCG TPM 2.0 Library Part 2) and a
+//! command layer ([`Tpm2`]) over anything implementing [`Tpm2Transport`],
+//! such as [`SafeTpm2`] or [`SafeTcg2`]. [`event_log`] parses and replays
+//! the measured boot log.
+
+pub mod commands;
+pub mod event_log;
+pub mod marshal;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod types;
+
+pub use commands::*;
+pub use marshal::{Marshal, Reader, Unmarshal};
+pub use types::*;
+
+use crate::crypto::hash::HashAlgorithm;
+use crate::error::{Error, ErrorData, Result};
+use crate::ffi::*;
+use crate::protocols::security::SafeTpm2;
+use crate::protocols::tcg2::SafeTcg2;
+use core::fmt;
+
+// TPM_ALG_ID
+pub const TPM_ALG_RSA: u16 = 0x0001;
+pub const TPM_ALG_SHA1: u16 = 0x0004;
+pub const TPM_ALG_HMAC: u16 = 0x0005;
+pub const TPM_ALG_AES: u16 = 0x0006;
+pub const TPM_ALG_KEYEDHASH: u16 = 0x0008;
+pub const TPM_ALG_SHA256: u16 = 0x000B;
+pub const TPM_ALG_SHA384: u16 = 0x000C;
+pub const TPM_ALG_SHA512: u16 = 0x000D;
+pub const TPM_ALG_NULL: u16 = 0x0010;
+pub const TPM_ALG_SM3_256: u16 = 0x0012;
+pub const TPM_ALG_ECC: u16 = 0x0023;
+pub const TPM_ALG_SYMCIPHER: u16 = 0x0025;
+
+// TPM_RH / TPM_RS handles
+pub const TPM_RH_OWNER: u32 = 0x4000_0001;
+pub const TPM_RH_NULL: u32 = 0x4000_0007;
+pub const TPM_RS_PW: u32 = 0x4000_0009;
+pub const TPM_RH_LOCKOUT: u32 = 0x4000_000A;
+pub const TPM_RH_ENDORSEMENT: u32 = 0x4000_000B;
+pub const TPM_RH_PLATFORM: u32 = 0x4000_000C;
+
+// TPM_CAP
+pub const TPM_CAP_ALGS: u32 = 0x0000_0000;
+pub const TPM_CAP_HANDLES: u32 = 0x0000_0001;
+pub const TPM_CAP_COMMANDS: u32 = 0x0000_0002;
+pub const TPM_CAP_PCRS: u32 = 0x0000_0005;
+pub const TPM_CAP_TPM_PROPERTIES: u32 = 0x0000_0006;
+
+// TPM_PT
+pub const TPM_PT_FIXED: u32 = 0x100;
+pub const TPM_PT_FAMILY_INDICATOR: u32 = TPM_PT_FIXED;
+pub const TPM_PT_MANUFACTURER: u32 = TPM_PT_FIXED + 5;
+pub const TPM_PT_INPUT_BUFFER: u32 = TPM_PT_FIXED + 13;
+pub const TPM_PT_PCR_COUNT: u32 = TPM_PT_FIXED + 18;
+pub const TPM_PT_MAX_DIGEST: u32 = TPM_PT_FIXED + 32;
+pub const TPM_PT_NV_BUFFER_MAX: u32 = TPM_PT_FIXED + 44;
+
+/// Digest size of a TPM hash algorithm
+pub fn digest_size(hash_alg: u16) -> Option<usize> {
+    match hash_alg {
+        TPM_ALG_SHA1 => Some(20),
+        TPM_ALG_SHA256 | TPM_ALG_SM3_256 => Some(32),
+        TPM_ALG_SHA384 => Some(48),
+        TPM_ALG_SHA512 => Some(64),
+        _ => None,
+    }
+}
+
+/// TPM_ALG_ID of a hash algorithm
+pub fn hash_alg_id(algorithm: HashAlgorithm) -> u16 {
+    match algorithm {
+        HashAlgorithm::Sha1 => TPM_ALG_SHA1,
+        HashAlgorithm::Sha256 => TPM_ALG_SHA256,
+        HashAlgorithm::Sha384 => TPM_ALG_SHA384,
+        HashAlgorithm::Sha512 => TPM_ALG_SHA512,
+    }
+}
+
+/// Hash algorithm of a TPM_ALG_ID, if it is one this crate implements
+pub fn hash_algorithm(hash_alg: u16) -> Option<HashAlgorithm> {
+    match hash_alg {
+        TPM_ALG_SHA1 => Some(HashAlgorithm::Sha1),
+        TPM_ALG_SHA256 => Some(HashAlgorithm::Sha256),
+        TPM_ALG_SHA384 => Some(HashAlgorithm::Sha384),
+        TPM_ALG_SHA512 => Some(HashAlgorithm::Sha512),
+        _ => None,
+    }
+}
+
+const RC_VER1: u32 = 0x100;
+const RC_FMT1: u32 = 0x080;
+const RC_WARN: u32 = 0x900;
+
+/// Names of the response codes, with format-one codes stripped of their
+/// handle, parameter or session number
+const RESPONSE_CODE_NAMES: &[(u32, &str)] = &[
+    (0x000, "TPM_RC_SUCCESS"),
+    (0x01E, "TPM_RC_BAD_TAG"),
+    (RC_VER1, "TPM_RC_INITIALIZE"),
+    (RC_VER1 + 0x001, "TPM_RC_FAILURE"),
+    (RC_VER1 + 0x003, "TPM_RC_SEQUENCE"),
+    (RC_VER1 + 0x00B, "TPM_RC_PRIVATE"),
+    (RC_VER1 + 0x019, "TPM_RC_HMAC"),
+    (RC_VER1 + 0x020, "TPM_RC_DISABLED"),
+    (RC_VER1 + 0x021, "TPM_RC_EXCLUSIVE"),
+    (RC_VER1 + 0x024, "TPM_RC_AUTH_TYPE"),
+    (RC_VER1 + 0x025, "TPM_RC_AUTH_MISSING"),
+    (RC_VER1 + 0x026, "TPM_RC_POLICY"),
+    (RC_VER1 + 0x027, "TPM_RC_PCR"),
+    (RC_VER1 + 0x028, "TPM_RC_PCR_CHANGED"),
+    (RC_VER1 + 0x02D, "TPM_RC_UPGRADE"),
+    (RC_VER1 + 0x02E, "TPM_RC_TOO_MANY_CONTEXTS"),
+    (RC_VER1 + 0x02F, "TPM_RC_AUTH_UNAVAILABLE"),
+    (RC_VER1 + 0x030, "TPM_RC_REBOOT"),
+    (RC_VER1 + 0x031, "TPM_RC_UNBALANCED"),
+    (RC_VER1 + 0x042, "TPM_RC_COMMAND_SIZE"),
+    (RC_VER1 + 0x043, "TPM_RC_COMMAND_CODE"),
+    (RC_VER1 + 0x044, "TPM_RC_AUTHSIZE"),
+    (RC_VER1 + 0x045, "TPM_RC_AUTH_CONTEXT"),
+    (RC_VER1 + 0x046, "TPM_RC_NV_RANGE"),
+    (RC_VER1 + 0x047, "TPM_RC_NV_SIZE"),
+    (RC_VER1 + 0x048, "TPM_RC_NV_LOCKED"),
+    (RC_VER1 + 0x049, "TPM_RC_NV_AUTHORIZATION"),
+    (RC_VER1 + 0x04A, "TPM_RC_NV_UNINITIALIZED"),
+    (RC_VER1 + 0x04B, "TPM_RC_NV_SPACE"),
+    (RC_VER1 + 0x04C, "TPM_RC_NV_DEFINED"),
+    (RC_VER1 + 0x050, "TPM_RC_BAD_CONTEXT"),
+    (RC_VER1 + 0x051, "TPM_RC_CPHASH"),
+    (RC_VER1 + 0x052, "TPM_RC_PARENT"),
+    (RC_VER1 + 0x053, "TPM_RC_NEEDS_TEST"),
+    (RC_VER1 + 0x054, "TPM_RC_NO_RESULT"),
+    (RC_VER1 + 0x055, "TPM_RC_SENSITIVE"),
+    (RC_FMT1 + 0x001, "TPM_RC_ASYMMETRIC"),
+    (RC_FMT1 + 0x002, "TPM_RC_ATTRIBUTES"),
+    (RC_FMT1 + 0x003, "TPM_RC_HASH"),
+    (RC_FMT1 + 0x004, "TPM_RC_VALUE"),
+    (RC_FMT1 + 0x005, "TPM_RC_HIERARCHY"),
+    (RC_FMT1 + 0x007, "TPM_RC_KEY_SIZE"),
+    (RC_FMT1 + 0x008, "TPM_RC_MGF"),
+    (RC_FMT1 + 0x009, "TPM_RC_MODE"),
+    (RC_FMT1 + 0x00A, "TPM_RC_TYPE"),
+    (RC_FMT1 + 0x00B, "TPM_RC_HANDLE"),
+    (RC_FMT1 + 0x00C, "TPM_RC_KDF"),
+    (RC_FMT1 + 0x00D, "TPM_RC_RANGE"),
+    (RC_FMT1 + 0x00E, "TPM_RC_AUTH_FAIL"),
+    (RC_FMT1 + 0x00F, "TPM_RC_NONCE"),
+    (RC_FMT1 + 0x010, "TPM_RC_PP"),
+    (RC_FMT1 + 0x012, "TPM_RC_SCHEME"),
+    (RC_FMT1 + 0x015, "TPM_RC_SIZE"),
+    (RC_FMT1 + 0x016, "TPM_RC_SYMMETRIC"),
+    (RC_FMT1 + 0x017, "TPM_RC_TAG"),
+    (RC_FMT1 + 0x018, "TPM_RC_SELECTOR"),
+    (RC_FMT1 + 0x01A, "TPM_RC_INSUFFICIENT"),
+    (RC_FMT1 + 0x01B, "TPM_RC_SIGNATURE"),
+    (RC_FMT1 + 0x01C, "TPM_RC_KEY"),
+    (RC_FMT1 + 0x01D, "TPM_RC_POLICY_FAIL"),
+    (RC_FMT1 + 0x01F, "TPM_RC_INTEGRITY"),
+    (RC_FMT1 + 0x020, "TPM_RC_TICKET"),
+    (RC_FMT1 + 0x021, "TPM_RC_RESERVED_BITS"),
+    (RC_FMT1 + 0x022, "TPM_RC_BAD_AUTH"),
+    (RC_FMT1 + 0x023, "TPM_RC_EXPIRED"),
+    (RC_FMT1 + 0x024, "TPM_RC_POLICY_CC"),
+    (RC_FMT1 + 0x025, "TPM_RC_BINDING"),
+    (RC_FMT1 + 0x026, "TPM_RC_CURVE"),
+    (RC_FMT1 + 0x027, "TPM_RC_ECC_POINT"),
+    (RC_WARN + 0x001, "TPM_RC_CONTEXT_GAP"),
+    (RC_WARN + 0x002, "TPM_RC_OBJECT_MEMORY"),
+    (RC_WARN + 0x003, "TPM_RC_SESSION_MEMORY"),
+    (RC_WARN + 0x004, "TPM_RC_MEMORY"),
+    (RC_WARN + 0x005, "TPM_RC_SESSION_HANDLES"),
+    (RC_WARN + 0x006, "TPM_RC_OBJECT_HANDLES"),
+    (RC_WARN + 0x007, "TPM_RC_LOCALITY"),
+    (RC_WARN + 0x008, "TPM_RC_YIELDED"),
+    (RC_WARN + 0x009, "TPM_RC_CANCELED"),
+    (RC_WARN + 0x00A, "TPM_RC_TESTING"),
+    (RC_WARN + 0x020, "TPM_RC_NV_RATE"),
+    (RC_WARN + 0x021, "TPM_RC_LOCKOUT"),
+    (RC_WARN + 0x022, "TPM_RC_RETRY"),
+    (RC_WARN + 0x023, "TPM_RC_NV_UNAVAILABLE"),
+];
+
+/// A TPM_RC value
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct ResponseCode(pub u32);
+
+impl ResponseCode {
+    pub const SUCCESS: ResponseCode = ResponseCode(0);
+
+    /// Check for TPM_RC_SUCCESS
+    pub fn is_success(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check for a format-one code, which names a handle, parameter or session
+    pub fn is_format_one(self) -> bool {
+        self.0 & RC_FMT1 != 0
+    }
+
+    /// Check for a TPM 1.2 response code
+    pub fn is_tpm12(self) -> bool {
+        !self.is_success() && !self.is_format_one() && self.0 & RC_VER1 == 0
+    }
+
+    /// Check for a vendor defined code
+    pub fn is_vendor(self) -> bool {
+        !self.is_format_one() && self.0 & 0x400 != 0
+    }
+
+    /// Check for a warning, after which the command may be retried
+    pub fn is_warning(self) -> bool {
+        !self.is_format_one() && self.0 & 0x800 != 0 && self.0 & RC_VER1 != 0
+    }
+
+    /// Code with any handle, parameter or session number removed
+    pub fn base(self) -> u32 {
+        if self.is_format_one() {
+            RC_FMT1 | (self.0 & 0x3F)
+        } else {
+            self.0 & 0xFFF
+        }
+    }
+
+    /// 1-based number of the parameter the code refers to
+    pub fn parameter(self) -> Option<u8> {
+        (self.is_format_one() && self.0 & 0x40 != 0).then_some(((self.0 >> 8) & 0xF) as u8)
+    }
+
+    /// 1-based number of the handle the code refers to
+    pub fn handle(self) -> Option<u8> {
+        let number = ((self.0 >> 8) & 0x7) as u8;
+        (self.is_format_one() && self.0 & 0x840 == 0 && number != 0).then_some(number)
+    }
+
+    /// 1-based number of the session the code refers to
+    pub fn session(self) -> Option<u8> {
+        let number = ((self.0 >> 8) & 0x7) as u8;
+        (self.is_format_one() && self.0 & 0x840 == 0x800).then_some(number)
+    }
+
+    /// Spec name of the code, such as `TPM_RC_HANDLE`
+    pub fn name(self) -> Option<&'static str> {
+        if self.is_vendor() || self.is_tpm12() {
+            return None;
+        }
+        let base = self.base();
+        RESPONSE_CODE_NAMES
+            .iter()
+            .find(|(code, _)| *code == base)
+            .map(|(_, name)| *name)
+    }
+
+    /// EFI_STATUS reported for this code
+    ///
+    /// Warnings map to `EFI_NOT_READY`, authorization failures to
+    /// `EFI_SECURITY_VIOLATION` and everything else to `EFI_DEVICE_ERROR`.
+    pub fn status(self) -> Status {
+        const AUTH_FAIL: u32 = RC_FMT1 + 0x00E;
+        const BAD_AUTH: u32 = RC_FMT1 + 0x022;
+        const LOCKOUT: u32 = RC_WARN + 0x021;
+        if self.is_success() {
+            return EFI_SUCCESS;
+        }
+        match self.base() {
+            AUTH_FAIL | BAD_AUTH | LOCKOUT => EFI_SECURITY_VIOLATION,
+            _ if self.is_warning() => EFI_NOT_READY,
+            _ => EFI_DEVICE_ERROR,
+        }
+    }
+
+    /// Convert to a result, keeping the code in the error
+    pub fn to_result(self) -> Result<()> {
+        if self.is_success() {
+            Ok(())
+        } else {
+            Err(Error::with_data(
+                self.status(),
+                ErrorData::TpmResponseCode(self.0),
+            ))
+        }
+    }
+}
+
+impl fmt::Display for ResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name)?,
+            None => write!(f, "TPM_RC({:#x})", self.0)?,
+        }
+        if let Some(n) = self.parameter() {
+            write!(f, " (parameter {})", n)?;
+        } else if let Some(n) = self.handle() {
+            write!(f, " (handle {})", n)?;
+        } else if let Some(n) = self.session() {
+            write!(f, " (session {})", n)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ResponseCode({})", self)
+    }
+}
+
+/// Something that can execute marshalled TPM 2.0 commands
+pub trait Tpm2Transport {
+    /// Send `command` and write the response to `response`
+    ///
+    /// Returns the length of the response.
+    fn submit_command(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize>;
+}
+
+impl Tpm2Transport for SafeTpm2<'_> {
+    fn submit_command(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize> {
+        SafeTpm2::submit_command(self, command, response)
+    }
+}
+
+impl Tpm2Transport for SafeTcg2<'_> {
+    fn submit_command(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize> {
+        SafeTcg2::submit_command(self, command, response)
+    }
+}
+
+impl<T: Tpm2Transport + ?Sized> Tpm2Transport for &mut T {
+    fn submit_command(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize> {
+        (**self).submit_command(command, response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_code_decoding() {
+        // TPM_RC_HANDLE on handle 1
+        let rc = ResponseCode(0x18B);
+        assert!(rc.is_format_one());
+        assert_eq!(rc.base(), 0x08B);
+        assert_eq!(rc.handle(), Some(1));
+        assert_eq!((rc.parameter(), rc.session()), (None, None));
+        assert_eq!(rc.name(), Some("TPM_RC_HANDLE"));
+        assert_eq!(format!("{}", rc), "TPM_RC_HANDLE (handle 1)");
+
+        // TPM_RC_VALUE on parameter 2
+        let rc = ResponseCode(0x2C4);
+        assert_eq!(rc.parameter(), Some(2));
+        assert_eq!(format!("{}", rc), "TPM_RC_VALUE (parameter 2)");
+
+        // TPM_RC_AUTH_FAIL on session 1
+        let rc = ResponseCode(0x98E);
+        assert_eq!(rc.session(), Some(1));
+        assert_eq!(rc.status(), EFI_SECURITY_VIOLATION);
+
+        let retry = ResponseCode(0x922);
+        assert!(retry.is_warning());
+        assert_eq!(retry.name(), Some("TPM_RC_RETRY"));
+        assert_eq!(retry.status(), EFI_NOT_READY);
+
+        let initialize = ResponseCode(0x100);
+        assert_eq!(initialize.name(), Some("TPM_RC_INITIALIZE"));
+        let err = initialize.to_result().unwrap_err();
+        assert_eq!(err.status(), EFI_DEVICE_ERROR);
+        assert_eq!(err.tpm_response_code(), Some(0x100));
+        assert_eq!(format!("{}", err), "EFI_DEVICE_ERROR (TPM_RC 0x100)");
+
+        assert!(ResponseCode(0x001).is_tpm12());
+        assert_eq!(ResponseCode(0x001).name(), None);
+        assert_eq!(format!("{}", ResponseCode(0x501)), "TPM_RC(0x501)");
+        assert!(ResponseCode::SUCCESS.to_result().is_ok());
+    }
+}
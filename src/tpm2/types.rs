@@ -0,0 +1,534 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+//! TPM 2.0 Structures
+//!
+//! The TPM2B, TPMS, TPML and TPMT types used by [`super::commands`]. Names
+//! follow TCG TPM 2.0 Library Part 2.
+
+use super::marshal::{marshal_sized, unmarshal_sized, Marshal, Reader, Unmarshal};
+use super::{
+    digest_size, TPM_CAP_ALGS, TPM_CAP_HANDLES, TPM_CAP_PCRS, TPM_CAP_TPM_PROPERTIES, TPM_RS_PW,
+};
+use crate::error::{Error, Result};
+use crate::ffi::*;
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+/// PCR_SELECT_MIN, the smallest select size a TPM accepts
+pub const PCR_SELECT_MIN: usize = 3;
+
+// TPMA_NV
+pub const TPMA_NV_PPWRITE: u32 = 1 << 0;
+pub const TPMA_NV_OWNERWRITE: u32 = 1 << 1;
+pub const TPMA_NV_AUTHWRITE: u32 = 1 << 2;
+pub const TPMA_NV_POLICYWRITE: u32 = 1 << 3;
+pub const TPMA_NV_WRITELOCKED: u32 = 1 << 11;
+pub const TPMA_NV_PPREAD: u32 = 1 << 16;
+pub const TPMA_NV_OWNERREAD: u32 = 1 << 17;
+pub const TPMA_NV_AUTHREAD: u32 = 1 << 18;
+pub const TPMA_NV_POLICYREAD: u32 = 1 << 19;
+pub const TPMA_NV_READLOCKED: u32 = 1 << 28;
+pub const TPMA_NV_WRITTEN: u32 = 1 << 29;
+
+/// TPM2B: bytes with a `u16` size
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tpm2b(pub Vec<u8>);
+
+/// TPM2B_DIGEST
+pub type Tpm2bDigest = Tpm2b;
+/// TPM2B_AUTH
+pub type Tpm2bAuth = Tpm2b;
+/// TPM2B_NAME
+pub type Tpm2bName = Tpm2b;
+/// TPM2B_MAX_BUFFER
+pub type Tpm2bMaxBuffer = Tpm2b;
+/// TPM2B_MAX_NV_BUFFER
+pub type Tpm2bMaxNvBuffer = Tpm2b;
+
+impl Tpm2b {
+    pub fn new(data: &[u8]) -> Self {
+        Tpm2b(data.to_vec())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Marshal for Tpm2b {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        (self.0.len() as u16).marshal(out);
+        out.extend_from_slice(&self.0);
+    }
+}
+
+impl Unmarshal for Tpm2b {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        let size = reader.read::<u16>()?;
+        Ok(Tpm2b(reader.bytes(size as usize)?.to_vec()))
+    }
+}
+
+/// TPML: a list with a `u32` count
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tpml<T>(pub Vec<T>);
+
+impl<T> Default for Tpml<T> {
+    fn default() -> Self {
+        Tpml(Vec::new())
+    }
+}
+
+/// TPML_PCR_SELECTION
+pub type TpmlPcrSelection = Tpml<TpmsPcrSelection>;
+/// TPML_DIGEST
+pub type TpmlDigest = Tpml<Tpm2bDigest>;
+/// TPML_DIGEST_VALUES
+pub type TpmlDigestValues = Tpml<TpmtHa>;
+/// TPML_ALG_PROPERTY
+pub type TpmlAlgProperty = Tpml<TpmsAlgProperty>;
+/// TPML_TAGGED_TPM_PROPERTY
+pub type TpmlTaggedTpmProperty = Tpml<TpmsTaggedProperty>;
+/// TPML_HANDLE
+pub type TpmlHandle = Tpml<u32>;
+
+impl<T: Marshal> Marshal for Tpml<T> {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        (self.0.len() as u32).marshal(out);
+        for item in &self.0 {
+            item.marshal(out);
+        }
+    }
+}
+
+impl<T: Unmarshal> Unmarshal for Tpml<T> {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        let count = reader.read::<u32>()?;
+        // Every item is at least one byte, which bounds the count
+        if count as usize > reader.remaining().len() {
+            return Err(Error::new(EFI_BAD_BUFFER_SIZE));
+        }
+        let mut items = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            items.push(reader.read()?);
+        }
+        Ok(Tpml(items))
+    }
+}
+
+/// TPMS_PCR_SELECTION
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmsPcrSelection {
+    pub hash: u16,
+    /// Bit `n % 8` of byte `n / 8` selects PCR `n`
+    pub pcr_select: Vec<u8>,
+}
+
+impl TpmsPcrSelection {
+    /// Select `pcrs` in bank `hash`
+    pub fn new(hash: u16, pcrs: &[u32]) -> Self {
+        let mut selection = TpmsPcrSelection {
+            hash,
+            pcr_select: vec![0; PCR_SELECT_MIN],
+        };
+        for &pcr in pcrs {
+            selection.select(pcr);
+        }
+        selection
+    }
+
+    /// Add `pcr` to the selection
+    pub fn select(&mut self, pcr: u32) {
+        let byte = (pcr / 8) as usize;
+        if byte >= self.pcr_select.len() {
+            self.pcr_select.resize(byte + 1, 0);
+        }
+        self.pcr_select[byte] |= 1 << (pcr % 8);
+    }
+
+    /// Remove `pcr` from the selection
+    pub fn deselect(&mut self, pcr: u32) {
+        if let Some(byte) = self.pcr_select.get_mut((pcr / 8) as usize) {
+            *byte &= !(1 << (pcr % 8));
+        }
+    }
+
+    /// Check whether `pcr` is selected
+    pub fn contains(&self, pcr: u32) -> bool {
+        self.pcr_select
+            .get((pcr / 8) as usize)
+            .is_some_and(|byte| byte & (1 << (pcr % 8)) != 0)
+    }
+
+    /// Selected PCRs in ascending order
+    pub fn pcrs(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.pcr_select.len() as u32 * 8).filter(|&pcr| self.contains(pcr))
+    }
+
+    /// Check whether no PCR is selected
+    pub fn is_empty(&self) -> bool {
+        self.pcr_select.iter().all(|&byte| byte == 0)
+    }
+}
+
+impl Marshal for TpmsPcrSelection {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        self.hash.marshal(out);
+        (self.pcr_select.len() as u8).marshal(out);
+        out.extend_from_slice(&self.pcr_select);
+    }
+}
+
+impl Unmarshal for TpmsPcrSelection {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        let hash = reader.read()?;
+        let size = reader.read::<u8>()?;
+        Ok(TpmsPcrSelection {
+            hash,
+            pcr_select: reader.bytes(size as usize)?.to_vec(),
+        })
+    }
+}
+
+/// TPMT_HA: a digest tagged with its algorithm
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmtHa {
+    pub hash_alg: u16,
+    pub digest: Vec<u8>,
+}
+
+impl Marshal for TpmtHa {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        self.hash_alg.marshal(out);
+        out.extend_from_slice(&self.digest);
+    }
+}
+
+impl Unmarshal for TpmtHa {
+    /// Fails with `EFI_UNSUPPORTED` for hash algorithms of unknown size
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        let hash_alg = reader.read()?;
+        let size = digest_size(hash_alg).ok_or(Error::new(EFI_UNSUPPORTED))?;
+        Ok(TpmtHa {
+            hash_alg,
+            digest: reader.bytes(size)?.to_vec(),
+        })
+    }
+}
+
+/// TPMS_ALG_PROPERTY
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TpmsAlgProperty {
+    pub alg: u16,
+    pub alg_properties: u32,
+}
+
+impl Marshal for TpmsAlgProperty {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        self.alg.marshal(out);
+        self.alg_properties.marshal(out);
+    }
+}
+
+impl Unmarshal for TpmsAlgProperty {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(TpmsAlgProperty {
+            alg: reader.read()?,
+            alg_properties: reader.read()?,
+        })
+    }
+}
+
+/// TPMS_TAGGED_PROPERTY
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TpmsTaggedProperty {
+    pub property: u32,
+    pub value: u32,
+}
+
+impl Marshal for TpmsTaggedProperty {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        self.property.marshal(out);
+        self.value.marshal(out);
+    }
+}
+
+impl Unmarshal for TpmsTaggedProperty {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(TpmsTaggedProperty {
+            property: reader.read()?,
+            value: reader.read()?,
+        })
+    }
+}
+
+/// TPMS_CAPABILITY_DATA for the supported capabilities
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityData {
+    Algorithms(TpmlAlgProperty),
+    Handles(TpmlHandle),
+    Pcrs(TpmlPcrSelection),
+    TpmProperties(TpmlTaggedTpmProperty),
+}
+
+impl CapabilityData {
+    /// TPM_CAP value of this data
+    pub fn capability(&self) -> u32 {
+        match self {
+            CapabilityData::Algorithms(_) => TPM_CAP_ALGS,
+            CapabilityData::Handles(_) => TPM_CAP_HANDLES,
+            CapabilityData::Pcrs(_) => TPM_CAP_PCRS,
+            CapabilityData::TpmProperties(_) => TPM_CAP_TPM_PROPERTIES,
+        }
+    }
+}
+
+impl Marshal for CapabilityData {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        self.capability().marshal(out);
+        match self {
+            CapabilityData::Algorithms(list) => list.marshal(out),
+            CapabilityData::Handles(list) => list.marshal(out),
+            CapabilityData::Pcrs(list) => list.marshal(out),
+            CapabilityData::TpmProperties(list) => list.marshal(out),
+        }
+    }
+}
+
+impl Unmarshal for CapabilityData {
+    /// Fails with `EFI_UNSUPPORTED` for other capabilities
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        match reader.read::<u32>()? {
+            TPM_CAP_ALGS => Ok(CapabilityData::Algorithms(reader.read()?)),
+            TPM_CAP_HANDLES => Ok(CapabilityData::Handles(reader.read()?)),
+            TPM_CAP_PCRS => Ok(CapabilityData::Pcrs(reader.read()?)),
+            TPM_CAP_TPM_PROPERTIES => Ok(CapabilityData::TpmProperties(reader.read()?)),
+            _ => Err(Error::new(EFI_UNSUPPORTED)),
+        }
+    }
+}
+
+/// TPMS_NV_PUBLIC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmsNvPublic {
+    pub nv_index: u32,
+    pub name_alg: u16,
+    /// TPMA_NV_* bits
+    pub attributes: u32,
+    pub auth_policy: Tpm2bDigest,
+    pub data_size: u16,
+}
+
+impl Marshal for TpmsNvPublic {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        self.nv_index.marshal(out);
+        self.name_alg.marshal(out);
+        self.attributes.marshal(out);
+        self.auth_policy.marshal(out);
+        self.data_size.marshal(out);
+    }
+}
+
+impl Unmarshal for TpmsNvPublic {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(TpmsNvPublic {
+            nv_index: reader.read()?,
+            name_alg: reader.read()?,
+            attributes: reader.read()?,
+            auth_policy: reader.read()?,
+            data_size: reader.read()?,
+        })
+    }
+}
+
+/// TPM2B_NV_PUBLIC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tpm2bNvPublic(pub TpmsNvPublic);
+
+impl Marshal for Tpm2bNvPublic {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        marshal_sized(&self.0, out);
+    }
+}
+
+impl Unmarshal for Tpm2bNvPublic {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        unmarshal_sized(reader).map(Tpm2bNvPublic)
+    }
+}
+
+/// TPMT_TK_HASHCHECK
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmtTkHashcheck {
+    pub tag: u16,
+    pub hierarchy: u32,
+    pub digest: Tpm2bDigest,
+}
+
+impl Marshal for TpmtTkHashcheck {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        self.tag.marshal(out);
+        self.hierarchy.marshal(out);
+        self.digest.marshal(out);
+    }
+}
+
+impl Unmarshal for TpmtTkHashcheck {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(TpmtTkHashcheck {
+            tag: reader.read()?,
+            hierarchy: reader.read()?,
+            digest: reader.read()?,
+        })
+    }
+}
+
+/// The algorithm-independent head of a TPMT_PUBLIC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmtPublicHeader {
+    /// TPM_ALG_RSA, TPM_ALG_ECC, TPM_ALG_KEYEDHASH or TPM_ALG_SYMCIPHER
+    pub object_type: u16,
+    pub name_alg: u16,
+    /// TPMA_OBJECT bits
+    pub object_attributes: u32,
+    pub auth_policy: Tpm2bDigest,
+}
+
+impl TpmtPublicHeader {
+    /// Parse the head of a marshalled TPMT_PUBLIC
+    pub fn parse(public_area: &[u8]) -> Result<Self> {
+        Reader::new(public_area).read()
+    }
+}
+
+impl Unmarshal for TpmtPublicHeader {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(TpmtPublicHeader {
+            object_type: reader.read()?,
+            name_alg: reader.read()?,
+            object_attributes: reader.read()?,
+            auth_policy: reader.read()?,
+        })
+    }
+}
+
+/// TPMS_AUTH_COMMAND
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthCommand {
+    pub session_handle: u32,
+    pub nonce: Tpm2b,
+    /// TPMA_SESSION bits
+    pub session_attributes: u8,
+    pub hmac: Tpm2bAuth,
+}
+
+impl AuthCommand {
+    /// Password authorization (TPM_RS_PW) with `auth` as the password
+    pub fn password(auth: &[u8]) -> Self {
+        AuthCommand {
+            session_handle: TPM_RS_PW,
+            nonce: Tpm2b::default(),
+            session_attributes: 0,
+            hmac: Tpm2b::new(auth),
+        }
+    }
+}
+
+impl Marshal for AuthCommand {
+    fn marshal(&self, out: &mut Vec<u8>) {
+        self.session_handle.marshal(out);
+        self.nonce.marshal(out);
+        self.session_attributes.marshal(out);
+        self.hmac.marshal(out);
+    }
+}
+
+impl Unmarshal for AuthCommand {
+    fn unmarshal(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(AuthCommand {
+            session_handle: reader.read()?,
+            nonce: reader.read()?,
+            session_attributes: reader.read()?,
+            hmac: reader.read()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm2::{TPM_ALG_SHA1, TPM_ALG_SHA256};
+
+    fn round_trip<T: Marshal + Unmarshal + PartialEq + core::fmt::Debug>(value: &T) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        value.marshal(&mut bytes);
+        let mut reader = Reader::new(&bytes);
+        assert_eq!(&reader.read::<T>().unwrap(), value);
+        reader.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_pcr_selection() {
+        let mut selection = TpmsPcrSelection::new(TPM_ALG_SHA256, &[0, 7, 16]);
+        assert_eq!(selection.pcr_select, [0x81, 0x00, 0x01]);
+        assert_eq!(selection.pcrs().collect::<Vec<_>>(), [0, 7, 16]);
+        selection.deselect(7);
+        assert!(!selection.contains(7));
+        selection.select(30);
+        assert_eq!(selection.pcr_select.len(), 4);
+
+        let list = Tpml(vec![selection, TpmsPcrSelection::new(TPM_ALG_SHA1, &[])]);
+        let bytes = round_trip(&list);
+        assert_eq!(
+            bytes[..8],
+            [0, 0, 0, 2, 0x00, 0x0B, 4, 0x01],
+            "count, hash and sizeofSelect are big-endian"
+        );
+        assert!(list.0[1].is_empty());
+    }
+
+    #[test]
+    fn test_structure_round_trips() {
+        round_trip(&Tpml(vec![
+            TpmtHa {
+                hash_alg: TPM_ALG_SHA1,
+                digest: vec![1; 20],
+            },
+            TpmtHa {
+                hash_alg: TPM_ALG_SHA256,
+                digest: vec![2; 32],
+            },
+        ]));
+        round_trip(&CapabilityData::TpmProperties(Tpml(vec![
+            TpmsTaggedProperty {
+                property: 0x105,
+                value: 0x4946_5800,
+            },
+        ])));
+        let nv = round_trip(&Tpm2bNvPublic(TpmsNvPublic {
+            nv_index: 0x0150_0000,
+            name_alg: TPM_ALG_SHA256,
+            attributes: TPMA_NV_AUTHREAD | TPMA_NV_AUTHWRITE,
+            auth_policy: Tpm2b::default(),
+            data_size: 64,
+        }));
+        assert_eq!(nv[..2], [0, 14]);
+        round_trip(&AuthCommand::password(b"secret"));
+
+        // Digest of unknown size
+        let mut reader = Reader::new(&[0x00, 0x42, 1, 2, 3]);
+        assert_eq!(
+            reader.read::<TpmtHa>().unwrap_err().status(),
+            EFI_UNSUPPORTED
+        );
+        // Truncated TPM2B and an oversized TPML count
+        let mut reader = Reader::new(&[0x00, 0x05, 1, 2]);
+        assert_eq!(
+            reader.read::<Tpm2b>().unwrap_err().status(),
+            EFI_BAD_BUFFER_SIZE
+        );
+        let mut reader = Reader::new(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert!(reader.read::<TpmlHandle>().is_err());
+    }
+}
@@ -163,25 +163,7 @@ fn test_locate_protocol_not_found() {
     let result = wrapper.locate_protocol(&guid);
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), EFI_NOT_FOUND);
-}
-
-#[test]
-fn test_exit_boot_services_valid_key() {
-    let bs = create_mock_boot_services();
-    let wrapper = BootServicesWrapper::new(&bs);
-
-    let result = wrapper.exit_boot_services(core::ptr::null_mut(), 0x12345678);
-    assert!(result.is_ok());
-}
-
-#[test]
-fn test_exit_boot_services_invalid_key() {
-    let bs = create_mock_boot_services();
-    let wrapper = BootServicesWrapper::new(&bs);
-
-    let result = wrapper.exit_boot_services(core::ptr::null_mut(), 0xDEADBEEF);
-    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().status(), EFI_NOT_FOUND);
 }
 
 #[test]
@@ -10,7 +10,7 @@ use uefi_rust_intergration::*;
 fn test_module_compilation() {
     // If this compiles, all modules are accessible
     let _ = core::mem::size_of::<ffi::Guid>();
-    let _ = core::mem::size_of::<system_table::SystemTable>();
+    let _ = core::mem::size_of::<system_table::RawSystemTable>();
     let _ = core::mem::size_of::<boot_services::BootServices>();
     let _ = core::mem::size_of::<runtime_services::RuntimeServices>();
 }
@@ -39,7 +39,26 @@ fn test_repr_c_layouts() {
     assert_repr_c::<MemoryDescriptor>();
     assert_repr_c::<boot_services::BootServices>();
     assert_repr_c::<runtime_services::RuntimeServices>();
-    assert_repr_c::<system_table::SystemTable>();
+    assert_repr_c::<system_table::RawSystemTable>();
+}
+
+/// Verify the typed system table views can stand in for the raw pointer
+#[test]
+fn test_system_table_view_is_pointer_sized() {
+    use core::mem::size_of;
+
+    assert_eq!(
+        size_of::<SystemTable<Boot>>(),
+        size_of::<*mut RawSystemTable>()
+    );
+    assert_eq!(
+        size_of::<SystemTable<Runtime>>(),
+        size_of::<*mut RawSystemTable>()
+    );
+    assert_eq!(
+        size_of::<Option<SystemTable<Boot>>>(),
+        size_of::<*mut RawSystemTable>()
+    );
 }
 
 /// Verify no_std compatibility
@@ -27,6 +27,36 @@ fn test_table_header_size() {
     assert_eq!(core::mem::size_of::<TableHeader>(), 24);
 }
 
+#[test]
+fn test_crc32_check_value() {
+    // Standard CRC-32 check value
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    assert_eq!(crc32(&[]), 0);
+}
+
+#[test]
+fn test_table_header_crc32_verification() {
+    let mut header = TableHeader {
+        signature: 0x5453595320494249,
+        revision: 0x00020064,
+        header_size: core::mem::size_of::<TableHeader>() as u32,
+        crc32: 0,
+        reserved: 0,
+    };
+
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            &header as *const TableHeader as *const u8,
+            core::mem::size_of::<TableHeader>(),
+        )
+    };
+    header.crc32 = crc32(bytes);
+    assert!(unsafe { header.verify_crc32() });
+
+    header.revision += 1;
+    assert!(!unsafe { header.verify_crc32() });
+}
+
 #[test]
 fn test_memory_descriptor_alignment() {
     // MemoryDescriptor must be properly aligned
@@ -168,6 +168,111 @@ unsafe extern "efiapi" fn mock_exit_boot_services(
     }
 }
 
+/// Interface handed out by the mock OpenProtocol
+static mut MOCK_INTERFACE: u64 = 0x5A5A;
+
+/// Number of CloseProtocol calls seen by the mock
+static CLOSE_PROTOCOL_CALLS: Mutex<usize> = Mutex::new(0);
+
+unsafe extern "efiapi" fn mock_open_protocol(
+    handle: *mut Handle,
+    protocol: *const Guid,
+    interface: *mut *mut core::ffi::c_void,
+    _agent_handle: *mut Handle,
+    _controller_handle: *mut Handle,
+    attributes: Uint32,
+) -> Status {
+    if handle.is_null() || protocol.is_null() || interface.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+    // Pretend another driver already holds the protocol
+    if attributes == EFI_OPEN_PROTOCOL_BY_DRIVER {
+        return EFI_ACCESS_DENIED;
+    }
+    *interface = &raw mut MOCK_INTERFACE as *mut core::ffi::c_void;
+    EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn mock_close_protocol(
+    _handle: *mut Handle,
+    _protocol: *const Guid,
+    _agent_handle: *mut Handle,
+    _controller_handle: *mut Handle,
+) -> Status {
+    *CLOSE_PROTOCOL_CALLS.lock().unwrap() += 1;
+    EFI_SUCCESS
+}
+
+/// Protocols reported as installed on every mock handle
+static MOCK_HANDLE_PROTOCOLS: [Guid; 2] = [
+    Guid::new(0x12345678, 0x1234, 0x5678, [1, 2, 3, 4, 5, 6, 7, 8]),
+    Guid::new(0x87654321, 0x4321, 0x8765, [8, 7, 6, 5, 4, 3, 2, 1]),
+];
+
+unsafe extern "efiapi" fn mock_locate_handle_buffer(
+    search_type: Uint32,
+    protocol: *const Guid,
+    _search_key: *mut core::ffi::c_void,
+    no_handles: *mut Uintn,
+    buffer: *mut *mut *mut Handle,
+) -> Status {
+    if no_handles.is_null() || buffer.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+    if search_type == LocateSearchType::ByProtocol as u32
+        && !MOCK_HANDLE_PROTOCOLS.contains(&*protocol)
+    {
+        return EFI_NOT_FOUND;
+    }
+
+    let count = 3;
+    let mut pool: *mut core::ffi::c_void = null_mut();
+    let status = mock_allocate_pool(
+        MemoryType::BootServicesData,
+        count * core::mem::size_of::<*mut Handle>(),
+        &mut pool,
+    );
+    if status != EFI_SUCCESS {
+        return status;
+    }
+
+    let handles = pool as *mut *mut Handle;
+    for i in 0..count {
+        *handles.add(i) = (0x1000 * (i + 1)) as *mut Handle;
+    }
+    *no_handles = count;
+    *buffer = handles;
+    EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn mock_protocols_per_handle(
+    handle: *mut Handle,
+    protocol_buffer: *mut *mut *const Guid,
+    protocol_buffer_count: *mut Uintn,
+) -> Status {
+    if handle.is_null() || protocol_buffer.is_null() || protocol_buffer_count.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let mut pool: *mut core::ffi::c_void = null_mut();
+    let status = mock_allocate_pool(
+        MemoryType::BootServicesData,
+        MOCK_HANDLE_PROTOCOLS.len() * core::mem::size_of::<*const Guid>(),
+        &mut pool,
+    );
+    if status != EFI_SUCCESS {
+        return status;
+    }
+
+    let guids = pool as *mut *const Guid;
+    for (i, guid) in MOCK_HANDLE_PROTOCOLS.iter().enumerate() {
+        *guids.add(i) = guid;
+    }
+    *protocol_buffer_count = MOCK_HANDLE_PROTOCOLS.len();
+    *protocol_buffer = guids;
+    EFI_SUCCESS
+}
+
 // Stub implementations for other functions
 unsafe extern "efiapi" fn stub_not_implemented() -> Status {
     EFI_UNSUPPORTED
@@ -243,13 +348,13 @@ pub fn create_mock_boot_services() -> BootServices {
         set_watchdog_timer: unsafe { core::mem::transmute(stub_not_implemented as *const ()) },
         connect_controller: unsafe { core::mem::transmute(stub_not_implemented as *const ()) },
         disconnect_controller: unsafe { core::mem::transmute(stub_not_implemented as *const ()) },
-        open_protocol: unsafe { core::mem::transmute(stub_not_implemented as *const ()) },
-        close_protocol: unsafe { core::mem::transmute(stub_not_implemented as *const ()) },
+        open_protocol: mock_open_protocol,
+        close_protocol: mock_close_protocol,
         open_protocol_information: unsafe {
             core::mem::transmute(stub_not_implemented as *const ())
         },
-        protocols_per_handle: unsafe { core::mem::transmute(stub_not_implemented as *const ()) },
-        locate_handle_buffer: unsafe { core::mem::transmute(stub_not_implemented as *const ()) },
+        protocols_per_handle: mock_protocols_per_handle,
+        locate_handle_buffer: mock_locate_handle_buffer,
         locate_protocol: mock_locate_protocol,
         install_multiple_protocol_interfaces: null_mut(),
         uninstall_multiple_protocol_interfaces: null_mut(),
@@ -259,3 +364,82 @@ pub fn create_mock_boot_services() -> BootServices {
         create_event_ex: unsafe { core::mem::transmute(stub_not_implemented as *const ()) },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uefi_rust_intergration::protocols::Protocol;
+
+    /// Minimal protocol used to exercise the generic open path
+    #[repr(C)]
+    struct MockProtocol {
+        value: u64,
+    }
+
+    unsafe impl Protocol for MockProtocol {
+        const GUID: Guid = Guid::new(0x12345678, 0x1234, 0x5678, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_open_protocol_closes_on_drop() {
+        let bs = create_mock_boot_services();
+        let wrapper = BootServicesWrapper::new(&bs);
+        let handle = 0x1000 as *mut Handle;
+        let before = *CLOSE_PROTOCOL_CALLS.lock().unwrap();
+
+        {
+            let protocol = unsafe {
+                wrapper
+                    .open_protocol::<MockProtocol>(
+                        handle,
+                        handle,
+                        null_mut(),
+                        OpenProtocolAttributes::Exclusive,
+                    )
+                    .unwrap()
+            };
+            assert_eq!(protocol.value, 0x5A5A);
+            assert_eq!(protocol.handle(), handle);
+        }
+
+        assert_eq!(*CLOSE_PROTOCOL_CALLS.lock().unwrap(), before + 1);
+    }
+
+    #[test]
+    fn test_find_handles_and_protocols_on() {
+        init_mock_pool();
+        let bs = create_mock_boot_services();
+        let wrapper = BootServicesWrapper::new(&bs);
+
+        {
+            let handles = wrapper.find_handles::<MockProtocol>().unwrap();
+            assert_eq!(handles.len(), 3);
+            assert_eq!(handles[0], 0x1000 as *mut Handle);
+
+            let guids = unsafe { wrapper.protocols_on(handles[1]).unwrap() };
+            assert_eq!(guids, MOCK_HANDLE_PROTOCOLS);
+            assert_eq!(get_pool_stats().0, 1);
+        }
+
+        // Both pool buffers were returned to the firmware
+        assert_eq!(get_pool_stats(), (0, 0));
+        clear_mock_pool();
+    }
+
+    #[test]
+    fn test_open_protocol_by_driver_denied() {
+        let bs = create_mock_boot_services();
+        let wrapper = BootServicesWrapper::new(&bs);
+        let handle = 0x1000 as *mut Handle;
+
+        let result = unsafe {
+            wrapper.open_protocol::<MockProtocol>(
+                handle,
+                handle,
+                handle,
+                OpenProtocolAttributes::ByDriver,
+            )
+        };
+        assert_eq!(result.err().map(|e| e.status()), Some(EFI_ACCESS_DENIED));
+    }
+}
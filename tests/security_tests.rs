@@ -42,22 +42,23 @@ fn test_sha256_guid_value() {
 
 #[test]
 fn test_hash_output_sizes() {
-    let output: HashOutput = unsafe { core::mem::zeroed() };
+    let mut digest = [0u8; 64];
+    let output = HashOutput::new(&mut digest);
 
     unsafe {
-        assert_eq!(output.sha1_hash.len(), 20);
-        assert_eq!(output.sha224_hash.len(), 28);
-        assert_eq!(output.sha256_hash.len(), 32);
-        assert_eq!(output.sha384_hash.len(), 48);
-        assert_eq!(output.sha512_hash.len(), 64);
+        assert_eq!((*output.sha1_hash).len(), 20);
+        assert_eq!((*output.sha224_hash).len(), 28);
+        assert_eq!((*output.sha256_hash).len(), 32);
+        assert_eq!((*output.sha384_hash).len(), 48);
+        assert_eq!((*output.sha512_hash).len(), 64);
     }
 }
 
 #[test]
 fn test_hash_output_union_size() {
     let size = core::mem::size_of::<HashOutput>();
-    // Union should be as large as the largest member (SHA-512 = 64 bytes)
-    assert_eq!(size, 64);
+    // Every member is a pointer to a caller-allocated digest buffer
+    assert_eq!(size, core::mem::size_of::<*mut u8>());
 }
 
 // ============================================================================
@@ -318,7 +319,10 @@ fn test_security_structure_alignments() {
         core::mem::align_of::<SignatureList>(),
         core::mem::align_of::<Guid>()
     );
-    assert_eq!(core::mem::align_of::<HashOutput>(), 1); // Union of arrays
+    assert_eq!(
+        core::mem::align_of::<HashOutput>(),
+        core::mem::align_of::<*mut u8>()
+    ); // Union of pointers
 }
 
 #[test]
@@ -345,13 +349,14 @@ fn test_hash_sizes_match_algorithms() {
     // SHA-512: 512 bits = 64 bytes
     let sha512_size = 64;
 
-    let output: HashOutput = unsafe { core::mem::zeroed() };
+    let mut digest = [0u8; 64];
+    let output = HashOutput::new(&mut digest);
     unsafe {
-        assert_eq!(output.sha1_hash.len(), sha1_size);
-        assert_eq!(output.sha224_hash.len(), sha224_size);
-        assert_eq!(output.sha256_hash.len(), sha256_size);
-        assert_eq!(output.sha384_hash.len(), sha384_size);
-        assert_eq!(output.sha512_hash.len(), sha512_size);
+        assert_eq!((*output.sha1_hash).len(), sha1_size);
+        assert_eq!((*output.sha224_hash).len(), sha224_size);
+        assert_eq!((*output.sha256_hash).len(), sha256_size);
+        assert_eq!((*output.sha384_hash).len(), sha384_size);
+        assert_eq!((*output.sha512_hash).len(), sha512_size);
     }
 }
 
@@ -546,10 +551,11 @@ fn test_multiple_hash_algorithms() {
     assert_ne!(sha256_guid, sha512_guid);
 
     // Verify sizes
-    let output: HashOutput = unsafe { core::mem::zeroed() };
+    let mut digest = [0u8; 64];
+    let output = HashOutput::new(&mut digest);
     unsafe {
-        assert_eq!(output.sha256_hash.len(), 32);
-        assert_eq!(output.sha384_hash.len(), 48);
-        assert_eq!(output.sha512_hash.len(), 64);
+        assert_eq!((*output.sha256_hash).len(), 32);
+        assert_eq!((*output.sha384_hash).len(), 48);
+        assert_eq!((*output.sha512_hash).len(), 64);
     }
 }